@@ -0,0 +1,47 @@
+// Measures the forward pass and the dot product kernel.
+// Run with `cargo bench --bench forward` and again with `--features fast-math` to compare.
+use std::hint::black_box;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use backprop::kernels;
+use backprop::network::{Activation, Layer, Network};
+
+fn network(width: u64) -> Network {
+    Network::new(vec![
+        Layer::dense(width, width, Activation::Relu, true).unwrap(),
+        Layer::dense(width, width, Activation::Relu, true).unwrap(),
+        Layer::dense(width, 1, Activation::Linear, true).unwrap(),
+    ]).unwrap()
+}
+
+fn dot(c: &mut Criterion) {
+    let mut group = c.benchmark_group("kernels::dot");
+
+    for len in [16, 256, 4096] {
+        let a: Vec<f64> = (0..len).map(|i| i as f64 * 0.001).collect();
+        let b: Vec<f64> = (0..len).map(|i| 1.0 - i as f64 * 0.001).collect();
+
+        group.bench_with_input(BenchmarkId::from_parameter(len), &len, |bencher, _| {
+            bencher.iter(|| kernels::dot(black_box(&a), black_box(&b)));
+        });
+    }
+
+    group.finish();
+}
+
+fn forward(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Network::forward");
+
+    for width in [8, 32, 128] {
+        let network = network(width);
+        let inputs: Vec<f64> = (0..width).map(|i| i as f64 / width as f64).collect();
+
+        group.bench_with_input(BenchmarkId::from_parameter(width), &width, |bencher, _| {
+            bencher.iter(|| network.forward(black_box(&inputs)).unwrap());
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, dot, forward);
+criterion_main!(benches);
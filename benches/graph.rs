@@ -0,0 +1,169 @@
+// Measures the graph machinery: building the graph in the forward pass, the backward pass, the
+// backward pass through a cached GradPlan, a full training epoch of forward, backward and parameter updates,
+// Trainer epochs with and without a static graph, and a Tensor matrix product with its backward pass.
+// Run with `cargo bench --bench graph`.
+use std::hint::black_box;
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use backprop::network::{Activation, Layer, Network};
+use backprop::optim::Sgd;
+use backprop::tensor::Tensor;
+use backprop::train::Trainer;
+use backprop::value::{GradPlan, Value};
+
+const WIDTHS: [u64; 3] = [4, 16, 64];
+
+fn network(width: u64) -> Network {
+    Network::new(vec![
+        Layer::dense(width, width, Activation::Relu, true).unwrap(),
+        Layer::dense(width, 1, Activation::Linear, true).unwrap(),
+    ]).unwrap()
+}
+
+fn inputs(width: u64) -> Vec<f64> {
+    (0..width).map(|i| i as f64 / width as f64 - 0.5).collect()
+}
+
+fn forward_values(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Network::forward_values");
+
+    for width in WIDTHS {
+        let network = network(width);
+        let inputs = inputs(width);
+
+        group.bench_with_input(BenchmarkId::from_parameter(width), &width, |bencher, _| {
+            bencher.iter(|| network.forward_values(black_box(&inputs)).unwrap());
+        });
+    }
+
+    group.finish();
+}
+
+fn run_grad(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Value::run_grad");
+
+    for width in WIDTHS {
+        let network = network(width);
+        let inputs = inputs(width);
+
+        // The graph is rebuilt for every iteration so only the backward pass is timed
+        group.bench_with_input(BenchmarkId::from_parameter(width), &width, |bencher, _| {
+            bencher.iter_batched(
+                || network.forward_values(&inputs).unwrap().remove(0),
+                |output| output.run_grad(),
+                BatchSize::SmallInput,
+            );
+        });
+    }
+
+    group.finish();
+}
+
+fn grad_plan(c: &mut Criterion) {
+    let mut group = c.benchmark_group("GradPlan::run");
+
+    for width in WIDTHS {
+        let network = network(width);
+        let inputs: Vec<Value<f64>> = inputs(width).into_iter().map(Value::new).collect();
+
+        // Unlike run_grad, the graph is built and sorted once; each iteration recomputes its data
+        // and backpropagates through the cached order
+        let plan = GradPlan::compile(&network.forward_graph(&inputs).unwrap().remove(0));
+        group.bench_with_input(BenchmarkId::from_parameter(width), &width, |bencher, _| {
+            bencher.iter(|| plan.run());
+        });
+    }
+
+    group.finish();
+}
+
+fn train_epoch(c: &mut Criterion) {
+    let mut group = c.benchmark_group("train_epoch");
+    group.sample_size(10);
+
+    for width in WIDTHS {
+        let network = network(width);
+        let optimizer = Sgd::new(0.01);
+        let parameters = network.parameters();
+
+        // Regress the mean of the inputs over a small dataset
+        let dataset: Vec<(Vec<f64>, f64)> = (0..32)
+            .map(|i| {
+                let x: Vec<f64> = (0..width).map(|j| ((i * 7 + j as usize) % 11) as f64 / 11.0).collect();
+                let y = x.iter().sum::<f64>() / width as f64;
+                (x, y)
+            })
+            .collect();
+
+        group.bench_with_input(BenchmarkId::from_parameter(width), &width, |bencher, _| {
+            bencher.iter(|| {
+                for (x, y) in &dataset {
+                    let output = network.forward_values(x).unwrap().remove(0);
+                    let error = &output - &Value::new(*y);
+                    let loss = &error * &error;
+
+                    optimizer.zero_grad(&parameters);
+                    loss.run_grad();
+                    optimizer.step(&parameters);
+                }
+            });
+        });
+    }
+
+    group.finish();
+}
+
+fn trainer_epoch(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Trainer::train_epoch");
+    group.sample_size(10);
+
+    for width in WIDTHS {
+        let network = network(width);
+        let dataset: Vec<(Vec<f64>, Vec<f64>)> = (0..32)
+            .map(|i| {
+                let x: Vec<f64> = (0..width).map(|j| ((i * 7 + j as usize) % 11) as f64 / 11.0).collect();
+                let y = x.iter().sum::<f64>() / width as f64;
+                (x, vec![y])
+            })
+            .collect();
+
+        // The static graph is compiled once per epoch and rerun for every sample, while the
+        // rebuilt one allocates and sorts a fresh graph for each sample
+        for static_graph in [true, false] {
+            let mut trainer = Trainer::new(Sgd::new(0.01));
+            trainer.batch_size = 8;
+            trainer.static_graph = static_graph;
+
+            let name = if static_graph { "static" } else { "rebuilt" };
+            group.bench_with_input(BenchmarkId::new(name, width), &width, |bencher, _| {
+                bencher.iter(|| trainer.train_epoch(&network, &dataset).unwrap());
+            });
+        }
+    }
+
+    group.finish();
+}
+
+fn tensor_matmul(c: &mut Criterion) {
+    let mut group = c.benchmark_group("tensor_matmul");
+
+    for width in [16, 64, 256] {
+        let matrix = |offset: usize| {
+            let data = (0..width * width).map(|i| ((i + offset) % 17) as f64 / 17.0 - 0.5).collect();
+            Tensor::new(data, &[width, width]).unwrap()
+        };
+        let (a, b) = (matrix(0), matrix(5));
+
+        group.bench_with_input(BenchmarkId::from_parameter(width), &width, |bencher, _| {
+            bencher.iter(|| {
+                let product = a.matmul(&b).unwrap();
+                product.run_grad();
+                black_box(product.data());
+            });
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, forward_values, run_grad, grad_plan, train_epoch, trainer_epoch, tensor_matmul);
+criterion_main!(benches);
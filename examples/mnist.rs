@@ -0,0 +1,56 @@
+// Trains a small MLP classifier on MNIST.
+//
+//     cargo run --release --example mnist -- [data directory] [training samples] [epochs]
+//
+// The dataset is downloaded into the data directory (default `data/mnist`) when it's missing.
+// Every scalar operation is a node in the computation graph, so training on the full 60,000
+// images is slow; the default trains on the first 2,000 and evaluates on the first 1,000 test images.
+use std::env;
+use std::error::Error;
+use backprop::data::mnist::{self, NUM_CLASSES};
+use backprop::prelude::*;
+
+fn argmax(values: &[f64]) -> usize {
+    values
+        .iter()
+        .enumerate()
+        .fold((0, f64::NEG_INFINITY), |best, (i, v)| if *v > best.1 { (i, *v) } else { best })
+        .0
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = env::args().collect();
+    let dir = args.get(1).cloned().unwrap_or_else(|| "data/mnist".to_string());
+    let train_samples: usize = args.get(2).map(|n| n.parse()).transpose()?.unwrap_or(2_000);
+    let epochs: usize = args.get(3).map(|n| n.parse()).transpose()?.unwrap_or(3);
+
+    mnist::download(&dir)?;
+    let dataset = mnist::load(&dir)?;
+    println!("loaded {} training and {} test images", dataset.train.len(), dataset.test.len());
+
+    let pixels = (dataset.train.rows * dataset.train.columns) as u64;
+    let network: Network = Network::new(vec![
+        Layer::dense(pixels, 32, Activation::Relu, true)?,
+        Layer::dense(32, NUM_CLASSES as u64, Activation::Linear, true)?,
+    ])?;
+
+    let mut samples = dataset.train.samples();
+    samples.truncate(train_samples);
+
+    let mut trainer = Trainer::new(Sgd::new(0.01));
+    trainer.batch_size = 16;
+
+    trainer.fit(&network, &samples, epochs, &mut ProgressBar::new())?;
+
+    let test_samples = dataset.test.len().min(1_000);
+    let mut correct = 0;
+    for (image, label) in dataset.test.images.iter().zip(&dataset.test.labels).take(test_samples) {
+        if argmax(&network.forward(image)?) == *label as usize {
+            correct += 1;
+        }
+    }
+
+    println!("test accuracy: {:.1}% ({}/{})", 100.0 * correct as f64 / test_samples as f64, correct, test_samples);
+
+    Ok(())
+}
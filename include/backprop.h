@@ -0,0 +1,45 @@
+/*
+ * C interface to backprop models, built with the `cabi` feature:
+ *
+ *     cargo rustc --release --lib --features cabi --crate-type cdylib
+ *
+ * Models are experiment bundles written by backprop::experiment::export_bundle.
+ */
+
+#ifndef BACKPROP_H
+#define BACKPROP_H
+
+#include <stddef.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct BackpropModel {
+    size_t num_inputs;
+    size_t num_outputs;
+    void *network; /* owned by the library */
+} BackpropModel;
+
+typedef enum BackpropStatus {
+    BACKPROP_OK = 0,
+    BACKPROP_NULL_POINTER = 1,
+    BACKPROP_DIMENSION_MISMATCH = 2,
+    BACKPROP_PANIC = 3, /* the library failed internally */
+} BackpropStatus;
+
+/* Returns NULL if the bundle can't be read. No function unwinds a Rust panic into C. */
+BackpropModel *backprop_load_model(const char *path);
+
+BackpropStatus backprop_predict(const BackpropModel *model,
+                                const double *inputs, size_t num_inputs,
+                                double *outputs, size_t num_outputs);
+
+/* Accepts NULL. */
+void backprop_free(BackpropModel *model);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* BACKPROP_H */
@@ -0,0 +1,270 @@
+// Minimal reader and writer for the zip container format, which is used by NumPy .npz files.
+// Only the subset of the format needed for weight archives is supported: no encryption,
+// no multi-disk archives and no zip64 records.
+// A gzip decoder is also provided for downloaded datasets.
+
+const LOCAL_HEADER_SIGNATURE: u32 = 0x04034b50;
+const CENTRAL_HEADER_SIGNATURE: u32 = 0x02014b50;
+const END_OF_CENTRAL_DIRECTORY_SIGNATURE: u32 = 0x06054b50;
+const END_OF_CENTRAL_DIRECTORY_SIZE: usize = 22;
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const GZIP_HEADER_SIZE: usize = 10;
+const GZIP_FLAG_HCRC: u8 = 0x02;
+const GZIP_FLAG_EXTRA: u8 = 0x04;
+const GZIP_FLAG_NAME: u8 = 0x08;
+const GZIP_FLAG_COMMENT: u8 = 0x10;
+
+const METHOD_STORED: u16 = 0;
+const METHOD_DEFLATED: u16 = 8;
+
+/// Entry is a single named file within an archive.
+pub(crate) struct Entry {
+    pub name: String,
+    pub data: Vec<u8>,
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> Result<u16, String> {
+    bytes
+        .get(offset..offset + 2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]))
+        .ok_or_else(|| "unexpected end of archive".to_string())
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32, String> {
+    bytes
+        .get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| "unexpected end of archive".to_string())
+}
+
+/// crc32 computes the CRC-32 (IEEE) checksum used by zip entries.
+pub(crate) fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffffffffu32;
+
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb88320 & mask);
+        }
+    }
+
+    !crc
+}
+
+/// read_entries decodes every file stored in a zip archive, in central directory order.
+pub(crate) fn read_entries(bytes: &[u8]) -> Result<Vec<Entry>, String> {
+    if bytes.len() < END_OF_CENTRAL_DIRECTORY_SIZE {
+        return Err("file is too small to be a zip archive".to_string());
+    }
+
+    // The end of central directory record sits at the end of the file, followed by an optional comment.
+    let eocd_offset = (0..=bytes.len() - END_OF_CENTRAL_DIRECTORY_SIZE)
+        .rev()
+        .find(|&offset| read_u32(bytes, offset) == Ok(END_OF_CENTRAL_DIRECTORY_SIGNATURE))
+        .ok_or_else(|| "missing end of central directory record".to_string())?;
+
+    let entry_count = read_u16(bytes, eocd_offset + 10)? as usize;
+    let mut offset = read_u32(bytes, eocd_offset + 16)? as usize;
+
+    let mut entries = Vec::with_capacity(entry_count);
+    for _ in 0..entry_count {
+        if read_u32(bytes, offset)? != CENTRAL_HEADER_SIGNATURE {
+            return Err("corrupt central directory".to_string());
+        }
+
+        let method = read_u16(bytes, offset + 10)?;
+        let crc = read_u32(bytes, offset + 16)?;
+        let compressed_size = read_u32(bytes, offset + 20)? as usize;
+        let name_length = read_u16(bytes, offset + 28)? as usize;
+        let extra_length = read_u16(bytes, offset + 30)? as usize;
+        let comment_length = read_u16(bytes, offset + 32)? as usize;
+        let local_header_offset = read_u32(bytes, offset + 42)? as usize;
+
+        let name_bytes = bytes
+            .get(offset + 46..offset + 46 + name_length)
+            .ok_or_else(|| "unexpected end of archive".to_string())?;
+        let name = String::from_utf8_lossy(name_bytes).into_owned();
+
+        offset += 46 + name_length + extra_length + comment_length;
+
+        // The local header repeats the name and may carry a different extra field, so the
+        // data offset has to be computed from its own lengths.
+        if read_u32(bytes, local_header_offset)? != LOCAL_HEADER_SIGNATURE {
+            return Err(format!("corrupt local header for {}", name));
+        }
+        let local_name_length = read_u16(bytes, local_header_offset + 26)? as usize;
+        let local_extra_length = read_u16(bytes, local_header_offset + 28)? as usize;
+        let data_offset = local_header_offset + 30 + local_name_length + local_extra_length;
+
+        let raw = bytes
+            .get(data_offset..data_offset + compressed_size)
+            .ok_or_else(|| format!("truncated data for {}", name))?;
+
+        let data = match method {
+            METHOD_STORED => raw.to_vec(),
+            METHOD_DEFLATED => miniz_oxide::inflate::decompress_to_vec(raw)
+                .map_err(|err| format!("failed to inflate {}: {:?}", name, err))?,
+            other => return Err(format!("unsupported compression method {} for {}", other, name)),
+        };
+
+        if crc32(&data) != crc {
+            return Err(format!("checksum mismatch for {}", name));
+        }
+
+        entries.push(Entry { name, data });
+    }
+
+    Ok(entries)
+}
+
+/// write_entries encodes the given files as an uncompressed zip archive.
+pub(crate) fn write_entries(entries: &[Entry]) -> Vec<u8> {
+    let mut output = Vec::new();
+    let mut central_directory = Vec::new();
+
+    for entry in entries {
+        let local_header_offset = output.len() as u32;
+        let crc = crc32(&entry.data);
+        let size = entry.data.len() as u32;
+        let name = entry.name.as_bytes();
+
+        // Local file header
+        output.extend_from_slice(&LOCAL_HEADER_SIGNATURE.to_le_bytes());
+        output.extend_from_slice(&20u16.to_le_bytes()); // version needed to extract
+        output.extend_from_slice(&0u16.to_le_bytes()); // flags
+        output.extend_from_slice(&METHOD_STORED.to_le_bytes());
+        output.extend_from_slice(&0u32.to_le_bytes()); // modification time and date
+        output.extend_from_slice(&crc.to_le_bytes());
+        output.extend_from_slice(&size.to_le_bytes());
+        output.extend_from_slice(&size.to_le_bytes());
+        output.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        output.extend_from_slice(&0u16.to_le_bytes()); // extra field length
+        output.extend_from_slice(name);
+        output.extend_from_slice(&entry.data);
+
+        // Central directory header
+        central_directory.extend_from_slice(&CENTRAL_HEADER_SIGNATURE.to_le_bytes());
+        central_directory.extend_from_slice(&20u16.to_le_bytes()); // version made by
+        central_directory.extend_from_slice(&20u16.to_le_bytes()); // version needed to extract
+        central_directory.extend_from_slice(&0u16.to_le_bytes()); // flags
+        central_directory.extend_from_slice(&METHOD_STORED.to_le_bytes());
+        central_directory.extend_from_slice(&0u32.to_le_bytes()); // modification time and date
+        central_directory.extend_from_slice(&crc.to_le_bytes());
+        central_directory.extend_from_slice(&size.to_le_bytes());
+        central_directory.extend_from_slice(&size.to_le_bytes());
+        central_directory.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        central_directory.extend_from_slice(&[0u8; 12]); // extra, comment, disk number, attributes
+        central_directory.extend_from_slice(&local_header_offset.to_le_bytes());
+        central_directory.extend_from_slice(name);
+    }
+
+    let central_directory_offset = output.len() as u32;
+    output.extend_from_slice(&central_directory);
+
+    // End of central directory record
+    output.extend_from_slice(&END_OF_CENTRAL_DIRECTORY_SIGNATURE.to_le_bytes());
+    output.extend_from_slice(&[0u8; 4]); // disk numbers
+    output.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+    output.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+    output.extend_from_slice(&(central_directory.len() as u32).to_le_bytes());
+    output.extend_from_slice(&central_directory_offset.to_le_bytes());
+    output.extend_from_slice(&0u16.to_le_bytes()); // comment length
+
+    output
+}
+
+/// is_gzip reports whether the bytes start with the gzip magic number.
+pub(crate) fn is_gzip(bytes: &[u8]) -> bool {
+    bytes.starts_with(&GZIP_MAGIC)
+}
+
+/// gunzip decompresses a single member gzip file, verifying its checksum.
+pub(crate) fn gunzip(bytes: &[u8]) -> Result<Vec<u8>, String> {
+    if !is_gzip(bytes) || bytes.len() < GZIP_HEADER_SIZE + 8 {
+        return Err("not a gzip file".to_string());
+    }
+    if bytes[2] != METHOD_DEFLATED as u8 {
+        return Err(format!("unsupported gzip compression method {}", bytes[2]));
+    }
+
+    let flags = bytes[3];
+    let mut offset = GZIP_HEADER_SIZE;
+
+    if flags & GZIP_FLAG_EXTRA != 0 {
+        offset += 2 + read_u16(bytes, offset)? as usize;
+    }
+
+    // The file name and comment are zero terminated
+    for flag in [GZIP_FLAG_NAME, GZIP_FLAG_COMMENT] {
+        if flags & flag != 0 {
+            let end = bytes
+                .get(offset..)
+                .and_then(|rest| rest.iter().position(|b| *b == 0))
+                .ok_or_else(|| "unexpected end of gzip header".to_string())?;
+            offset += end + 1;
+        }
+    }
+
+    if flags & GZIP_FLAG_HCRC != 0 {
+        offset += 2;
+    }
+
+    let trailer = bytes.len() - 8;
+    let compressed = bytes.get(offset..trailer).ok_or_else(|| "unexpected end of gzip header".to_string())?;
+    let data = miniz_oxide::inflate::decompress_to_vec(compressed)
+        .map_err(|err| format!("failed to inflate gzip data: {:?}", err))?;
+
+    if crc32(&data) != read_u32(bytes, trailer)? || data.len() as u32 != read_u32(bytes, trailer + 4)? {
+        return Err("gzip checksum mismatch".to_string());
+    }
+
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::archive::{crc32, gunzip, read_entries, write_entries, Entry};
+
+    #[test]
+    fn crc32_matches_reference() {
+        assert_eq!(crc32(b"123456789"), 0xcbf43926);
+    }
+
+    #[test]
+    fn entries_round_trip() {
+        let entries = vec![
+            Entry { name: "a.txt".to_string(), data: b"hello".to_vec() },
+            Entry { name: "b.bin".to_string(), data: vec![1, 2, 3, 4] },
+        ];
+
+        let bytes = write_entries(&entries);
+        let decoded = read_entries(&bytes).unwrap();
+
+        assert_eq!(decoded.len(), 2);
+        assert_eq!(decoded[0].name, "a.txt");
+        assert_eq!(decoded[0].data, b"hello");
+        assert_eq!(decoded[1].name, "b.bin");
+        assert_eq!(decoded[1].data, vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn gunzip_with_file_name() {
+        let data = b"some data which compresses, some data which compresses".to_vec();
+
+        // Header with the FNAME flag set, as written by the gzip command line tool
+        let mut bytes = vec![0x1f, 0x8b, 8, 0x08, 0, 0, 0, 0, 0, 3];
+        bytes.extend_from_slice(b"data.txt\0");
+        bytes.extend(miniz_oxide::deflate::compress_to_vec(&data, 6));
+        bytes.extend_from_slice(&crc32(&data).to_le_bytes());
+        bytes.extend_from_slice(&(data.len() as u32).to_le_bytes());
+
+        assert_eq!(gunzip(&bytes).unwrap(), data);
+
+        let last = bytes.len() - 5;
+        bytes[last] ^= 1;
+        assert!(gunzip(&bytes).is_err());
+        assert!(gunzip(b"plain text, not gzip").is_err());
+    }
+}
@@ -0,0 +1,165 @@
+use rand::Rng;
+use crate::network::NetworkError;
+use crate::tensor::{Tensor, TensorError};
+
+/// SelfAttention is a single scaled dot-product attention head over a sequence of vectors, built
+/// on the tensor API. The sequence is a [positions, num_inputs] matrix X, projected to queries
+/// Q = X Wq, keys K = X Wk and values V = X Wv of `head_size` columns, and the output is
+/// softmax(Q K^T / sqrt(head_size)) V, one row of head_size values per position.
+///
+/// With `causal` set, position i only attends to positions up to i, as in a decoder block.
+pub struct SelfAttention {
+    // Each projection is a [num_inputs, head_size] matrix
+    query: Tensor,
+    key: Tensor,
+    value: Tensor,
+    pub causal: bool,
+}
+
+impl SelfAttention {
+    /// Creates a head attending over vectors of `num_inputs` values, failing if either size is zero.
+    pub fn new(num_inputs: u64, head_size: u64) -> Result<SelfAttention, NetworkError> {
+        if num_inputs == 0 || head_size == 0 {
+            return Err(NetworkError::EmptyLayer { inputs: num_inputs, outputs: head_size });
+        }
+
+        // uniform(-k, k) with k = 1/sqrt(num_inputs), keeping the scores small enough that the
+        // softmax doesn't start out saturated
+        let mut rng = crate::rand::global_rng();
+        let bound = 1.0 / (num_inputs as f64).sqrt();
+        let shape = [num_inputs as usize, head_size as usize];
+        let mut projection = || {
+            let weights = (0..shape[0] * shape[1]).map(|_| rng.gen_range(-bound..=bound)).collect();
+            Tensor::new(weights, &shape).expect("the weights fill the projection")
+        };
+
+        Ok(SelfAttention {
+            query: projection(),
+            key: projection(),
+            value: projection(),
+            causal: false,
+        })
+    }
+
+    pub fn num_inputs(&self) -> u64 {
+        self.query.shape()[0] as u64
+    }
+
+    pub fn head_size(&self) -> u64 {
+        self.query.shape()[1] as u64
+    }
+
+    /// Attends over `sequence`, a [positions, num_inputs] matrix, returning a [positions, head_size]
+    /// matrix.
+    pub fn forward(&self, sequence: &Tensor) -> Result<Tensor, NetworkError> {
+        let shape = sequence.shape();
+        let &[positions, inputs] = shape.as_slice() else {
+            return Err(TensorError::RankMismatch { expected: 2, found: shape.len() }.into());
+        };
+        if inputs != self.num_inputs() as usize {
+            return Err(NetworkError::DimensionMismatch { expected: self.num_inputs() as usize, found: inputs });
+        }
+
+        let (queries, keys, values) = (sequence.matmul(&self.query)?, sequence.matmul(&self.key)?, sequence.matmul(&self.value)?);
+
+        let scale = Tensor::scalar((self.head_size() as f64).sqrt());
+        let mut scores = queries.matmul(&keys.transpose()?)?.div(&scale)?;
+        if self.causal {
+            // Positions after i get a score of -inf, so their weight is 0
+            let mask = (0..positions * positions)
+                .map(|index| if index % positions > index / positions { f64::NEG_INFINITY } else { 0.0 })
+                .collect();
+            scores = scores.add(&Tensor::new(mask, &[positions, positions])?)?;
+        }
+
+        Ok(scores.softmax(1)?.matmul(&values)?)
+    }
+
+    /// Like forward, for raw input data with one vector per position.
+    pub fn forward_values(&self, sequence: &[Vec<f64>]) -> Result<Tensor, NetworkError> {
+        let inputs = self.num_inputs() as usize;
+        if let Some(position) = sequence.iter().find(|position| position.len() != inputs) {
+            return Err(NetworkError::DimensionMismatch { expected: inputs, found: position.len() });
+        }
+
+        self.forward(&Tensor::new(sequence.concat(), &[sequence.len(), inputs])?)
+    }
+
+    /// Returns handles to the query, key and value projection weights, in that order.
+    pub fn parameters(&self) -> Vec<Tensor> {
+        vec![self.query.clone(), self.key.clone(), self.value.clone()]
+    }
+
+    pub fn num_parameters(&self) -> usize {
+        3 * self.query.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::attention::SelfAttention;
+    use crate::network::NetworkError;
+    use crate::tensor::{Tensor, TensorError};
+
+    fn sequence() -> Vec<Vec<f64>> {
+        vec![vec![0.5, -1.0, 0.25], vec![1.5, 0.5, -0.5], vec![-0.25, 1.0, 2.0]]
+    }
+
+    #[test]
+    fn gradients_match_numerical_gradients() {
+        let attention = SelfAttention::new(3, 2).unwrap();
+        assert_eq!(attention.parameters().iter().map(Tensor::len).sum::<usize>(), attention.num_parameters());
+
+        // The sum of the squared outputs
+        let loss = || {
+            let outputs = attention.forward_values(&sequence()).unwrap();
+            outputs.mul(&outputs).unwrap().sum(1).unwrap().sum(0).unwrap()
+        };
+
+        loss().run_grad();
+
+        let h = 1e-6;
+        for parameter in attention.parameters() {
+            let gradient = parameter.gradient();
+            for (i, analytical) in gradient.iter().enumerate() {
+                let original = parameter.borrow().data[i];
+
+                parameter.borrow_mut().data[i] = original + h;
+                let plus = loss().data()[0];
+                parameter.borrow_mut().data[i] = original - h;
+                let minus = loss().data()[0];
+                parameter.borrow_mut().data[i] = original;
+
+                let numerical = (plus - minus) / (2.0 * h);
+                assert!((analytical - numerical).abs() < 1e-6, "{} vs {}", analytical, numerical);
+            }
+        }
+    }
+
+    #[test]
+    fn causal_attention_only_sees_the_past() {
+        let mut attention = SelfAttention::new(3, 2).unwrap();
+        attention.causal = true;
+
+        let full = attention.forward_values(&sequence()).unwrap();
+        let prefix = attention.forward_values(&sequence()[..2]).unwrap();
+        assert_eq!(full.shape(), vec![3, 2]);
+
+        // Earlier outputs don't change when later positions are appended
+        assert_eq!(full.data()[..4], prefix.data()[..]);
+
+        // The first position can only attend to itself, so its output is its own value
+        let first = attention.forward_values(&sequence()[..1]).unwrap();
+        let value = Tensor::new(sequence()[0].clone(), &[1, 3]).unwrap().matmul(&attention.parameters()[2]).unwrap();
+        assert_eq!(first.data(), value.data());
+
+        assert_eq!(
+            attention.forward_values(&[vec![1.0, 2.0]]).err(),
+            Some(NetworkError::DimensionMismatch { expected: 3, found: 2 })
+        );
+        assert_eq!(
+            attention.forward(&Tensor::zeros(&[3])).err(),
+            Some(NetworkError::Tensor(TensorError::RankMismatch { expected: 2, found: 1 }))
+        );
+    }
+}
@@ -0,0 +1,178 @@
+use std::ffi::{c_char, CStr};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
+use crate::experiment;
+use crate::inference::InferenceNetwork;
+
+/// BackpropModel is a trained network loaded for inference from C, see include/backprop.h.
+/// Only the sizes are meant to be read from C; the network behind it is owned by Rust and must be
+/// released with backprop_free.
+#[repr(C)]
+pub struct BackpropModel {
+    pub num_inputs: usize,
+    pub num_outputs: usize,
+    network: *mut InferenceNetwork,
+}
+
+/// BackpropStatus is returned by backprop_predict.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackpropStatus {
+    Ok = 0,
+    NullPointer = 1,
+    DimensionMismatch = 2,
+
+    // The library panicked; the panic was caught rather than unwinding into C.
+    Panic = 3,
+}
+
+// guard runs the body of an extern "C" function, returning `fallback` if it panics, since
+// unwinding across the FFI boundary is undefined behaviour
+fn guard<R>(fallback: R, body: impl FnOnce() -> R) -> R {
+    panic::catch_unwind(AssertUnwindSafe(body)).unwrap_or(fallback)
+}
+
+/// Loads an experiment bundle written by experiment::export_bundle, returning null if `path` is
+/// null, isn't valid UTF-8 or doesn't hold a valid bundle, or if loading panics.
+///
+/// # Safety
+///
+/// `path` must be null or point to a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn backprop_load_model(path: *const c_char) -> *mut BackpropModel {
+    guard(ptr::null_mut(), || load_model(path))
+}
+
+unsafe fn load_model(path: *const c_char) -> *mut BackpropModel {
+    if path.is_null() {
+        return ptr::null_mut();
+    }
+
+    let Ok(path) = CStr::from_ptr(path).to_str() else {
+        return ptr::null_mut();
+    };
+    let Ok(experiment) = experiment::import_bundle(path) else {
+        return ptr::null_mut();
+    };
+
+    let network = InferenceNetwork::from_network(&experiment.network);
+    let layers = network.layers();
+
+    let model = BackpropModel {
+        num_inputs: layers[0].num_inputs(),
+        num_outputs: layers[layers.len() - 1].num_outputs(),
+        network: Box::into_raw(Box::new(network)),
+    };
+
+    Box::into_raw(Box::new(model))
+}
+
+/// Runs the model on `num_inputs` values, writing its `num_outputs` outputs. Both sizes must
+/// match the model's. Returns BackpropStatus::Panic, with the outputs unspecified, if the
+/// prediction panics.
+///
+/// # Safety
+///
+/// `model` must be null or come from backprop_load_model and not have been freed. `inputs` and
+/// `outputs` must be null or point to at least `num_inputs` and `num_outputs` doubles.
+#[no_mangle]
+pub unsafe extern "C" fn backprop_predict(
+    model: *const BackpropModel,
+    inputs: *const f64,
+    num_inputs: usize,
+    outputs: *mut f64,
+    num_outputs: usize,
+) -> BackpropStatus {
+    guard(BackpropStatus::Panic, || predict(model, inputs, num_inputs, outputs, num_outputs))
+}
+
+unsafe fn predict(model: *const BackpropModel, inputs: *const f64, num_inputs: usize, outputs: *mut f64, num_outputs: usize) -> BackpropStatus {
+    if model.is_null() || inputs.is_null() || outputs.is_null() {
+        return BackpropStatus::NullPointer;
+    }
+
+    let model = &*model;
+    if num_inputs != model.num_inputs || num_outputs != model.num_outputs {
+        return BackpropStatus::DimensionMismatch;
+    }
+
+    let Ok(result) = (*model.network).forward(slice::from_raw_parts(inputs, num_inputs)) else {
+        return BackpropStatus::DimensionMismatch;
+    };
+    slice::from_raw_parts_mut(outputs, num_outputs).copy_from_slice(&result);
+
+    BackpropStatus::Ok
+}
+
+/// Releases a model. Freeing null does nothing.
+///
+/// # Safety
+///
+/// `model` must be null or come from backprop_load_model, and must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn backprop_free(model: *mut BackpropModel) {
+    if model.is_null() {
+        return;
+    }
+
+    // A panicking destructor leaks what's left of the model rather than unwinding into C
+    guard((), || {
+        let model = Box::from_raw(model);
+        drop(Box::from_raw(model.network));
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::CString;
+    use std::ptr;
+    use crate::cabi::{backprop_free, backprop_load_model, backprop_predict, guard, BackpropStatus};
+    use crate::experiment::{export_bundle, Experiment};
+    use crate::network::{Activation, Layer, Network};
+
+    #[test]
+    fn predicts_through_the_c_interface() {
+        let path = std::env::temp_dir().join("backprop_cabi.bundle");
+        let network = Network::new(vec![
+            Layer::dense(2, 3, Activation::Relu, true).unwrap(),
+            Layer::dense(3, 1, Activation::Linear, true).unwrap(),
+        ]).unwrap();
+        let expected = network.forward(&[0.5, -0.25]).unwrap();
+        export_bundle(&Experiment::new(network), &path).unwrap();
+
+        let path = CString::new(path.to_str().unwrap()).unwrap();
+        unsafe {
+            let model = backprop_load_model(path.as_ptr());
+            assert!(!model.is_null());
+            assert_eq!(((*model).num_inputs, (*model).num_outputs), (2, 1));
+
+            let inputs = [0.5, -0.25];
+            let mut outputs = [0.0];
+            assert_eq!(backprop_predict(model, inputs.as_ptr(), 2, outputs.as_mut_ptr(), 1), BackpropStatus::Ok);
+            assert!((outputs[0] - expected[0]).abs() < 1e-12);
+
+            assert_eq!(backprop_predict(model, inputs.as_ptr(), 1, outputs.as_mut_ptr(), 1), BackpropStatus::DimensionMismatch);
+            assert_eq!(backprop_predict(model, ptr::null(), 2, outputs.as_mut_ptr(), 1), BackpropStatus::NullPointer);
+
+            backprop_free(model);
+        }
+    }
+
+    #[test]
+    fn invalid_paths_load_nothing() {
+        let missing = CString::new("/nonexistent/backprop.bundle").unwrap();
+
+        unsafe {
+            assert!(backprop_load_model(ptr::null()).is_null());
+            assert!(backprop_load_model(missing.as_ptr()).is_null());
+            backprop_free(ptr::null_mut());
+        }
+    }
+
+    #[test]
+    fn panics_become_error_codes() {
+        assert_eq!(guard(BackpropStatus::Panic, || BackpropStatus::Ok), BackpropStatus::Ok);
+        assert_eq!(guard(BackpropStatus::Panic, || panic!("caught before reaching C")), BackpropStatus::Panic);
+    }
+}
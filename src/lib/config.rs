@@ -0,0 +1,352 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use crate::network::Activation;
+use crate::train::DifferentialPrivacy;
+
+/// LayerConfig describes the architecture of a single layer, independently of its parameters.
+/// It is the shared representation used when building networks in code, loading them from
+/// TOML config files and saving them alongside their weights.
+///
+/// Dropout and batch normalisation layers have as many outputs as `features`, see Layer::dropout
+/// and Layer::batch_norm.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LayerConfig {
+    Dense {
+        inputs: u64,
+        outputs: u64,
+
+        #[serde(default)]
+        activation: Activation,
+
+        #[serde(default = "default_bias")]
+        bias: bool,
+    },
+    Dropout {
+        features: u64,
+        p: f64,
+    },
+    BatchNorm {
+        features: u64,
+
+        #[serde(default = "default_momentum")]
+        momentum: f64,
+
+        #[serde(default = "default_epsilon")]
+        epsilon: f64,
+    },
+}
+
+fn default_bias() -> bool {
+    true
+}
+
+fn default_momentum() -> f64 {
+    0.1
+}
+
+fn default_epsilon() -> f64 {
+    1e-5
+}
+
+/// NetworkConfig describes a network as an ordered list of layers.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NetworkConfig {
+    pub layers: Vec<LayerConfig>,
+}
+
+/// ConfigError represents a failure to load a network configuration.
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(io::Error),
+    Parse(toml::de::Error),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(err) => write!(f, "failed to read config: {}", err),
+            ConfigError::Parse(err) => write!(f, "failed to parse config: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl From<io::Error> for ConfigError {
+    fn from(err: io::Error) -> Self {
+        ConfigError::Io(err)
+    }
+}
+
+impl From<toml::de::Error> for ConfigError {
+    fn from(err: toml::de::Error) -> Self {
+        ConfigError::Parse(err)
+    }
+}
+
+impl NetworkConfig {
+    pub fn from_toml_str(contents: &str) -> Result<NetworkConfig, toml::de::Error> {
+        toml::from_str(contents)
+    }
+
+    pub fn to_toml_string(&self) -> Result<String, toml::ser::Error> {
+        toml::to_string(self)
+    }
+
+    /// Loads a network configuration from a TOML file.
+    pub fn load(path: impl AsRef<Path>) -> Result<NetworkConfig, ConfigError> {
+        let contents = fs::read_to_string(path)?;
+
+        Ok(NetworkConfig::from_toml_str(&contents)?)
+    }
+}
+
+/// TrainingConfig describes a complete training run: the network architecture (as `[[layers]]`
+/// tables, like NetworkConfig), the optimizer, the dataset and where to write the results.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrainingConfig {
+    #[serde(flatten)]
+    pub network: NetworkConfig,
+
+    #[serde(default)]
+    pub optimizer: OptimizerConfig,
+
+    pub dataset: DatasetConfig,
+
+    #[serde(default)]
+    pub training: TrainingOptions,
+
+    #[serde(default)]
+    pub output: OutputConfig,
+}
+
+/// OptimizerConfig selects the optimizer and its hyperparameters.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OptimizerConfig {
+    Sgd { learning_rate: f64 },
+}
+
+impl Default for OptimizerConfig {
+    fn default() -> Self {
+        OptimizerConfig::Sgd { learning_rate: 0.01 }
+    }
+}
+
+/// DatasetConfig points at a numeric CSV file whose last `targets` columns are the targets.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DatasetConfig {
+    // path is resolved relative to the directory of the config file
+    pub path: PathBuf,
+
+    #[serde(default = "default_targets")]
+    pub targets: usize,
+}
+
+fn default_targets() -> usize {
+    1
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TrainingOptions {
+    pub epochs: usize,
+    pub batch_size: usize,
+    pub accumulate_steps: usize,
+
+    // differential_privacy enables DP-SGD, from a [training.differential_privacy] table
+    pub differential_privacy: Option<DifferentialPrivacy>,
+}
+
+impl Default for TrainingOptions {
+    fn default() -> Self {
+        TrainingOptions {
+            epochs: 10,
+            batch_size: 1,
+            accumulate_steps: 1,
+            differential_privacy: None,
+        }
+    }
+}
+
+/// OutputConfig sets where the trained model (an experiment bundle) and the per-epoch metrics (CSV) are written.
+/// Like the dataset path, relative paths are resolved against the directory of the config file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OutputConfig {
+    pub model: PathBuf,
+    pub metrics: PathBuf,
+}
+
+impl Default for OutputConfig {
+    fn default() -> Self {
+        OutputConfig {
+            model: PathBuf::from("model.bin"),
+            metrics: PathBuf::from("metrics.csv"),
+        }
+    }
+}
+
+impl TrainingConfig {
+    pub fn from_toml_str(contents: &str) -> Result<TrainingConfig, toml::de::Error> {
+        toml::from_str(contents)
+    }
+
+    /// Loads a training configuration from a TOML file. Relative dataset and output paths are
+    /// resolved against the directory containing the config file.
+    pub fn load(path: impl AsRef<Path>) -> Result<TrainingConfig, ConfigError> {
+        let path = path.as_ref();
+        let mut config = TrainingConfig::from_toml_str(&fs::read_to_string(path)?)?;
+
+        if let Some(dir) = path.parent() {
+            for path in [&mut config.dataset.path, &mut config.output.model, &mut config.output.metrics] {
+                *path = dir.join(&*path);
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::{LayerConfig, NetworkConfig, OptimizerConfig, TrainingConfig};
+    use crate::train::DifferentialPrivacy;
+    use crate::network::{Activation, Network, NetworkError};
+
+    #[test]
+    fn parse_toml_config() {
+        let contents = r#"
+            [[layers]]
+            type = "dense"
+            inputs = 3
+            outputs = 4
+
+            [[layers]]
+            type = "dense"
+            inputs = 4
+            outputs = 1
+            activation = "linear"
+            bias = false
+        "#;
+
+        let config = NetworkConfig::from_toml_str(contents).unwrap();
+
+        assert_eq!(config.layers, vec![
+            LayerConfig::Dense { inputs: 3, outputs: 4, activation: Activation::Relu, bias: true },
+            LayerConfig::Dense { inputs: 4, outputs: 1, activation: Activation::Linear, bias: false },
+        ]);
+    }
+
+    #[test]
+    fn config_round_trips_through_network_and_toml() {
+        let config = NetworkConfig {
+            layers: vec![
+                LayerConfig::Dense { inputs: 2, outputs: 8, activation: Activation::Relu, bias: true },
+                LayerConfig::Dense { inputs: 8, outputs: 1, activation: Activation::Linear, bias: false },
+            ],
+        };
+
+        let network: Network = Network::from_config(&config).unwrap();
+        assert_eq!(network.config(), config);
+
+        let serialized = config.to_toml_string().unwrap();
+        assert_eq!(NetworkConfig::from_toml_str(&serialized).unwrap(), config);
+    }
+
+    #[test]
+    fn dropout_and_batch_norm_layers_round_trip() {
+        let contents = r#"
+            [[layers]]
+            type = "dense"
+            inputs = 2
+            outputs = 4
+
+            [[layers]]
+            type = "batch_norm"
+            features = 4
+
+            [[layers]]
+            type = "dropout"
+            features = 4
+            p = 0.25
+
+            [[layers]]
+            type = "dense"
+            inputs = 4
+            outputs = 1
+            activation = "linear"
+        "#;
+
+        let config = NetworkConfig::from_toml_str(contents).unwrap();
+        assert_eq!(config.layers[1], LayerConfig::BatchNorm { features: 4, momentum: 0.1, epsilon: 1e-5 });
+        assert_eq!(config.layers[2], LayerConfig::Dropout { features: 4, p: 0.25 });
+
+        let network: Network = Network::from_config(&config).unwrap();
+        assert_eq!(network.config(), config);
+        assert_eq!(NetworkConfig::from_toml_str(&config.to_toml_string().unwrap()).unwrap(), config);
+
+        let invalid = NetworkConfig { layers: vec![LayerConfig::Dropout { features: 4, p: 1.0 }] };
+        assert!(matches!(Network::<f64>::from_config(&invalid), Err(NetworkError::InvalidHyperparameter { .. })));
+    }
+
+    #[test]
+    fn parse_training_config() {
+        let contents = r#"
+            [[layers]]
+            type = "dense"
+            inputs = 2
+            outputs = 1
+            activation = "linear"
+
+            [optimizer]
+            type = "sgd"
+            learning_rate = 0.1
+
+            [dataset]
+            path = "data.csv"
+
+            [training]
+            epochs = 5
+
+            [training.differential_privacy]
+            max_grad_norm = 1.0
+            noise_multiplier = 0.5
+        "#;
+
+        let config = TrainingConfig::from_toml_str(contents).unwrap();
+
+        assert_eq!(config.network.layers.len(), 1);
+        assert_eq!(config.optimizer, OptimizerConfig::Sgd { learning_rate: 0.1 });
+        assert_eq!(config.dataset.targets, 1);
+        assert_eq!(config.training.epochs, 5);
+        assert_eq!(config.training.batch_size, 1);
+        assert_eq!(config.training.differential_privacy, Some(DifferentialPrivacy { max_grad_norm: 1.0, noise_multiplier: 0.5 }));
+        assert_eq!(config.output.model.to_str(), Some("model.bin"));
+    }
+
+    #[test]
+    fn load_resolves_paths_against_the_config_directory() {
+        let dir = std::env::temp_dir().join("backprop_config_paths");
+        std::fs::create_dir_all(&dir).unwrap();
+        let metrics = std::env::temp_dir().join("backprop_metrics.csv");
+
+        let path = dir.join("model.toml");
+        let contents = format!(
+            "[[layers]]\ntype = \"dense\"\ninputs = 1\noutputs = 1\n\n[dataset]\npath = \"data.csv\"\n\n[output]\nmodel = \"out/model.bin\"\nmetrics = {:?}\n",
+            metrics.display().to_string()
+        );
+        std::fs::write(&path, contents).unwrap();
+
+        let config = TrainingConfig::load(&path).unwrap();
+        assert_eq!(config.dataset.path, dir.join("data.csv"));
+        assert_eq!(config.output.model, dir.join("out/model.bin"));
+
+        // Absolute paths are kept as they are
+        assert_eq!(config.output.metrics, metrics);
+    }
+}
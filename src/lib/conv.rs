@@ -0,0 +1,782 @@
+use rand::Rng;
+use crate::network::NetworkError;
+use crate::scalar::Scalar;
+use crate::value::Value;
+
+/// Signal is a multi-channel sequence laid out channel first: one Vec per channel, holding its values in order.
+pub type Signal<T = f64> = Vec<Vec<Value<T>>>;
+
+/// Image is a multi-channel grid laid out channel first, then by row: image[channel][row][column].
+pub type Image<T = f64> = Vec<Vec<Vec<Value<T>>>>;
+
+// Draws a weight from uniform(-k, k) with k = 1/sqrt(fan_in), so the sums over a window keep a
+// similar scale whatever the kernel and channel counts.
+fn init_weight<T: Scalar>(rng: &mut impl Rng, fan_in: usize) -> Value<T> {
+    let bound = 1.0 / (fan_in as f64).sqrt();
+
+    Value::new(T::from_f64(rng.gen_range(-bound..=bound)))
+}
+
+/// Conv1d slides `out_channels` learnable kernels over a multi-channel signal. Each kernel spans
+/// `kernel_size` consecutive positions of every input channel and moves `stride` positions at a
+/// time, and the signal is extended with `padding` zeros at both ends. See Signal for the layout.
+pub struct Conv1d<T = f64> {
+    // kernels[o][c][k] weighs position k of the window over input channel c for output channel o
+    kernels: Vec<Vec<Vec<Value<T>>>>,
+    biases: Vec<Value<T>>,
+    in_channels: usize,
+    kernel_size: usize,
+    stride: usize,
+    padding: usize,
+}
+
+impl<T: Scalar> Conv1d<T> {
+    /// Creates a convolution, failing if it would have no channels or its kernel size or stride is zero.
+    pub fn new(in_channels: usize, out_channels: usize, kernel_size: usize, stride: usize, padding: usize) -> Result<Conv1d<T>, NetworkError> {
+        if in_channels == 0 || out_channels == 0 {
+            return Err(NetworkError::EmptyLayer { inputs: in_channels as u64, outputs: out_channels as u64 });
+        }
+        if kernel_size == 0 || stride == 0 {
+            return Err(NetworkError::InvalidKernel { kernel_size, stride });
+        }
+
+        let mut rng = crate::rand::global_rng();
+        let fan_in = in_channels * kernel_size;
+
+        let kernels = (0..out_channels)
+            .map(|_| {
+                (0..in_channels)
+                    .map(|_| (0..kernel_size).map(|_| init_weight(&mut rng, fan_in)).collect())
+                    .collect()
+            })
+            .collect();
+
+        Ok(Conv1d {
+            kernels,
+            biases: (0..out_channels).map(|_| Value::new(T::from_f64(0.0))).collect(),
+            in_channels,
+            kernel_size,
+            stride,
+            padding,
+        })
+    }
+
+    pub fn in_channels(&self) -> usize {
+        self.in_channels
+    }
+
+    pub fn out_channels(&self) -> usize {
+        self.kernels.len()
+    }
+
+    pub fn kernel_size(&self) -> usize {
+        self.kernel_size
+    }
+
+    pub fn stride(&self) -> usize {
+        self.stride
+    }
+
+    pub fn padding(&self) -> usize {
+        self.padding
+    }
+
+    /// Returns the length of each output channel for inputs of `length`, failing when the padded
+    /// input is shorter than the kernel.
+    pub fn output_length(&self, length: usize) -> Result<usize, NetworkError> {
+        window_count(length, self.kernel_size, self.stride, self.padding)
+    }
+
+    /// Performs the convolution, returning one Vec per output channel.
+    pub fn forward(&self, input: &[Vec<Value<T>>]) -> Result<Signal<T>, NetworkError> {
+        if input.len() != self.in_channels {
+            return Err(NetworkError::DimensionMismatch { expected: self.in_channels, found: input.len() });
+        }
+
+        let length = input[0].len();
+        if let Some(channel) = input.iter().find(|channel| channel.len() != length) {
+            return Err(NetworkError::DimensionMismatch { expected: length, found: channel.len() });
+        }
+        let output_length = self.output_length(length)?;
+
+        let outputs = self
+            .kernels
+            .iter()
+            .zip(&self.biases)
+            .map(|(kernel, bias)| {
+                (0..output_length)
+                    .map(|position| {
+                        let start = position * self.stride;
+
+                        // Padded positions hold zeros, so they're skipped rather than multiplied
+                        let mut sum = bias.clone();
+                        for (weights, channel) in kernel.iter().zip(input) {
+                            for (offset, weight) in weights.iter().enumerate() {
+                                let index = start + offset;
+                                if index >= self.padding && index - self.padding < length {
+                                    sum = &sum + &(weight * &channel[index - self.padding]);
+                                }
+                            }
+                        }
+
+                        sum
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Ok(outputs)
+    }
+
+    /// Like forward, for raw input data.
+    pub fn forward_values(&self, input: &[Vec<T>]) -> Result<Signal<T>, NetworkError> {
+        let input: Vec<Vec<Value<T>>> = input.iter().map(|channel| channel.iter().map(|x| Value::constant(*x)).collect()).collect();
+
+        self.forward(&input)
+    }
+
+    /// Returns the kernels' weights, indexed by output channel, input channel and kernel position.
+    pub fn kernels(&self) -> Vec<Vec<Vec<T>>> {
+        self.kernels
+            .iter()
+            .map(|kernel| kernel.iter().map(|weights| weights.iter().map(|w| w.get_data()).collect()).collect())
+            .collect()
+    }
+
+    /// Overwrites the kernels' weights, e.g. with a known filter.
+    ///
+    /// Panics if `kernels` doesn't have the shape returned by kernels.
+    pub fn set_kernels(&self, kernels: &[Vec<Vec<T>>]) {
+        assert_eq!(kernels.len(), self.kernels.len(), "expected one kernel per output channel");
+
+        for (kernel, values) in self.kernels.iter().zip(kernels) {
+            assert_eq!(values.len(), self.in_channels, "expected one row of weights per input channel");
+
+            for (weights, row) in kernel.iter().zip(values) {
+                assert_eq!(row.len(), self.kernel_size, "expected kernel_size weights per row");
+
+                for (weight, value) in weights.iter().zip(row) {
+                    weight.set_data(*value);
+                }
+            }
+        }
+    }
+
+    /// Returns handles to every kernel weight followed by the biases.
+    pub fn parameters(&self) -> Vec<Value<T>> {
+        let mut parameters: Vec<Value<T>> = self.kernels.iter().flat_map(|kernel| kernel.concat()).collect();
+        parameters.extend(self.biases.iter().cloned());
+
+        parameters
+    }
+
+    pub fn num_parameters(&self) -> usize {
+        self.out_channels() * (self.in_channels * self.kernel_size + 1)
+    }
+}
+
+/// Conv2d slides `out_channels` learnable square kernels over a multi-channel image, see Image for
+/// the layout. Each kernel spans a `kernel_size` x `kernel_size` window of every input channel and
+/// moves `stride` rows or columns at a time, and the image is surrounded by `padding` rows and
+/// columns of zeros.
+pub struct Conv2d<T = f64> {
+    // kernels[o][c][y][x] weighs row y and column x of the window over input channel c for output channel o
+    kernels: Vec<Vec<Vec<Vec<Value<T>>>>>,
+    biases: Vec<Value<T>>,
+    in_channels: usize,
+    kernel_size: usize,
+    stride: usize,
+    padding: usize,
+}
+
+impl<T: Scalar> Conv2d<T> {
+    /// Creates a convolution, failing if it would have no channels or its kernel size or stride is zero.
+    pub fn new(in_channels: usize, out_channels: usize, kernel_size: usize, stride: usize, padding: usize) -> Result<Conv2d<T>, NetworkError> {
+        if in_channels == 0 || out_channels == 0 {
+            return Err(NetworkError::EmptyLayer { inputs: in_channels as u64, outputs: out_channels as u64 });
+        }
+        if kernel_size == 0 || stride == 0 {
+            return Err(NetworkError::InvalidKernel { kernel_size, stride });
+        }
+
+        let mut rng = crate::rand::global_rng();
+        let fan_in = in_channels * kernel_size * kernel_size;
+
+        let kernels = (0..out_channels)
+            .map(|_| {
+                (0..in_channels)
+                    .map(|_| {
+                        (0..kernel_size)
+                            .map(|_| (0..kernel_size).map(|_| init_weight(&mut rng, fan_in)).collect())
+                            .collect()
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Ok(Conv2d {
+            kernels,
+            biases: (0..out_channels).map(|_| Value::new(T::from_f64(0.0))).collect(),
+            in_channels,
+            kernel_size,
+            stride,
+            padding,
+        })
+    }
+
+    pub fn in_channels(&self) -> usize {
+        self.in_channels
+    }
+
+    pub fn out_channels(&self) -> usize {
+        self.kernels.len()
+    }
+
+    pub fn kernel_size(&self) -> usize {
+        self.kernel_size
+    }
+
+    pub fn stride(&self) -> usize {
+        self.stride
+    }
+
+    pub fn padding(&self) -> usize {
+        self.padding
+    }
+
+    /// Returns the (height, width) of each output channel for inputs of `height` x `width`, failing
+    /// when the padded input is smaller than the kernel.
+    pub fn output_size(&self, height: usize, width: usize) -> Result<(usize, usize), NetworkError> {
+        Ok((
+            window_count(height, self.kernel_size, self.stride, self.padding)?,
+            window_count(width, self.kernel_size, self.stride, self.padding)?,
+        ))
+    }
+
+    /// Performs the convolution, returning one grid per output channel.
+    pub fn forward(&self, input: &[Vec<Vec<Value<T>>>]) -> Result<Image<T>, NetworkError> {
+        let (height, width) = image_size(input, self.in_channels)?;
+        let (output_height, output_width) = self.output_size(height, width)?;
+
+        // Returns the input at a position of the padded image, or None for the padding zeros
+        let pixel = |channel: &Vec<Vec<Value<T>>>, y: usize, x: usize| -> Option<Value<T>> {
+            if y < self.padding || x < self.padding || y - self.padding >= height || x - self.padding >= width {
+                return None;
+            }
+
+            Some(channel[y - self.padding][x - self.padding].clone())
+        };
+
+        let outputs = self
+            .kernels
+            .iter()
+            .zip(&self.biases)
+            .map(|(kernel, bias)| {
+                (0..output_height)
+                    .map(|row| {
+                        (0..output_width)
+                            .map(|column| {
+                                let (top, left) = (row * self.stride, column * self.stride);
+
+                                let mut sum = bias.clone();
+                                for (weights, channel) in kernel.iter().zip(input) {
+                                    for (dy, weight_row) in weights.iter().enumerate() {
+                                        for (dx, weight) in weight_row.iter().enumerate() {
+                                            if let Some(x) = pixel(channel, top + dy, left + dx) {
+                                                sum = &sum + &(weight * &x);
+                                            }
+                                        }
+                                    }
+                                }
+
+                                sum
+                            })
+                            .collect()
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Ok(outputs)
+    }
+
+    /// Like forward, for raw input data.
+    pub fn forward_values(&self, input: &[Vec<Vec<T>>]) -> Result<Image<T>, NetworkError> {
+        self.forward(&image_values(input))
+    }
+
+    /// Returns the kernels' weights, indexed by output channel, input channel, row and column.
+    pub fn kernels(&self) -> Vec<Vec<Vec<Vec<T>>>> {
+        self.kernels
+            .iter()
+            .map(|kernel| {
+                kernel
+                    .iter()
+                    .map(|weights| weights.iter().map(|row| row.iter().map(|w| w.get_data()).collect()).collect())
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Overwrites the kernels' weights, e.g. with a known filter.
+    ///
+    /// Panics if `kernels` doesn't have the shape returned by kernels.
+    pub fn set_kernels(&self, kernels: &[Vec<Vec<Vec<T>>>]) {
+        assert_eq!(kernels.len(), self.kernels.len(), "expected one kernel per output channel");
+
+        for (kernel, values) in self.kernels.iter().zip(kernels) {
+            assert_eq!(values.len(), self.in_channels, "expected one grid of weights per input channel");
+
+            for (weights, grid) in kernel.iter().zip(values) {
+                assert_eq!(grid.len(), self.kernel_size, "expected kernel_size rows of weights");
+
+                for (weight_row, row) in weights.iter().zip(grid) {
+                    assert_eq!(row.len(), self.kernel_size, "expected kernel_size weights per row");
+
+                    for (weight, value) in weight_row.iter().zip(row) {
+                        weight.set_data(*value);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns handles to every kernel weight followed by the biases.
+    pub fn parameters(&self) -> Vec<Value<T>> {
+        let mut parameters: Vec<Value<T>> = self.kernels.iter().flat_map(|kernel| kernel.concat().concat()).collect();
+        parameters.extend(self.biases.iter().cloned());
+
+        parameters
+    }
+
+    pub fn num_parameters(&self) -> usize {
+        self.out_channels() * (self.in_channels * self.kernel_size * self.kernel_size + 1)
+    }
+}
+
+/// MaxPool2d downsamples each channel of an image independently, keeping the largest value of every
+/// `kernel_size` x `kernel_size` window, with windows `stride` apart. Gradients only flow to the
+/// selected values. It has no parameters.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MaxPool2d {
+    kernel_size: usize,
+    stride: usize,
+}
+
+impl MaxPool2d {
+    /// Creates a pooling layer, failing if its kernel size or stride is zero.
+    pub fn new(kernel_size: usize, stride: usize) -> Result<MaxPool2d, NetworkError> {
+        if kernel_size == 0 || stride == 0 {
+            return Err(NetworkError::InvalidKernel { kernel_size, stride });
+        }
+
+        Ok(MaxPool2d { kernel_size, stride })
+    }
+
+    pub fn kernel_size(&self) -> usize {
+        self.kernel_size
+    }
+
+    pub fn stride(&self) -> usize {
+        self.stride
+    }
+
+    /// Returns the (height, width) of each output channel for inputs of `height` x `width`.
+    pub fn output_size(&self, height: usize, width: usize) -> Result<(usize, usize), NetworkError> {
+        Ok((
+            window_count(height, self.kernel_size, self.stride, 0)?,
+            window_count(width, self.kernel_size, self.stride, 0)?,
+        ))
+    }
+
+    pub fn forward<T: Scalar>(&self, input: &[Vec<Vec<Value<T>>>]) -> Result<Image<T>, NetworkError> {
+        let (height, width) = image_size(input, input.len())?;
+        let (output_height, output_width) = self.output_size(height, width)?;
+
+        let outputs = input
+            .iter()
+            .map(|channel| {
+                (0..output_height)
+                    .map(|row| {
+                        (0..output_width)
+                            .map(|column| {
+                                let (top, left) = (row * self.stride, column * self.stride);
+
+                                let mut window = channel[top..top + self.kernel_size]
+                                    .iter()
+                                    .flat_map(|pixels| &pixels[left..left + self.kernel_size]);
+                                let first = window.next().expect("windows are never empty").clone();
+
+                                window.fold(first, |max, x| max.max(x))
+                            })
+                            .collect()
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Ok(outputs)
+    }
+}
+
+/// Flatten turns an image of a fixed (channels, height, width) shape into a flat Vec, channel by
+/// channel and row by row, so convolutional features can feed dense layers.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Flatten {
+    shape: (usize, usize, usize),
+}
+
+impl Flatten {
+    /// Creates a layer flattening images of `channels` x `height` x `width`, failing if any of them is zero.
+    pub fn new(channels: usize, height: usize, width: usize) -> Result<Flatten, NetworkError> {
+        check_shape(channels, height, width)?;
+
+        Ok(Flatten { shape: (channels, height, width) })
+    }
+
+    /// Returns the (channels, height, width) of the images the layer accepts.
+    pub fn shape(&self) -> (usize, usize, usize) {
+        self.shape
+    }
+
+    pub fn output_size(&self) -> usize {
+        let (channels, height, width) = self.shape;
+
+        channels * height * width
+    }
+
+    pub fn forward<T: Scalar>(&self, input: &[Vec<Vec<Value<T>>>]) -> Result<Vec<Value<T>>, NetworkError> {
+        let (channels, height, width) = self.shape;
+
+        let (found_height, found_width) = image_size(input, channels)?;
+        if found_height != height {
+            return Err(NetworkError::DimensionMismatch { expected: height, found: found_height });
+        }
+        if found_width != width {
+            return Err(NetworkError::DimensionMismatch { expected: width, found: found_width });
+        }
+
+        Ok(input.iter().flatten().flatten().cloned().collect())
+    }
+}
+
+/// Reshape arranges a flat Vec, e.g. the outputs of a dense layer, into an image of a fixed
+/// (channels, height, width) shape. It's the inverse of Flatten.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Reshape {
+    shape: (usize, usize, usize),
+}
+
+impl Reshape {
+    /// Creates a layer arranging `inputs` values into `channels` x `height` x `width` images, failing
+    /// if any dimension is zero or the image doesn't hold exactly `inputs` values.
+    pub fn new(inputs: usize, channels: usize, height: usize, width: usize) -> Result<Reshape, NetworkError> {
+        check_shape(channels, height, width)?;
+        if inputs != channels * height * width {
+            return Err(NetworkError::IncompatibleShape { inputs, shape: vec![channels, height, width] });
+        }
+
+        Ok(Reshape { shape: (channels, height, width) })
+    }
+
+    /// Returns the (channels, height, width) of the images the layer produces.
+    pub fn shape(&self) -> (usize, usize, usize) {
+        self.shape
+    }
+
+    pub fn num_inputs(&self) -> usize {
+        let (channels, height, width) = self.shape;
+
+        channels * height * width
+    }
+
+    pub fn forward<T: Scalar>(&self, input: &[Value<T>]) -> Result<Image<T>, NetworkError> {
+        if input.len() != self.num_inputs() {
+            return Err(NetworkError::DimensionMismatch { expected: self.num_inputs(), found: input.len() });
+        }
+
+        let (_, height, width) = self.shape;
+
+        Ok(input
+            .chunks(height * width)
+            .map(|channel| channel.chunks(width).map(|row| row.to_vec()).collect())
+            .collect())
+    }
+}
+
+fn check_shape(channels: usize, height: usize, width: usize) -> Result<(), NetworkError> {
+    if channels == 0 || height == 0 || width == 0 {
+        return Err(NetworkError::EmptyShape { shape: vec![channels, height, width] });
+    }
+
+    Ok(())
+}
+
+// Returns how many windows of `kernel_size` fit along `length` inputs padded with `padding` zeros
+// at both ends, moving `stride` at a time.
+fn window_count(length: usize, kernel_size: usize, stride: usize, padding: usize) -> Result<usize, NetworkError> {
+    let padded = length + 2 * padding;
+    if padded < kernel_size {
+        return Err(NetworkError::InputTooShort { minimum: kernel_size, found: padded });
+    }
+
+    Ok((padded - kernel_size) / stride + 1)
+}
+
+// Checks that the image has `channels` channels of the same, rectangular size and returns its
+// (height, width).
+fn image_size<T>(input: &[Vec<Vec<Value<T>>>], channels: usize) -> Result<(usize, usize), NetworkError> {
+    if input.len() != channels || channels == 0 {
+        return Err(NetworkError::DimensionMismatch { expected: channels, found: input.len() });
+    }
+
+    let height = input[0].len();
+    let width = input[0].first().map_or(0, |row| row.len());
+
+    for channel in input {
+        if channel.len() != height {
+            return Err(NetworkError::DimensionMismatch { expected: height, found: channel.len() });
+        }
+        if let Some(row) = channel.iter().find(|row| row.len() != width) {
+            return Err(NetworkError::DimensionMismatch { expected: width, found: row.len() });
+        }
+    }
+
+    Ok((height, width))
+}
+
+/// Wraps raw image data in new leaf nodes.
+pub fn image_values<T: Scalar>(input: &[Vec<Vec<T>>]) -> Image<T> {
+    input
+        .iter()
+        .map(|channel| channel.iter().map(|row| row.iter().map(|x| Value::new(*x)).collect()).collect())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::conv::{image_values, Conv1d, Conv2d, Flatten, MaxPool2d, Reshape};
+    use crate::loss::mse;
+    use crate::network::{Activation, Layer, Network, NetworkError};
+    use crate::optim::Sgd;
+    use crate::value::Value;
+
+    #[test]
+    fn output_length_follows_stride_and_padding() {
+        let conv: Conv1d = Conv1d::new(1, 1, 3, 2, 1).unwrap();
+
+        assert_eq!(conv.output_length(7).unwrap(), 4);
+        assert_eq!(conv.output_length(1).unwrap(), 1);
+        assert_eq!(Conv1d::<f64>::new(1, 1, 3, 1, 0).unwrap().output_length(2), Err(NetworkError::InputTooShort { minimum: 3, found: 2 }));
+
+        assert_eq!(Conv1d::<f64>::new(1, 1, 3, 0, 0).err(), Some(NetworkError::InvalidKernel { kernel_size: 3, stride: 0 }));
+        assert!(Conv1d::<f64>::new(0, 1, 3, 1, 0).is_err());
+    }
+
+    #[test]
+    fn applies_a_known_filter() {
+        let conv: Conv1d = Conv1d::new(2, 1, 2, 1, 1).unwrap();
+        conv.set_kernels(&[vec![vec![1.0, -1.0], vec![0.5, 0.5]]]);
+        assert_eq!(conv.kernels(), vec![vec![vec![1.0, -1.0], vec![0.5, 0.5]]]);
+
+        // With a zero on each side: [0, 1, 3, 0] and [0, 2, 4, 0]
+        let output = conv.forward_values(&[vec![1.0, 3.0], vec![2.0, 4.0]]).unwrap();
+        let data: Vec<f64> = output[0].iter().map(|y| y.get_data()).collect();
+        assert_eq!(data, vec![0.0, 1.0, 5.0]);
+
+        assert_eq!(conv.forward_values(&[vec![1.0, 3.0]]).err(), Some(NetworkError::DimensionMismatch { expected: 2, found: 1 }));
+    }
+
+    #[test]
+    fn kernel_gradients_match_numerical_gradients() {
+        let conv: Conv1d = Conv1d::new(2, 3, 3, 2, 1).unwrap();
+        assert_eq!(conv.parameters().len(), conv.num_parameters());
+
+        let input = vec![vec![0.5, -1.0, 0.25, 2.0, -0.75], vec![1.5, 0.5, -0.5, 0.0, 1.0]];
+        let loss = || {
+            let output = conv.forward_values(&input).unwrap();
+            output.concat().iter().fold(Value::new(0.0), |acc, y| acc + y * y)
+        };
+
+        loss().run_grad();
+
+        let h = 1e-6;
+        for parameter in conv.parameters() {
+            let original = parameter.get_data();
+
+            parameter.set_data(original + h);
+            let plus = loss().get_data();
+            parameter.set_data(original - h);
+            let minus = loss().get_data();
+            parameter.set_data(original);
+
+            let numerical = (plus - minus) / (2.0 * h);
+            assert!((parameter.get_gradient() - numerical).abs() < 1e-5, "{} vs {}", parameter.get_gradient(), numerical);
+        }
+    }
+
+    #[test]
+    fn learns_a_difference_filter() {
+        let conv: Conv1d = Conv1d::new(1, 1, 2, 1, 0).unwrap();
+        let optimizer = Sgd::new(0.05);
+
+        let signals: Vec<Vec<f64>> = (0..4).map(|i| (0..6).map(|t| ((t * (i + 1)) as f64 * 0.7).sin()).collect()).collect();
+
+        for _ in 0..200 {
+            for signal in &signals {
+                let target: Vec<f64> = signal.windows(2).map(|pair| pair[1] - pair[0]).collect();
+
+                optimizer.zero_grad(&conv.parameters());
+                let output = conv.forward_values(std::slice::from_ref(signal)).unwrap();
+                mse(&output[0], &target).run_grad();
+                optimizer.step(&conv.parameters());
+            }
+        }
+
+        let kernel = &conv.kernels()[0][0];
+        assert!((kernel[0] + 1.0).abs() < 0.01 && (kernel[1] - 1.0).abs() < 0.01, "{:?}", kernel);
+    }
+
+    #[test]
+    fn conv2d_output_size_and_known_filter() {
+        let conv: Conv2d = Conv2d::new(1, 1, 3, 2, 1).unwrap();
+        assert_eq!(conv.output_size(28, 28).unwrap(), (14, 14));
+
+        // A vertical edge detector over a step from 0 to 1
+        let conv: Conv2d = Conv2d::new(1, 1, 2, 1, 0).unwrap();
+        conv.set_kernels(&[vec![vec![vec![-1.0, 1.0], vec![-1.0, 1.0]]]]);
+
+        let image = vec![vec![vec![0.0, 0.0, 1.0], vec![0.0, 0.0, 1.0], vec![0.0, 0.0, 1.0]]];
+        let output = conv.forward_values(&image).unwrap();
+        let data: Vec<Vec<f64>> = output[0].iter().map(|row| row.iter().map(|y| y.get_data()).collect()).collect();
+        assert_eq!(data, vec![vec![0.0, 2.0], vec![0.0, 2.0]]);
+
+        let ragged = vec![vec![vec![0.0, 1.0], vec![0.0]]];
+        assert_eq!(conv.forward_values(&ragged).err(), Some(NetworkError::DimensionMismatch { expected: 2, found: 1 }));
+    }
+
+    #[test]
+    fn conv2d_gradients_match_numerical_gradients() {
+        let conv: Conv2d = Conv2d::new(2, 2, 2, 1, 1).unwrap();
+        assert_eq!(conv.parameters().len(), conv.num_parameters());
+
+        let input = vec![
+            vec![vec![0.5, -1.0, 0.25], vec![2.0, -0.75, 1.0]],
+            vec![vec![1.5, 0.5, -0.5], vec![0.0, 1.0, -2.0]],
+        ];
+        let loss = || {
+            let output = conv.forward_values(&input).unwrap();
+            output.concat().concat().iter().fold(Value::new(0.0), |acc, y| acc + y * y)
+        };
+
+        loss().run_grad();
+
+        let h = 1e-6;
+        for parameter in conv.parameters() {
+            let original = parameter.get_data();
+
+            parameter.set_data(original + h);
+            let plus = loss().get_data();
+            parameter.set_data(original - h);
+            let minus = loss().get_data();
+            parameter.set_data(original);
+
+            let numerical = (plus - minus) / (2.0 * h);
+            assert!((parameter.get_gradient() - numerical).abs() < 1e-5, "{} vs {}", parameter.get_gradient(), numerical);
+        }
+    }
+
+    #[test]
+    fn max_pooling_routes_gradients_to_the_maximum() {
+        let pool = MaxPool2d::new(2, 2).unwrap();
+        assert_eq!(pool.output_size(28, 28).unwrap(), (14, 14));
+
+        let image = image_values(&[vec![vec![1.0, 3.0, 0.0, 0.0], vec![2.0, -1.0, 0.5, 0.25]]]);
+        let output = pool.forward(&image).unwrap();
+        assert_eq!(output[0][0].iter().map(|y| y.get_data()).collect::<Vec<f64>>(), vec![3.0, 0.5]);
+
+        (&output[0][0][0] + &output[0][0][1]).run_grad();
+        let gradients: Vec<Vec<f64>> = image[0].iter().map(|row| row.iter().map(|x| x.get_gradient()).collect()).collect();
+        assert_eq!(gradients, vec![vec![0.0, 1.0, 0.0, 0.0], vec![0.0, 0.0, 1.0, 0.0]]);
+
+        assert!(MaxPool2d::new(2, 0).is_err());
+    }
+
+    #[test]
+    fn small_cnn_tells_lines_apart() {
+        // A few initialisations get stuck, so seed them
+        crate::rand::global_seed(7);
+        let conv: Conv2d = Conv2d::new(1, 4, 3, 1, 1).unwrap();
+        // Pools each 5x5 feature map down to its maximum
+        let pool = MaxPool2d::new(5, 1).unwrap();
+        let flatten = Flatten::new(4, 1, 1).unwrap();
+        let head: Network = Network::new(vec![Layer::dense(4, 1, Activation::Linear, true).unwrap()]).unwrap();
+        crate::rand::clear_global_seed();
+
+        let mut parameters = conv.parameters();
+        parameters.extend(head.parameters());
+        let optimizer = Sgd::new(0.05);
+
+        // 5x5 images with a single horizontal (target 1) or vertical (target -1) line
+        let line = |index: usize, horizontal: bool| -> Vec<Vec<Vec<f64>>> {
+            let grid = (0..5)
+                .map(|y| (0..5).map(|x| if (horizontal && y == index) || (!horizontal && x == index) { 1.0 } else { 0.0 }).collect())
+                .collect();
+            vec![grid]
+        };
+        let samples: Vec<(Vec<Vec<Vec<f64>>>, f64)> = (0..5)
+            .flat_map(|index| [(line(index, true), 1.0), (line(index, false), -1.0)])
+            .collect();
+
+        let predict = |image: &[Vec<Vec<f64>>]| {
+            let features = pool.forward(&conv.forward_values(image).unwrap()).unwrap();
+
+            head.forward_graph(&flatten.forward(&features).unwrap()).unwrap()
+        };
+
+        for _ in 0..20 {
+            for (image, target) in &samples {
+                optimizer.zero_grad(&parameters);
+                mse(&predict(image), &[*target]).run_grad();
+                optimizer.step(&parameters);
+            }
+        }
+
+        let errors = samples.iter().filter(|(image, target)| predict(image)[0].get_data().signum() != *target).count();
+        assert_eq!(errors, 0);
+    }
+
+    #[test]
+    fn flatten_and_reshape_round_trip() {
+        let image = image_values(&[
+            vec![vec![1.0, 2.0, 3.0], vec![4.0, 5.0, 6.0]],
+            vec![vec![7.0, 8.0, 9.0], vec![10.0, 11.0, 12.0]],
+        ]);
+
+        let flatten = Flatten::new(2, 2, 3).unwrap();
+        let flat = flatten.forward(&image).unwrap();
+        assert_eq!(flat.len(), flatten.output_size());
+        assert_eq!(flat.iter().map(|x| x.get_data()).collect::<Vec<f64>>(), (1..=12).map(|x| x as f64).collect::<Vec<f64>>());
+
+        let reshape = Reshape::new(12, 2, 2, 3).unwrap();
+        let restored = reshape.forward(&flat).unwrap();
+        assert!(restored.concat().concat().iter().zip(image.concat().concat()).all(|(a, b)| a.get_id() == b.get_id()));
+
+        // Gradients flow through both layers untouched
+        (&flat[4] * &Value::new(2.0)).run_grad();
+        assert_eq!(image[0][1][1].get_gradient(), 2.0);
+    }
+
+    #[test]
+    fn shape_errors_are_reported() {
+        assert_eq!(Flatten::new(1, 0, 3), Err(NetworkError::EmptyShape { shape: vec![1, 0, 3] }));
+        assert_eq!(Reshape::new(10, 1, 3, 3), Err(NetworkError::IncompatibleShape { inputs: 10, shape: vec![1, 3, 3] }));
+
+        let flatten = Flatten::new(1, 2, 2).unwrap();
+        let wrong_width = image_values(&[vec![vec![1.0, 2.0, 3.0], vec![4.0, 5.0, 6.0]]]);
+        assert_eq!(flatten.forward(&wrong_width).err(), Some(NetworkError::DimensionMismatch { expected: 2, found: 3 }));
+
+        let reshape = Reshape::new(4, 1, 2, 2).unwrap();
+        assert_eq!(reshape.forward(&[Value::new(1.0)]).err(), Some(NetworkError::DimensionMismatch { expected: 4, found: 1 }));
+    }
+}
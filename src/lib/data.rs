@@ -0,0 +1,159 @@
+use std::fmt;
+use std::io;
+use serde::{Deserialize, Serialize};
+
+pub mod csv;
+#[cfg(feature = "image")]
+pub mod image;
+pub mod jsonl;
+pub mod mnist;
+pub mod sampler;
+pub mod stream;
+pub mod toy;
+pub mod transforms;
+
+/// Sample is an (input, target) pair.
+pub type Sample = (Vec<f64>, Vec<f64>);
+
+/// Samples are (input, target) pairs, ready to be passed to Trainer::train_epoch.
+pub type Samples = Vec<Sample>;
+
+/// DataError represents a failure to download, read or decode a dataset.
+#[derive(Debug)]
+pub enum DataError {
+    Io(io::Error),
+
+    // The dataset files exist but their contents are malformed.
+    InvalidFormat(String),
+
+    // The dataset couldn't be downloaded, e.g. because `curl` isn't installed or the request failed.
+    Download(String),
+
+    // A label isn't one of the classes a LabelEncoder was fitted on, or is out of range for one_hot.
+    UnknownLabel(String),
+}
+
+impl fmt::Display for DataError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DataError::Io(err) => write!(f, "io error: {}", err),
+            DataError::InvalidFormat(reason) => write!(f, "invalid dataset: {}", reason),
+            DataError::Download(reason) => write!(f, "download failed: {}", reason),
+            DataError::UnknownLabel(label) => write!(f, "unknown label {}", label),
+        }
+    }
+}
+
+impl std::error::Error for DataError {}
+
+impl From<io::Error> for DataError {
+    fn from(err: io::Error) -> Self {
+        DataError::Io(err)
+    }
+}
+
+/// one_hot turns class indices into target vectors of `num_classes` elements, 1.0 at the
+/// label's index and 0.0 elsewhere.
+pub fn one_hot(labels: &[usize], num_classes: usize) -> Result<Vec<Vec<f64>>, DataError> {
+    labels
+        .iter()
+        .map(|&label| {
+            if label >= num_classes {
+                return Err(DataError::UnknownLabel(format!("{} (expected fewer than {} classes)", label, num_classes)));
+            }
+
+            let mut target = vec![0.0; num_classes];
+            target[label] = 1.0;
+            Ok(target)
+        })
+        .collect()
+}
+
+/// class_weights returns a weight per class inversely proportional to how often it appears in
+/// `labels`, n / (num_classes * count), for loss::cross_entropy. The weights of an evenly balanced
+/// dataset are all 1; classes which don't appear get a weight of 0.
+pub fn class_weights(labels: &[usize], num_classes: usize) -> Result<Vec<f64>, DataError> {
+    let mut counts = vec![0usize; num_classes];
+    for &label in labels {
+        if label >= num_classes {
+            return Err(DataError::UnknownLabel(format!("{} (expected fewer than {} classes)", label, num_classes)));
+        }
+        counts[label] += 1;
+    }
+
+    let scale = labels.len() as f64 / num_classes as f64;
+    Ok(counts.iter().map(|&count| if count == 0 { 0.0 } else { scale / count as f64 }).collect())
+}
+
+/// LabelEncoder maps string class labels to the indices a classifier predicts, and back.
+/// Store it in Experiment::labels so predictions can be decoded after loading a bundle.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LabelEncoder {
+    // classes holds each label at its index, sorted so the mapping doesn't depend on the order
+    // the labels were seen in
+    classes: Vec<String>,
+}
+
+impl LabelEncoder {
+    /// Builds an encoder over the distinct labels in `labels`.
+    pub fn fit<S: AsRef<str>>(labels: &[S]) -> LabelEncoder {
+        let mut classes: Vec<String> = labels.iter().map(|label| label.as_ref().to_string()).collect();
+        classes.sort();
+        classes.dedup();
+
+        LabelEncoder { classes }
+    }
+
+    pub fn classes(&self) -> &[String] {
+        &self.classes
+    }
+
+    pub fn num_classes(&self) -> usize {
+        self.classes.len()
+    }
+
+    pub fn encode(&self, label: &str) -> Result<usize, DataError> {
+        self.classes
+            .binary_search_by(|class| class.as_str().cmp(label))
+            .map_err(|_| DataError::UnknownLabel(label.to_string()))
+    }
+
+    pub fn encode_all<S: AsRef<str>>(&self, labels: &[S]) -> Result<Vec<usize>, DataError> {
+        labels.iter().map(|label| self.encode(label.as_ref())).collect()
+    }
+
+    /// Returns the label of class `index`, e.g. one returned by Network::predict_classes.
+    pub fn decode(&self, index: usize) -> Option<&str> {
+        self.classes.get(index).map(String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::{class_weights, one_hot, DataError, LabelEncoder};
+
+    #[test]
+    fn labels_encode_to_one_hot_targets() {
+        let labels = ["setosa", "virginica", "setosa", "versicolor"];
+        let encoder = LabelEncoder::fit(&labels);
+
+        assert_eq!(encoder.classes(), ["setosa", "versicolor", "virginica"]);
+        let indices = encoder.encode_all(&labels).unwrap();
+        assert_eq!(indices, vec![0, 2, 0, 1]);
+        assert_eq!(encoder.decode(2), Some("virginica"));
+        assert_eq!(encoder.decode(3), None);
+        assert!(matches!(encoder.encode("rose"), Err(DataError::UnknownLabel(label)) if label == "rose"));
+
+        let targets = one_hot(&indices, encoder.num_classes()).unwrap();
+        assert_eq!(targets[1], vec![0.0, 0.0, 1.0]);
+        assert_eq!(targets[3], vec![0.0, 1.0, 0.0]);
+        assert!(matches!(one_hot(&[3], 3), Err(DataError::UnknownLabel(_))));
+    }
+
+    #[test]
+    fn rare_classes_get_larger_weights() {
+        assert_eq!(class_weights(&[0, 0, 0, 1], 3).unwrap(), vec![4.0 / 9.0, 4.0 / 3.0, 0.0]);
+        assert_eq!(class_weights(&[0, 1, 1, 0], 2).unwrap(), vec![1.0, 1.0]);
+        assert!(matches!(class_weights(&[2], 2), Err(DataError::UnknownLabel(_))));
+    }
+}
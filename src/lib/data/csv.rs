@@ -0,0 +1,125 @@
+use std::fs;
+use std::path::Path;
+use crate::data::{DataError, Samples};
+
+/// Table is a numeric CSV file: an optional header row followed by rows of numbers.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Table {
+    pub header: Option<Vec<String>>,
+    pub rows: Vec<Vec<f64>>,
+}
+
+impl Table {
+    /// Splits each row into (input, target) pairs, taking the last `targets` columns as the target.
+    pub fn samples(&self, targets: usize) -> Result<Samples, DataError> {
+        self.rows.iter().enumerate().map(|(i, row)| split_row(i + 1, row, targets)).collect()
+    }
+}
+
+// split_row splits the `number`th row into an (input, target) pair, see Table::samples
+pub(crate) fn split_row(number: usize, row: &[f64], targets: usize) -> Result<(Vec<f64>, Vec<f64>), DataError> {
+    if row.len() <= targets {
+        return Err(DataError::InvalidFormat(format!(
+            "row {} has {} columns, expected at least one input and {} targets",
+            number,
+            row.len(),
+            targets,
+        )));
+    }
+
+    let (input, target) = row.split_at(row.len() - targets);
+    Ok((input.to_vec(), target.to_vec()))
+}
+
+// is_header is true for a line with a field which isn't a number
+pub(crate) fn is_header(line: &str) -> bool {
+    line.split(',').any(|field| field.trim().parse::<f64>().is_err())
+}
+
+// parse_row parses the numbers on line `number`
+pub(crate) fn parse_row(number: usize, line: &str) -> Result<Vec<f64>, DataError> {
+    line.split(',')
+        .map(|field| {
+            field
+                .trim()
+                .parse::<f64>()
+                .map_err(|_| DataError::InvalidFormat(format!("line {}: {:?} is not a number", number, field.trim())))
+        })
+        .collect()
+}
+
+/// parse reads comma separated numbers, one row per line. Blank lines are skipped, and the
+/// first line is treated as a header when any of its fields isn't a number.
+pub fn parse(contents: &str) -> Result<Table, DataError> {
+    let mut lines = contents.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()).peekable();
+
+    let header = match lines.peek() {
+        Some((_, line)) if is_header(line) => {
+            let header = line.split(',').map(|field| field.trim().to_string()).collect();
+            lines.next();
+
+            Some(header)
+        }
+        _ => None,
+    };
+
+    let rows = lines.map(|(number, line)| parse_row(number + 1, line)).collect::<Result<Vec<Vec<f64>>, DataError>>()?;
+
+    Ok(Table { header, rows })
+}
+
+/// Reads a numeric CSV file, see parse.
+pub fn read(path: impl AsRef<Path>) -> Result<Table, DataError> {
+    parse(&fs::read_to_string(path)?)
+}
+
+/// Writes rows of numbers as a CSV file, preceded by the header when one is given.
+pub fn write(path: impl AsRef<Path>, header: Option<&[String]>, rows: &[Vec<f64>]) -> Result<(), DataError> {
+    let mut output = String::new();
+
+    if let Some(header) = header {
+        output.push_str(&header.join(","));
+        output.push('\n');
+    }
+
+    for row in rows {
+        let fields: Vec<String> = row.iter().map(|value| value.to_string()).collect();
+        output.push_str(&fields.join(","));
+        output.push('\n');
+    }
+
+    fs::write(path, output)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::csv::{parse, read, write};
+
+    #[test]
+    fn parse_detects_header_and_splits_samples() {
+        let table = parse("x1, x2, y\n0.5, 1, 2\n\n-1, 0, 3.5\n").unwrap();
+
+        assert_eq!(table.header, Some(vec!["x1".to_string(), "x2".to_string(), "y".to_string()]));
+        assert_eq!(table.rows, vec![vec![0.5, 1.0, 2.0], vec![-1.0, 0.0, 3.5]]);
+        assert_eq!(table.samples(1).unwrap()[1], (vec![-1.0, 0.0], vec![3.5]));
+        assert!(table.samples(3).is_err());
+
+        assert_eq!(parse("1,2\n3,4").unwrap().header, None);
+        assert!(parse("1,2\n3,four").is_err());
+    }
+
+    #[test]
+    fn write_round_trip() {
+        let path = std::env::temp_dir().join("backprop_csv_round_trip.csv");
+        let header = vec!["a".to_string(), "b".to_string()];
+        let rows = vec![vec![1.0, 2.5], vec![-3.0, 0.125]];
+
+        write(&path, Some(&header), &rows).unwrap();
+
+        let table = read(&path).unwrap();
+        assert_eq!(table.header, Some(header));
+        assert_eq!(table.rows, rows);
+    }
+}
@@ -0,0 +1,51 @@
+use std::path::Path;
+use ::image::imageops::FilterType;
+use ::image::ImageError;
+use crate::data::DataError;
+
+/// Loads a PNG or JPEG image as grayscale, resized to `width` x `height`, and flattens it row by
+/// row with pixels scaled from 0..=255 to 0.0..=1.0, like the MNIST images in data::mnist.
+///
+/// The image is stretched to the requested size, so its aspect ratio isn't preserved.
+pub fn load_grayscale(path: impl AsRef<Path>, width: u32, height: u32) -> Result<Vec<f64>, DataError> {
+    let image = ::image::open(path).map_err(image_error)?;
+    let pixels = image.resize_exact(width, height, FilterType::Triangle).into_luma8();
+
+    Ok(pixels.as_raw().iter().map(|pixel| *pixel as f64 / 255.0).collect())
+}
+
+fn image_error(err: ImageError) -> DataError {
+    match err {
+        ImageError::IoError(err) => DataError::Io(err),
+        err => DataError::InvalidFormat(err.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::image::{GrayImage, Luma, Rgb, RgbImage};
+    use crate::data::image::load_grayscale;
+    use crate::data::DataError;
+
+    #[test]
+    fn loads_resized_normalised_pixels() {
+        let dir = std::env::temp_dir();
+
+        // Left half black, right half white
+        let path = dir.join("backprop_grayscale.png");
+        GrayImage::from_fn(8, 4, |x, _| Luma([if x < 4 { 0 } else { 255 }])).save(&path).unwrap();
+        let pixels = load_grayscale(&path, 2, 2).unwrap();
+        assert_eq!(pixels.len(), 4);
+        assert!(pixels[0] < 0.2 && pixels[2] < 0.2 && pixels[1] > 0.8 && pixels[3] > 0.8);
+
+        let path = dir.join("backprop_color.png");
+        RgbImage::from_pixel(3, 3, Rgb([255, 255, 255])).save(&path).unwrap();
+        assert_eq!(load_grayscale(&path, 3, 3).unwrap(), vec![1.0; 9]);
+
+        assert!(matches!(load_grayscale(dir.join("backprop_missing.png"), 2, 2), Err(DataError::Io(_))));
+
+        let path = dir.join("backprop_not_an_image.png");
+        std::fs::write(&path, b"not an image").unwrap();
+        assert!(matches!(load_grayscale(&path, 2, 2), Err(DataError::InvalidFormat(_))));
+    }
+}
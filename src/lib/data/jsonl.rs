@@ -0,0 +1,118 @@
+use std::fs;
+use std::path::Path;
+use serde_json::Value as Json;
+use crate::data::{DataError, Samples};
+
+/// JsonlDataset holds training samples read from JSON lines: one JSON object per line, with the
+/// input taken from a chosen set of fields and the target from another.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonlDataset {
+    pub samples: Samples,
+}
+
+impl JsonlDataset {
+    /// Reads a JSON-lines file, see parse.
+    pub fn from_path<S: AsRef<str>>(path: impl AsRef<Path>, feature_fields: &[S], target_field: &str) -> Result<JsonlDataset, DataError> {
+        JsonlDataset::parse(&fs::read_to_string(path)?, feature_fields, target_field)
+    }
+
+    /// Parses one JSON object per line. The input of each sample is the values of `feature_fields`
+    /// in order and the target is the value of `target_field`. Fields can be numbers, booleans
+    /// (0.0 or 1.0) or arrays of numbers, which are flattened, and other fields are ignored.
+    /// Blank lines are skipped.
+    pub fn parse<S: AsRef<str>>(contents: &str, feature_fields: &[S], target_field: &str) -> Result<JsonlDataset, DataError> {
+        let samples = contents
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(number, line)| {
+                let number = number + 1;
+                let record: Json = serde_json::from_str(line)
+                    .map_err(|err| DataError::InvalidFormat(format!("line {}: {}", number, err)))?;
+
+                let mut input = Vec::new();
+                for field in feature_fields {
+                    read_field(number, &record, field.as_ref(), &mut input)?;
+                }
+
+                let mut target = Vec::new();
+                read_field(number, &record, target_field, &mut target)?;
+
+                Ok((input, target))
+            })
+            .collect::<Result<Samples, DataError>>()?;
+
+        Ok(JsonlDataset { samples })
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+}
+
+// read_field appends the numbers in `field` of the record on line `number` to `values`
+fn read_field(number: usize, record: &Json, field: &str, values: &mut Vec<f64>) -> Result<(), DataError> {
+    let invalid = |reason: &str| DataError::InvalidFormat(format!("line {}: field {:?} {}", number, field, reason));
+
+    let value = match record {
+        Json::Object(fields) => fields.get(field).ok_or_else(|| invalid("is missing"))?,
+        _ => return Err(DataError::InvalidFormat(format!("line {}: expected a JSON object", number))),
+    };
+
+    match value {
+        Json::Array(items) => {
+            for item in items {
+                values.push(number_of(item).ok_or_else(|| invalid("is not an array of numbers"))?);
+            }
+        }
+        value => values.push(number_of(value).ok_or_else(|| invalid("is not a number"))?),
+    }
+
+    Ok(())
+}
+
+fn number_of(value: &Json) -> Option<f64> {
+    match value {
+        Json::Number(number) => number.as_f64(),
+        Json::Bool(flag) => Some(if *flag { 1.0 } else { 0.0 }),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::jsonl::JsonlDataset;
+    use crate::data::DataError;
+
+    #[test]
+    fn selected_fields_become_samples() {
+        let contents = concat!(
+            "{\"id\": \"a\", \"age\": 31, \"scores\": [0.5, 1.5], \"churned\": true}\n",
+            "\n",
+            "{\"scores\": [2, 3], \"age\": 45.5, \"churned\": false, \"extra\": null}\n",
+        );
+
+        let dataset = JsonlDataset::parse(contents, &["age", "scores"], "churned").unwrap();
+        assert_eq!(dataset.len(), 2);
+        assert_eq!(dataset.samples[0], (vec![31.0, 0.5, 1.5], vec![1.0]));
+        assert_eq!(dataset.samples[1], (vec![45.5, 2.0, 3.0], vec![0.0]));
+
+        let path = std::env::temp_dir().join("backprop_dataset.jsonl");
+        std::fs::write(&path, contents).unwrap();
+        assert_eq!(JsonlDataset::from_path(&path, &["age", "scores"], "churned").unwrap(), dataset);
+
+        let invalid = |contents: &str| match JsonlDataset::parse(contents, &["x"], "y") {
+            Err(DataError::InvalidFormat(reason)) => reason,
+            other => panic!("expected an invalid format, got {:?}", other),
+        };
+        assert_eq!(invalid("{\"x\": 1, \"y\": 2}\n{\"y\": 3}"), "line 2: field \"x\" is missing");
+        assert_eq!(invalid("{\"x\": \"one\", \"y\": 2}"), "line 1: field \"x\" is not a number");
+        assert_eq!(invalid("{\"x\": [1, null], \"y\": 2}"), "line 1: field \"x\" is not an array of numbers");
+        assert_eq!(invalid("[1, 2]"), "line 1: expected a JSON object");
+        assert!(invalid("{\"x\": 1,").starts_with("line 1: "));
+    }
+}
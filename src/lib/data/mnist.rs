@@ -0,0 +1,222 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use crate::archive;
+use crate::data::{DataError, Samples};
+
+const TRAIN_IMAGES: &str = "train-images-idx3-ubyte";
+const TRAIN_LABELS: &str = "train-labels-idx1-ubyte";
+const TEST_IMAGES: &str = "t10k-images-idx3-ubyte";
+const TEST_LABELS: &str = "t10k-labels-idx1-ubyte";
+
+// MIRROR serves the original MNIST files, gzip compressed.
+const MIRROR: &str = "https://ossci-datasets.s3.amazonaws.com/mnist";
+
+const IDX_TYPE_UNSIGNED_BYTE: u8 = 0x08;
+
+pub const NUM_CLASSES: usize = 10;
+
+/// MnistSplit holds the images and labels of either the training or the test set.
+/// Images are flattened row by row, with pixels scaled from 0..=255 to 0.0..=1.0.
+pub struct MnistSplit {
+    pub images: Vec<Vec<f64>>,
+    pub labels: Vec<u8>,
+    pub rows: usize,
+    pub columns: usize,
+}
+
+impl MnistSplit {
+    pub fn len(&self) -> usize {
+        self.labels.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.labels.is_empty()
+    }
+
+    /// Returns (image, target) pairs for training, where the target one-hot encodes the label.
+    pub fn samples(&self) -> Samples {
+        self.images
+            .iter()
+            .zip(&self.labels)
+            .map(|(image, label)| {
+                let mut target = vec![0.0; NUM_CLASSES];
+                target[*label as usize] = 1.0;
+
+                (image.clone(), target)
+            })
+            .collect()
+    }
+}
+
+/// Mnist is the MNIST handwritten digits dataset: 60,000 training and 10,000 test images of 28x28 pixels.
+pub struct Mnist {
+    pub train: MnistSplit,
+    pub test: MnistSplit,
+}
+
+/// load reads the MNIST IDX files from the `dir` directory.
+/// Both the original file names (e.g. `train-images-idx3-ubyte`) and their gzip compressed
+/// variants (e.g. `train-images-idx3-ubyte.gz`) are accepted.
+pub fn load(dir: impl AsRef<Path>) -> Result<Mnist, DataError> {
+    let dir = dir.as_ref();
+
+    Ok(Mnist {
+        train: load_split(dir, TRAIN_IMAGES, TRAIN_LABELS)?,
+        test: load_split(dir, TEST_IMAGES, TEST_LABELS)?,
+    })
+}
+
+/// download fetches any MNIST files missing from `dir` with `curl`, creating the directory if needed.
+/// The files are stored gzip compressed, as served, and can be read with load.
+pub fn download(dir: impl AsRef<Path>) -> Result<(), DataError> {
+    let dir = dir.as_ref();
+    fs::create_dir_all(dir)?;
+
+    for name in [TRAIN_IMAGES, TRAIN_LABELS, TEST_IMAGES, TEST_LABELS] {
+        if find_file(dir, name).is_some() {
+            continue;
+        }
+
+        let file_name = format!("{}.gz", name);
+        let output = Command::new("curl")
+            .args(["--fail", "--silent", "--show-error", "--location", "--output"])
+            .arg(dir.join(&file_name))
+            .arg(format!("{}/{}", MIRROR, file_name))
+            .output()
+            .map_err(|err| match err.kind() {
+                io::ErrorKind::NotFound => DataError::Download("curl is not installed".to_string()),
+                _ => DataError::Io(err),
+            })?;
+
+        if !output.status.success() {
+            return Err(DataError::Download(String::from_utf8_lossy(&output.stderr).trim().to_string()));
+        }
+    }
+
+    Ok(())
+}
+
+fn find_file(dir: &Path, name: &str) -> Option<PathBuf> {
+    [dir.join(name), dir.join(format!("{}.gz", name))]
+        .into_iter()
+        .find(|path| path.is_file())
+}
+
+fn read_idx_file(dir: &Path, name: &str) -> Result<(Vec<usize>, Vec<u8>), DataError> {
+    let path = find_file(dir, name).ok_or_else(|| {
+        DataError::Io(io::Error::new(io::ErrorKind::NotFound, format!("{} not found in {}", name, dir.display())))
+    })?;
+
+    let bytes = fs::read(path)?;
+    let bytes = if archive::is_gzip(&bytes) {
+        archive::gunzip(&bytes).map_err(|err| DataError::InvalidFormat(format!("{}: {}", name, err)))?
+    } else {
+        bytes
+    };
+
+    parse_idx(&bytes).map_err(|err| DataError::InvalidFormat(format!("{}: {}", name, err)))
+}
+
+fn load_split(dir: &Path, images_name: &str, labels_name: &str) -> Result<MnistSplit, DataError> {
+    let (image_shape, pixels) = read_idx_file(dir, images_name)?;
+    let (label_shape, labels) = read_idx_file(dir, labels_name)?;
+
+    let [count, rows, columns] = image_shape[..] else {
+        return Err(DataError::InvalidFormat(format!("{}: expected 3 dimensions, found {}", images_name, image_shape.len())));
+    };
+    if label_shape != [count] {
+        return Err(DataError::InvalidFormat(format!("{}: shape {:?} doesn't match {} images", labels_name, label_shape, count)));
+    }
+    if let Some(label) = labels.iter().find(|label| **label as usize >= NUM_CLASSES) {
+        return Err(DataError::InvalidFormat(format!("{}: invalid label {}", labels_name, label)));
+    }
+
+    let images = pixels
+        .chunks_exact((rows * columns).max(1))
+        .map(|image| image.iter().map(|pixel| *pixel as f64 / 255.0).collect())
+        .collect();
+
+    Ok(MnistSplit { images, labels, rows, columns })
+}
+
+/// parse_idx decodes an IDX file of unsigned bytes, returning its shape and its data in row-major order.
+pub fn parse_idx(bytes: &[u8]) -> Result<(Vec<usize>, Vec<u8>), String> {
+    if bytes.len() < 4 || bytes[0] != 0 || bytes[1] != 0 {
+        return Err("missing IDX magic number".to_string());
+    }
+    if bytes[2] != IDX_TYPE_UNSIGNED_BYTE {
+        return Err(format!("unsupported IDX data type 0x{:02x}", bytes[2]));
+    }
+
+    let dimensions = bytes[3] as usize;
+    let header_size = 4 + 4 * dimensions;
+    let header = bytes.get(4..header_size).ok_or_else(|| "unexpected end of IDX header".to_string())?;
+
+    let shape: Vec<usize> = header
+        .chunks_exact(4)
+        .map(|size| u32::from_be_bytes([size[0], size[1], size[2], size[3]]) as usize)
+        .collect();
+
+    let data = &bytes[header_size..];
+    let expected: usize = shape.iter().product();
+    if data.len() != expected {
+        return Err(format!("expected {} values for shape {:?}, found {}", expected, shape, data.len()));
+    }
+
+    Ok((shape, data.to_vec()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use crate::data::mnist::{load, parse_idx, NUM_CLASSES};
+    use crate::data::DataError;
+
+    fn idx(shape: &[u32], data: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![0, 0, 0x08, shape.len() as u8];
+        for size in shape {
+            bytes.extend_from_slice(&size.to_be_bytes());
+        }
+        bytes.extend_from_slice(data);
+
+        bytes
+    }
+
+    #[test]
+    fn parse_idx_validates_header_and_size() {
+        let (shape, data) = parse_idx(&idx(&[2, 3], &[1, 2, 3, 4, 5, 6])).unwrap();
+        assert_eq!(shape, vec![2, 3]);
+        assert_eq!(data, vec![1, 2, 3, 4, 5, 6]);
+
+        assert!(parse_idx(&idx(&[2, 3], &[1, 2, 3])).is_err());
+        assert!(parse_idx(b"not an idx file").is_err());
+    }
+
+    #[test]
+    fn load_reads_train_and_test_splits() {
+        let dir = std::env::temp_dir().join("backprop_mnist_test");
+        fs::create_dir_all(&dir).unwrap();
+
+        // Two 2x2 training images and one test image
+        fs::write(dir.join("train-images-idx3-ubyte"), idx(&[2, 2, 2], &[0, 255, 51, 0, 255, 255, 0, 0])).unwrap();
+        fs::write(dir.join("train-labels-idx1-ubyte"), idx(&[2], &[3, 9])).unwrap();
+        fs::write(dir.join("t10k-images-idx3-ubyte"), idx(&[1, 2, 2], &[0, 0, 0, 0])).unwrap();
+        fs::write(dir.join("t10k-labels-idx1-ubyte"), idx(&[1], &[0])).unwrap();
+
+        let mnist = load(&dir).unwrap();
+        assert_eq!(mnist.train.len(), 2);
+        assert_eq!(mnist.test.len(), 1);
+        assert_eq!((mnist.train.rows, mnist.train.columns), (2, 2));
+        assert_eq!(mnist.train.images[0], vec![0.0, 1.0, 0.2, 0.0]);
+
+        let samples = mnist.train.samples();
+        assert_eq!(samples[1].1.len(), NUM_CLASSES);
+        assert_eq!(samples[1].1[9], 1.0);
+
+        // Labels must match the number of images
+        fs::write(dir.join("t10k-labels-idx1-ubyte"), idx(&[2], &[0, 1])).unwrap();
+        assert!(matches!(load(&dir), Err(DataError::InvalidFormat(_))));
+    }
+}
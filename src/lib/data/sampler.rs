@@ -0,0 +1,71 @@
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::Rng;
+
+/// WeightedRandomSampler draws sample indices with replacement, each with probability
+/// proportional to its weight. Oversampling the rare classes of an imbalanced dataset this way
+/// keeps a classifier from settling on always predicting the majority class.
+#[derive(Debug, Clone)]
+pub struct WeightedRandomSampler {
+    distribution: WeightedIndex<f64>,
+
+    // num_samples is the number of indices drawn per epoch
+    pub num_samples: usize,
+}
+
+impl WeightedRandomSampler {
+    /// Panics if there are no weights, any weight is negative or not finite, or they're all 0.
+    pub fn new(weights: &[f64], num_samples: usize) -> WeightedRandomSampler {
+        let distribution = WeightedIndex::new(weights).unwrap_or_else(|err| panic!("invalid sampler weights: {}", err));
+
+        WeightedRandomSampler { distribution, num_samples }
+    }
+
+    /// Weights each sample by the inverse frequency of its class label, so every class is drawn
+    /// equally often on average. Draws as many samples per epoch as there are labels.
+    ///
+    /// Panics if there are no labels.
+    pub fn balanced(labels: &[usize]) -> WeightedRandomSampler {
+        let mut counts = vec![0usize; labels.iter().max().map_or(0, |max| max + 1)];
+        for &label in labels {
+            counts[label] += 1;
+        }
+
+        let weights: Vec<f64> = labels.iter().map(|&label| 1.0 / counts[label] as f64).collect();
+        WeightedRandomSampler::new(&weights, labels.len())
+    }
+
+    /// Draws `num_samples` indices.
+    pub fn indices<R: Rng + ?Sized>(&self, rng: &mut R) -> Vec<usize> {
+        (0..self.num_samples).map(|_| self.distribution.sample(rng)).collect()
+    }
+
+    /// Draws `num_samples` samples from `dataset`, e.g. a fresh epoch for Trainer::train_epoch.
+    /// `dataset` must have one sample per weight.
+    pub fn sample<S: Clone, R: Rng + ?Sized>(&self, dataset: &[S], rng: &mut R) -> Vec<S> {
+        self.indices(rng).into_iter().map(|index| dataset[index].clone()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use crate::data::sampler::WeightedRandomSampler;
+
+    #[test]
+    fn balanced_sampling_evens_out_classes() {
+        // Nine samples of class 0 and one of class 1
+        let labels = [0, 0, 0, 0, 0, 0, 0, 0, 0, 1];
+        let mut rng = StdRng::seed_from_u64(3);
+
+        let mut sampler = WeightedRandomSampler::balanced(&labels);
+        assert_eq!(sampler.num_samples, 10);
+        sampler.num_samples = 4000;
+
+        let minority = sampler.sample(&labels, &mut rng).iter().filter(|&&label| label == 1).count();
+        assert!((1800..2200).contains(&minority), "drew {} minority samples", minority);
+
+        let never_first = WeightedRandomSampler::new(&[0.0, 1.0, 1.0], 100);
+        assert!(never_first.indices(&mut rng).iter().all(|&index| index != 0));
+    }
+}
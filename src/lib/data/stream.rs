@@ -0,0 +1,139 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use crate::data::csv::{is_header, parse_row, split_row};
+use crate::data::{DataError, Sample, Samples};
+
+/// StreamingDataset yields samples one at a time from a source which doesn't have to fit in
+/// memory, such as a large file or a generator. Pass one to Trainer::train_epoch_stream, which
+/// only holds one optimizer step's worth of samples at a time.
+pub trait StreamingDataset {
+    /// Returns the next (input, target) sample of the current pass, None once the pass is over,
+    /// or an error if the sample can't be read.
+    fn next_sample(&mut self) -> Option<Result<Sample, DataError>>;
+
+    /// Starts a new pass from the first sample.
+    fn reset(&mut self) -> Result<(), DataError>;
+
+    /// Returns the next batch of up to `size` samples, None once the pass is over.
+    fn next_batch(&mut self, size: usize) -> Option<Result<Samples, DataError>> {
+        let mut batch = Vec::with_capacity(size);
+        while batch.len() < size.max(1) {
+            match self.next_sample() {
+                Some(Ok(sample)) => batch.push(sample),
+                Some(Err(err)) => return Some(Err(err)),
+                None => break,
+            }
+        }
+
+        if batch.is_empty() { None } else { Some(Ok(batch)) }
+    }
+}
+
+/// CsvStream reads samples from a numeric CSV file line by line, see data::csv for the format.
+/// The last `targets` columns of each row are the target.
+pub struct CsvStream {
+    path: PathBuf,
+    targets: usize,
+    lines: std::io::Lines<BufReader<File>>,
+    line: usize,
+}
+
+impl CsvStream {
+    pub fn open(path: impl AsRef<Path>, targets: usize) -> Result<CsvStream, DataError> {
+        let path = path.as_ref().to_path_buf();
+        let lines = BufReader::new(File::open(&path)?).lines();
+
+        Ok(CsvStream { path, targets, lines, line: 0 })
+    }
+}
+
+impl StreamingDataset for CsvStream {
+    fn next_sample(&mut self) -> Option<Result<Sample, DataError>> {
+        loop {
+            let line = match self.lines.next()? {
+                Ok(line) => line,
+                Err(err) => return Some(Err(err.into())),
+            };
+            self.line += 1;
+
+            if line.trim().is_empty() || (self.line == 1 && is_header(&line)) {
+                continue;
+            }
+
+            return Some(parse_row(self.line, &line).and_then(|row| split_row(self.line, &row, self.targets)));
+        }
+    }
+
+    fn reset(&mut self) -> Result<(), DataError> {
+        self.lines = BufReader::new(File::open(&self.path)?).lines();
+        self.line = 0;
+
+        Ok(())
+    }
+}
+
+/// Generator streams samples computed on demand: `generate` is called with the index of each
+/// sample in the pass and returns None after the last one, e.g. to synthesise a large dataset.
+pub struct Generator<F> {
+    generate: F,
+    index: usize,
+}
+
+impl<F: FnMut(usize) -> Option<Sample>> Generator<F> {
+    pub fn new(generate: F) -> Generator<F> {
+        Generator { generate, index: 0 }
+    }
+}
+
+impl<F: FnMut(usize) -> Option<Sample>> StreamingDataset for Generator<F> {
+    fn next_sample(&mut self) -> Option<Result<Sample, DataError>> {
+        let sample = (self.generate)(self.index)?;
+        self.index += 1;
+
+        Some(Ok(sample))
+    }
+
+    fn reset(&mut self) -> Result<(), DataError> {
+        self.index = 0;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::stream::{CsvStream, Generator, StreamingDataset};
+    use crate::data::DataError;
+
+    #[test]
+    fn csv_streams_batches_and_restarts() {
+        let path = std::env::temp_dir().join("backprop_stream.csv");
+        std::fs::write(&path, "x1,x2,y\n1,2,3\n\n4,5,6\n7,8,9\n").unwrap();
+
+        let mut stream = CsvStream::open(&path, 1).unwrap();
+        let first = stream.next_batch(2).unwrap().unwrap();
+        assert_eq!(first, vec![(vec![1.0, 2.0], vec![3.0]), (vec![4.0, 5.0], vec![6.0])]);
+        assert_eq!(stream.next_batch(2).unwrap().unwrap().len(), 1);
+        assert!(stream.next_batch(2).is_none());
+
+        stream.reset().unwrap();
+        assert_eq!(stream.next_sample().unwrap().unwrap(), (vec![1.0, 2.0], vec![3.0]));
+
+        std::fs::write(&path, "1,2\n3,x\n").unwrap();
+        let mut stream = CsvStream::open(&path, 1).unwrap();
+        assert!(stream.next_sample().unwrap().is_ok());
+        assert!(matches!(stream.next_sample(), Some(Err(DataError::InvalidFormat(reason))) if reason.starts_with("line 2")));
+    }
+
+    #[test]
+    fn generators_restart_from_the_first_sample() {
+        let mut squares = Generator::new(|i| (i < 3).then(|| (vec![i as f64], vec![(i * i) as f64])));
+
+        let targets: Vec<f64> = std::iter::from_fn(|| squares.next_sample()).map(|sample| sample.unwrap().1[0]).collect();
+        assert_eq!(targets, vec![0.0, 1.0, 4.0]);
+
+        squares.reset().unwrap();
+        assert_eq!(squares.next_batch(5).unwrap().unwrap().len(), 3);
+    }
+}
@@ -0,0 +1,124 @@
+use std::f64::consts::PI;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use crate::data::Samples;
+use crate::rand::gaussian;
+
+fn one_hot(class: usize, classes: usize) -> Vec<f64> {
+    let mut target = vec![0.0; classes];
+    target[class] = 1.0;
+
+    target
+}
+
+/// xor samples points around the four corners of the unit square, labelled 1.0 when exactly one
+/// coordinate is 1 and 0.0 otherwise. Gaussian noise with standard deviation `noise` is added to the inputs.
+pub fn xor(samples: usize, noise: f64, seed: u64) -> Samples {
+    let mut rng = StdRng::seed_from_u64(seed);
+
+    (0..samples)
+        .map(|i| {
+            let (a, b) = ((i & 1) as f64, ((i >> 1) & 1) as f64);
+            let label = if a != b { 1.0 } else { 0.0 };
+
+            (vec![a + gaussian(&mut rng, noise), b + gaussian(&mut rng, noise)], vec![label])
+        })
+        .collect()
+}
+
+/// two_moons samples two interleaving half circles, labelled 0.0 for the upper moon and 1.0 for the lower one.
+pub fn two_moons(samples: usize, noise: f64, seed: u64) -> Samples {
+    let mut rng = StdRng::seed_from_u64(seed);
+
+    (0..samples)
+        .map(|i| {
+            let t = rng.gen_range(0.0..PI);
+            let (x, y, label) = if i % 2 == 0 {
+                (t.cos(), t.sin(), 0.0)
+            } else {
+                (1.0 - t.cos(), 0.5 - t.sin(), 1.0)
+            };
+
+            (vec![x + gaussian(&mut rng, noise), y + gaussian(&mut rng, noise)], vec![label])
+        })
+        .collect()
+}
+
+/// spirals samples `classes` interleaved spiral arms with `samples_per_class` points each.
+/// Targets one-hot encode the arm, and `noise` perturbs the angle of each point.
+pub fn spirals(samples_per_class: usize, classes: usize, noise: f64, seed: u64) -> Samples {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut samples = Vec::with_capacity(samples_per_class * classes);
+
+    for class in 0..classes {
+        for i in 0..samples_per_class {
+            let radius = i as f64 / samples_per_class as f64;
+            let angle = 4.0 * radius + 2.0 * PI * class as f64 / classes as f64 + gaussian(&mut rng, noise);
+
+            samples.push((vec![radius * angle.sin(), radius * angle.cos()], one_hot(class, classes)));
+        }
+    }
+
+    samples
+}
+
+/// linear_regression samples inputs uniformly from [-1, 1] with targets `weights . x + bias`,
+/// plus gaussian noise with standard deviation `noise` on the target.
+pub fn linear_regression(samples: usize, weights: &[f64], bias: f64, noise: f64, seed: u64) -> Samples {
+    let mut rng = StdRng::seed_from_u64(seed);
+
+    (0..samples)
+        .map(|_| {
+            let x: Vec<f64> = weights.iter().map(|_| rng.gen_range(-1.0..=1.0)).collect();
+            let y = x.iter().zip(weights).map(|(x, w)| x * w).sum::<f64>() + bias;
+
+            (x, vec![y + gaussian(&mut rng, noise)])
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::toy::{linear_regression, spirals, two_moons, xor};
+    use crate::network::{Activation, Layer, Network};
+    use crate::optim::Sgd;
+    use crate::train::Trainer;
+
+    #[test]
+    fn xor_labels_corners() {
+        let samples = xor(8, 0.0, 1);
+
+        assert_eq!(samples.len(), 8);
+        assert_eq!(samples[0], (vec![0.0, 0.0], vec![0.0]));
+        assert_eq!(samples[1], (vec![1.0, 0.0], vec![1.0]));
+        assert_eq!(samples[2], (vec![0.0, 1.0], vec![1.0]));
+        assert_eq!(samples[3], (vec![1.0, 1.0], vec![0.0]));
+    }
+
+    #[test]
+    fn generators_are_seedable() {
+        assert_eq!(two_moons(20, 0.1, 7), two_moons(20, 0.1, 7));
+        assert_ne!(two_moons(20, 0.1, 7), two_moons(20, 0.1, 8));
+
+        let spirals = spirals(10, 3, 0.2, 3);
+        assert_eq!(spirals.len(), 30);
+        assert_eq!(spirals[25].1, vec![0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn linear_regression_is_learnable() {
+        let samples = linear_regression(64, &[2.0, -1.0], 0.5, 0.0, 42);
+        let network: Network = Network::new(vec![Layer::dense(2, 1, Activation::Linear, true).unwrap()]).unwrap();
+
+        let mut trainer = Trainer::new(Sgd::new(0.3));
+        trainer.batch_size = 8;
+
+        let mut loss = f64::INFINITY;
+        for _ in 0..100 {
+            loss = trainer.train_epoch(&network, &samples).unwrap();
+        }
+
+        assert!(loss < 1e-6, "loss {}", loss);
+        assert!((network.layers[0].weights()[0][0] - 2.0).abs() < 1e-3);
+    }
+}
@@ -0,0 +1,209 @@
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, RngCore, SeedableRng};
+use crate::data::stream::StreamingDataset;
+use crate::data::{DataError, Sample, Samples};
+use crate::rand::gaussian;
+
+/// Transform augments a batch of samples in place, e.g. to regularise a network by never showing
+/// it exactly the same input twice. Transforms draw their randomness from `rng`, so a seeded rng
+/// reproduces the same augmentations.
+pub trait Transform {
+    fn apply(&self, batch: &mut Samples, rng: &mut dyn RngCore);
+}
+
+/// GaussianNoise adds noise with standard deviation `std_dev` to every input value.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GaussianNoise {
+    pub std_dev: f64,
+}
+
+impl Transform for GaussianNoise {
+    fn apply(&self, batch: &mut Samples, rng: &mut dyn RngCore) {
+        for (input, _) in batch.iter_mut() {
+            for x in input.iter_mut() {
+                *x += gaussian(rng, self.std_dev);
+            }
+        }
+    }
+}
+
+/// ScaleJitter multiplies each sample's input by a factor drawn uniformly from
+/// [1 - `amount`, 1 + `amount`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScaleJitter {
+    pub amount: f64,
+}
+
+impl Transform for ScaleJitter {
+    fn apply(&self, batch: &mut Samples, rng: &mut dyn RngCore) {
+        if self.amount == 0.0 {
+            return;
+        }
+
+        for (input, _) in batch.iter_mut() {
+            let scale = rng.gen_range(1.0 - self.amount..=1.0 + self.amount);
+            input.iter_mut().for_each(|x| *x *= scale);
+        }
+    }
+}
+
+/// Mixup replaces each sample with a blend λ * a + (1 - λ) * b of itself and another sample of
+/// the batch, inputs and targets alike, with λ drawn from Beta(`alpha`, `alpha`) once per batch.
+/// Small alphas keep most blends close to one of the two samples. Targets should be one-hot (or
+/// otherwise continuous) for the blended targets to make sense.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mixup {
+    pub alpha: f64,
+}
+
+impl Transform for Mixup {
+    fn apply(&self, batch: &mut Samples, rng: &mut dyn RngCore) {
+        assert!(self.alpha > 0.0, "mixup alpha must be positive, got {}", self.alpha);
+        if batch.len() < 2 {
+            return;
+        }
+
+        let a = gamma(rng, self.alpha);
+        let lambda = a / (a + gamma(rng, self.alpha));
+
+        let mut partners: Vec<usize> = (0..batch.len()).collect();
+        partners.shuffle(rng);
+
+        let original = batch.clone();
+        let blend = |a: &[f64], b: &[f64]| a.iter().zip(b).map(|(a, b)| lambda * a + (1.0 - lambda) * b).collect();
+        for ((input, target), partner) in batch.iter_mut().zip(partners) {
+            let (other_input, other_target) = &original[partner];
+            *input = blend(input, other_input);
+            *target = blend(target, other_target);
+        }
+    }
+}
+
+/// Compose applies several transforms in order.
+#[derive(Default)]
+pub struct Compose(pub Vec<Box<dyn Transform>>);
+
+impl Transform for Compose {
+    fn apply(&self, batch: &mut Samples, rng: &mut dyn RngCore) {
+        for transform in &self.0 {
+            transform.apply(batch, rng);
+        }
+    }
+}
+
+/// Augmented applies a transform to every batch read from a stream, so
+/// Trainer::train_epoch_stream trains on freshly augmented samples each epoch.
+pub struct Augmented<S, T> {
+    pub stream: S,
+    pub transform: T,
+    rng: StdRng,
+}
+
+impl<S: StreamingDataset, T: Transform> Augmented<S, T> {
+    pub fn new(stream: S, transform: T, seed: u64) -> Augmented<S, T> {
+        Augmented { stream, transform, rng: StdRng::seed_from_u64(seed) }
+    }
+}
+
+impl<S: StreamingDataset, T: Transform> StreamingDataset for Augmented<S, T> {
+    // Single samples are passed through as they are, since transforms like mixup need a batch
+    fn next_sample(&mut self) -> Option<Result<Sample, DataError>> {
+        self.stream.next_sample()
+    }
+
+    fn reset(&mut self) -> Result<(), DataError> {
+        self.stream.reset()
+    }
+
+    fn next_batch(&mut self, size: usize) -> Option<Result<Samples, DataError>> {
+        let mut batch = match self.stream.next_batch(size)? {
+            Ok(batch) => batch,
+            Err(err) => return Some(Err(err)),
+        };
+
+        self.transform.apply(&mut batch, &mut self.rng);
+        Some(Ok(batch))
+    }
+}
+
+// gamma draws from a Gamma(shape, 1) distribution (Marsaglia and Tsang's method)
+fn gamma<R: Rng + ?Sized>(rng: &mut R, shape: f64) -> f64 {
+    if shape < 1.0 {
+        // Gamma(shape) = Gamma(shape + 1) * U^(1 / shape)
+        let u: f64 = rng.gen_range(f64::EPSILON..1.0);
+        return gamma(rng, shape + 1.0) * u.powf(1.0 / shape);
+    }
+
+    let d = shape - 1.0 / 3.0;
+    let c = 1.0 / (9.0 * d).sqrt();
+    loop {
+        let x = gaussian(rng, 1.0);
+        let v = (1.0 + c * x).powi(3);
+        if v <= 0.0 {
+            continue;
+        }
+
+        let u: f64 = rng.gen_range(f64::EPSILON..1.0);
+        if u.ln() < 0.5 * x * x + d - d * v + d * v.ln() {
+            return d * v;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use crate::data::stream::{Generator, StreamingDataset};
+    use crate::data::transforms::{Augmented, Compose, GaussianNoise, Mixup, ScaleJitter, Transform};
+
+    fn batch() -> Vec<(Vec<f64>, Vec<f64>)> {
+        (0..6).map(|i| (vec![i as f64, 1.0], vec![(i % 2) as f64, 1.0 - (i % 2) as f64])).collect()
+    }
+
+    #[test]
+    fn transforms_perturb_inputs() {
+        let mut rng = StdRng::seed_from_u64(11);
+
+        let mut noisy = batch();
+        GaussianNoise { std_dev: 0.1 }.apply(&mut noisy, &mut rng);
+        for ((input, target), (clean, clean_target)) in noisy.iter().zip(batch()) {
+            assert_eq!(*target, clean_target);
+            assert!(input.iter().zip(&clean).all(|(x, y)| x != y && (x - y).abs() < 0.6));
+        }
+
+        let mut jittered = batch();
+        ScaleJitter { amount: 0.2 }.apply(&mut jittered, &mut rng);
+        for ((input, _), (clean, _)) in jittered.iter().zip(batch()) {
+            // The whole input is scaled by the same factor
+            let scale = input[1];
+            assert!((0.8..=1.2).contains(&scale));
+            assert!((input[0] - clean[0] * scale).abs() < 1e-12);
+        }
+
+        let mut mixed = batch();
+        Compose(vec![Box::new(Mixup { alpha: 0.4 }), Box::new(GaussianNoise { std_dev: 0.0 })]).apply(&mut mixed, &mut rng);
+        for (input, target) in &mixed {
+            // Blends of one-hot targets still sum to 1, and the constant input stays constant
+            assert!((target[0] + target[1] - 1.0).abs() < 1e-12);
+            assert!((input[1] - 1.0).abs() < 1e-12);
+        }
+        assert_ne!(mixed, batch());
+    }
+
+    #[test]
+    fn augmented_streams_transform_each_batch() {
+        let samples = batch();
+        let generator = Generator::new(move |i| samples.get(i).cloned());
+        let mut stream = Augmented::new(generator, ScaleJitter { amount: 0.5 }, 5);
+
+        let first = stream.next_batch(4).unwrap().unwrap();
+        assert_eq!(first.len(), 4);
+        assert_ne!(first, batch()[..4]);
+
+        stream.reset().unwrap();
+        let again = stream.next_batch(4).unwrap().unwrap();
+        assert_ne!(first, again);
+    }
+}
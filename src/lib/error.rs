@@ -0,0 +1,33 @@
+use std::fmt;
+
+/// BackpropError represents a numerical failure in the computation graph.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BackpropError {
+    // A division's denominator was zero. The ids identify the numerator and denominator nodes.
+    DivisionByZero {
+        numerator: String,
+        denominator: String,
+    },
+
+    // An operation produced NaN or an infinite value.
+    NonFinite {
+        id: String,
+        operation: &'static str,
+        data: f64,
+    },
+}
+
+impl fmt::Display for BackpropError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BackpropError::DivisionByZero { numerator, denominator } => {
+                write!(f, "division by zero: {} / {}", numerator, denominator)
+            }
+            BackpropError::NonFinite { id, operation, data } => {
+                write!(f, "node {} (operation {}) produced non-finite value {}", id, operation, data)
+            }
+        }
+    }
+}
+
+impl std::error::Error for BackpropError {}
@@ -0,0 +1,260 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use crate::archive::{self, Entry};
+use crate::config::NetworkConfig;
+use crate::data::LabelEncoder;
+use crate::interop::{self, InteropError};
+use crate::network::{Network, NetworkError};
+
+const MANIFEST_ENTRY: &str = "manifest.json";
+const CONFIG_ENTRY: &str = "config.toml";
+const WEIGHTS_ENTRY: &str = "weights.npz";
+
+/// Experiment captures everything needed to reproduce a training run: the trained network,
+/// the seed used for randomness, preprocessing state (e.g. feature means and standard deviations)
+/// and the recorded metrics history (e.g. per-epoch loss).
+pub struct Experiment {
+    pub network: Network,
+    pub seed: Option<u64>,
+    pub preprocessing: BTreeMap<String, Vec<f64>>,
+    pub metrics: BTreeMap<String, Vec<f64>>,
+
+    // labels maps a classifier's output indices to class names
+    pub labels: Option<LabelEncoder>,
+
+    // crate_version is the version of backprop which produced the experiment, written into its
+    // bundle and checked by import_bundle
+    pub crate_version: String,
+}
+
+impl Experiment {
+    pub fn new(network: Network) -> Experiment {
+        Experiment {
+            network,
+            seed: None,
+            preprocessing: BTreeMap::new(),
+            metrics: BTreeMap::new(),
+            labels: None,
+            crate_version: env!("CARGO_PKG_VERSION").to_string(),
+        }
+    }
+}
+
+// Manifest holds the non-network parts of an experiment inside a bundle.
+#[derive(Serialize, Deserialize)]
+struct Manifest {
+    crate_version: String,
+    seed: Option<u64>,
+    preprocessing: BTreeMap<String, Vec<f64>>,
+    metrics: BTreeMap<String, Vec<f64>>,
+
+    // Bundles written before labels were added don't have them
+    #[serde(default)]
+    labels: Option<LabelEncoder>,
+}
+
+/// ExperimentError represents a failure to export or import an experiment bundle.
+#[derive(Debug)]
+pub enum ExperimentError {
+    Io(io::Error),
+    Interop(InteropError),
+    Network(NetworkError),
+
+    // The bundle is missing an entry or one of its entries could not be decoded.
+    InvalidBundle(String),
+
+    // A checkpoint was written with an optimizer, named here, whose state resume can't restore.
+    UnsupportedOptimizer(String),
+
+    // The bundle was produced by a version of backprop this one can't read, see import_bundle.
+    IncompatibleVersion { found: String, supported: String },
+}
+
+impl fmt::Display for ExperimentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExperimentError::Io(err) => write!(f, "io error: {}", err),
+            ExperimentError::Interop(err) => write!(f, "failed to restore weights: {}", err),
+            ExperimentError::Network(err) => write!(f, "failed to rebuild network: {}", err),
+            ExperimentError::InvalidBundle(reason) => write!(f, "invalid bundle: {}", reason),
+            ExperimentError::UnsupportedOptimizer(name) => write!(f, "can't resume a run trained with the {} optimizer", name),
+            ExperimentError::IncompatibleVersion { found, supported } => {
+                write!(f, "bundle was written by backprop {}, which backprop {} can't read", found, supported)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ExperimentError {}
+
+impl From<io::Error> for ExperimentError {
+    fn from(err: io::Error) -> Self {
+        ExperimentError::Io(err)
+    }
+}
+
+impl From<InteropError> for ExperimentError {
+    fn from(err: InteropError) -> Self {
+        ExperimentError::Interop(err)
+    }
+}
+
+impl From<NetworkError> for ExperimentError {
+    fn from(err: NetworkError) -> Self {
+        ExperimentError::Network(err)
+    }
+}
+
+/// Writes an experiment into a single archive containing the network config (`config.toml`),
+/// its weights (`weights.npz`) and a manifest with the seed, preprocessing state, metrics and crate version.
+pub fn export_bundle(experiment: &Experiment, path: impl AsRef<Path>) -> Result<(), ExperimentError> {
+    let manifest = Manifest {
+        crate_version: experiment.crate_version.clone(),
+        seed: experiment.seed,
+        preprocessing: experiment.preprocessing.clone(),
+        metrics: experiment.metrics.clone(),
+        labels: experiment.labels.clone(),
+    };
+
+    let manifest = serde_json::to_vec_pretty(&manifest)
+        .map_err(|err| ExperimentError::InvalidBundle(format!("failed to encode manifest: {}", err)))?;
+    let config = experiment
+        .network
+        .config()
+        .to_toml_string()
+        .map_err(|err| ExperimentError::InvalidBundle(format!("failed to encode config: {}", err)))?;
+
+    let entries = vec![
+        Entry { name: MANIFEST_ENTRY.to_string(), data: manifest },
+        Entry { name: CONFIG_ENTRY.to_string(), data: config.into_bytes() },
+        Entry { name: WEIGHTS_ENTRY.to_string(), data: interop::encode_npz(&experiment.network) },
+    ];
+
+    fs::write(path, archive::write_entries(&entries))?;
+
+    Ok(())
+}
+
+/// Restores an experiment written by export_bundle. Bundles written by a version of backprop with
+/// a different major version (minor version before 1.0), or a newer one, may use a format this
+/// version doesn't know and fail with ExperimentError::IncompatibleVersion.
+pub fn import_bundle(path: impl AsRef<Path>) -> Result<Experiment, ExperimentError> {
+    let bytes = fs::read(path)?;
+    let entries = archive::read_entries(&bytes).map_err(ExperimentError::InvalidBundle)?;
+
+    let entry = |name: &str| {
+        entries
+            .iter()
+            .find(|entry| entry.name == name)
+            .map(|entry| entry.data.as_slice())
+            .ok_or_else(|| ExperimentError::InvalidBundle(format!("missing {}", name)))
+    };
+
+    let manifest: Manifest = serde_json::from_slice(entry(MANIFEST_ENTRY)?)
+        .map_err(|err| ExperimentError::InvalidBundle(format!("failed to decode manifest: {}", err)))?;
+    check_version(&manifest.crate_version)?;
+
+    let config = String::from_utf8_lossy(entry(CONFIG_ENTRY)?);
+    let config = NetworkConfig::from_toml_str(&config)
+        .map_err(|err| ExperimentError::InvalidBundle(format!("failed to decode config: {}", err)))?;
+
+    let network = Network::from_config(&config)?;
+    interop::apply_npz(&network, &interop::decode_npz(entry(WEIGHTS_ENTRY)?)?)?;
+
+    Ok(Experiment {
+        network,
+        seed: manifest.seed,
+        preprocessing: manifest.preprocessing,
+        metrics: manifest.metrics,
+        labels: manifest.labels,
+        crate_version: manifest.crate_version,
+    })
+}
+
+// check_version fails unless a bundle written by backprop `found` has the same major version as
+// this one, or minor version before 1.0, and isn't newer
+fn check_version(found: &str) -> Result<(), ExperimentError> {
+    let supported = env!("CARGO_PKG_VERSION");
+    let parse = |version: &str| -> Option<(u64, u64)> {
+        let mut parts = version.split('.').map(|part| part.parse::<u64>().ok());
+        Some((parts.next()??, parts.next()??))
+    };
+
+    let compatible = match (parse(found), parse(supported)) {
+        (Some((0, found_minor)), Some((0, minor))) => found_minor == minor,
+        (Some((found_major, found_minor)), Some((major, minor))) => found_major == major && found_minor <= minor,
+        _ => false,
+    };
+    if !compatible {
+        return Err(ExperimentError::IncompatibleVersion { found: found.to_string(), supported: supported.to_string() });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::LabelEncoder;
+    use crate::experiment::{check_version, export_bundle, import_bundle, Experiment, ExperimentError};
+    use crate::network::{Activation, Layer, Network};
+
+    #[test]
+    fn bundle_round_trip() {
+        let path = std::env::temp_dir().join("backprop_experiment.bundle");
+
+        let network = Network{
+            layers: vec![
+                Layer::dense(2, 3, Activation::Relu, true).unwrap(),
+                Layer::dense(3, 1, Activation::Linear, false).unwrap(),
+            ],
+        };
+
+        let mut experiment = Experiment::new(network);
+        experiment.seed = Some(u64::MAX);
+        experiment.preprocessing.insert("mean".to_string(), vec![0.5, 1.5]);
+        experiment.metrics.insert("loss".to_string(), vec![0.9, 0.4, 0.1]);
+        experiment.labels = Some(LabelEncoder::fit(&["cat", "dog"]));
+
+        export_bundle(&experiment, &path).unwrap();
+        let restored = import_bundle(&path).unwrap();
+
+        assert_eq!(restored.seed, Some(u64::MAX));
+        assert_eq!(restored.preprocessing, experiment.preprocessing);
+        assert_eq!(restored.metrics, experiment.metrics);
+        assert_eq!(restored.labels, experiment.labels);
+        assert_eq!(restored.crate_version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(restored.network.config(), experiment.network.config());
+        assert_eq!(restored.network.forward(&[0.3, -0.7]).unwrap(), experiment.network.forward(&[0.3, -0.7]).unwrap());
+    }
+
+    #[test]
+    fn import_checks_the_crate_version() {
+        let path = std::env::temp_dir().join("backprop_experiment_version.bundle");
+        let network = Network::new(vec![Layer::dense(1, 1, Activation::Linear, true).unwrap()]).unwrap();
+
+        let mut experiment = Experiment::new(network);
+        experiment.crate_version = "99.0.0".to_string();
+        export_bundle(&experiment, &path).unwrap();
+        assert!(matches!(
+            import_bundle(&path),
+            Err(ExperimentError::IncompatibleVersion { found, .. }) if found == "99.0.0"
+        ));
+
+        // Patch releases share a format
+        let (major, minor) = (env!("CARGO_PKG_VERSION_MAJOR"), env!("CARGO_PKG_VERSION_MINOR"));
+        assert!(check_version(&format!("{}.{}.99", major, minor)).is_ok());
+        assert!(check_version("not a version").is_err());
+    }
+
+    #[test]
+    fn import_rejects_non_bundles() {
+        let path = std::env::temp_dir().join("backprop_not_a_bundle");
+        std::fs::write(&path, b"definitely not a zip archive").unwrap();
+
+        assert!(matches!(import_bundle(&path), Err(ExperimentError::InvalidBundle(_))));
+    }
+}
@@ -0,0 +1,174 @@
+use std::f64::consts::PI;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use crate::data::Samples;
+use crate::rand::gaussian;
+
+/// FeatureMap is a fixed, non-trainable expansion of a network's inputs. Applying one before the
+/// dense stack lets a small network fit curves which would otherwise need large hidden layers.
+pub trait FeatureMap {
+    /// Returns how many features `transform` produces for `inputs` input values.
+    fn output_size(&self, inputs: usize) -> usize;
+
+    fn transform(&self, input: &[f64]) -> Vec<f64>;
+
+    /// Expands the inputs of every (input, target) pair, leaving the targets unchanged.
+    fn transform_samples(&self, samples: &[(Vec<f64>, Vec<f64>)]) -> Samples {
+        samples.iter().map(|(input, target)| (self.transform(input), target.clone())).collect()
+    }
+}
+
+/// PolynomialFeatures expands the inputs into every monomial of degree 1 up to `degree`, e.g.
+/// [a, b] with degree 2 becomes [a, b, a², ab, b²]. With `include_bias`, a constant 1 comes first.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PolynomialFeatures {
+    pub degree: usize,
+    pub include_bias: bool,
+}
+
+impl PolynomialFeatures {
+    pub fn new(degree: usize) -> PolynomialFeatures {
+        PolynomialFeatures { degree, include_bias: false }
+    }
+
+    // Returns the input indices multiplied together by each monomial, ordered by degree and then
+    // lexicographically, with the indices of each monomial in non-decreasing order.
+    fn monomials(&self, inputs: usize) -> Vec<Vec<usize>> {
+        let mut monomials = Vec::new();
+        let mut previous: Vec<Vec<usize>> = vec![vec![]];
+
+        for _ in 0..self.degree {
+            let mut current = Vec::new();
+            for monomial in &previous {
+                let start = monomial.last().copied().unwrap_or(0);
+                for index in start..inputs {
+                    let mut next = monomial.clone();
+                    next.push(index);
+                    current.push(next);
+                }
+            }
+
+            monomials.extend(current.iter().cloned());
+            previous = current;
+        }
+
+        monomials
+    }
+}
+
+impl FeatureMap for PolynomialFeatures {
+    fn output_size(&self, inputs: usize) -> usize {
+        self.monomials(inputs).len() + self.include_bias as usize
+    }
+
+    fn transform(&self, input: &[f64]) -> Vec<f64> {
+        let mut features = Vec::with_capacity(self.output_size(input.len()));
+        if self.include_bias {
+            features.push(1.0);
+        }
+
+        for monomial in self.monomials(input.len()) {
+            features.push(monomial.iter().map(|index| input[*index]).product());
+        }
+
+        features
+    }
+}
+
+/// FourierFeatures maps the inputs x to [sin(2π Bx), cos(2π Bx)] for a fixed random matrix B whose
+/// entries are drawn from a normal distribution with standard deviation `scale`. Larger scales let
+/// the network fit higher frequency functions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FourierFeatures {
+    // frequencies holds one row of B per sin/cos pair
+    pub frequencies: Vec<Vec<f64>>,
+}
+
+impl FourierFeatures {
+    /// Draws `features` random frequencies for `inputs` input values. The same seed always
+    /// produces the same frequencies, so a trained network can be paired with its feature map again.
+    pub fn new(inputs: usize, features: usize, scale: f64, seed: u64) -> FourierFeatures {
+        let mut rng = StdRng::seed_from_u64(seed);
+
+        let frequencies = (0..features)
+            .map(|_| (0..inputs).map(|_| gaussian(&mut rng, scale)).collect())
+            .collect();
+
+        FourierFeatures { frequencies }
+    }
+}
+
+impl FeatureMap for FourierFeatures {
+    fn output_size(&self, _inputs: usize) -> usize {
+        2 * self.frequencies.len()
+    }
+
+    fn transform(&self, input: &[f64]) -> Vec<f64> {
+        let projections: Vec<f64> = self
+            .frequencies
+            .iter()
+            .map(|row| 2.0 * PI * row.iter().zip(input).map(|(b, x)| b * x).sum::<f64>())
+            .collect();
+
+        let mut features: Vec<f64> = projections.iter().map(|p| p.sin()).collect();
+        features.extend(projections.iter().map(|p| p.cos()));
+
+        features
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::features::{FeatureMap, FourierFeatures, PolynomialFeatures};
+    use crate::network::{Activation, Layer, Network};
+    use crate::optim::Sgd;
+    use crate::train::Trainer;
+
+    #[test]
+    fn polynomial_features_expand_monomials() {
+        let features = PolynomialFeatures::new(2);
+        assert_eq!(features.transform(&[2.0, 3.0]), vec![2.0, 3.0, 4.0, 6.0, 9.0]);
+        assert_eq!(features.output_size(3), 9);
+
+        let with_bias = PolynomialFeatures { degree: 3, include_bias: true };
+        assert_eq!(with_bias.transform(&[2.0]), vec![1.0, 2.0, 4.0, 8.0]);
+    }
+
+    #[test]
+    fn fourier_features_are_seeded() {
+        let features = FourierFeatures::new(2, 4, 1.0, 3);
+        assert_eq!(features, FourierFeatures::new(2, 4, 1.0, 3));
+
+        let expanded = features.transform(&[0.25, -0.5]);
+        assert_eq!(expanded.len(), features.output_size(2));
+
+        // Each sin/cos pair lies on the unit circle
+        for i in 0..4 {
+            assert!((expanded[i].powi(2) + expanded[i + 4].powi(2) - 1.0).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn linear_network_fits_a_cubic() {
+        let samples: Vec<(Vec<f64>, Vec<f64>)> = (0..16)
+            .map(|i| {
+                let x = i as f64 / 8.0 - 1.0;
+                (vec![x], vec![x * x * x - 0.5 * x])
+            })
+            .collect();
+
+        let features = PolynomialFeatures::new(3);
+        let expanded = features.transform_samples(&samples);
+
+        let network: Network = Network::new(vec![Layer::dense(3, 1, Activation::Linear, true).unwrap()]).unwrap();
+        let mut trainer = Trainer::new(Sgd::new(0.5));
+        trainer.batch_size = 16;
+
+        let mut loss = f64::INFINITY;
+        for _ in 0..500 {
+            loss = trainer.train_epoch(&network, &expanded).unwrap();
+        }
+
+        assert!(loss < 1e-4, "loss {}", loss);
+    }
+}
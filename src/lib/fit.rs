@@ -0,0 +1,218 @@
+use std::fmt;
+use crate::optimize::{minimize, MinimizeConfig};
+use crate::value::Value;
+
+/// CurveFit is the result of fitting a model to data with curve.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CurveFit {
+    pub params: Vec<f64>,
+
+    // covariance estimates the covariance of the fitted parameters, None when the data can't
+    // determine it (no more points than parameters, or parameters the model doesn't depend on)
+    pub covariance: Option<Vec<Vec<f64>>>,
+
+    // residuals are y - f(x; params) at each data point
+    pub residuals: Vec<f64>,
+    pub converged: bool,
+}
+
+impl CurveFit {
+    /// Returns the standard error of each parameter, the square root of its variance.
+    pub fn std_errors(&self) -> Option<Vec<f64>> {
+        let covariance = self.covariance.as_ref()?;
+
+        Some((0..covariance.len()).map(|i| covariance[i][i].sqrt()).collect())
+    }
+
+    /// Returns the sum of the squared residuals.
+    pub fn sum_of_squares(&self) -> f64 {
+        self.residuals.iter().map(|r| r * r).sum()
+    }
+}
+
+/// FitError represents data which can't be fitted.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FitError {
+    LengthMismatch { xs: usize, ys: usize },
+    NoData,
+}
+
+impl fmt::Display for FitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FitError::LengthMismatch { xs, ys } => write!(f, "got {} xs but {} ys", xs, ys),
+            FitError::NoData => write!(f, "there are no data points to fit"),
+        }
+    }
+}
+
+impl std::error::Error for FitError {}
+
+/// curve fits the parameters of `model_fn`, which computes y = f(x; params) from Values, to the
+/// points (xs[i], ys[i]) by least squares: it minimizes Σ (ys[i] - f(xs[i]; params))² with
+/// optimize::minimize, starting from `initial_params`. Set `config.bounds` to keep parameters
+/// within physical limits.
+///
+/// The covariance is estimated as s² (JᵀJ)⁻¹, where J is the Jacobian of the model with respect
+/// to the parameters at each point and s² = Σ residual² / (points - parameters), as in SciPy's
+/// curve_fit.
+///
+/// ```
+/// use backprop::fit;
+/// use backprop::optimize::MinimizeConfig;
+/// use backprop::value::Value;
+///
+/// // y = a * e^(b * x)
+/// let model = |x: f64, p: &[Value<f64>]| &p[0] * &(&p[1] * &Value::constant(x)).exp();
+/// let xs: [f64; 5] = [0.0, 0.5, 1.0, 1.5, 2.0];
+/// let ys: Vec<f64> = xs.iter().map(|x| 2.0 * (0.7 * x).exp()).collect();
+///
+/// let fitted = fit::curve(model, &xs, &ys, &[1.0, 0.0], &MinimizeConfig::default()).unwrap();
+/// assert!((fitted.params[0] - 2.0).abs() < 1e-4 && (fitted.params[1] - 0.7).abs() < 1e-4);
+/// ```
+pub fn curve(
+    model_fn: impl Fn(f64, &[Value<f64>]) -> Value<f64>,
+    xs: &[f64],
+    ys: &[f64],
+    initial_params: &[f64],
+    config: &MinimizeConfig,
+) -> Result<CurveFit, FitError> {
+    if xs.len() != ys.len() {
+        return Err(FitError::LengthMismatch { xs: xs.len(), ys: ys.len() });
+    }
+    if xs.is_empty() {
+        return Err(FitError::NoData);
+    }
+
+    let sum_of_squares = |params: &[Value<f64>]| {
+        xs.iter()
+            .zip(ys)
+            .map(|(x, y)| {
+                let residual = &Value::constant(*y) - &model_fn(*x, params);
+                &residual * &residual
+            })
+            .fold(Value::constant(0.0), |acc, term| acc + term)
+    };
+    let minimum = minimize(sum_of_squares, initial_params, config);
+
+    // The residuals and the Jacobian of the model at the fitted parameters
+    let params: Vec<Value<f64>> = minimum.x.iter().map(|p| Value::new(*p)).collect();
+    let mut residuals = Vec::with_capacity(xs.len());
+    let mut jacobian = Vec::with_capacity(xs.len());
+    for (x, y) in xs.iter().zip(ys) {
+        let prediction = model_fn(*x, &params);
+        residuals.push(y - prediction.get_data());
+        jacobian.push(prediction.grad_wrt(&params));
+    }
+
+    let covariance = estimate_covariance(&jacobian, &residuals);
+
+    Ok(CurveFit { params: minimum.x, covariance, residuals, converged: minimum.converged })
+}
+
+// estimate_covariance returns s² (JᵀJ)⁻¹, see curve
+fn estimate_covariance(jacobian: &[Vec<f64>], residuals: &[f64]) -> Option<Vec<Vec<f64>>> {
+    let params = jacobian.first().map_or(0, Vec::len);
+    if residuals.len() <= params {
+        return None;
+    }
+
+    let mut normal = vec![vec![0.0; params]; params];
+    for row in jacobian {
+        for i in 0..params {
+            for j in 0..params {
+                normal[i][j] += row[i] * row[j];
+            }
+        }
+    }
+
+    let variance = residuals.iter().map(|r| r * r).sum::<f64>() / (residuals.len() - params) as f64;
+    let inverse = invert(normal)?;
+
+    Some(inverse.into_iter().map(|row| row.into_iter().map(|x| x * variance).collect()).collect())
+}
+
+// invert inverts a square matrix by Gauss-Jordan elimination with partial pivoting, returning
+// None if it's singular
+fn invert(mut matrix: Vec<Vec<f64>>) -> Option<Vec<Vec<f64>>> {
+    let n = matrix.len();
+    let scale = matrix.iter().flatten().fold(0.0f64, |max, x| max.max(x.abs()));
+    let mut inverse: Vec<Vec<f64>> = (0..n).map(|i| (0..n).map(|j| if i == j { 1.0 } else { 0.0 }).collect()).collect();
+
+    for column in 0..n {
+        let pivot = (column..n).max_by(|&a, &b| matrix[a][column].abs().total_cmp(&matrix[b][column].abs()))?;
+        if matrix[pivot][column].abs() <= scale * 1e-12 {
+            return None;
+        }
+        matrix.swap(column, pivot);
+        inverse.swap(column, pivot);
+
+        let divisor = matrix[column][column];
+        matrix[column].iter_mut().for_each(|x| *x /= divisor);
+        inverse[column].iter_mut().for_each(|x| *x /= divisor);
+
+        for row in 0..n {
+            if row == column {
+                continue;
+            }
+
+            let factor = matrix[row][column];
+            for k in 0..n {
+                matrix[row][k] -= factor * matrix[column][k];
+                inverse[row][k] -= factor * inverse[column][k];
+            }
+        }
+    }
+
+    Some(inverse)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fit::{curve, FitError};
+    use crate::optimize::MinimizeConfig;
+    use crate::value::Value;
+
+    #[test]
+    fn straight_lines_match_ordinary_least_squares() {
+        // y = m * x + c, fitted to noisy points
+        let line = |x: f64, p: &[Value<f64>]| &(&p[0] * &Value::constant(x)) + &p[1];
+        let xs = [0.0, 1.0, 2.0, 3.0, 4.0];
+        let ys = [1.1, 2.9, 5.2, 6.8, 9.1];
+
+        let fitted = curve(line, &xs, &ys, &[0.0, 0.0], &MinimizeConfig::default()).unwrap();
+        assert!(fitted.converged);
+
+        // Closed form: m = Sxy / Sxx = 19.9 / 10, c = mean(y) - m * mean(x)
+        let (m, c) = (1.99, 5.02 - 1.99 * 2.0);
+        assert!((fitted.params[0] - m).abs() < 1e-6 && (fitted.params[1] - c).abs() < 1e-6);
+
+        // Var(m) = s² / Sxx and Var(c) = s² (1/n + mean(x)² / Sxx)
+        let s2 = fitted.sum_of_squares() / 3.0;
+        let covariance = fitted.covariance.as_ref().unwrap();
+        assert!((covariance[0][0] - s2 / 10.0).abs() < 1e-9);
+        assert!((covariance[1][1] - s2 * (0.2 + 0.4)).abs() < 1e-9);
+        assert!((covariance[0][1] - covariance[1][0]).abs() < 1e-12);
+        assert_eq!(fitted.std_errors().unwrap()[0], covariance[0][0].sqrt());
+
+        let residuals: f64 = fitted.residuals.iter().sum();
+        assert!(residuals.abs() < 1e-6);
+    }
+
+    #[test]
+    fn undetermined_fits_have_no_covariance() {
+        let line = |x: f64, p: &[Value<f64>]| &(&p[0] * &Value::constant(x)) + &p[1];
+
+        // Two points determine the line exactly, leaving no degrees of freedom
+        let exact = curve(line, &[0.0, 1.0], &[1.0, 3.0], &[0.0, 0.0], &MinimizeConfig::default()).unwrap();
+        assert!(exact.covariance.is_none());
+
+        // The second parameter doesn't affect the model
+        let constant = |_: f64, p: &[Value<f64>]| &p[0] * &(&p[1] * &Value::constant(0.0));
+        let degenerate = curve(constant, &[0.0, 1.0, 2.0], &[1.0, 1.0, 1.0], &[1.0, 1.0], &MinimizeConfig::default()).unwrap();
+        assert!(degenerate.covariance.is_none());
+
+        assert_eq!(curve(line, &[0.0], &[], &[0.0, 0.0], &MinimizeConfig::default()), Err(FitError::LengthMismatch { xs: 1, ys: 0 }));
+        assert_eq!(curve(line, &[], &[], &[0.0, 0.0], &MinimizeConfig::default()), Err(FitError::NoData));
+    }
+}
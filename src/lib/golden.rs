@@ -0,0 +1,306 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use serde::Deserialize;
+use crate::value::Value;
+
+// Inputs maps each fixture input name to its leaf in the built graph
+type Inputs = BTreeMap<String, Value<f64>>;
+
+/// Tolerance bounds how far a computed value may be from its reference: |found - expected| must
+/// be at most `absolute + relative * |expected|`.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct Tolerance {
+    pub absolute: f64,
+    pub relative: f64,
+}
+
+impl Default for Tolerance {
+    fn default() -> Tolerance {
+        Tolerance { absolute: 1e-6, relative: 1e-9 }
+    }
+}
+
+impl Tolerance {
+    pub fn accepts(&self, expected: f64, found: f64) -> bool {
+        (found - expected).abs() <= self.absolute + self.relative * expected.abs()
+    }
+}
+
+/// Operand is an argument of a fixture node: a named input or node, or a constant.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum Operand {
+    Name(String),
+    Constant(f64),
+}
+
+/// FixtureNode computes `name` by applying `op` to `args`. Binary ops are `+`, `-`, `*`, `/`,
+/// `max` and `min`; unary ops use the names from ValueOp::to_str, e.g. `relu` or `tanh`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct FixtureNode {
+    pub name: String,
+    pub op: String,
+    pub args: Vec<Operand>,
+}
+
+/// Expected holds the reference output and the reference gradient of the output with respect to
+/// each input.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Expected {
+    pub data: f64,
+    pub gradients: BTreeMap<String, f64>,
+}
+
+/// Fixture is a golden test: an expression written as a list of nodes over named inputs, with
+/// reference values produced by another implementation such as micrograd or PyTorch.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Fixture {
+    pub name: String,
+
+    // source describes where the reference values come from
+    #[serde(default)]
+    pub source: Option<String>,
+
+    // tolerance overrides the tolerance passed to check, for fixtures needing a tighter or
+    // looser bound
+    #[serde(default)]
+    pub tolerance: Option<Tolerance>,
+
+    pub inputs: BTreeMap<String, f64>,
+    pub nodes: Vec<FixtureNode>,
+    pub output: String,
+    pub expected: Expected,
+}
+
+/// Mismatch is a computed value outside the tolerance of its reference. `quantity` is `data` for
+/// the output, or the name of the input whose gradient differs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mismatch {
+    pub quantity: String,
+    pub expected: f64,
+    pub found: f64,
+}
+
+/// GoldenError represents a fixture which couldn't be loaded or built, or whose results don't
+/// match its reference values.
+#[derive(Debug)]
+pub enum GoldenError {
+    Io(io::Error),
+    Parse(serde_json::Error),
+
+    // A node uses an operation this harness doesn't know.
+    UnknownOp { node: String, op: String },
+
+    // A node refers to a name which isn't an input or an earlier node.
+    UnknownName { node: String, name: String },
+
+    // A node passes the wrong number of arguments to its operation.
+    WrongArity { node: String, expected: usize, found: usize },
+
+    Mismatches { fixture: String, mismatches: Vec<Mismatch> },
+}
+
+impl fmt::Display for GoldenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GoldenError::Io(err) => write!(f, "io error: {}", err),
+            GoldenError::Parse(err) => write!(f, "failed to parse fixtures: {}", err),
+            GoldenError::UnknownOp { node, op } => write!(f, "node `{}` uses unknown operation `{}`", node, op),
+            GoldenError::UnknownName { node, name } => write!(f, "node `{}` refers to unknown name `{}`", node, name),
+            GoldenError::WrongArity { node, expected, found } => {
+                write!(f, "node `{}` takes {} arguments, found {}", node, expected, found)
+            }
+            GoldenError::Mismatches { fixture, mismatches } => {
+                write!(f, "fixture `{}` differs from its reference:", fixture)?;
+                for mismatch in mismatches {
+                    write!(f, " {} expected {} found {};", mismatch.quantity, mismatch.expected, mismatch.found)?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for GoldenError {}
+
+impl From<io::Error> for GoldenError {
+    fn from(err: io::Error) -> Self {
+        GoldenError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for GoldenError {
+    fn from(err: serde_json::Error) -> Self {
+        GoldenError::Parse(err)
+    }
+}
+
+/// Loads every fixture from the `.json` files in `dir`, each holding an array of fixtures, in
+/// file name order.
+pub fn load_fixtures(dir: impl AsRef<Path>) -> Result<Vec<Fixture>, GoldenError> {
+    let mut paths: Vec<_> = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<_, _>>()?;
+    paths.retain(|path| path.extension().is_some_and(|extension| extension == "json"));
+    paths.sort();
+
+    let mut fixtures = Vec::new();
+    for path in paths {
+        let file: Vec<Fixture> = serde_json::from_str(&fs::read_to_string(path)?)?;
+        fixtures.extend(file);
+    }
+
+    Ok(fixtures)
+}
+
+impl Fixture {
+    /// Builds the fixture's graph, returning its output and its inputs by name.
+    pub fn build(&self) -> Result<(Value<f64>, Inputs), GoldenError> {
+        let inputs: Inputs = self.inputs.iter().map(|(name, data)| (name.clone(), Value::new(*data))).collect();
+        let mut values: HashMap<&str, Value<f64>> = inputs.iter().map(|(name, value)| (name.as_str(), value.clone())).collect();
+
+        for node in &self.nodes {
+            let args = node
+                .args
+                .iter()
+                .map(|arg| match arg {
+                    Operand::Constant(data) => Ok(Value::constant(*data)),
+                    Operand::Name(name) => values
+                        .get(name.as_str())
+                        .cloned()
+                        .ok_or_else(|| GoldenError::UnknownName { node: node.name.clone(), name: name.clone() }),
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+
+            let value = apply(node, &args)?;
+            value.borrow_mut().label = Some(node.name.clone());
+            values.insert(&node.name, value);
+        }
+
+        let output = values
+            .get(self.output.as_str())
+            .cloned()
+            .ok_or_else(|| GoldenError::UnknownName { node: self.output.clone(), name: self.output.clone() })?;
+
+        Ok((output, inputs))
+    }
+
+    /// Runs the forward and backward passes and compares the output and input gradients to the
+    /// reference values, within the fixture's own tolerance if it has one.
+    pub fn check(&self, tolerance: Tolerance) -> Result<(), GoldenError> {
+        let tolerance = self.tolerance.unwrap_or(tolerance);
+        let (output, inputs) = self.build()?;
+        output.run_grad();
+
+        let mut mismatches = Vec::new();
+        let mut compare = |quantity: &str, expected: f64, found: f64| {
+            if !tolerance.accepts(expected, found) {
+                mismatches.push(Mismatch { quantity: quantity.to_string(), expected, found });
+            }
+        };
+
+        compare("data", self.expected.data, output.get_data());
+        for (name, expected) in &self.expected.gradients {
+            let found = inputs.get(name).map_or(f64::NAN, |input| input.get_gradient());
+            compare(name, *expected, found);
+        }
+
+        if mismatches.is_empty() {
+            Ok(())
+        } else {
+            Err(GoldenError::Mismatches { fixture: self.name.clone(), mismatches })
+        }
+    }
+}
+
+fn apply(node: &FixtureNode, args: &[Value<f64>]) -> Result<Value<f64>, GoldenError> {
+    let arity = |expected: usize| {
+        if args.len() == expected {
+            Ok(())
+        } else {
+            Err(GoldenError::WrongArity { node: node.name.clone(), expected, found: args.len() })
+        }
+    };
+
+    let binary = ["+", "-", "*", "/", "max", "min"];
+    if binary.contains(&node.op.as_str()) {
+        arity(2)?;
+        let (a, b) = (&args[0], &args[1]);
+
+        return Ok(match node.op.as_str() {
+            "+" => a + b,
+            "-" => a - b,
+            "*" => a * b,
+            "/" => a / b,
+            "max" => a.max(b),
+            _ => a.min(b),
+        });
+    }
+
+    let unary: fn(&Value<f64>) -> Value<f64> = match node.op.as_str() {
+        "relu" => Value::relu,
+        "softplus" => Value::softplus,
+        "gelu" => Value::gelu,
+        "elu" => Value::elu,
+        "swish" => Value::swish,
+        "exp" => Value::exp,
+        "ln" => Value::ln,
+        "sqrt" => Value::sqrt,
+        "abs" => Value::abs,
+        "tanh" => Value::tanh,
+        "sigmoid" => Value::sigmoid,
+        _ => return Err(GoldenError::UnknownOp { node: node.name.clone(), op: node.op.clone() }),
+    };
+    arity(1)?;
+
+    Ok(unary(&args[0]))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::golden::{load_fixtures, Fixture, GoldenError, Tolerance};
+
+    #[test]
+    fn gradients_match_reference_implementations() {
+        let fixtures = load_fixtures(concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/golden")).unwrap();
+        assert!(fixtures.len() >= 4);
+
+        for fixture in fixtures {
+            if let Err(err) = fixture.check(Tolerance::default()) {
+                panic!("{}", err);
+            }
+        }
+    }
+
+    #[test]
+    fn reports_mismatches_and_malformed_fixtures() {
+        let fixture = |nodes: &str, gradient: f64| -> Fixture {
+            serde_json::from_str(&format!(
+                r#"{{"name": "square", "inputs": {{"x": 3.0}}, "nodes": [{}], "output": "y",
+                    "expected": {{"data": 9.0, "gradients": {{"x": {}}}}}}}"#,
+                nodes, gradient
+            ))
+            .unwrap()
+        };
+
+        let square = r#"{"name": "y", "op": "*", "args": ["x", "x"]}"#;
+        assert!(fixture(square, 6.0).check(Tolerance::default()).is_ok());
+
+        let Err(GoldenError::Mismatches { mismatches, .. }) = fixture(square, 5.0).check(Tolerance::default()) else {
+            panic!("expected a mismatch");
+        };
+        assert_eq!((mismatches[0].quantity.as_str(), mismatches[0].found), ("x", 6.0));
+        assert!(fixture(square, 5.0).check(Tolerance { absolute: 1.0, relative: 0.0 }).is_ok());
+
+        let unknown_op = r#"{"name": "y", "op": "pow", "args": ["x", 2.0]}"#;
+        assert!(matches!(fixture(unknown_op, 6.0).check(Tolerance::default()), Err(GoldenError::UnknownOp { .. })));
+        let unknown_name = r#"{"name": "y", "op": "*", "args": ["x", "z"]}"#;
+        assert!(matches!(fixture(unknown_name, 6.0).check(Tolerance::default()), Err(GoldenError::UnknownName { .. })));
+        let arity = r#"{"name": "y", "op": "relu", "args": ["x", "x"]}"#;
+        assert!(matches!(fixture(arity, 6.0).check(Tolerance::default()), Err(GoldenError::WrongArity { expected: 1, found: 2, .. })));
+    }
+}
@@ -0,0 +1,280 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+use crate::scalar::Scalar;
+use crate::value::{anomaly_detection_enabled, build_topological_graph, Value, ValueOp};
+
+// Node is the structure of a single entry in a Graph arena; its data and gradient are kept in
+// Graph::data and Graph::gradients at the same index.
+#[derive(Debug, Clone, Copy)]
+struct Node {
+    operation: ValueOp,
+
+    // requires_grad is false for frozen nodes and constants, which don't accumulate gradients
+    requires_grad: bool,
+
+    // hooked is true for nodes with backward hooks, which are called through the node's Value
+    hooked: bool,
+
+    // start and end are the range of Graph::ancestors holding the indices of the node's ancestors
+    start: usize,
+    end: usize,
+}
+
+/// Graph is a Value graph compiled into an arena: every node is an index into a few contiguous
+/// Vecs, in topological order, with its ancestors stored as indices rather than Rc pointers.
+///
+/// Passes over the arena read and write plain slices rather than borrowing a RefCell per node
+/// and ancestor, and need neither sorting nor allocation, so a graph whose structure doesn't
+/// change can be run many times cheaply. The Values stay the source of truth: forward reads the
+/// data of the leaves from their Values and writes every result back, and backward does the same
+/// with gradients. See GradPlan, which wraps a Graph for training loops.
+#[derive(Debug)]
+pub struct Graph<T> {
+    data: Vec<T>,
+    gradients: Vec<f64>,
+    nodes: Vec<Node>,
+    ancestors: Vec<usize>,
+    values: Vec<Value<T>>,
+
+    // operands holds the data of a node's ancestors while it's evaluated
+    operands: Vec<T>,
+}
+
+impl<T: Scalar> Graph<T> {
+    /// Compiles the graph of `root` and its ancestors. Backward hooks have to be registered before
+    /// compiling to be called.
+    pub fn compile(root: &Value<T>) -> Graph<T> {
+        let order = build_topological_graph(root);
+        let indices: HashMap<_, usize> = order.iter().enumerate().map(|(index, node)| (Rc::as_ptr(node), index)).collect();
+
+        let mut graph = Graph {
+            data: Vec::with_capacity(order.len()),
+            gradients: Vec::with_capacity(order.len()),
+            nodes: Vec::with_capacity(order.len()),
+            ancestors: vec![],
+            values: Vec::with_capacity(order.len()),
+            operands: vec![],
+        };
+
+        for node in order {
+            {
+                let inner = node.borrow();
+                let start = graph.ancestors.len();
+                graph.ancestors.extend(inner.ancestors.iter().map(|ancestor| indices[&Rc::as_ptr(ancestor)]));
+
+                graph.data.push(inner.data);
+                graph.gradients.push(inner.gradient);
+                graph.nodes.push(Node {
+                    operation: inner.operation,
+                    requires_grad: inner.requires_grad && inner.operation != ValueOp::Const,
+                    hooked: !inner.backward_hooks.is_empty(),
+                    start,
+                    end: graph.ancestors.len(),
+                });
+            }
+
+            graph.values.push(Value(node));
+        }
+
+        graph
+    }
+
+    /// Returns the number of nodes in the graph.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn root(&self) -> &Value<T> {
+        &self.values[self.values.len() - 1]
+    }
+
+    /// Returns the Values of the graph's nodes in topological order, ending with the root.
+    pub fn values(&self) -> &[Value<T>] {
+        &self.values
+    }
+
+    fn is_leaf(&self, index: usize) -> bool {
+        self.nodes[index].start == self.nodes[index].end
+    }
+
+    /// Recomputes the data of every operation from its ancestors, picking up leaves changed with
+    /// set_data since the graph was compiled or last run.
+    pub fn forward(&mut self) {
+        for index in 0..self.nodes.len() {
+            let Node { operation, start, end, .. } = self.nodes[index];
+
+            if start == end {
+                self.data[index] = self.values[index].get_data();
+                continue;
+            }
+
+            self.operands.clear();
+            self.operands.extend(self.ancestors[start..end].iter().map(|&ancestor| self.data[ancestor]));
+            self.data[index] = operation.evaluate(&self.operands);
+
+            self.values[index].set_data(self.data[index]);
+            self.values[index].check_anomaly();
+        }
+    }
+
+    /// Backpropagates from the root like Value::run_grad. The gradients of intermediate nodes are
+    /// reset first, as they belong to the previous pass; leaves keep accumulating, as with
+    /// run_grad, so parameters still need zeroing between steps.
+    pub fn backward(&mut self) {
+        for index in 0..self.nodes.len() {
+            if self.is_leaf(index) {
+                let value = &self.values[index];
+                self.gradients[index] = value.get_gradient();
+                self.nodes[index].requires_grad = value.requires_grad() && !value.is_constant();
+            } else {
+                self.gradients[index] = 0.0;
+            }
+        }
+
+        let root = self.nodes.len() - 1;
+        self.gradients[root] = 1.0;
+
+        for index in (0..self.nodes.len()).rev() {
+            let Node { operation, hooked, start, end, .. } = self.nodes[index];
+            let gradient = self.gradients[index];
+
+            // Every node which depends on this one has already been visited, so its gradient is final
+            if hooked {
+                self.values[index].set_gradient(gradient);
+                self.values[index].call_backward_hooks();
+            }
+
+            let (data, gradients, nodes) = (&self.data, &mut self.gradients, &self.nodes);
+            let ancestors = &self.ancestors[start..end];
+
+            operation.backpropagate(ancestors.len(), |k| data[ancestors[k]].to_f64(), gradient, |k, delta| {
+                if nodes[ancestors[k]].requires_grad {
+                    gradients[ancestors[k]] += delta;
+                }
+            });
+
+            if anomaly_detection_enabled() {
+                self.check_gradients(index);
+            }
+        }
+
+        for (value, gradient) in self.values.iter().zip(&self.gradients) {
+            value.set_gradient(*gradient);
+        }
+    }
+
+    /// Runs forward then backward.
+    pub fn run(&mut self) {
+        self.forward();
+        self.backward();
+    }
+
+    // check_gradients panics if backpropagating through the node at `index` gave any of its
+    // ancestors a NaN or infinite gradient.
+    fn check_gradients(&self, index: usize) {
+        let Node { operation, start, end, .. } = self.nodes[index];
+
+        for &ancestor in &self.ancestors[start..end] {
+            let gradient = self.gradients[ancestor];
+            if !gradient.is_finite() {
+                panic!(
+                    "anomaly detected: backward pass of node {} (operation {}) produced a non-finite gradient ({}) for ancestor {}",
+                    self.values[index].get_id(),
+                    operation.to_str(),
+                    gradient,
+                    self.values[ancestor].get_id(),
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::rc::Rc;
+    use crate::graph::Graph;
+    use crate::value::{axpy, dot_values, Value};
+
+    // build uses every operation, with some nodes used more than once
+    fn build(x: &Value<f64>, w: &Value<f64>, b: &Value<f64>) -> Value<f64> {
+        let y = &(x * w) + b;
+        let z = &(&y / &w.sigmoid()) - &y.leaky_relu(0.1);
+        let u = z.tanh().max(&y.relu()) + z.gelu().min(&x.exp());
+        let v = dot_values(&[u.clone(), x.clone()], &[w.clone(), u.clone()]);
+
+        axpy(2.0, &[v.softplus()], &[(&u * &u).abs().sqrt().ln()]).remove(0)
+    }
+
+    #[test]
+    fn passes_match_value() {
+        let (x, w, b) = (Value::constant(0.7), Value::new(-1.3), Value::new(0.4));
+        let (x2, w2, b2) = (Value::constant(0.7), Value::new(-1.3), Value::new(0.4));
+
+        let mut graph = Graph::compile(&build(&x, &w, &b));
+
+        for (input, weight) in [(0.7, -1.3), (-0.2, 0.9), (1.5, 0.3)] {
+            x.set_data(input);
+            w.set_data(weight);
+            x2.set_data(input);
+            w2.set_data(weight);
+
+            graph.run();
+
+            let expected = build(&x2, &w2, &b2);
+            expected.run_grad();
+
+            assert_eq!(graph.root().get_data(), expected.get_data());
+            assert_eq!(w.get_gradient(), w2.get_gradient());
+            assert_eq!(b.get_gradient(), b2.get_gradient());
+            assert_eq!(x.get_gradient(), 0.0);
+        }
+    }
+
+    #[test]
+    fn backward_respects_frozen_nodes_and_hooks() {
+        let a = Value::new(2.0);
+        let b = Value::new(3.0);
+        let c = &a * &b;
+
+        let seen = Rc::new(Cell::new(0.0));
+        let recorded = Rc::clone(&seen);
+        c.register_backward_hook(move |gradient| recorded.set(gradient));
+
+        let mut graph = Graph::compile(&(&c + &a));
+        assert_eq!(graph.len(), 4);
+
+        b.set_requires_grad(false);
+        graph.run();
+
+        assert_eq!(seen.get(), 1.0);
+        assert_eq!(a.get_gradient(), 4.0);
+        assert_eq!(b.get_gradient(), 0.0);
+
+        // Leaf gradients accumulate across passes, like run_grad
+        graph.backward();
+        assert_eq!(a.get_gradient(), 8.0);
+    }
+
+    #[test]
+    fn backward_pass_harder_case() {
+        let a = Value::new(2.0);
+        let b = Value::new(3.0);
+
+        let c = &a + &b;          // c = a + b
+        let d = &c * &(&b - &a);  // d = c * (b - a)
+        let z = &d / &b;          // z = d / b
+
+        let mut graph = Graph::compile(&z);
+        graph.backward();
+
+        assert_eq!(z.get_gradient(), 1.0);
+        assert_eq!((c.get_gradient() * 100.0).round() / 100.0, 0.33);
+        assert_eq!((b.get_gradient() * 100.0).round() / 100.0, 1.44);
+        assert_eq!((a.get_gradient() * 100.0).round() / 100.0, -1.33);
+    }
+}
@@ -0,0 +1,297 @@
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::scalar::Scalar;
+
+#[cfg(feature = "std")]
+use crate::network::{Activation, Network};
+
+/// InferenceActivation mirrors network::Activation for the inference core, which can't depend on
+/// the computation graph.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InferenceActivation {
+    Relu,
+    Linear,
+    LeakyRelu { alpha: f64 },
+    Softplus,
+    Gelu,
+    Elu,
+    Swish,
+}
+
+impl InferenceActivation {
+    pub fn apply<T: Scalar>(&self, x: T) -> T {
+        let x = x.to_f64();
+
+        let y = match *self {
+            InferenceActivation::Relu => if x > 0.0 { x } else { 0.0 },
+            InferenceActivation::Linear => x,
+            InferenceActivation::LeakyRelu { alpha } => if x > 0.0 { x } else { alpha * x },
+            // Past 30, ln(1 + e^x) equals x to f64 precision and e^x would only lose accuracy
+            InferenceActivation::Softplus => if x > 30.0 { x } else { math::ln(1.0 + math::exp(x)) },
+            InferenceActivation::Gelu => {
+                let inner = 0.7978845608028654 * (x + 0.044715 * x * x * x);
+                0.5 * x * (1.0 + math::tanh(inner))
+            }
+            InferenceActivation::Elu => if x > 0.0 { x } else { math::exp(x) - 1.0 },
+            InferenceActivation::Swish => x / (1.0 + math::exp(-x)),
+        };
+
+        T::from_f64(y)
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<Activation> for InferenceActivation {
+    fn from(activation: Activation) -> InferenceActivation {
+        match activation {
+            Activation::Relu => InferenceActivation::Relu,
+            Activation::Linear => InferenceActivation::Linear,
+            Activation::LeakyRelu { alpha } => InferenceActivation::LeakyRelu { alpha },
+            Activation::Softplus => InferenceActivation::Softplus,
+            Activation::Gelu => InferenceActivation::Gelu,
+            Activation::Elu => InferenceActivation::Elu,
+            Activation::Swish => InferenceActivation::Swish,
+        }
+    }
+}
+
+/// InferenceError represents an invalid inference network or input.
+#[derive(Debug, Clone, PartialEq)]
+pub enum InferenceError {
+    // The network has no layers.
+    EmptyNetwork,
+
+    // A layer's weights don't split into rows of num_inputs values, or its biases don't match its
+    // number of outputs.
+    InvalidLayer {
+        index: usize,
+    },
+
+    // The input, or the previous layer's output, doesn't have the size a layer expects.
+    DimensionMismatch {
+        expected: usize,
+        found: usize,
+    },
+}
+
+impl fmt::Display for InferenceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InferenceError::EmptyNetwork => write!(f, "network has no layers"),
+            InferenceError::InvalidLayer { index } => write!(f, "layer {} has inconsistent weights or biases", index),
+            InferenceError::DimensionMismatch { expected, found } => {
+                write!(f, "expected {} inputs, found {}", expected, found)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for InferenceError {}
+
+/// InferenceLayer is a dense layer over owned weights, without the Rc/RefCell graph Layer builds.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InferenceLayer<T = f64> {
+    // weights holds one row of num_inputs values per output, row after row
+    weights: Vec<T>,
+    biases: Option<Vec<T>>,
+    num_inputs: usize,
+    activation: InferenceActivation,
+}
+
+impl<T: Scalar> InferenceLayer<T> {
+    /// Creates a layer from row-major weights, failing with InvalidLayer (index 0) if they don't
+    /// split into whole rows of num_inputs values or the biases don't match the number of rows.
+    pub fn new(weights: Vec<T>, biases: Option<Vec<T>>, num_inputs: usize, activation: InferenceActivation) -> Result<InferenceLayer<T>, InferenceError> {
+        let layer = InferenceLayer { weights, biases, num_inputs, activation };
+        if !layer.is_valid() {
+            return Err(InferenceError::InvalidLayer { index: 0 });
+        }
+
+        Ok(layer)
+    }
+
+    fn is_valid(&self) -> bool {
+        let whole_rows = self.num_inputs > 0 && !self.weights.is_empty() && self.weights.len().is_multiple_of(self.num_inputs);
+
+        whole_rows && self.biases.as_ref().is_none_or(|biases| biases.len() == self.num_outputs())
+    }
+
+    pub fn num_inputs(&self) -> usize {
+        self.num_inputs
+    }
+
+    pub fn num_outputs(&self) -> usize {
+        self.weights.len() / self.num_inputs
+    }
+
+    pub fn activation(&self) -> InferenceActivation {
+        self.activation
+    }
+
+    pub fn weights(&self) -> &[T] {
+        &self.weights
+    }
+
+    pub fn biases(&self) -> Option<&[T]> {
+        self.biases.as_deref()
+    }
+
+    fn forward(&self, inputs: &[T]) -> Vec<T> {
+        self.weights
+            .chunks(self.num_inputs)
+            .enumerate()
+            .map(|(output, row)| {
+                let bias = self.biases.as_ref().map_or(T::from_f64(0.0), |biases| biases[output]);
+
+                self.activation.apply(crate::kernels::dot(row, inputs) + bias)
+            })
+            .collect()
+    }
+}
+
+/// InferenceNetwork runs the forward pass of a trained network without the standard library, for
+/// embedded targets. It only needs `alloc`; build with `--no-default-features --features no_std`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InferenceNetwork<T = f64> {
+    layers: Vec<InferenceLayer<T>>,
+}
+
+impl<T: Scalar> InferenceNetwork<T> {
+    /// Creates a network, failing if it has no layers or consecutive layers don't fit together.
+    pub fn new(layers: Vec<InferenceLayer<T>>) -> Result<InferenceNetwork<T>, InferenceError> {
+        if layers.is_empty() {
+            return Err(InferenceError::EmptyNetwork);
+        }
+
+        for (index, layer) in layers.iter().enumerate() {
+            if !layer.is_valid() {
+                return Err(InferenceError::InvalidLayer { index });
+            }
+        }
+
+        for pair in layers.windows(2) {
+            if pair[0].num_outputs() != pair[1].num_inputs() {
+                return Err(InferenceError::DimensionMismatch { expected: pair[1].num_inputs(), found: pair[0].num_outputs() });
+            }
+        }
+
+        Ok(InferenceNetwork { layers })
+    }
+
+    /// Copies the current weights of a trained network.
+    #[cfg(feature = "std")]
+    pub fn from_network(network: &Network<T>) -> InferenceNetwork<T> {
+        let layers = network
+            .layers
+            .iter()
+            .map(|layer| InferenceLayer {
+                weights: layer.weights().concat(),
+                biases: layer.biases(),
+                num_inputs: layer.num_inputs() as usize,
+                activation: layer.activation().into(),
+            })
+            .collect();
+
+        InferenceNetwork { layers }
+    }
+
+    pub fn layers(&self) -> &[InferenceLayer<T>] {
+        &self.layers
+    }
+
+    pub fn forward(&self, inputs: &[T]) -> Result<Vec<T>, InferenceError> {
+        let expected = self.layers[0].num_inputs();
+        if inputs.len() != expected {
+            return Err(InferenceError::DimensionMismatch { expected, found: inputs.len() });
+        }
+
+        let mut result = inputs.to_vec();
+        for layer in &self.layers {
+            result = layer.forward(&result);
+        }
+
+        Ok(result)
+    }
+}
+
+/// softmax turns raw logits into probabilities which sum to 1, as e^x_i / Σ e^x_j, without
+/// building a graph. The largest logit is subtracted first so large logits don't overflow.
+///
+/// Panics if there are no logits.
+pub fn softmax(logits: &[f64]) -> Vec<f64> {
+    assert!(!logits.is_empty(), "softmax needs at least one logit");
+
+    let max = logits.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let exps: Vec<f64> = logits.iter().map(|x| math::exp(x - max)).collect();
+    let sum: f64 = exps.iter().sum();
+
+    exps.iter().map(|e| e / sum).collect()
+}
+
+// math picks the standard library's float functions, or libm's without it, as core has neither
+mod math {
+    #[cfg(feature = "std")]
+    pub fn exp(x: f64) -> f64 {
+        x.exp()
+    }
+
+    #[cfg(feature = "std")]
+    pub fn ln(x: f64) -> f64 {
+        x.ln()
+    }
+
+    #[cfg(feature = "std")]
+    pub fn tanh(x: f64) -> f64 {
+        x.tanh()
+    }
+
+    #[cfg(not(feature = "std"))]
+    pub use libm::{exp, log as ln, tanh};
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use crate::inference::{InferenceActivation, InferenceError, InferenceLayer, InferenceNetwork};
+    use crate::network::{Activation, Layer, Network};
+
+    #[test]
+    fn matches_the_graph_forward_pass() {
+        let network: Network = Network::new(vec![
+            Layer::dense(3, 5, Activation::Gelu, true).unwrap(),
+            Layer::dense(5, 4, Activation::LeakyRelu { alpha: 0.1 }, false).unwrap(),
+            Layer::dense(4, 2, Activation::Swish, true).unwrap(),
+        ]).unwrap();
+
+        let inference = InferenceNetwork::from_network(&network);
+
+        let input = [0.4, -1.2, 0.7];
+        let expected = network.forward(&input).unwrap();
+        let outputs = inference.forward(&input).unwrap();
+        for (output, expected) in outputs.iter().zip(&expected) {
+            assert!((output - expected).abs() < 1e-12, "{} vs {}", output, expected);
+        }
+
+        assert_eq!(inference.forward(&[1.0]), Err(InferenceError::DimensionMismatch { expected: 3, found: 1 }));
+    }
+
+    #[test]
+    fn validates_layers() {
+        let relu = InferenceActivation::Relu;
+
+        assert_eq!(InferenceLayer::new(vec![1.0f32; 5], None, 2, relu), Err(InferenceError::InvalidLayer { index: 0 }));
+        assert_eq!(InferenceLayer::new(vec![1.0f32; 4], Some(vec![0.0]), 2, relu), Err(InferenceError::InvalidLayer { index: 0 }));
+        assert_eq!(InferenceNetwork::<f32>::new(vec![]), Err(InferenceError::EmptyNetwork));
+
+        let first = InferenceLayer::new(vec![1.0f32, -1.0, 0.5, 0.5], Some(vec![0.0, 1.0]), 2, relu).unwrap();
+        let second = InferenceLayer::new(vec![1.0f32; 3], None, 3, InferenceActivation::Linear).unwrap();
+        assert_eq!(
+            InferenceNetwork::new(vec![first.clone(), second]),
+            Err(InferenceError::DimensionMismatch { expected: 3, found: 2 })
+        );
+
+        let network = InferenceNetwork::new(vec![first]).unwrap();
+        assert_eq!(network.forward(&[2.0, 1.0]).unwrap(), vec![1.0, 2.5]);
+    }
+}
@@ -0,0 +1,640 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use crate::archive::{self, Entry};
+use crate::config::LayerConfig;
+use crate::network::{Activation, Network};
+use crate::proto::Message;
+
+const NPY_MAGIC: &[u8] = b"\x93NUMPY";
+
+// ONNX format constants, see https://github.com/onnx/onnx/blob/main/onnx/onnx.proto
+const ONNX_IR_VERSION: u64 = 8;
+const ONNX_OPSET_VERSION: u64 = 13;
+const ONNX_TENSOR_FLOAT: u64 = 1;
+const ONNX_ATTRIBUTE_FLOAT: u64 = 1;
+const ONNX_ATTRIBUTE_INT: u64 = 2;
+
+/// NdArray is a dense, row-major array of numbers exchanged with NumPy.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NdArray {
+    pub shape: Vec<usize>,
+    pub data: Vec<f64>,
+}
+
+/// InteropError represents a failure to exchange weights with external tools.
+#[derive(Debug)]
+pub enum InteropError {
+    Io(io::Error),
+
+    // The file is not a valid archive or array, or uses an unsupported encoding.
+    InvalidFormat(String),
+
+    // An array required by the network is not present in the archive.
+    MissingArray(String),
+
+    // An array exists but its shape doesn't match the layer it maps to.
+    ShapeMismatch {
+        name: String,
+        expected: Vec<usize>,
+        found: Vec<usize>,
+    },
+}
+
+impl fmt::Display for InteropError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InteropError::Io(err) => write!(f, "io error: {}", err),
+            InteropError::InvalidFormat(reason) => write!(f, "invalid format: {}", reason),
+            InteropError::MissingArray(name) => write!(f, "missing array `{}`", name),
+            InteropError::ShapeMismatch { name, expected, found } => write!(
+                f,
+                "array `{}` has shape {:?} but the network expects {:?}",
+                name, found, expected
+            ),
+        }
+    }
+}
+
+impl std::error::Error for InteropError {}
+
+impl From<io::Error> for InteropError {
+    fn from(err: io::Error) -> Self {
+        InteropError::Io(err)
+    }
+}
+
+/// Extracts the value of a key from the Python dict literal stored in a .npy header.
+fn header_field<'a>(header: &'a str, key: &str) -> Result<&'a str, InteropError> {
+    let pattern = format!("'{}':", key);
+    let start = header
+        .find(&pattern)
+        .map(|index| index + pattern.len())
+        .ok_or_else(|| InteropError::InvalidFormat(format!("npy header is missing `{}`", key)))?;
+
+    Ok(header[start..].trim_start())
+}
+
+/// Decodes a single array in the NumPy .npy format.
+pub(crate) fn parse_npy(bytes: &[u8]) -> Result<NdArray, InteropError> {
+    let invalid = |reason: &str| InteropError::InvalidFormat(reason.to_string());
+
+    if bytes.len() < 10 || &bytes[..6] != NPY_MAGIC {
+        return Err(invalid("missing npy magic string"));
+    }
+
+    // Version 1 stores the header length as a u16, later versions use a u32.
+    let (header_length, header_start) = match bytes[6] {
+        1 => (u16::from_le_bytes([bytes[8], bytes[9]]) as usize, 10),
+        2 | 3 if bytes.len() >= 12 => (u32::from_le_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]) as usize, 12),
+        version => return Err(InteropError::InvalidFormat(format!("unsupported npy version {}", version))),
+    };
+
+    let header = bytes
+        .get(header_start..header_start + header_length)
+        .map(String::from_utf8_lossy)
+        .ok_or_else(|| invalid("truncated npy header"))?;
+
+    let descr = header_field(&header, "descr")?;
+    let descr = descr
+        .strip_prefix('\'')
+        .and_then(|rest| rest.split('\'').next())
+        .ok_or_else(|| invalid("malformed descr in npy header"))?;
+
+    if header_field(&header, "fortran_order")?.starts_with("True") {
+        return Err(invalid("fortran-ordered arrays are not supported, save a C-contiguous array instead"));
+    }
+
+    let shape = header_field(&header, "shape")?;
+    let shape = shape
+        .strip_prefix('(')
+        .and_then(|rest| rest.split(')').next())
+        .ok_or_else(|| invalid("malformed shape in npy header"))?;
+    let shape = shape
+        .split(',')
+        .map(str::trim)
+        .filter(|dim| !dim.is_empty())
+        .map(|dim| dim.parse::<usize>().map_err(|_| invalid("malformed shape in npy header")))
+        .collect::<Result<Vec<usize>, InteropError>>()?;
+
+    let count: usize = shape.iter().product();
+    let body = &bytes[header_start + header_length..];
+
+    let data: Vec<f64> = match descr {
+        "<f8" => body.chunks_exact(8).take(count).map(|b| f64::from_le_bytes(b.try_into().unwrap())).collect(),
+        "<f4" => body.chunks_exact(4).take(count).map(|b| f32::from_le_bytes(b.try_into().unwrap()) as f64).collect(),
+        "<i8" => body.chunks_exact(8).take(count).map(|b| i64::from_le_bytes(b.try_into().unwrap()) as f64).collect(),
+        "<i4" => body.chunks_exact(4).take(count).map(|b| i32::from_le_bytes(b.try_into().unwrap()) as f64).collect(),
+        other => return Err(InteropError::InvalidFormat(format!("unsupported dtype `{}`", other))),
+    };
+
+    if data.len() != count {
+        return Err(invalid("npy data is shorter than its shape"));
+    }
+
+    Ok(NdArray { shape, data })
+}
+
+/// Encodes an array in the NumPy .npy (version 1.0) format with little-endian f64 values.
+pub(crate) fn write_npy(array: &NdArray) -> Vec<u8> {
+    let shape = match array.shape.len() {
+        1 => format!("({},)", array.shape[0]),
+        _ => format!("({})", array.shape.iter().map(|dim| dim.to_string()).collect::<Vec<_>>().join(", ")),
+    };
+
+    let mut header = format!("{{'descr': '<f8', 'fortran_order': False, 'shape': {}, }}", shape);
+
+    // The header is padded with spaces so the data starts on a 64 byte boundary.
+    let unpadded_length = NPY_MAGIC.len() + 4 + header.len() + 1;
+    header.push_str(&" ".repeat((64 - unpadded_length % 64) % 64));
+    header.push('\n');
+
+    let mut output = Vec::with_capacity(NPY_MAGIC.len() + 4 + header.len() + array.data.len() * 8);
+    output.extend_from_slice(NPY_MAGIC);
+    output.extend_from_slice(&[1, 0]);
+    output.extend_from_slice(&(header.len() as u16).to_le_bytes());
+    output.extend_from_slice(header.as_bytes());
+    for value in &array.data {
+        output.extend_from_slice(&value.to_le_bytes());
+    }
+
+    output
+}
+
+/// Reads every array in a NumPy .npz archive, keyed by array name.
+pub fn read_npz(path: impl AsRef<Path>) -> Result<HashMap<String, NdArray>, InteropError> {
+    decode_npz(&fs::read(path)?)
+}
+
+pub(crate) fn decode_npz(bytes: &[u8]) -> Result<HashMap<String, NdArray>, InteropError> {
+    let entries = archive::read_entries(bytes).map_err(InteropError::InvalidFormat)?;
+
+    let mut arrays = HashMap::new();
+    for entry in entries {
+        let name = entry.name.strip_suffix(".npy").unwrap_or(&entry.name).to_string();
+        arrays.insert(name, parse_npy(&entry.data)?);
+    }
+
+    Ok(arrays)
+}
+
+fn weight_name(index: usize) -> String {
+    format!("layers.{}.weight", index)
+}
+
+fn bias_name(index: usize) -> String {
+    format!("layers.{}.bias", index)
+}
+
+fn buffer_name(index: usize, buffer: &str) -> String {
+    format!("layers.{}.{}", index, buffer)
+}
+
+/// Loads weights from a NumPy .npz archive into the layers of a network.
+///
+/// Arrays are mapped to layers by name, following the layout of a PyTorch `nn.Linear` state dict:
+/// `layers.{index}.weight` with shape (outputs, inputs) and `layers.{index}.bias` with shape (outputs,).
+/// In PyTorch, `np.savez(path, **{k: v.numpy() for k, v in model.state_dict().items()})` produces
+/// a compatible archive once the keys are renamed.
+///
+/// Batch normalisation layers follow `nn.BatchNorm1d` instead, with `layers.{index}.weight` and
+/// `layers.{index}.bias` holding each feature's scale and shift, and `layers.{index}.running_mean`
+/// and `layers.{index}.running_var` its statistics, all of shape (features,). Dropout layers have
+/// no arrays.
+///
+/// Every array is validated before any weight is written, so the network is left untouched on error.
+pub fn load_npz(network: &Network, path: impl AsRef<Path>) -> Result<(), InteropError> {
+    apply_npz(network, &read_npz(path)?)
+}
+
+pub(crate) fn apply_npz(network: &Network, arrays: &HashMap<String, NdArray>) -> Result<(), InteropError> {
+    let lookup = |name: String, expected: Vec<usize>| -> Result<&NdArray, InteropError> {
+        let array = arrays.get(&name).ok_or_else(|| InteropError::MissingArray(name.clone()))?;
+
+        if array.shape != expected {
+            return Err(InteropError::ShapeMismatch { name, expected, found: array.shape.clone() });
+        }
+
+        Ok(array)
+    };
+
+    let mut updates = Vec::with_capacity(network.layers.len());
+    for (index, layer) in network.layers.iter().enumerate() {
+        let outputs = layer.num_outputs() as usize;
+        let inputs = layer.num_inputs() as usize;
+
+        let config = layer.config();
+        let arrays = match config {
+            LayerConfig::Dense { .. } => {
+                let mut arrays = vec![lookup(weight_name(index), vec![outputs, inputs])?];
+                if layer.biases().is_some() {
+                    arrays.push(lookup(bias_name(index), vec![outputs])?);
+                }
+                arrays
+            }
+            LayerConfig::Dropout { .. } => vec![],
+            LayerConfig::BatchNorm { .. } => vec![
+                lookup(weight_name(index), vec![outputs])?,
+                lookup(bias_name(index), vec![outputs])?,
+                lookup(buffer_name(index, "running_mean"), vec![outputs])?,
+                lookup(buffer_name(index, "running_var"), vec![outputs])?,
+            ],
+        };
+
+        updates.push((layer, config, arrays));
+    }
+
+    for (layer, config, arrays) in updates {
+        match (config, arrays.as_slice()) {
+            (LayerConfig::BatchNorm { .. }, [scales, shifts, means, variances]) => {
+                // Each feature's scale and shift are the weight and bias of a neuron of its own
+                for (parameters, (scale, shift)) in layer.parameters().chunks(2).zip(scales.data.iter().zip(&shifts.data)) {
+                    parameters[0].set_data(*scale);
+                    parameters[1].set_data(*shift);
+                }
+                layer.set_running_stats(&means.data, &variances.data);
+            }
+            (LayerConfig::Dense { .. }, [weights, biases @ ..]) => {
+                let rows: Vec<Vec<f64>> = weights.data.chunks(layer.num_inputs().max(1) as usize).map(|row| row.to_vec()).collect();
+                layer.set_weights(&rows);
+
+                if let Some(biases) = biases.first() {
+                    layer.set_biases(&biases.data);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    Ok(())
+}
+
+/// Saves the weights of a network to a NumPy .npz archive, using the same names as load_npz.
+pub fn save_npz(network: &Network, path: impl AsRef<Path>) -> Result<(), InteropError> {
+    fs::write(path, encode_npz(network))?;
+
+    Ok(())
+}
+
+pub(crate) fn encode_npz(network: &Network) -> Vec<u8> {
+    let mut entries = Vec::new();
+
+    for (index, layer) in network.layers.iter().enumerate() {
+        match layer.config() {
+            LayerConfig::Dense { .. } => (),
+            LayerConfig::Dropout { .. } => continue,
+            LayerConfig::BatchNorm { .. } => {
+                let parameters: Vec<f64> = layer.parameters().iter().map(|parameter| parameter.get_data()).collect();
+                let (means, variances) = layer.running_stats().unwrap_or_default();
+                let arrays = [
+                    (weight_name(index), parameters.iter().step_by(2).copied().collect()),
+                    (bias_name(index), parameters.iter().skip(1).step_by(2).copied().collect()),
+                    (buffer_name(index, "running_mean"), means),
+                    (buffer_name(index, "running_var"), variances),
+                ];
+
+                for (name, data) in arrays {
+                    let array = NdArray { shape: vec![data.len()], data };
+                    entries.push(Entry { name: format!("{}.npy", name), data: write_npy(&array) });
+                }
+                continue;
+            }
+        }
+
+        let weights = NdArray {
+            shape: vec![layer.num_outputs() as usize, layer.num_inputs() as usize],
+            data: layer.weights().concat(),
+        };
+        entries.push(Entry { name: format!("{}.npy", weight_name(index)), data: write_npy(&weights) });
+
+        if let Some(biases) = layer.biases() {
+            let biases = NdArray { shape: vec![biases.len()], data: biases };
+            entries.push(Entry { name: format!("{}.npy", bias_name(index)), data: write_npy(&biases) });
+        }
+    }
+
+    archive::write_entries(&entries)
+}
+
+/// Builds an ONNX float tensor initializer.
+fn onnx_tensor(name: &str, dims: &[usize], data: &[f64]) -> Message {
+    let raw: Vec<u8> = data.iter().flat_map(|value| (*value as f32).to_le_bytes()).collect();
+
+    let mut tensor = Message::new();
+    for dim in dims {
+        tensor.varint(1, *dim as u64);
+    }
+    tensor.varint(2, ONNX_TENSOR_FLOAT).string(8, name).bytes(9, &raw);
+
+    tensor
+}
+
+/// Builds an ONNX graph input/output description for a float tensor of shape (batch, features).
+fn onnx_value_info(name: &str, features: u64) -> Message {
+    let mut batch = Message::new();
+    batch.string(2, "batch");
+
+    let mut features_dim = Message::new();
+    features_dim.varint(1, features);
+
+    let mut shape = Message::new();
+    shape.message(1, &batch).message(1, &features_dim);
+
+    let mut tensor_type = Message::new();
+    tensor_type.varint(1, ONNX_TENSOR_FLOAT).message(2, &shape);
+
+    let mut type_proto = Message::new();
+    type_proto.message(1, &tensor_type);
+
+    let mut value_info = Message::new();
+    value_info.string(1, name).message(2, &type_proto);
+
+    value_info
+}
+
+fn onnx_node(op_type: &str, name: &str, inputs: &[&str], output: &str) -> Message {
+    let mut node = Message::new();
+    for input in inputs {
+        node.string(1, input);
+    }
+    node.string(2, output).string(3, name).string(4, op_type);
+
+    node
+}
+
+// Appends the nodes applying `activation` to `input` to the graph, writing the result to `output`.
+// Opset 13 has no Gelu or Swish operators, so those are composed from elementwise nodes.
+fn onnx_activation(graph: &mut Message, activation: Activation, index: usize, input: &str, output: &str) {
+    let name = |suffix: &str| format!("layers.{}.{}", index, suffix);
+
+    match activation {
+        Activation::Linear => (),
+        Activation::Relu => {
+            graph.message(1, &onnx_node("Relu", &name("relu"), &[input], output));
+        }
+        Activation::LeakyRelu { alpha } => {
+            let mut slope = Message::new();
+            slope.string(1, "alpha").float(2, alpha as f32).varint(20, ONNX_ATTRIBUTE_FLOAT);
+
+            let mut node = onnx_node("LeakyRelu", &name("leaky_relu"), &[input], output);
+            node.message(5, &slope);
+            graph.message(1, &node);
+        }
+        Activation::Softplus => {
+            graph.message(1, &onnx_node("Softplus", &name("softplus"), &[input], output));
+        }
+        // Elu's alpha attribute defaults to 1, matching Value::elu
+        Activation::Elu => {
+            graph.message(1, &onnx_node("Elu", &name("elu"), &[input], output));
+        }
+        Activation::Swish => {
+            let sigmoid = name("swish.sigmoid");
+            graph.message(1, &onnx_node("Sigmoid", &sigmoid, &[input], &sigmoid));
+            graph.message(1, &onnx_node("Mul", &name("swish"), &[input, &sigmoid], output));
+        }
+        // 0.5 * x * (1 + tanh(sqrt(2 / pi) * (x + 0.044715 * x^3)))
+        Activation::Gelu => {
+            let constants = [("half", 0.5), ("one", 1.0), ("scale", (2.0 / std::f64::consts::PI).sqrt()), ("cubic", 0.044715)];
+            for (constant, value) in constants {
+                graph.message(5, &onnx_tensor(&name(&format!("gelu.{}", constant)), &[], &[value]));
+            }
+
+            let (square, cube, cubic_term) = (name("gelu.square"), name("gelu.cube"), name("gelu.cubic_term"));
+            let (inner, scaled, tanh) = (name("gelu.inner"), name("gelu.scaled"), name("gelu.tanh"));
+            let (shifted, product) = (name("gelu.shifted"), name("gelu.product"));
+
+            graph
+                .message(1, &onnx_node("Mul", &square, &[input, input], &square))
+                .message(1, &onnx_node("Mul", &cube, &[&square, input], &cube))
+                .message(1, &onnx_node("Mul", &cubic_term, &[&cube, &name("gelu.cubic")], &cubic_term))
+                .message(1, &onnx_node("Add", &inner, &[input, &cubic_term], &inner))
+                .message(1, &onnx_node("Mul", &scaled, &[&inner, &name("gelu.scale")], &scaled))
+                .message(1, &onnx_node("Tanh", &tanh, &[&scaled], &tanh))
+                .message(1, &onnx_node("Add", &shifted, &[&tanh, &name("gelu.one")], &shifted))
+                .message(1, &onnx_node("Mul", &product, &[input, &shifted], &product));
+
+            graph.message(1, &onnx_node("Mul", &name("gelu"), &[&name("gelu.product"), &name("gelu.half")], output));
+        }
+    }
+}
+
+/// Exports a dense network as an ONNX model so it can be served by standard runtimes.
+///
+/// Each layer becomes a `Gemm` node (with `transB=1`, as weights are stored as (outputs, inputs))
+/// followed by the nodes of its activation (`Relu`, `LeakyRelu`, `Softplus`, `Elu`, or elementwise
+/// compositions for GELU and Swish). Dropout and batch normalisation layers are exported as the
+/// dense maps they apply outside of training, see Layer::weights. Parameters are stored as float32 initializers and the model
+/// takes an `input` tensor of shape (batch, inputs) and produces an `output` tensor.
+pub fn export_onnx(network: &Network, path: impl AsRef<Path>) -> Result<(), InteropError> {
+    let first_layer = network
+        .layers
+        .first()
+        .ok_or_else(|| InteropError::InvalidFormat("cannot export a network without layers".to_string()))?;
+    let last_layer = &network.layers[network.layers.len() - 1];
+
+    let mut graph = Message::new();
+    let mut previous_output = "input".to_string();
+
+    for (index, layer) in network.layers.iter().enumerate() {
+        let weight = weight_name(index);
+        let bias = bias_name(index);
+        let is_last = index == network.layers.len() - 1;
+
+        let weights = layer.weights().concat();
+        graph.message(5, &onnx_tensor(&weight, &[layer.num_outputs() as usize, layer.num_inputs() as usize], &weights));
+
+        let mut gemm_inputs = vec![previous_output.as_str(), weight.as_str()];
+        if let Some(biases) = layer.biases() {
+            graph.message(5, &onnx_tensor(&bias, &[biases.len()], &biases));
+            gemm_inputs.push(bias.as_str());
+        }
+
+        let activation = layer.activation();
+        let gemm_output = if is_last && activation == Activation::Linear {
+            "output".to_string()
+        } else {
+            format!("layers.{}.gemm", index)
+        };
+
+        let mut trans_b = Message::new();
+        trans_b.string(1, "transB").varint(3, 1).varint(20, ONNX_ATTRIBUTE_INT);
+
+        let mut gemm = onnx_node("Gemm", &format!("layers.{}.gemm", index), &gemm_inputs, &gemm_output);
+        gemm.message(5, &trans_b);
+        graph.message(1, &gemm);
+
+        previous_output = gemm_output;
+
+        if activation != Activation::Linear {
+            let activation_output = if is_last { "output".to_string() } else { format!("layers.{}.{}", index, activation.to_str()) };
+            onnx_activation(&mut graph, activation, index, &previous_output, &activation_output);
+
+            previous_output = activation_output;
+        }
+    }
+
+    graph
+        .string(2, "backprop")
+        .message(11, &onnx_value_info("input", first_layer.num_inputs()))
+        .message(12, &onnx_value_info("output", last_layer.num_outputs()));
+
+    let mut opset = Message::new();
+    opset.string(1, "").varint(2, ONNX_OPSET_VERSION);
+
+    let mut model = Message::new();
+    model
+        .varint(1, ONNX_IR_VERSION)
+        .string(2, "backprop")
+        .string(3, env!("CARGO_PKG_VERSION"))
+        .message(7, &graph)
+        .message(8, &opset);
+
+    fs::write(path, model.as_bytes())?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::archive::{self, Entry};
+    use crate::interop::{export_onnx, load_npz, parse_npy, read_npz, save_npz, write_npy, InteropError, NdArray};
+    use crate::network::{Activation, Layer, Network};
+
+    #[test]
+    fn npy_round_trip() {
+        let array = NdArray { shape: vec![2, 3], data: vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0] };
+
+        let bytes = write_npy(&array);
+        assert_eq!((bytes.len() - 6 * 8) % 64, 0);
+
+        assert_eq!(parse_npy(&bytes).unwrap(), array);
+    }
+
+    #[test]
+    fn load_weights_saved_from_another_network() {
+        let path = std::env::temp_dir().join("backprop_interop_round_trip.npz");
+
+        let source = Network{
+            layers: vec![
+                Layer::dense(3, 2, Activation::Relu, true).unwrap(),
+                Layer::dense(2, 1, Activation::Linear, false).unwrap(),
+            ],
+        };
+        save_npz(&source, &path).unwrap();
+
+        let target = Network::from_config(&source.config()).unwrap();
+        load_npz(&target, &path).unwrap();
+
+        assert_eq!(target.layers[0].weights(), source.layers[0].weights());
+        assert_eq!(target.layers[0].biases(), source.layers[0].biases());
+        assert_eq!(target.layers[1].weights(), source.layers[1].weights());
+        assert_eq!(target.forward(&[0.1, 0.2, 0.3]).unwrap(), source.forward(&[0.1, 0.2, 0.3]).unwrap());
+
+        assert_eq!(read_npz(&path).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn batch_norm_layers_save_their_statistics() {
+        let path = std::env::temp_dir().join("backprop_interop_batch_norm.npz");
+
+        let source = Network::new(vec![
+            Layer::dense(2, 3, Activation::Relu, true).unwrap(),
+            Layer::batch_norm(3, 0.1, 1e-5).unwrap(),
+            Layer::dropout(3, 0.5).unwrap(),
+            Layer::dense(3, 1, Activation::Linear, true).unwrap(),
+        ]).unwrap();
+        source.layers[1].set_running_stats(&[0.1, 0.2, 0.3], &[1.5, 2.5, 3.5]);
+        source.layers[1].parameters()[0].set_data(0.5);
+        save_npz(&source, &path).unwrap();
+
+        let arrays = read_npz(&path).unwrap();
+        assert_eq!(arrays.len(), 2 + 4 + 2);
+        assert_eq!(arrays["layers.1.weight"].data, vec![0.5, 1.0, 1.0]);
+        assert_eq!(arrays["layers.1.running_var"].data, vec![1.5, 2.5, 3.5]);
+
+        let target = Network::from_config(&source.config()).unwrap();
+        load_npz(&target, &path).unwrap();
+        assert_eq!(target.layers[1].running_stats(), source.layers[1].running_stats());
+        assert_eq!(target.get_flat_params(), source.get_flat_params());
+        assert_eq!(target.forward(&[0.4, -0.2]).unwrap(), source.forward(&[0.4, -0.2]).unwrap());
+    }
+
+    #[test]
+    fn export_dense_network_to_onnx() {
+        let path = std::env::temp_dir().join("backprop_export.onnx");
+
+        let network = Network{
+            layers: vec![
+                Layer::dense(3, 4, Activation::Relu, true).unwrap(),
+                Layer::dense(4, 1, Activation::Linear, true).unwrap(),
+            ],
+        };
+        export_onnx(&network, &path).unwrap();
+
+        let bytes = std::fs::read(&path).unwrap();
+        let count = |needle: &[u8]| bytes.windows(needle.len()).filter(|w| *w == needle).count();
+
+        // ModelProto starts with the ir_version field
+        assert_eq!(&bytes[..2], &[0x08, 0x08]);
+        assert_eq!(count(b"Gemm"), 2);
+        assert_eq!(count(b"Relu"), 1);
+        assert_eq!(count(b"layers.1.bias"), 2);
+    }
+
+    #[test]
+    fn export_composes_activations() {
+        let path = std::env::temp_dir().join("backprop_export_smooth.onnx");
+
+        let network = Network{
+            layers: vec![
+                Layer::dense(3, 4, Activation::Gelu, true).unwrap(),
+                Layer::dense(4, 4, Activation::Elu, true).unwrap(),
+                Layer::dense(4, 4, Activation::LeakyRelu { alpha: 0.1 }, true).unwrap(),
+                Layer::dense(4, 1, Activation::Swish, true).unwrap(),
+            ],
+        };
+        export_onnx(&network, &path).unwrap();
+
+        let bytes = std::fs::read(&path).unwrap();
+        let count = |needle: &[u8]| bytes.windows(needle.len()).filter(|w| *w == needle).count();
+
+        assert_eq!(count(b"Tanh"), 1);
+        assert_eq!(count(b"Elu"), 1);
+        assert_eq!(count(b"LeakyRelu"), 1);
+        assert_eq!(count(b"Sigmoid"), 1);
+        assert_eq!(count(b"layers.0.gelu.half"), 2);
+        assert_eq!(count(b"output"), 2);
+    }
+
+    #[test]
+    fn export_empty_network_fails() {
+        let network = Network{ layers: vec![] };
+        let path = std::env::temp_dir().join("backprop_export_empty.onnx");
+
+        assert!(matches!(export_onnx(&network, &path), Err(InteropError::InvalidFormat(_))));
+    }
+
+    #[test]
+    fn shape_mismatch_is_reported() {
+        let path = std::env::temp_dir().join("backprop_interop_mismatch.npz");
+
+        let weights = NdArray { shape: vec![3, 2], data: vec![0.0; 6] };
+        let bytes = archive::write_entries(&[Entry { name: "layers.0.weight.npy".to_string(), data: write_npy(&weights) }]);
+        std::fs::write(&path, bytes).unwrap();
+
+        let network = Network{
+            layers: vec![Layer::dense(3, 2, Activation::Linear, false).unwrap()],
+        };
+
+        match load_npz(&network, &path) {
+            Err(InteropError::ShapeMismatch { name, expected, found }) => {
+                assert_eq!(name, "layers.0.weight");
+                assert_eq!(expected, vec![2, 3]);
+                assert_eq!(found, vec![3, 2]);
+            }
+            other => panic!("expected a shape mismatch, got {:?}", other),
+        }
+    }
+}
@@ -1,3 +1,88 @@
+// Without the `std` feature only the inference core is built, see the `inference` module
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+#[cfg(not(any(feature = "std", feature = "no_std")))]
+compile_error!("backprop needs either the `std` feature or, for embedded targets, the `no_std` feature");
+
+#[cfg(feature = "std")]
+pub mod error;
+pub mod scalar;
+pub mod kernels;
+pub mod inference;
+#[cfg(feature = "std")]
 pub mod value;
+#[cfg(feature = "std")]
+pub mod sync_value;
+#[cfg(feature = "std")]
+pub mod graph;
+#[cfg(feature = "std")]
+pub mod tensor;
+#[cfg(feature = "std")]
 pub mod network;
-pub mod utils;
\ No newline at end of file
+#[cfg(feature = "std")]
+pub mod recurrent;
+#[cfg(feature = "std")]
+pub mod conv;
+#[cfg(feature = "std")]
+pub mod attention;
+#[cfg(feature = "std")]
+pub mod quantize;
+#[cfg(feature = "std")]
+pub mod sample;
+#[cfg(feature = "std")]
+pub mod rand;
+#[cfg(feature = "std")]
+pub mod utils;
+// Golden-value fixtures checking the computation graph against reference results, only
+// needed by the test suite
+#[cfg(all(test, feature = "std"))]
+mod golden;
+#[cfg(feature = "std")]
+pub mod config;
+#[cfg(feature = "std")]
+pub mod optim;
+#[cfg(feature = "std")]
+pub mod optimize;
+#[cfg(feature = "std")]
+pub mod fit;
+#[cfg(feature = "std")]
+pub mod models;
+#[cfg(feature = "std")]
+pub mod loss;
+#[cfg(feature = "std")]
+pub mod train;
+#[cfg(feature = "std")]
+pub mod logging;
+#[cfg(feature = "std")]
+pub mod tune;
+#[cfg(feature = "std")]
+pub mod data;
+#[cfg(feature = "std")]
+pub mod features;
+#[cfg(feature = "std")]
+pub mod interop;
+#[cfg(feature = "std")]
+pub mod experiment;
+#[cfg(feature = "std")]
+pub mod viz;
+#[cfg(feature = "std")]
+pub mod prelude;
+
+#[cfg(feature = "parallel")]
+pub mod parallel;
+
+#[cfg(feature = "instrumentation")]
+pub mod stats;
+
+#[cfg(feature = "cabi")]
+pub mod cabi;
+
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+#[cfg(feature = "std")]
+mod archive;
+#[cfg(feature = "std")]
+mod proto;
\ No newline at end of file
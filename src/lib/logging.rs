@@ -0,0 +1,181 @@
+use std::io::{self, Stderr, Write};
+use std::time::Duration;
+
+pub mod tensorboard;
+
+/// Progress describes how far training has got. It's passed to a Logger after every optimizer
+/// step and at the end of every epoch.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Progress {
+    // epoch is 1-based, out of `epochs`
+    pub epoch: usize,
+    pub epochs: usize,
+
+    // batch is the number of optimizer steps completed in this epoch, out of `batches`
+    pub batch: usize,
+    pub batches: usize,
+
+    // loss is the mean loss of the samples seen so far in this epoch, and val_loss the mean loss
+    // over the validation set, which is only known at the end of an epoch
+    pub loss: f64,
+    pub val_loss: Option<f64>,
+    pub learning_rate: f64,
+
+    // grad_norm is the L2 norm of the (averaged) gradients applied by the last optimizer step
+    pub grad_norm: f64,
+
+    // elapsed is the time since training started, and eta the estimated time until it finishes
+    pub elapsed: Duration,
+    pub eta: Duration,
+}
+
+/// Logger receives training progress from Trainer::fit. Both methods default to doing nothing,
+/// so implementations only need to override the events they care about.
+pub trait Logger {
+    fn on_batch_end(&mut self, _progress: &Progress) {}
+
+    fn on_epoch_end(&mut self, _progress: &Progress) {}
+}
+
+/// Silent is a Logger which discards all progress.
+pub struct Silent;
+
+impl Logger for Silent {}
+
+/// ProgressBar is a Logger which redraws a single terminal line after every optimizer step, e.g.
+///
+/// `epoch 2/10 [##########----------] 20/40 loss 0.123456 lr 0.01 eta 3.2s`
+///
+/// and leaves one line per epoch behind once the epoch ends. It writes to stderr by default.
+pub struct ProgressBar<W: Write = Stderr> {
+    writer: W,
+    pub width: usize,
+
+    // The length of the last line drawn, so a shorter redraw can blank out what's left of it
+    last_len: usize,
+}
+
+impl ProgressBar {
+    pub fn new() -> ProgressBar {
+        ProgressBar::to_writer(io::stderr())
+    }
+}
+
+impl Default for ProgressBar {
+    fn default() -> Self {
+        ProgressBar::new()
+    }
+}
+
+impl<W: Write> ProgressBar<W> {
+    pub fn to_writer(writer: W) -> ProgressBar<W> {
+        ProgressBar { writer, width: 20, last_len: 0 }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    fn render(&self, progress: &Progress, done: bool) -> String {
+        let filled = (progress.batch * self.width).checked_div(progress.batches).unwrap_or(self.width);
+        let bar = format!("{}{}", "#".repeat(filled), "-".repeat(self.width - filled));
+
+        let val_loss = progress.val_loss.map(|loss| format!(" val_loss {:.6}", loss)).unwrap_or_default();
+        let timing = if done {
+            format!("elapsed {}", format_duration(progress.elapsed))
+        } else {
+            format!("eta {}", format_duration(progress.eta))
+        };
+
+        format!(
+            "epoch {}/{} [{}] {}/{} loss {:.6}{} lr {} {}",
+            progress.epoch,
+            progress.epochs,
+            bar,
+            progress.batch,
+            progress.batches,
+            progress.loss,
+            val_loss,
+            progress.learning_rate,
+            timing,
+        )
+    }
+
+    fn draw(&mut self, line: String, end: &str) {
+        let padding = " ".repeat(self.last_len.saturating_sub(line.len()));
+        self.last_len = if end.is_empty() { line.len() } else { 0 };
+
+        // Progress output is best effort, a closed terminal shouldn't stop training
+        let _ = write!(self.writer, "\r{}{}{}", line, padding, end);
+        let _ = self.writer.flush();
+    }
+}
+
+impl<W: Write> Logger for ProgressBar<W> {
+    fn on_batch_end(&mut self, progress: &Progress) {
+        let line = self.render(progress, false);
+        self.draw(line, "");
+    }
+
+    fn on_epoch_end(&mut self, progress: &Progress) {
+        let line = self.render(progress, true);
+        self.draw(line, "\n");
+    }
+}
+
+// Formats a duration as e.g. "850ms", "3.2s" or "4m05s".
+fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs_f64();
+
+    if seconds < 1.0 {
+        format!("{}ms", duration.as_millis())
+    } else if seconds < 60.0 {
+        format!("{:.1}s", seconds)
+    } else {
+        format!("{}m{:02}s", duration.as_secs() / 60, duration.as_secs() % 60)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use crate::logging::{format_duration, Logger, Progress, ProgressBar};
+
+    fn progress(batch: usize) -> Progress {
+        Progress {
+            epoch: 2,
+            epochs: 10,
+            batch,
+            batches: 4,
+            loss: 0.5,
+            val_loss: None,
+            learning_rate: 0.01,
+            grad_norm: 1.0,
+            elapsed: Duration::from_millis(1500),
+            eta: Duration::from_secs(125),
+        }
+    }
+
+    #[test]
+    fn progress_bar_redraws_one_line_per_epoch() {
+        let mut bar = ProgressBar::to_writer(Vec::new());
+        bar.width = 8;
+
+        bar.on_batch_end(&progress(1));
+        bar.on_epoch_end(&Progress { val_loss: Some(0.25), ..progress(4) });
+
+        let output = String::from_utf8(bar.into_inner()).unwrap();
+        assert_eq!(
+            output,
+            "\repoch 2/10 [##------] 1/4 loss 0.500000 lr 0.01 eta 2m05s\
+             \repoch 2/10 [########] 4/4 loss 0.500000 val_loss 0.250000 lr 0.01 elapsed 1.5s\n"
+        );
+    }
+
+    #[test]
+    fn durations_are_formatted() {
+        assert_eq!(format_duration(Duration::from_millis(850)), "850ms");
+        assert_eq!(format_duration(Duration::from_millis(3240)), "3.2s");
+        assert_eq!(format_duration(Duration::from_secs(245)), "4m05s");
+    }
+}
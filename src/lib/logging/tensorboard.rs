@@ -0,0 +1,246 @@
+// Writes scalar summaries in the TensorBoard event file format, so training runs can be monitored
+// with `tensorboard --logdir <dir>`.
+//
+// An event file is a sequence of TFRecords, each holding one serialized `Event` protobuf:
+//
+//     u64 length | u32 masked crc32c(length) | data | u32 masked crc32c(data)
+//
+// Only the handful of Event and Summary fields needed for scalars are emitted.
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::logging::{Logger, Progress};
+use crate::proto::Message;
+
+// Field numbers from tensorflow/core/util/event.proto and tensorflow/core/framework/summary.proto
+const EVENT_WALL_TIME: u32 = 1;
+const EVENT_STEP: u32 = 2;
+const EVENT_FILE_VERSION: u32 = 3;
+const EVENT_SUMMARY: u32 = 5;
+const SUMMARY_VALUE: u32 = 1;
+const VALUE_TAG: u32 = 1;
+const VALUE_SIMPLE_VALUE: u32 = 2;
+
+const FILE_VERSION: &str = "brain.Event:2";
+
+/// SummaryWriter appends scalar summaries to a new event file inside a log directory.
+///
+/// As a Logger it records `batch/loss` and `batch/grad_norm` after every optimizer step (indexed
+/// by the global step), and `epoch/loss`, `epoch/val_loss` and `epoch/learning_rate` after every
+/// epoch. Logger methods can't return errors, so the first write error is kept and reported by finish.
+pub struct SummaryWriter {
+    writer: BufWriter<File>,
+    path: PathBuf,
+    error: Option<io::Error>,
+}
+
+impl SummaryWriter {
+    /// Creates `dir` if needed and opens a new `events.out.tfevents.*` file inside it.
+    pub fn create(dir: impl AsRef<Path>) -> io::Result<SummaryWriter> {
+        fs::create_dir_all(&dir)?;
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        let path = dir.as_ref().join(format!("events.out.tfevents.{}.{}.backprop", now.as_secs(), now.subsec_nanos()));
+
+        let mut writer = SummaryWriter {
+            writer: BufWriter::new(File::create(&path)?),
+            path,
+            error: None,
+        };
+
+        let mut event = Message::new();
+        event.double(EVENT_WALL_TIME, wall_time()).string(EVENT_FILE_VERSION, FILE_VERSION);
+        writer.write_record(event.as_bytes())?;
+        writer.flush()?;
+
+        Ok(writer)
+    }
+
+    /// Returns the path of the event file being written.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Records `value` for the series `tag` at `step`.
+    pub fn add_scalar(&mut self, tag: &str, value: f64, step: u64) -> io::Result<()> {
+        let mut summary_value = Message::new();
+        summary_value.string(VALUE_TAG, tag).float(VALUE_SIMPLE_VALUE, value as f32);
+
+        let mut summary = Message::new();
+        summary.message(SUMMARY_VALUE, &summary_value);
+
+        let mut event = Message::new();
+        event
+            .double(EVENT_WALL_TIME, wall_time())
+            .varint(EVENT_STEP, step)
+            .message(EVENT_SUMMARY, &summary);
+
+        self.write_record(event.as_bytes())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    /// Flushes the event file, returning the first error hit while logging, if any.
+    pub fn finish(mut self) -> io::Result<()> {
+        if let Some(err) = self.error.take() {
+            return Err(err);
+        }
+
+        self.flush()
+    }
+
+    fn write_record(&mut self, data: &[u8]) -> io::Result<()> {
+        let length = (data.len() as u64).to_le_bytes();
+
+        self.writer.write_all(&length)?;
+        self.writer.write_all(&masked_crc32c(&length).to_le_bytes())?;
+        self.writer.write_all(data)?;
+        self.writer.write_all(&masked_crc32c(data).to_le_bytes())
+    }
+
+    // Keeps the first error from a Logger callback for finish to report.
+    fn keep_error(&mut self, result: io::Result<()>) {
+        if let Err(err) = result {
+            self.error.get_or_insert(err);
+        }
+    }
+}
+
+impl Logger for SummaryWriter {
+    fn on_batch_end(&mut self, progress: &Progress) {
+        let step = ((progress.epoch - 1) * progress.batches + progress.batch) as u64;
+
+        let result = self
+            .add_scalar("batch/loss", progress.loss, step)
+            .and_then(|_| self.add_scalar("batch/grad_norm", progress.grad_norm, step));
+        self.keep_error(result);
+    }
+
+    fn on_epoch_end(&mut self, progress: &Progress) {
+        let step = progress.epoch as u64;
+
+        let mut result = self
+            .add_scalar("epoch/loss", progress.loss, step)
+            .and_then(|_| self.add_scalar("epoch/learning_rate", progress.learning_rate, step));
+        if let Some(val_loss) = progress.val_loss {
+            result = result.and_then(|_| self.add_scalar("epoch/val_loss", val_loss, step));
+        }
+
+        // Flush once per epoch so TensorBoard picks up progress while training is still running
+        let result = result.and_then(|_| self.flush());
+        self.keep_error(result);
+    }
+}
+
+fn wall_time() -> f64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs_f64()
+}
+
+// crc32c computes the CRC-32C (Castagnoli) checksum used by TFRecords.
+fn crc32c(data: &[u8]) -> u32 {
+    let mut crc = 0xffffffffu32;
+
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0x82f63b78 & mask);
+        }
+    }
+
+    !crc
+}
+
+fn masked_crc32c(data: &[u8]) -> u32 {
+    let crc = crc32c(data);
+    crc.rotate_right(15).wrapping_add(0xa282ead8)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use crate::logging::tensorboard::{crc32c, masked_crc32c, SummaryWriter};
+    use crate::network::{Activation, Layer, Network};
+    use crate::optim::Sgd;
+    use crate::train::Trainer;
+
+    // Splits an event file into its records, checking the length and data checksums.
+    fn read_records(bytes: &[u8]) -> Vec<Vec<u8>> {
+        let mut records = Vec::new();
+        let mut offset = 0;
+
+        while offset < bytes.len() {
+            let length_bytes = &bytes[offset..offset + 8];
+            let length = u64::from_le_bytes(length_bytes.try_into().unwrap()) as usize;
+            assert_eq!(&bytes[offset + 8..offset + 12], &masked_crc32c(length_bytes).to_le_bytes());
+
+            let data = &bytes[offset + 12..offset + 12 + length];
+            assert_eq!(&bytes[offset + 12 + length..offset + 16 + length], &masked_crc32c(data).to_le_bytes());
+
+            records.push(data.to_vec());
+            offset += 16 + length;
+        }
+
+        records
+    }
+
+    fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+        haystack.windows(needle.len()).any(|window| window == needle)
+    }
+
+    #[test]
+    fn crc32c_matches_reference() {
+        assert_eq!(crc32c(b"123456789"), 0xe3069283);
+    }
+
+    #[test]
+    fn writes_scalar_events() {
+        let dir = std::env::temp_dir().join("backprop_tensorboard_scalars");
+        let _ = fs::remove_dir_all(&dir);
+
+        let mut writer = SummaryWriter::create(&dir).unwrap();
+        writer.add_scalar("loss", 0.5, 3).unwrap();
+        let path = writer.path().to_path_buf();
+        writer.finish().unwrap();
+
+        assert!(path.file_name().unwrap().to_str().unwrap().starts_with("events.out.tfevents."));
+
+        let records = read_records(&fs::read(&path).unwrap());
+        assert_eq!(records.len(), 2);
+        assert!(contains(&records[0], b"brain.Event:2"));
+
+        // step = 3, then summary { value { tag: "loss", simple_value: 0.5 } }
+        let scalar = &records[1][9..];
+        assert_eq!(scalar, &[
+            0x10, 0x03,
+            0x2a, 0x0d, 0x0a, 0x0b, 0x0a, 0x04, b'l', b'o', b's', b's', 0x15, 0x00, 0x00, 0x00, 0x3f,
+        ]);
+    }
+
+    #[test]
+    fn logs_training_progress() {
+        let dir = std::env::temp_dir().join("backprop_tensorboard_training");
+        let _ = fs::remove_dir_all(&dir);
+
+        let network: Network = Network::new(vec![Layer::dense(1, 1, Activation::Linear, true).unwrap()]).unwrap();
+        let dataset = vec![(vec![1.0], vec![2.0]), (vec![2.0], vec![4.0])];
+
+        let mut writer = SummaryWriter::create(&dir).unwrap();
+        let path = writer.path().to_path_buf();
+        Trainer::new(Sgd::new(0.1)).fit_with_validation(&network, &dataset, &dataset, 3, &mut writer).unwrap();
+        writer.finish().unwrap();
+
+        let records = read_records(&fs::read(path).unwrap());
+        let count = |tag: &[u8]| records.iter().filter(|record| contains(record, tag)).count();
+
+        // 2 optimizer steps per epoch, over 3 epochs
+        assert_eq!(count(b"batch/loss"), 6);
+        assert_eq!(count(b"batch/grad_norm"), 6);
+        assert_eq!(count(b"epoch/loss"), 3);
+        assert_eq!(count(b"epoch/val_loss"), 3);
+        assert_eq!(count(b"epoch/learning_rate"), 3);
+    }
+}
@@ -0,0 +1,230 @@
+use serde::{Deserialize, Serialize};
+use crate::scalar::Scalar;
+use crate::value::Value;
+
+/// Loss selects the loss Trainer minimises.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub enum Loss {
+    #[default]
+    Mse,
+
+    // CrossEntropy treats the outputs as logits of one-hot targets, see cross_entropy
+    CrossEntropy { class_weights: Option<Vec<f64>> },
+}
+
+impl Loss {
+    /// Computes the loss of `outputs` against `targets` as the root of a backward pass.
+    pub fn compute<T: Scalar>(&self, outputs: &[Value<T>], targets: &[T]) -> Value<T> {
+        match self {
+            Loss::Mse => mse(outputs, targets),
+            Loss::CrossEntropy { class_weights } => cross_entropy(outputs, targets, class_weights.as_deref()),
+        }
+    }
+}
+
+/// mse computes the mean squared error between the network outputs and their targets,
+/// as a node of the computation graph so it can be used as the root of the backward pass.
+/// Panics if there are no outputs or the number of targets doesn't match the outputs.
+pub fn mse<T: Scalar>(outputs: &[Value<T>], targets: &[T]) -> Value<T> {
+    let targets: Vec<Value<T>> = targets.iter().map(|target| Value::constant(*target)).collect();
+
+    mse_values(outputs, &targets)
+}
+
+/// Like mse, with targets which are nodes of the graph, e.g. constants whose data is set for each
+/// sample of a compiled graph.
+pub fn mse_values<T: Scalar>(outputs: &[Value<T>], targets: &[Value<T>]) -> Value<T> {
+    assert!(!outputs.is_empty(), "mse needs at least one output");
+    assert_eq!(outputs.len(), targets.len(), "expected one target per output");
+
+    let sum = outputs
+        .iter()
+        .zip(targets)
+        .map(|(output, target)| {
+            let error = output - target;
+            &error * &error
+        })
+        .fold(Value::constant(T::from_f64(0.0)), |acc, item| acc + item);
+
+    &sum / &Value::constant(T::from_f64(outputs.len() as f64))
+}
+
+/// huber computes the mean Huber loss: 0.5 * e² for errors within `delta` of the target, and
+/// delta * (|e| - 0.5 * delta) beyond it, so outliers contribute linearly rather than quadratically.
+/// Panics if there are no outputs or the number of targets doesn't match the outputs.
+pub fn huber<T: Scalar>(outputs: &[Value<T>], targets: &[T], delta: f64) -> Value<T> {
+    assert!(!outputs.is_empty(), "huber needs at least one output");
+    assert_eq!(outputs.len(), targets.len(), "expected one target per output");
+
+    let half = Value::constant(T::from_f64(0.5));
+    let delta_value = Value::constant(T::from_f64(delta));
+
+    let sum = outputs
+        .iter()
+        .zip(targets)
+        .map(|(output, target)| {
+            // With q = min(|e|, delta), the loss is 0.5 * q² + delta * (|e| - q)
+            let error = (output - &Value::constant(*target)).abs();
+            let quadratic = error.clamp(0.0, delta);
+            let linear = &error - &quadratic;
+
+            &(&half * &(&quadratic * &quadratic)) + &(&delta_value * &linear)
+        })
+        .fold(Value::constant(T::from_f64(0.0)), |acc, item| acc + item);
+
+    &sum / &Value::constant(T::from_f64(outputs.len() as f64))
+}
+
+/// hinge computes the mean hinge loss max(0, 1 - y * output) for targets y of -1 or 1, as used to
+/// train maximum-margin classifiers.
+/// Panics if there are no outputs or the number of targets doesn't match the outputs.
+pub fn hinge<T: Scalar>(outputs: &[Value<T>], targets: &[T]) -> Value<T> {
+    assert!(!outputs.is_empty(), "hinge needs at least one output");
+    assert_eq!(outputs.len(), targets.len(), "expected one target per output");
+
+    let one = Value::constant(T::from_f64(1.0));
+    let zero = Value::constant(T::from_f64(0.0));
+
+    let sum = outputs
+        .iter()
+        .zip(targets)
+        .map(|(output, target)| {
+            let margin = &one - &(output * &Value::constant(*target));
+            margin.max(&zero)
+        })
+        .fold(Value::constant(T::from_f64(0.0)), |acc, item| acc + item);
+
+    &sum / &Value::constant(T::from_f64(outputs.len() as f64))
+}
+
+/// cross_entropy computes -Σ w_c * t_c * ln(softmax(logits)_c) for targets t, usually one-hot.
+/// `class_weights` scales each class's term, e.g. by data::class_weights, so the rare classes of
+/// an imbalanced dataset aren't drowned out by the common ones; without it every weight is 1.
+///
+/// The log-probabilities are computed as x_c - ln(Σ e^x_j) with the largest logit subtracted
+/// first, so confident predictions don't overflow or take the log of 0.
+/// Panics if there are no logits, or the number of targets or class weights doesn't match the logits.
+pub fn cross_entropy<T: Scalar>(logits: &[Value<T>], targets: &[T], class_weights: Option<&[f64]>) -> Value<T> {
+    assert!(!logits.is_empty(), "cross_entropy needs at least one logit");
+    assert_eq!(logits.len(), targets.len(), "expected one target per logit");
+    if let Some(class_weights) = class_weights {
+        assert_eq!(logits.len(), class_weights.len(), "expected one class weight per logit");
+    }
+
+    let max = logits.iter().map(|x| x.get_data().to_f64()).fold(f64::NEG_INFINITY, f64::max);
+    let shift = Value::constant(T::from_f64(max));
+
+    let shifted: Vec<Value<T>> = logits.iter().map(|x| x - &shift).collect();
+    let log_sum = shifted
+        .iter()
+        .fold(Value::constant(T::from_f64(0.0)), |acc, x| acc + x.exp())
+        .ln();
+
+    shifted
+        .iter()
+        .zip(targets)
+        .enumerate()
+        .filter(|(_, (_, target))| target.to_f64() != 0.0)
+        .map(|(class, (x, target))| {
+            let weight = class_weights.map_or(1.0, |weights| weights[class]);
+            &(&log_sum - x) * &Value::constant(T::from_f64(weight * target.to_f64()))
+        })
+        .fold(Value::constant(T::from_f64(0.0)), |acc, item| acc + item)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+    use crate::loss::{cross_entropy, hinge, huber, mse, Loss};
+    use crate::value::{build_topological_graph, Value, ValueOp};
+
+    #[test]
+    fn mse_value_and_gradient() {
+        let a = Value::new(1.0);
+        let b = Value::new(4.0);
+
+        // ((1 - 2)^2 + (4 - 2)^2) / 2 = 2.5
+        let loss = mse(&[a.clone(), b.clone()], &[2.0, 2.0]);
+        assert_eq!(loss.get_data(), 2.5);
+
+        // d/da = (a - 2), d/db = (b - 2)
+        loss.run_grad();
+        assert_eq!(a.get_gradient(), -1.0);
+        assert_eq!(b.get_gradient(), 2.0);
+    }
+
+    #[test]
+    fn huber_is_quadratic_then_linear() {
+        let near = Value::new(1.5);
+        let far = Value::new(-3.0);
+
+        // 0.5 * 0.5^2 = 0.125 and 1 * (3 - 0.5) = 2.5, averaged
+        let loss = huber(&[near.clone(), far.clone()], &[1.0, 0.0], 1.0);
+        assert_eq!(loss.get_data(), 1.3125);
+
+        // The outlier's gradient is capped at delta
+        loss.run_grad();
+        assert_eq!(near.get_gradient(), 0.25);
+        assert_eq!(far.get_gradient(), -0.5);
+    }
+
+    #[test]
+    fn hinge_ignores_confident_predictions() {
+        let correct = Value::new(2.0);
+        let wrong = Value::new(0.5);
+
+        // max(0, 1 - 2) = 0 and max(0, 1 + 0.5) = 1.5, averaged
+        let loss = hinge(&[correct.clone(), wrong.clone()], &[1.0, -1.0]);
+        assert_eq!(loss.get_data(), 0.75);
+
+        loss.run_grad();
+        assert_eq!(correct.get_gradient(), 0.0);
+        assert_eq!(wrong.get_gradient(), 0.5);
+    }
+
+    #[test]
+    fn cross_entropy_weights_classes() {
+        let logits = [Value::new(2.0), Value::new(0.0)];
+
+        // -ln(e^0 / (e^2 + e^0)) = ln(1 + e^2)
+        let loss = cross_entropy(&logits, &[0.0, 1.0], None);
+        assert!((loss.get_data() - (1.0 + 2.0f64.exp()).ln()).abs() < 1e-12);
+
+        // d/dx_i = p_i - t_i
+        loss.run_grad();
+        let p = 2.0f64.exp() / (2.0f64.exp() + 1.0);
+        assert!((logits[0].get_gradient() - p).abs() < 1e-12);
+        assert!((logits[1].get_gradient() + p).abs() < 1e-12);
+
+        let weighted = Loss::CrossEntropy { class_weights: Some(vec![1.0, 4.0]) }.compute(&logits, &[0.0, 1.0]);
+        assert!((weighted.get_data() - 4.0 * loss.get_data()).abs() < 1e-12);
+
+        // Confident logits stay finite
+        let confident: [Value<f64>; 2] = [Value::new(1000.0), Value::new(-1000.0)];
+        assert_eq!(cross_entropy(&confident, &[0.0, 1.0], None).get_data(), 2000.0);
+    }
+
+    #[test]
+    fn targets_and_literals_are_constants() {
+        let outputs = [Value::new(0.5), Value::new(-1.0)];
+        let targets = [1.0, 0.0];
+
+        let losses = [
+            mse(&outputs, &targets),
+            huber(&outputs, &targets, 1.0),
+            hinge(&outputs, &[1.0, -1.0]),
+            cross_entropy(&outputs, &targets, Some(&[1.0, 2.0])),
+        ];
+
+        // Only the outputs are trainable leaves, so the backward pass doesn't touch anything else
+        for loss in losses {
+            for node in build_topological_graph(&loss) {
+                let is_output = outputs.iter().any(|output| Rc::ptr_eq(output, &node));
+                let inner = node.borrow();
+                if inner.ancestors.is_empty() && !is_output {
+                    assert_eq!(inner.operation, ValueOp::Const, "{} isn't a constant", inner);
+                }
+            }
+        }
+    }
+}
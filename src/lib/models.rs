@@ -0,0 +1,219 @@
+use std::fmt;
+use crate::optimize::{minimize, MinimizeConfig};
+use crate::value::{self, Value};
+
+/// ModelError represents data a model can't be fitted to or make predictions for.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ModelError {
+    LengthMismatch { inputs: usize, targets: usize },
+    NoData,
+
+    // An input has a different number of features than the model was fitted on.
+    DimensionMismatch { expected: usize, found: usize },
+
+    // A logistic regression target isn't 0 or 1.
+    InvalidLabel(f64),
+}
+
+impl fmt::Display for ModelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ModelError::LengthMismatch { inputs, targets } => write!(f, "got {} inputs but {} targets", inputs, targets),
+            ModelError::NoData => write!(f, "there are no samples to fit"),
+            ModelError::DimensionMismatch { expected, found } => write!(f, "expected {} features, got {}", expected, found),
+            ModelError::InvalidLabel(label) => write!(f, "labels must be 0 or 1, got {}", label),
+        }
+    }
+}
+
+impl std::error::Error for ModelError {}
+
+/// LinearRegression predicts a target as a weighted sum of the features plus a bias, fitted by
+/// minimizing the mean squared error plus `l2` times the sum of the squared weights (ridge
+/// regression when `l2` is positive).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LinearRegression {
+    pub weights: Vec<f64>,
+    pub bias: f64,
+    pub l2: f64,
+}
+
+impl LinearRegression {
+    pub fn new() -> LinearRegression {
+        LinearRegression::default()
+    }
+
+    /// Fits the weights and bias to `inputs` and their `targets`, replacing any earlier fit.
+    pub fn fit(&mut self, inputs: &[Vec<f64>], targets: &[f64]) -> Result<(), ModelError> {
+        let features = check_samples(inputs, targets.len())?;
+
+        let (weights, bias) = fit_linear(inputs, features, self.l2, |prediction, target| {
+            let error = prediction - &Value::constant(target);
+            &error * &error
+        }, targets);
+        self.weights = weights;
+        self.bias = bias;
+
+        Ok(())
+    }
+
+    pub fn predict(&self, inputs: &[Vec<f64>]) -> Result<Vec<f64>, ModelError> {
+        inputs.iter().map(|input| linear(&self.weights, self.bias, input)).collect()
+    }
+}
+
+/// LogisticRegression predicts the probability that a sample belongs to class 1 rather than
+/// class 0 as sigmoid(weights · features + bias), fitted by minimizing the mean binary cross
+/// entropy plus `l2` times the sum of the squared weights. The default `l2` is small but positive
+/// so the weights stay finite when the classes are linearly separable.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogisticRegression {
+    pub weights: Vec<f64>,
+    pub bias: f64,
+    pub l2: f64,
+}
+
+impl Default for LogisticRegression {
+    fn default() -> Self {
+        LogisticRegression { weights: Vec::new(), bias: 0.0, l2: 1e-4 }
+    }
+}
+
+impl LogisticRegression {
+    pub fn new() -> LogisticRegression {
+        LogisticRegression::default()
+    }
+
+    /// Fits the weights and bias to `inputs` and their labels, each 0.0 or 1.0, replacing any
+    /// earlier fit.
+    pub fn fit(&mut self, inputs: &[Vec<f64>], labels: &[f64]) -> Result<(), ModelError> {
+        let features = check_samples(inputs, labels.len())?;
+        if let Some(label) = labels.iter().find(|&&label| label != 0.0 && label != 1.0) {
+            return Err(ModelError::InvalidLabel(*label));
+        }
+
+        // -ln(sigmoid(z)) = softplus(-z) and -ln(1 - sigmoid(z)) = softplus(z)
+        let (weights, bias) = fit_linear(inputs, features, self.l2, |logit, label| {
+            if label == 1.0 { (&Value::constant(0.0) - logit).softplus() } else { logit.softplus() }
+        }, labels);
+        self.weights = weights;
+        self.bias = bias;
+
+        Ok(())
+    }
+
+    /// Returns the probability of class 1 for each input.
+    pub fn predict_proba(&self, inputs: &[Vec<f64>]) -> Result<Vec<f64>, ModelError> {
+        inputs
+            .iter()
+            .map(|input| linear(&self.weights, self.bias, input).map(|logit| 1.0 / (1.0 + (-logit).exp())))
+            .collect()
+    }
+
+    /// Returns the most likely class, 0 or 1, of each input.
+    pub fn predict(&self, inputs: &[Vec<f64>]) -> Result<Vec<usize>, ModelError> {
+        Ok(self.predict_proba(inputs)?.into_iter().map(|p| usize::from(p > 0.5)).collect())
+    }
+}
+
+// check_samples returns the number of features of the inputs, failing unless there's one target
+// per input and every input has the same number of features
+fn check_samples(inputs: &[Vec<f64>], targets: usize) -> Result<usize, ModelError> {
+    if inputs.len() != targets {
+        return Err(ModelError::LengthMismatch { inputs: inputs.len(), targets });
+    }
+
+    let features = inputs.first().ok_or(ModelError::NoData)?.len();
+    if let Some(input) = inputs.iter().find(|input| input.len() != features) {
+        return Err(ModelError::DimensionMismatch { expected: features, found: input.len() });
+    }
+
+    Ok(features)
+}
+
+// fit_linear minimizes the mean of `loss(weights · input + bias, target)` over the samples plus
+// the l2 penalty, returning the weights and bias
+fn fit_linear(
+    inputs: &[Vec<f64>],
+    features: usize,
+    l2: f64,
+    loss: impl Fn(&Value<f64>, f64) -> Value<f64>,
+    targets: &[f64],
+) -> (Vec<f64>, f64) {
+    let objective = |params: &[Value<f64>]| {
+        let (weights, bias) = params.split_at(features);
+
+        let total = inputs
+            .iter()
+            .zip(targets)
+            .map(|(input, target)| loss(&(&value::dot(weights, input) + &bias[0]), *target))
+            .fold(Value::constant(0.0), |acc, term| acc + term);
+        let mean = &total * &Value::constant(1.0 / inputs.len() as f64);
+
+        if l2 == 0.0 {
+            return mean;
+        }
+        &mean + &(&value::dot_values(weights, weights) * &Value::constant(l2))
+    };
+
+    let mut params = minimize(objective, &vec![0.0; features + 1], &MinimizeConfig::default()).x;
+    let bias = params.pop().unwrap_or_default();
+
+    (params, bias)
+}
+
+fn linear(weights: &[f64], bias: f64, input: &[f64]) -> Result<f64, ModelError> {
+    if input.len() != weights.len() {
+        return Err(ModelError::DimensionMismatch { expected: weights.len(), found: input.len() });
+    }
+
+    Ok(weights.iter().zip(input).map(|(w, x)| w * x).sum::<f64>() + bias)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::{LinearRegression, LogisticRegression, ModelError};
+
+    #[test]
+    fn linear_regression_recovers_the_coefficients() {
+        // y = 2 * x1 - 3 * x2 + 0.5
+        let inputs: Vec<Vec<f64>> = (0..12).map(|i| vec![i as f64 / 4.0, ((i * 5) % 7) as f64 / 3.0]).collect();
+        let targets: Vec<f64> = inputs.iter().map(|x| 2.0 * x[0] - 3.0 * x[1] + 0.5).collect();
+
+        let mut model = LinearRegression::new();
+        model.fit(&inputs, &targets).unwrap();
+        assert!((model.weights[0] - 2.0).abs() < 1e-6 && (model.weights[1] + 3.0).abs() < 1e-6);
+        assert!((model.bias - 0.5).abs() < 1e-6);
+        assert!((model.predict(&[vec![1.0, 1.0]]).unwrap()[0] + 0.5).abs() < 1e-6);
+
+        // Ridge regression shrinks the weights
+        let mut ridge = LinearRegression { l2: 1.0, ..LinearRegression::new() };
+        ridge.fit(&inputs, &targets).unwrap();
+        assert!(ridge.weights[0].abs() < model.weights[0].abs());
+
+        assert_eq!(model.predict(&[vec![1.0]]), Err(ModelError::DimensionMismatch { expected: 2, found: 1 }));
+        assert_eq!(model.fit(&inputs, &targets[1..]), Err(ModelError::LengthMismatch { inputs: 12, targets: 11 }));
+        assert_eq!(model.fit(&[vec![1.0], vec![1.0, 2.0]], &[1.0, 2.0]), Err(ModelError::DimensionMismatch { expected: 1, found: 2 }));
+        assert_eq!(model.fit(&[], &[]), Err(ModelError::NoData));
+    }
+
+    #[test]
+    fn logistic_regression_separates_classes() {
+        // Class 1 when x1 + x2 > 1.125, which is linearly separable
+        let inputs: Vec<Vec<f64>> = (0..40).map(|i| vec![(i % 8) as f64 / 4.0, (i / 8) as f64 / 2.0]).collect();
+        let labels: Vec<f64> = inputs.iter().map(|x| if x[0] + x[1] > 1.125 { 1.0 } else { 0.0 }).collect();
+
+        let mut model = LogisticRegression::new();
+        model.fit(&inputs, &labels).unwrap();
+
+        // The l2 penalty keeps the weights finite
+        assert!(model.weights.iter().all(|w| w.is_finite()), "{:?}", model);
+        let predictions: Vec<f64> = model.predict(&inputs).unwrap().into_iter().map(|p| p as f64).collect();
+        assert_eq!(predictions, labels);
+
+        let probabilities = model.predict_proba(&[vec![0.0, 0.0], vec![2.0, 2.0]]).unwrap();
+        assert!(probabilities[0] < 0.1 && probabilities[1] > 0.9, "{:?}", probabilities);
+
+        assert_eq!(model.fit(&inputs[..1], &[0.5]), Err(ModelError::InvalidLabel(0.5)));
+    }
+}
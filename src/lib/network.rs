@@ -1,101 +1,752 @@
-use rand::Rng;
-use crate::value::Value;
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use crate::inference::softmax;
+use crate::scalar::Scalar;
+use crate::tensor::TensorError;
+use crate::value::{self, Value, ValueOp};
+use crate::config::{LayerConfig, NetworkConfig};
+use crate::logging::Silent;
+use crate::quantize::QuantizedNetwork;
+use crate::rand::gaussian;
+use crate::train::{Trainer, TrainingHistory};
 
-// Weight represents the default weights type of float64 numbers
+// Weight represents a weight of the network's scalar type (float64 by default)
 // wrapped within the Value type.
-type Weight = Value<f64>;
-type Bias = Weight;
+type Weight<T> = Value<T>;
+type Bias<T> = Weight<T>;
+type ForwardHook<T> = Box<dyn Fn(&[T])>;
 
-pub struct Neuron {
-    weights: Vec<Weight>,
-    bias: Bias,
+/// Activation represents the non-linearity applied to a neuron's weighted sum.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Activation {
+    #[default]
+    Relu,
+    Linear,
+    // LeakyRelu scales negative inputs by alpha instead of zeroing them, e.g. alpha = 0.01
+    LeakyRelu { alpha: f64 },
+    Softplus,
+    Gelu,
+    Elu,
+    Swish,
 }
 
-// Neuron represents a single neuron with a given weight and bias value
-impl Neuron {
-    fn new(inputs: u64) -> Neuron {
-        let mut weights_rng = rand::thread_rng();
-        let mut bias_rng = rand::thread_rng();
+impl Activation {
+    pub fn apply<T: Scalar>(&self, x: &Value<T>) -> Value<T> {
+        match self {
+            Activation::Relu => x.relu(),
+            Activation::Linear => x.clone(),
+            Activation::LeakyRelu { alpha } => x.leaky_relu(*alpha),
+            Activation::Softplus => x.softplus(),
+            Activation::Gelu => x.gelu(),
+            Activation::Elu => x.elu(),
+            Activation::Swish => x.swish(),
+        }
+    }
+
+    /// Applies the activation to raw data, without recording it in a computation graph.
+    pub fn apply_scalar<T: Scalar>(&self, x: T) -> T {
+        match self {
+            Activation::Relu => {
+                let zero = T::from_f64(0.0);
+                if x > zero { x } else { zero }
+            }
+            Activation::Linear => x,
+            Activation::LeakyRelu { alpha } => {
+                if x > T::from_f64(0.0) { x } else { T::from_f64(alpha * x.to_f64()) }
+            }
+            Activation::Softplus => T::from_f64(ValueOp::Softplus.apply_unary(x.to_f64())),
+            Activation::Gelu => T::from_f64(ValueOp::Gelu.apply_unary(x.to_f64())),
+            Activation::Elu => T::from_f64(ValueOp::Elu.apply_unary(x.to_f64())),
+            Activation::Swish => T::from_f64(ValueOp::Swish.apply_unary(x.to_f64())),
+        }
+    }
 
-        let mut weights: Vec<Value<f64>> = Vec::with_capacity(inputs as usize);
+    pub fn to_str(&self) -> &'static str {
+        match self {
+            Activation::Relu => "relu",
+            Activation::Linear => "linear",
+            Activation::LeakyRelu { .. } => "leaky_relu",
+            Activation::Softplus => "softplus",
+            Activation::Gelu => "gelu",
+            Activation::Elu => "elu",
+            Activation::Swish => "swish",
+        }
+    }
+}
+
+pub struct Neuron<T = f64> {
+    weights: Vec<Weight<T>>,
+    bias: Option<Bias<T>>,
+    activation: Activation,
+}
+
+// Neuron represents a single neuron with a given weight and bias value
+impl<T: Scalar> Neuron<T> {
+    fn new<R: Rng>(inputs: u64, activation: Activation, use_bias: bool, rng: &mut R) -> Neuron<T> {
+        let mut weights: Vec<Value<T>> = Vec::with_capacity(inputs as usize);
         for _ in 0..inputs {
-            let raw_weight = weights_rng.gen_range(-1.0..=1.0);
-           let weight = Value::new(raw_weight);
+            let raw_weight = rng.gen_range(-1.0..=1.0);
+           let weight = Value::new(T::from_f64(raw_weight));
 
             weights.push(weight);
         }
 
-        let raw_bias = bias_rng.gen_range(-0.01..=0.01);
-        let bias = Value::new(raw_bias);
+        let bias = if use_bias {
+            let raw_bias = rng.gen_range(-0.01..=0.01);
+            Some(Value::new(T::from_f64(raw_bias)))
+        } else {
+            None
+        };
 
         Neuron {
             weights,
-            bias
+            bias,
+            activation,
         }
     }
 
-    fn relu(x: f64) -> f64 {
-        x.max(0.0)
-    }
-
     // Performs the forward pass on a given input and returns the activation
-    fn forward(&self, x: &Vec<f64>) -> f64 {
+    fn forward(&self, x: &[Value<T>]) -> Value<T> {
         // Compute the weighted sum of inputs for the neuron.
-        let weighted_sum = self.weights.
-            iter().
-            zip(x).
-            map(|(w, input)| {
-                let input_dim = &Value::new_from_ref(input);
-                
-                w * input_dim
-            }).
-            fold(Value::new(0.0), |acc, item| {
-                acc + item
-            });
-        
-        let weight_and_bias = &weighted_sum + &self.bias;
+        let weighted_sum = value::dot_values(&self.weights, x);
+
+        let weight_and_bias = match &self.bias {
+            Some(bias) => &weighted_sum + bias,
+            None => weighted_sum,
+        };
+
+        self.activation.apply(&weight_and_bias)
+    }
+
+    // Computes the activation on raw data without building a computation graph, for inference.
+    #[cfg(feature = "fast-math")]
+    fn forward_data(&self, x: &[T]) -> T {
+        let weights: Vec<T> = self.weights.iter().map(|w| w.get_data()).collect();
+        let weighted_sum = crate::kernels::dot(&weights, x);
+
+        let weight_and_bias = match &self.bias {
+            Some(bias) => weighted_sum + bias.get_data(),
+            None => weighted_sum,
+        };
+
+        self.activation.apply_scalar(weight_and_bias)
+    }
+
+    fn parameters(&self) -> Vec<Value<T>> {
+        let mut parameters = self.weights.clone();
+        parameters.extend(self.bias.clone());
 
-        let activation = Neuron::relu(weight_and_bias.get_data());
+        parameters
+    }
+
+    // Copies the neuron into new parameter nodes, keeping their data, labels and frozen state.
+    fn deep_clone(&self) -> Neuron<T> {
+        let copy = |parameter: &Value<T>| {
+            let copied = Value::new(parameter.get_data());
+            copied.borrow_mut().label = parameter.borrow().label.clone();
+            copied.set_requires_grad(parameter.requires_grad());
+
+            copied
+        };
+
+        Neuron {
+            weights: self.weights.iter().map(copy).collect(),
+            bias: self.bias.as_ref().map(copy),
+            activation: self.activation,
+        }
+    }
 
-         activation
+    // A ReLU unit is dead when none of its parameters received any gradient in the last backward
+    // pass, i.e. its pre-activation was negative for every sample. Frozen units never receive
+    // gradients, so they aren't counted.
+    fn is_dead(&self) -> bool {
+        self.activation == Activation::Relu && self.parameters().iter().all(|p| p.requires_grad() && p.get_gradient() == 0.0)
     }
 }
 
 // Layer consists of a set of neurons which receive inputs
-pub struct Layer {
-    neurons: Vec<Neuron>
+pub struct Layer<T = f64> {
+    neurons: Vec<Neuron<T>>,
+    num_inputs: u64,
+    activation: Activation,
+    bias: bool,
+    kind: LayerKind,
+
+    // training is set by Network::set_training, and changes what dropout and batch normalisation
+    // layers compute
+    training: Cell<bool>,
+
+    // forward_hooks are called with the layer's outputs after every forward pass
+    forward_hooks: Vec<ForwardHook<T>>,
+}
+
+// LayerKind is what a layer computes from its inputs. Dropout and batch normalisation layers have
+// one output per input. A batch normalisation layer keeps the scale and shift of each feature in a
+// linear neuron of its own, as its only weight and its bias.
+#[derive(Debug, Clone)]
+enum LayerKind {
+    Dense,
+    Dropout { p: f64 },
+    BatchNorm { momentum: f64, epsilon: f64, running_mean: RefCell<Vec<f64>>, running_var: RefCell<Vec<f64>> },
 }
 
-impl Layer {
-    pub fn new(num_inputs: u64, num_outputs: u64) -> Layer{
+impl<T: Scalar> Layer<T> {
+    /// Creates a dense layer with ReLU activations and a bias on every neuron.
+    pub fn new(num_inputs: u64, num_outputs: u64) -> Result<Layer<T>, NetworkError> {
+        Layer::dense(num_inputs, num_outputs, Activation::Relu, true)
+    }
+
+    /// Creates a dense layer, failing if it would have no inputs or no outputs.
+    pub fn dense(num_inputs: u64, num_outputs: u64, activation: Activation, bias: bool) -> Result<Layer<T>, NetworkError> {
+        Layer::dense_with_rng(num_inputs, num_outputs, activation, bias, &mut crate::rand::global_rng())
+    }
+
+    /// Like dense, drawing the initial parameters from `rng` rather than the thread's
+    /// global generator (see rand::global_seed), so a single layer can be seeded on its own.
+    pub fn dense_with_rng<R: Rng>(num_inputs: u64, num_outputs: u64, activation: Activation, bias: bool, rng: &mut R) -> Result<Layer<T>, NetworkError> {
+        if num_inputs == 0 || num_outputs == 0 {
+            return Err(NetworkError::EmptyLayer { inputs: num_inputs, outputs: num_outputs });
+        }
+
         let mut neurons = Vec::with_capacity(num_outputs as usize);
 
         for _ in 0..num_outputs {
-            let neuron = Neuron::new(num_inputs);
+            let neuron = Neuron::new(num_inputs, activation, bias, rng);
             neurons.push(neuron);
         }
 
-        Layer{neurons}
+        Ok(Layer::with_kind(neurons, num_inputs, activation, bias, LayerKind::Dense))
+    }
+
+    /// Creates a dropout layer over `features` inputs. While training (see Network::set_training)
+    /// it zeroes each input with probability `p`, drawn from rand::global_rng for every sample,
+    /// and scales the others by 1 / (1 - p) so their expected value is unchanged; otherwise it
+    /// passes its inputs through. Fails unless 0 <= p < 1.
+    pub fn dropout(features: u64, p: f64) -> Result<Layer<T>, NetworkError> {
+        if features == 0 {
+            return Err(NetworkError::EmptyLayer { inputs: features, outputs: features });
+        }
+        if !(0.0..1.0).contains(&p) {
+            return Err(NetworkError::InvalidHyperparameter { name: "dropout probability", value: p });
+        }
+
+        Ok(Layer::with_kind(vec![], features, Activation::Linear, false, LayerKind::Dropout { p }))
+    }
+
+    /// Creates a batch normalisation layer over `features` inputs, which normalises each feature
+    /// with a running mean and variance, then applies a learned scale (initially 1) and shift
+    /// (initially 0).
+    ///
+    /// Trainer builds the graph of one sample at a time, so the statistics can't come from the
+    /// batch itself: while training, each sample updates exponential moving averages of every
+    /// feature's mean and variance, weighted by `momentum`, and every pass normalises with the
+    /// averages. `epsilon` is added to the variance before dividing by its square root. Fails
+    /// unless 0 <= momentum <= 1 and epsilon > 0.
+    pub fn batch_norm(features: u64, momentum: f64, epsilon: f64) -> Result<Layer<T>, NetworkError> {
+        if features == 0 {
+            return Err(NetworkError::EmptyLayer { inputs: features, outputs: features });
+        }
+        if !(0.0..=1.0).contains(&momentum) {
+            return Err(NetworkError::InvalidHyperparameter { name: "batch norm momentum", value: momentum });
+        }
+        if epsilon.is_nan() || epsilon <= 0.0 {
+            return Err(NetworkError::InvalidHyperparameter { name: "batch norm epsilon", value: epsilon });
+        }
+
+        let neurons = (0..features)
+            .map(|_| Neuron {
+                weights: vec![Value::new(T::from_f64(1.0))],
+                bias: Some(Value::new(T::from_f64(0.0))),
+                activation: Activation::Linear,
+            })
+            .collect();
+        let kind = LayerKind::BatchNorm {
+            momentum,
+            epsilon,
+            running_mean: RefCell::new(vec![0.0; features as usize]),
+            running_var: RefCell::new(vec![1.0; features as usize]),
+        };
+
+        Ok(Layer::with_kind(neurons, features, Activation::Linear, true, kind))
+    }
+
+    fn with_kind(neurons: Vec<Neuron<T>>, num_inputs: u64, activation: Activation, bias: bool, kind: LayerKind) -> Layer<T> {
+        Layer { neurons, num_inputs, activation, bias, kind, training: Cell::new(false), forward_hooks: vec![] }
+    }
+
+    /// Creates a freshly initialised layer from its configuration.
+    pub fn from_config(config: &LayerConfig) -> Result<Layer<T>, NetworkError> {
+        match *config {
+            LayerConfig::Dense { inputs, outputs, activation, bias } => {
+                Layer::dense(inputs, outputs, activation, bias)
+            }
+            LayerConfig::Dropout { features, p } => Layer::dropout(features, p),
+            LayerConfig::BatchNorm { features, momentum, epsilon } => Layer::batch_norm(features, momentum, epsilon),
+        }
+    }
+
+    /// Returns the configuration describing this layer's architecture.
+    pub fn config(&self) -> LayerConfig {
+        match self.kind {
+            LayerKind::Dense => LayerConfig::Dense {
+                inputs: self.num_inputs,
+                outputs: self.neurons.len() as u64,
+                activation: self.activation,
+                bias: self.bias,
+            },
+            LayerKind::Dropout { p } => LayerConfig::Dropout { features: self.num_inputs, p },
+            LayerKind::BatchNorm { momentum, epsilon, .. } => LayerConfig::BatchNorm { features: self.num_inputs, momentum, epsilon },
+        }
+    }
+
+    /// Returns the name of the kind of layer, as in its configuration: "dense", "dropout" or "batch_norm".
+    pub fn layer_type(&self) -> &'static str {
+        match self.kind {
+            LayerKind::Dense => "dense",
+            LayerKind::Dropout { .. } => "dropout",
+            LayerKind::BatchNorm { .. } => "batch_norm",
+        }
+    }
+
+    pub fn num_inputs(&self) -> u64 {
+        self.num_inputs
+    }
+
+    pub fn num_outputs(&self) -> u64 {
+        match self.kind {
+            LayerKind::Dropout { .. } => self.num_inputs,
+            _ => self.neurons.len() as u64,
+        }
+    }
+
+    pub fn activation(&self) -> Activation {
+        self.activation
+    }
+
+    /// Returns the layer weights as an (outputs x inputs) matrix, one row per neuron.
+    ///
+    /// Dropout and batch normalisation layers return the matrix of the map they apply outside of
+    /// training: the identity, and a diagonal matrix of each feature's scale / sqrt(variance + epsilon).
+    /// With biases(), this lets inference-only copies (InferenceNetwork, QuantizedNetwork, ONNX
+    /// exports) treat every layer as a dense one.
+    pub fn weights(&self) -> Vec<Vec<T>> {
+        if let Some((scales, _)) = self.affine_map() {
+            let row = |(i, scale): (usize, f64)| (0..scales.len()).map(|j| T::from_f64(if i == j { scale } else { 0.0 })).collect();
+            return scales.iter().copied().enumerate().map(row).collect();
+        }
+
+        self.neurons
+            .iter()
+            .map(|neuron| neuron.weights.iter().map(|w| w.get_data()).collect())
+            .collect()
+    }
+
+    /// Returns the bias of each neuron, or None when the layer was built without biases. Like
+    /// weights, batch normalisation layers return the shift they apply outside of training,
+    /// shift - mean * scale / sqrt(variance + epsilon), and dropout layers have no biases.
+    pub fn biases(&self) -> Option<Vec<T>> {
+        if !self.bias {
+            return None;
+        }
+        if let Some((_, shifts)) = self.affine_map() {
+            return Some(shifts.into_iter().map(T::from_f64).collect());
+        }
+
+        Some(self.neurons.iter().filter_map(|neuron| neuron.bias.as_ref().map(|b| b.get_data())).collect())
     }
 
-    fn forward(&self, inputs: &Vec<f64>) -> Vec<f64> {
-        let mut outputs = Vec::with_capacity(self.neurons.len());
+    // affine_map returns the scale and shift which a dropout or batch normalisation layer applies
+    // to each input outside of training, or None for dense layers
+    fn affine_map(&self) -> Option<(Vec<f64>, Vec<f64>)> {
+        match &self.kind {
+            LayerKind::Dense => None,
+            LayerKind::Dropout { .. } => Some((vec![1.0; self.num_inputs as usize], vec![0.0; self.num_inputs as usize])),
+            LayerKind::BatchNorm { epsilon, running_mean, running_var, .. } => Some(
+                self.neurons
+                    .iter()
+                    .zip(running_mean.borrow().iter().zip(running_var.borrow().iter()))
+                    .map(|(neuron, (mean, variance))| {
+                        let scale = neuron.weights[0].get_data().to_f64() / (variance + epsilon).sqrt();
+                        let shift = neuron.bias.as_ref().map_or(0.0, |bias| bias.get_data().to_f64());
+                        (scale, shift - mean * scale)
+                    })
+                    .unzip(),
+            ),
+        }
+    }
 
-        for neuron in &self.neurons{
-            let neuron_result = neuron.forward(inputs);
-            outputs.push(neuron_result);
+    /// Returns the running mean and variance of each feature of a batch normalisation layer, see
+    /// batch_norm, or None for other layers.
+    pub fn running_stats(&self) -> Option<(Vec<f64>, Vec<f64>)> {
+        match &self.kind {
+            LayerKind::BatchNorm { running_mean, running_var, .. } => Some((running_mean.borrow().clone(), running_var.borrow().clone())),
+            _ => None,
+        }
+    }
+
+    /// Overwrites the running mean and variance of a batch normalisation layer, e.g. when
+    /// restoring a saved model. Panics if the layer isn't one, or the lengths don't match it.
+    pub fn set_running_stats(&self, mean: &[f64], variance: &[f64]) {
+        let LayerKind::BatchNorm { running_mean, running_var, .. } = &self.kind else {
+            panic!("only batch normalisation layers have running statistics");
+        };
+        assert!(mean.len() == self.neurons.len() && variance.len() == self.neurons.len(), "expected one mean and variance per feature");
+
+        running_mean.borrow_mut().copy_from_slice(mean);
+        running_var.borrow_mut().copy_from_slice(variance);
+    }
+
+    /// Switches the layer between training and inference, see Network::set_training.
+    pub fn set_training(&self, training: bool) {
+        self.training.set(training);
+    }
+
+    // Returns true if every forward pass builds the same graph, only with different data: dense
+    // layers without forward hooks, which a compiled graph would skip.
+    fn has_static_graph(&self) -> bool {
+        matches!(self.kind, LayerKind::Dense) && self.forward_hooks.is_empty()
+    }
+
+    /// Overwrites the layer weights from an (outputs x inputs) matrix.
+    /// Panics if the layer isn't dense or the matrix shape does not match the layer.
+    pub fn set_weights(&self, weights: &[Vec<T>]) {
+        assert!(matches!(self.kind, LayerKind::Dense), "only dense layers have a weight matrix");
+        assert_eq!(weights.len(), self.neurons.len(), "weight matrix must have one row per neuron");
+
+        for (neuron, row) in self.neurons.iter().zip(weights) {
+            assert_eq!(row.len(), neuron.weights.len(), "weight matrix must have one column per input");
+
+            for (weight, data) in neuron.weights.iter().zip(row) {
+                weight.set_data(*data);
+            }
+        }
+    }
+
+    /// Overwrites the bias of each neuron.
+    /// Panics if the layer isn't dense, has no biases or the number of biases does not match the layer.
+    pub fn set_biases(&self, biases: &[T]) {
+        assert!(matches!(self.kind, LayerKind::Dense), "only dense layers have a bias vector");
+        assert!(self.bias, "layer was built without biases");
+        assert_eq!(biases.len(), self.neurons.len(), "expected one bias per neuron");
+
+        for (neuron, data) in self.neurons.iter().zip(biases) {
+            if let Some(bias) = &neuron.bias {
+                bias.set_data(*data);
+            }
+        }
+    }
+
+    /// Returns the number of weights and biases in the layer.
+    pub fn num_parameters(&self) -> usize {
+        self.neurons.iter().map(|neuron| neuron.weights.len() + usize::from(neuron.bias.is_some())).sum()
+    }
+
+    /// Returns handles to every weight and bias in the layer.
+    pub fn parameters(&self) -> Vec<Value<T>> {
+        self.neurons.iter().flat_map(|neuron| neuron.parameters()).collect()
+    }
+
+    // neuron_parameters returns the parameters of each neuron, see parameters
+    pub(crate) fn neuron_parameters(&self) -> Vec<Vec<Value<T>>> {
+        self.neurons.iter().map(|neuron| neuron.parameters()).collect()
+    }
+
+    /// Returns a copy of the layer whose parameters are new nodes with the same values, so training
+    /// either layer leaves the other unchanged. Forward hooks aren't copied.
+    pub fn deep_clone(&self) -> Layer<T> {
+        Layer {
+            neurons: self.neurons.iter().map(|neuron| neuron.deep_clone()).collect(),
+            num_inputs: self.num_inputs,
+            activation: self.activation,
+            bias: self.bias,
+            kind: self.kind.clone(),
+            training: self.training.clone(),
+            forward_hooks: vec![],
+        }
+    }
+
+    /// Marks every parameter of the layer as non-trainable: backward passes leave their gradients
+    /// at zero and optimizers don't update them, e.g. to only train the head of a pretrained network.
+    pub fn freeze(&self) {
+        for parameter in self.parameters() {
+            parameter.set_requires_grad(false);
+            parameter.set_gradient(0.0);
+        }
+    }
+
+    pub fn unfreeze(&self) {
+        for parameter in self.parameters() {
+            parameter.set_requires_grad(true);
+        }
+    }
+
+    /// Returns true when none of the layer's parameters are trainable.
+    pub fn is_frozen(&self) -> bool {
+        self.parameters().iter().all(|parameter| !parameter.requires_grad())
+    }
+
+    /// Returns the number of parameters which aren't frozen.
+    pub fn num_trainable_parameters(&self) -> usize {
+        self.parameters().iter().filter(|parameter| parameter.requires_grad()).count()
+    }
+
+    /// Reports how many of the layer's parameters received an exactly zero gradient
+    /// in the last backward pass.
+    pub fn gradient_sparsity(&self) -> GradientSparsity {
+        let parameters = self.parameters();
+        let zero = parameters.iter().filter(|p| p.get_gradient() == 0.0).count();
+
+        GradientSparsity {
+            zero,
+            total: parameters.len(),
+        }
+    }
+
+    /// Reports statistics of the layer's parameters (weights and biases) and of their gradients
+    /// from the last backward pass, along with how many ReLU units are dead.
+    pub fn grad_report(&self) -> LayerReport {
+        let parameters = self.parameters();
+        let values: Vec<f64> = parameters.iter().map(|p| p.get_data().to_f64()).collect();
+        let gradients: Vec<f64> = parameters.iter().map(|p| p.get_gradient()).collect();
+
+        LayerReport {
+            weights: Stats::of(&values),
+            gradients: Stats::of(&gradients),
+            dead_units: self.neurons.iter().filter(|neuron| neuron.is_dead()).count(),
+            units: self.neurons.len(),
+        }
+    }
+
+    /// Registers `hook` to be called with the layer's activations (its outputs) after every
+    /// forward pass, e.g. to record them for inspection without modifying the network.
+    pub fn register_forward_hook(&mut self, hook: impl Fn(&[T]) + 'static) {
+        self.forward_hooks.push(Box::new(hook));
+    }
+
+    pub fn clear_forward_hooks(&mut self) {
+        self.forward_hooks.clear();
+    }
+
+    fn call_forward_hooks(&self, outputs: &[T]) {
+        for hook in &self.forward_hooks {
+            hook(outputs);
+        }
+    }
+
+    fn forward(&self, inputs: &[Value<T>]) -> Vec<Value<T>> {
+        let outputs = match &self.kind {
+            LayerKind::Dense => {
+                let mut outputs = Vec::with_capacity(self.neurons.len());
+
+                for neuron in &self.neurons{
+                    let neuron_result = neuron.forward(inputs);
+                    outputs.push(neuron_result);
+                }
+
+                outputs
+            }
+            LayerKind::Dropout { p } if self.training.get() => {
+                let mut rng = crate::rand::global_rng();
+
+                inputs
+                    .iter()
+                    .map(|x| {
+                        let mask = if rng.gen::<f64>() < *p { 0.0 } else { 1.0 / (1.0 - p) };
+                        x * &Value::constant(T::from_f64(mask))
+                    })
+                    .collect()
+            }
+            LayerKind::Dropout { .. } => inputs.to_vec(),
+            LayerKind::BatchNorm { momentum, epsilon, running_mean, running_var } => {
+                let (mut means, mut variances) = (running_mean.borrow_mut(), running_var.borrow_mut());
+
+                if self.training.get() {
+                    for ((x, mean), variance) in inputs.iter().zip(means.iter_mut()).zip(variances.iter_mut()) {
+                        let delta = x.get_data().to_f64() - *mean;
+                        *mean += momentum * delta;
+                        *variance = (1.0 - momentum) * (*variance + momentum * delta * delta);
+                    }
+                }
+
+                // The statistics are constants, so gradients reach the inputs and each feature's
+                // scale and shift, whose neuron computes scale * normalised + shift
+                inputs
+                    .iter()
+                    .zip(&self.neurons)
+                    .zip(means.iter().zip(variances.iter()))
+                    .map(|((x, neuron), (mean, variance))| {
+                        let centred = x - &Value::constant(T::from_f64(*mean));
+                        let normalised = centred * Value::constant(T::from_f64(1.0 / (variance + epsilon).sqrt()));
+                        neuron.forward(&[normalised])
+                    })
+                    .collect()
+            }
+        };
+
+        if !self.forward_hooks.is_empty() {
+            let data: Vec<T> = outputs.iter().map(|output| output.get_data()).collect();
+            self.call_forward_hooks(&data);
         }
 
         outputs
     }
+
+    #[cfg(feature = "fast-math")]
+    fn forward_data(&self, inputs: &[T]) -> Vec<T> {
+        let outputs: Vec<T> = match self.affine_map() {
+            Some((scales, shifts)) => inputs
+                .iter()
+                .zip(scales.iter().zip(shifts))
+                .map(|(x, (scale, shift))| T::from_f64(x.to_f64() * scale + shift))
+                .collect(),
+            None => self.neurons.iter().map(|neuron| neuron.forward_data(inputs)).collect(),
+        };
+        self.call_forward_hooks(&outputs);
+
+        outputs
+    }
 }
 
-pub struct Network {
-   pub layers: Vec<Layer>
+/// Network is a stack of layers (dense, dropout and batch normalisation) over a scalar type,
+/// float64 by default.
+/// A Network<f32> halves the memory used by its parameters and graph nodes.
+pub struct Network<T = f64> {
+   pub layers: Vec<Layer<T>>
 }
 
-impl Network {
-    pub fn forward(&self, inputs: &Vec<f64>) -> Vec<f64> {
+impl<T: Scalar> Network<T> {
+    /// Creates a network from its layers, checking that each layer accepts as many inputs
+    /// as the previous layer produces.
+    pub fn new(layers: Vec<Layer<T>>) -> Result<Network<T>, NetworkError> {
+        let network = Network { layers };
+        network.check_layers()?;
+
+        Ok(network)
+    }
+
+    /// Builds a network with freshly initialised parameters from an architecture configuration.
+    pub fn from_config(config: &NetworkConfig) -> Result<Network<T>, NetworkError> {
+        let layers = config.layers.iter().map(Layer::from_config).collect::<Result<_, _>>()?;
+
+        Network::new(layers)
+    }
+
+    /// Like from_config, with the initial parameters drawn from a generator seeded with `seed`,
+    /// so the same seed always builds the same network.
+    pub fn from_config_seeded(config: &NetworkConfig, seed: u64) -> Result<Network<T>, NetworkError> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let layers = config
+            .layers
+            .iter()
+            .map(|config| match *config {
+                LayerConfig::Dense { inputs, outputs, activation, bias } => {
+                    Layer::dense_with_rng(inputs, outputs, activation, bias, &mut rng)
+                }
+                _ => Layer::from_config(config),
+            })
+            .collect::<Result<_, _>>()?;
+
+        Network::new(layers)
+    }
+
+    /// Returns the architecture of this network, which can be used to rebuild an identically shaped network.
+    pub fn config(&self) -> NetworkConfig {
+        NetworkConfig {
+            layers: self.layers.iter().map(|layer| layer.config()).collect(),
+        }
+    }
+
+    /// Switches every layer between training and inference. While training, dropout layers drop
+    /// inputs at random and batch normalisation layers update their running statistics; otherwise
+    /// both apply a fixed map. Networks start out in inference mode, and Trainer switches them to
+    /// training only for its optimizer steps.
+    pub fn set_training(&self, training: bool) {
+        for layer in &self.layers {
+            layer.set_training(training);
+        }
+    }
+
+    /// Returns true if every forward pass builds the same graph with different data, so the graph
+    /// can be compiled once and rerun for each sample (see GradPlan). Dropout and batch norm
+    /// layers, whose graphs depend on the rng or running statistics, and forward hooks, which a
+    /// compiled graph wouldn't call, make it dynamic.
+    pub fn has_static_graph(&self) -> bool {
+        !self.layers.is_empty() && self.layers.iter().all(|layer| layer.has_static_graph())
+    }
+
+    // The layers are public, so networks which weren't built with Network::new are checked before every pass.
+    fn check_layers(&self) -> Result<(), NetworkError> {
+        if self.layers.is_empty() {
+            return Err(NetworkError::EmptyNetwork);
+        }
+
+        for (index, pair) in self.layers.windows(2).enumerate() {
+            if pair[1].num_inputs() != pair[0].num_outputs() {
+                return Err(NetworkError::IncompatibleLayers {
+                    layer: index + 1,
+                    expected: pair[0].num_outputs(),
+                    found: pair[1].num_inputs(),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks that the network is well formed and that `num_inputs` matches its first layer.
+    pub(crate) fn check_inputs(&self, num_inputs: usize) -> Result<(), NetworkError> {
+        self.check_layers()?;
+
+        let expected = self.layers[0].num_inputs() as usize;
+        if num_inputs != expected {
+            return Err(NetworkError::DimensionMismatch { expected, found: num_inputs });
+        }
+
+        Ok(())
+    }
+
+    /// Performs the forward pass and returns the outputs.
+    ///
+    /// With the `fast-math` feature the outputs are computed on raw data with the unrolled
+    /// kernels::dot instead of building a computation graph, so they can't be differentiated, and
+    /// always as in inference mode (see set_training). Use forward_values when gradients are needed.
+    pub fn forward(&self, inputs: &[T]) -> Result<Vec<T>, NetworkError> {
+        #[cfg(feature = "fast-math")]
+        {
+            self.check_inputs(inputs.len())?;
+
+            let mut result = inputs.to_vec();
+            for layer in &self.layers {
+                result = layer.forward_data(&result);
+            }
+
+            Ok(result)
+        }
+
+        #[cfg(not(feature = "fast-math"))]
+        {
+            Ok(self.forward_values(inputs)?.iter().map(|output| output.get_data()).collect())
+        }
+    }
+
+    /// Performs the forward pass and returns the output nodes of the computation graph,
+    /// which can be used as roots for the backward pass. The inputs are constants, so only
+    /// the parameters receive gradients.
+    pub fn forward_values(&self, inputs: &[T]) -> Result<Vec<Value<T>>, NetworkError> {
+        let inputs: Vec<Value<T>> = inputs.iter().map(|x| Value::constant(*x)).collect();
+
+        self.forward_graph(&inputs)
+    }
+
+    /// Performs the forward pass on inputs which are already nodes of a computation graph,
+    /// e.g. to differentiate the outputs with respect to the inputs.
+    pub fn forward_graph(&self, inputs: &[Value<T>]) -> Result<Vec<Value<T>>, NetworkError> {
+        self.check_inputs(inputs.len())?;
+
         let mut result = Vec::new();
 
         for (index, layer) in self.layers.iter().enumerate(){
@@ -109,29 +760,888 @@ impl Network {
             result = layer.forward(&result)
         }
 
-        result
+        Ok(result)
+    }
+
+    /// Runs a batch of inputs through a classifier and returns each sample's class probabilities.
+    ///
+    /// The outputs are treated as logits: a network with several outputs gets a softmax over
+    /// them, one with a single output is a binary classifier whose sigmoid is the probability of
+    /// class 1, returned as [P(class 0), P(class 1)]. The last layer must therefore be Linear;
+    /// any other activation returns NetworkError::NotLogits, as its outputs aren't logits.
+    pub fn predict_proba(&self, inputs: &[Vec<T>]) -> Result<Vec<Vec<f64>>, NetworkError> {
+        let activation = self.layers[self.layers.len() - 1].activation();
+        if activation != Activation::Linear {
+            return Err(NetworkError::NotLogits { activation });
+        }
+
+        inputs
+            .iter()
+            .map(|input| {
+                let logits: Vec<f64> = self.forward(input)?.iter().map(|x| x.to_f64()).collect();
+
+                Ok(match logits.as_slice() {
+                    [logit] => {
+                        let p = 1.0 / (1.0 + (-logit).exp());
+                        vec![1.0 - p, p]
+                    }
+                    _ => softmax(&logits),
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the most likely class of each input, see predict_proba. Ties go to the lower class.
+    pub fn predict_classes(&self, inputs: &[Vec<T>]) -> Result<Vec<usize>, NetworkError> {
+        Ok(self.predict_proba(inputs)?.iter().map(|probabilities| argmax(probabilities)).collect())
+    }
+
+    /// Returns handles to every trainable parameter in the network, layer by layer.
+    pub fn parameters(&self) -> Vec<Value<T>> {
+        self.layers.iter().flat_map(|layer| layer.parameters()).collect()
+    }
+
+    /// Returns the values of every parameter as one flat vector, in the order of parameters():
+    /// layer by layer, and within a layer neuron by neuron, each neuron's weights followed by its
+    /// bias. Frozen parameters are included.
+    pub fn get_flat_params(&self) -> Vec<f64> {
+        self.parameters().iter().map(|parameter| parameter.get_data().to_f64()).collect()
+    }
+
+    /// Sets every parameter from a flat vector in the order of get_flat_params, e.g. one produced
+    /// by an external optimizer. Fails without changing the network if the length doesn't match.
+    pub fn set_flat_params(&self, values: &[f64]) -> Result<(), NetworkError> {
+        let parameters = self.parameters();
+        if values.len() != parameters.len() {
+            return Err(NetworkError::DimensionMismatch { expected: parameters.len(), found: values.len() });
+        }
+
+        for (parameter, value) in parameters.iter().zip(values) {
+            parameter.set_data(T::from_f64(*value));
+        }
+
+        Ok(())
+    }
+
+    // parameter_layout returns the layer of each parameter and whether it's a bias, in the order
+    // of parameters()
+    pub(crate) fn parameter_layout(&self) -> Vec<(usize, bool)> {
+        self.layers
+            .iter()
+            .enumerate()
+            .flat_map(|(index, layer)| {
+                layer.neurons.iter().flat_map(move |neuron| {
+                    let weights = neuron.weights.iter().map(move |_| (index, false));
+                    weights.chain(neuron.bias.iter().map(move |_| (index, true)))
+                })
+            })
+            .collect()
+    }
+
+    /// Reports gradient sparsity statistics for each layer.
+    pub fn gradient_sparsity(&self) -> Vec<GradientSparsity> {
+        self.layers.iter().map(|layer| layer.gradient_sparsity()).collect()
+    }
+
+    /// Returns a copy of the network with independent parameters, e.g. for target networks,
+    /// ensembles or snapshots of the best model. Cloning the parameter handles instead would
+    /// share the underlying nodes, tying both networks together.
+    pub fn deep_clone(&self) -> Network<T> {
+        Network {
+            layers: self.layers.iter().map(|layer| layer.deep_clone()).collect(),
+        }
+    }
+
+    /// Adds gaussian noise with standard deviation `std_dev` to every trainable parameter and
+    /// returns the noise added to each parameter (0 for frozen ones), in the order of parameters().
+    /// Perturbing a deep_clone explores nearby weights without touching the original network.
+    pub fn perturb_weights<R: Rng + ?Sized>(&self, std_dev: f64, rng: &mut R) -> Vec<f64> {
+        self.parameters()
+            .iter()
+            .map(|parameter| {
+                if !parameter.requires_grad() {
+                    return 0.0;
+                }
+
+                let noise = gaussian(rng, std_dev);
+                parameter.set_data(T::from_f64(parameter.get_data().to_f64() + noise));
+                noise
+            })
+            .collect()
+    }
+
+    /// Returns an inference-only copy of the network with int8 weights, see QuantizedNetwork.
+    pub fn quantize_int8(&self) -> QuantizedNetwork {
+        QuantizedNetwork::from_network(self)
+    }
+
+    /// Returns a new network whose parameters are the element-wise mean of the parameters of
+    /// `networks`, which must all share the same architecture, e.g. to combine snapshots of a
+    /// single training run (stochastic weight averaging). Frozen state is taken from the first network.
+    pub fn average_weights(networks: &[Network<T>]) -> Result<Network<T>, NetworkError> {
+        let first = networks.first().ok_or(NetworkError::EmptyEnsemble)?;
+
+        let config = first.config();
+        if let Some(model) = networks.iter().position(|network| network.config() != config) {
+            return Err(NetworkError::ArchitectureMismatch { model });
+        }
+
+        let mut sums = vec![0.0; first.num_parameters()];
+        for network in networks {
+            for (sum, parameter) in sums.iter_mut().zip(network.parameters()) {
+                *sum += parameter.get_data().to_f64();
+            }
+        }
+
+        let average = first.deep_clone();
+        for (parameter, sum) in average.parameters().iter().zip(sums) {
+            parameter.set_data(T::from_f64(sum / networks.len() as f64));
+        }
+
+        Ok(average)
+    }
+
+    /// Returns true when both networks have the same architecture and every pair of corresponding
+    /// weights and biases differs by at most `eps`.
+    pub fn weights_approx_eq(&self, other: &Network<T>, eps: f64) -> bool {
+        self.config() == other.config()
+            && self.parameters().iter().zip(other.parameters()).all(|(a, b)| a.approx_eq(&b, eps))
+    }
+
+    /// Returns the total number of weights and biases in the network.
+    pub fn num_parameters(&self) -> usize {
+        self.layers.iter().map(|layer| layer.num_parameters()).sum()
+    }
+
+    /// Returns the number of parameters which aren't frozen, see Layer::freeze.
+    pub fn num_trainable_parameters(&self) -> usize {
+        self.layers.iter().map(|layer| layer.num_trainable_parameters()).sum()
+    }
+
+    /// Returns a table describing each layer's output shape, activation and parameter count,
+    /// followed by the network's total parameter count, e.g.
+    ///
+    /// ```text
+    /// Layer  Type   Output shape  Activation  Params
+    /// ==============================================
+    /// 0      dense  (4)           relu        12
+    /// 1      dense  (1)           linear      5
+    /// ==============================================
+    /// Total params: 17
+    /// Trainable params: 17
+    /// Non-trainable params: 0
+    /// ```
+    pub fn summary(&self) -> String {
+        let header = ["Layer", "Type", "Output shape", "Activation", "Params"];
+        let rows: Vec<[String; 5]> = self
+            .layers
+            .iter()
+            .enumerate()
+            .map(|(i, layer)| {
+                [
+                    i.to_string(),
+                    layer.layer_type().to_string(),
+                    format!("({})", layer.num_outputs()),
+                    layer.activation().to_str().to_string(),
+                    layer.num_parameters().to_string(),
+                ]
+            })
+            .collect();
+
+        // Each column is as wide as its longest cell, with two spaces between columns
+        let mut widths = header.map(|title| title.len());
+        for row in &rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.len());
+            }
+        }
+
+        let format_row = |cells: &[String]| {
+            let padded: Vec<String> = cells.iter().zip(widths).map(|(cell, width)| format!("{:<width$}", cell, width = width)).collect();
+            padded.join("  ").trim_end().to_string()
+        };
+        let rule = "=".repeat(widths.iter().sum::<usize>() + 2 * (widths.len() - 1));
+
+        let (total, trainable) = (self.num_parameters(), self.num_trainable_parameters());
+        let mut lines = vec![format_row(&header.map(String::from)), rule.clone()];
+        lines.extend(rows.iter().map(|row| format_row(row)));
+        lines.push(rule);
+        lines.push(format!("Total params: {}", total));
+        lines.push(format!("Trainable params: {}", trainable));
+        lines.push(format!("Non-trainable params: {}", total - trainable));
+
+        lines.join("\n") + "\n"
+    }
+
+    /// Reports weight and gradient statistics for each layer, see Layer::grad_report.
+    pub fn grad_report(&self) -> Vec<LayerReport> {
+        self.layers.iter().map(|layer| layer.grad_report()).collect()
+    }
+
+    /// Trains the network silently with `trainer` for `epochs` passes over `dataset`, evaluating
+    /// the loss over `validation` after each one (pass an empty slice to skip validation).
+    pub fn train(
+        &self,
+        trainer: &Trainer,
+        dataset: &[(Vec<T>, Vec<T>)],
+        validation: &[(Vec<T>, Vec<T>)],
+        epochs: usize,
+    ) -> Result<TrainingHistory, NetworkError> {
+        trainer.fit_with_validation(self, dataset, validation, epochs, &mut Silent)
+    }
+}
+
+/// Averages the predictions of `networks` for `input`, e.g. of models trained on different
+/// bootstrap samples of a dataset (bagging). Every network must produce the same number of outputs.
+pub fn ensemble_average<T: Scalar>(networks: &[Network<T>], input: &[T]) -> Result<Vec<T>, NetworkError> {
+    let mut sums: Vec<f64> = Vec::new();
+
+    for (model, network) in networks.iter().enumerate() {
+        let outputs = network.forward(input)?;
+        if model == 0 {
+            sums = vec![0.0; outputs.len()];
+        } else if outputs.len() != sums.len() {
+            return Err(NetworkError::ArchitectureMismatch { model });
+        }
+
+        for (sum, output) in sums.iter_mut().zip(outputs) {
+            *sum += output.to_f64();
+        }
+    }
+
+    if networks.is_empty() {
+        return Err(NetworkError::EmptyEnsemble);
+    }
+
+    Ok(sums.iter().map(|sum| T::from_f64(sum / networks.len() as f64)).collect())
+}
+
+/// Computes the Jacobian of the network's outputs with respect to its inputs at `input`.
+/// The result is an (outputs x inputs) matrix where entry [i][j] is d output_i / d input_j.
+pub fn jacobian<T: Scalar>(network: &Network<T>, input: &[T]) -> Result<Vec<Vec<f64>>, NetworkError> {
+    let inputs: Vec<Value<T>> = input.iter().map(Value::new_from_ref).collect();
+
+    Ok(network
+        .forward_graph(&inputs)?
+        .iter()
+        .map(|output| output.grad_wrt(&inputs))
+        .collect())
+}
+
+// argmax returns the index of the first largest value
+fn argmax(values: &[f64]) -> usize {
+    let mut best = 0;
+    for (index, value) in values.iter().enumerate() {
+        if *value > values[best] {
+            best = index;
+        }
+    }
+
+    best
+}
+
+/// NetworkError represents a network or layer which cannot be built, or inputs which don't fit the network.
+#[derive(Debug, Clone, PartialEq)]
+pub enum NetworkError {
+    // A network needs at least one layer.
+    EmptyNetwork,
+
+    // A layer needs at least one input and one output.
+    EmptyLayer { inputs: u64, outputs: u64 },
+
+    // The number of inputs passed to the network doesn't match its first layer.
+    DimensionMismatch { expected: usize, found: usize },
+
+    // The layer at index `layer` doesn't accept as many inputs as the previous layer produces.
+    IncompatibleLayers { layer: usize, expected: u64, found: u64 },
+
+    // Combining networks needs at least one network.
+    EmptyEnsemble,
+
+    // The network at index `model` doesn't have the same layers as the first network.
+    ArchitectureMismatch { model: usize },
+
+    // A convolution or pooling window needs a non-zero size and stride.
+    InvalidKernel { kernel_size: usize, stride: usize },
+
+    // The (padded) input is shorter than the window sliding over it.
+    InputTooShort { minimum: usize, found: usize },
+
+    // A shape needs every dimension to be non-zero.
+    EmptyShape { shape: Vec<usize> },
+
+    // `inputs` values can't be rearranged into `shape`, as it holds a different number of values.
+    IncompatibleShape { inputs: usize, shape: Vec<usize> },
+
+    // Training couldn't write its checkpoint, see Trainer::checkpoint_path.
+    Checkpoint(String),
+
+    // A streamed dataset couldn't be read during training.
+    Dataset(String),
+
+    // A layer built on the tensor API, such as SelfAttention, was given a tensor of the wrong shape.
+    Tensor(TensorError),
+
+    // The last layer applies `activation`, so its outputs can't be read as logits, see Network::predict_proba.
+    NotLogits { activation: Activation },
+
+    // A layer's hyperparameter, such as a dropout probability, is outside of its valid range.
+    InvalidHyperparameter { name: &'static str, value: f64 },
+}
+
+impl fmt::Display for NetworkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NetworkError::EmptyNetwork => write!(f, "network has no layers"),
+            NetworkError::EmptyLayer { inputs, outputs } => {
+                write!(f, "layer must have at least one input and output, got {} inputs and {} outputs", inputs, outputs)
+            }
+            NetworkError::DimensionMismatch { expected, found } => {
+                write!(f, "expected {} inputs, found {}", expected, found)
+            }
+            NetworkError::IncompatibleLayers { layer, expected, found } => {
+                write!(f, "layer {} takes {} inputs but the previous layer produces {}", layer, found, expected)
+            }
+            NetworkError::EmptyEnsemble => write!(f, "no networks to combine"),
+            NetworkError::ArchitectureMismatch { model } => {
+                write!(f, "network {} doesn't have the same architecture as the first network", model)
+            }
+            NetworkError::InvalidKernel { kernel_size, stride } => {
+                write!(f, "kernel size and stride must be non-zero, got kernel size {} and stride {}", kernel_size, stride)
+            }
+            NetworkError::InputTooShort { minimum, found } => {
+                write!(f, "input must have a length of at least {} once padded, found {}", minimum, found)
+            }
+            NetworkError::EmptyShape { shape } => write!(f, "shape {:?} has an empty dimension", shape),
+            NetworkError::IncompatibleShape { inputs, shape } => {
+                write!(f, "cannot arrange {} values into shape {:?}", inputs, shape)
+            }
+            NetworkError::Checkpoint(reason) => write!(f, "failed to write checkpoint: {}", reason),
+            NetworkError::Dataset(reason) => write!(f, "failed to read dataset: {}", reason),
+            NetworkError::Tensor(err) => write!(f, "invalid tensor: {}", err),
+            NetworkError::NotLogits { activation } => {
+                write!(f, "the last layer applies {}, so its outputs aren't logits", activation.to_str())
+            }
+            NetworkError::InvalidHyperparameter { name, value } => write!(f, "invalid {}: {}", name, value),
+        }
+    }
+}
+
+impl std::error::Error for NetworkError {}
+
+impl From<TensorError> for NetworkError {
+    fn from(err: TensorError) -> Self {
+        NetworkError::Tensor(err)
+    }
+}
+
+/// GradientSparsity counts the parameters whose gradient was exactly zero for a step.
+/// ReLU layers with dead units produce many such parameters, and updating them is wasted work.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GradientSparsity {
+    pub zero: usize,
+    pub total: usize,
+}
+
+impl GradientSparsity {
+    pub fn ratio(&self) -> f64 {
+        if self.total == 0 {
+            return 0.0;
+        }
+
+        self.zero as f64 / self.total as f64
+    }
+}
+
+impl fmt::Display for GradientSparsity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{} zero gradients ({:.1}%)", self.zero, self.total, self.ratio() * 100.0)
+    }
+}
+
+/// Stats summarises a set of numbers. The standard deviation is the population one, and
+/// all fields are zero for an empty set.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Stats {
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    pub std: f64,
+}
+
+impl Stats {
+    pub fn of(values: &[f64]) -> Stats {
+        if values.is_empty() {
+            return Stats { min: 0.0, max: 0.0, mean: 0.0, std: 0.0 };
+        }
+
+        let count = values.len() as f64;
+        let mean = values.iter().sum::<f64>() / count;
+        let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / count;
+
+        Stats {
+            min: values.iter().cloned().fold(f64::INFINITY, f64::min),
+            max: values.iter().cloned().fold(f64::NEG_INFINITY, f64::max),
+            mean,
+            std: variance.sqrt(),
+        }
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "min={:.4} max={:.4} mean={:.4} std={:.4}", self.min, self.max, self.mean, self.std)
+    }
+}
+
+/// LayerReport describes a layer's parameters and gradients, which is the first thing to check
+/// when training stalls: vanishing or exploding gradients show up in `gradients`, and ReLU units
+/// which never activate show up in `dead_units`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LayerReport {
+    pub weights: Stats,
+    pub gradients: Stats,
+    pub dead_units: usize,
+    pub units: usize,
+}
+
+impl fmt::Display for LayerReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "weights {} | gradients {} | dead units {}/{}",
+            self.weights, self.gradients, self.dead_units, self.units,
+        )
     }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::{network};
+    use crate::network::{ensemble_average, jacobian, Activation, Layer, Network, NetworkError, Stats};
 
     #[test]
     fn simple_network() {
-        let network = network::Network{
-            layers: vec![
-                network::Layer::new(3, 4),
-                network::Layer::new(4, 5),
-                network::Layer::new(5, 100),
-                network::Layer::new(100, 1),
-            ],
-        };
+        let network = network::Network::new(vec![
+            network::Layer::new(3, 4).unwrap(),
+            network::Layer::new(4, 5).unwrap(),
+            network::Layer::new(5, 100).unwrap(),
+            network::Layer::new(100, 1).unwrap(),
+        ]).unwrap();
 
         let inputs = vec![0.1, 0.2, 0.3];
 
-        let output = network.forward(&inputs);
+        let output = network.forward(&inputs).unwrap();
         
         println!("{:?}", output);
     }
+
+    #[test]
+    fn gradients_reach_parameters() {
+        let network = Network::new(vec![
+            Layer::dense(2, 3, Activation::Relu, true).unwrap(),
+            Layer::dense(3, 1, Activation::Linear, true).unwrap(),
+        ]).unwrap();
+
+        let outputs = network.forward_values(&[0.5, -0.5]).unwrap();
+        outputs[0].run_grad();
+
+        // The output layer's bias always receives the full gradient
+        let output_bias = network.layers[1].parameters().pop().unwrap();
+        assert_eq!(output_bias.get_gradient(), 1.0);
+    }
+
+    #[test]
+    fn gradient_sparsity_counts_zero_gradients() {
+        let network = Network::new(vec![Layer::dense(2, 2, Activation::Linear, false).unwrap()]).unwrap();
+
+        // Only the weights connected to the non-zero input receive a gradient
+        let outputs = network.forward_values(&[1.0, 0.0]).unwrap();
+        outputs[0].run_grad();
+
+        let sparsity = network.gradient_sparsity();
+        assert_eq!(sparsity[0].total, 4);
+        assert_eq!(sparsity[0].zero, 3);
+        assert_eq!(sparsity[0].to_string(), "3/4 zero gradients (75.0%)");
+    }
+
+    #[test]
+    fn jacobian_of_linear_network_is_its_weights() {
+        let network = Network::new(vec![Layer::dense(3, 2, Activation::Linear, true).unwrap()]).unwrap();
+
+        let weights = vec![vec![1.0, 2.0, 3.0], vec![-1.0, 0.5, 0.0]];
+        network.layers[0].set_weights(&weights);
+
+        assert_eq!(jacobian(&network, &[0.3, -0.2, 0.9]).unwrap(), weights);
+    }
+
+    #[test]
+    fn smooth_activations_match_their_scalar_forms() {
+        for activation in [Activation::Softplus, Activation::Gelu, Activation::Elu, Activation::Swish] {
+            let network: Network = Network::new(vec![Layer::dense(2, 1, activation, false).unwrap()]).unwrap();
+            network.layers[0].set_weights(&[vec![0.5, 1.5]]);
+
+            let output = network.forward(&[0.4, -1.3]).unwrap()[0];
+            let expected: f64 = activation.apply_scalar(0.5 * 0.4 + 1.5 * -1.3);
+            assert!((output - expected).abs() < 1e-12, "{}", activation.to_str());
+
+            let config = toml::to_string(&network.config()).unwrap();
+            assert!(config.contains(&format!("\"{}\"", activation.to_str())));
+        }
+    }
+
+    #[test]
+    fn weights_approx_eq_needs_matching_architectures() {
+        let network: Network = Network::new(vec![Layer::new(2, 3).unwrap(), Layer::new(3, 1).unwrap()]).unwrap();
+        let copy = network.deep_clone();
+        assert!(network.weights_approx_eq(&copy, 0.0));
+
+        let nudged = copy.parameters()[4].clone();
+        nudged.set_data(nudged.get_data() + 1e-6);
+        assert!(network.weights_approx_eq(&copy, 1e-5));
+        assert!(!network.weights_approx_eq(&copy, 1e-7));
+
+        let wider: Network = Network::new(vec![Layer::new(2, 4).unwrap(), Layer::new(4, 1).unwrap()]).unwrap();
+        assert!(!network.weights_approx_eq(&wider, f64::INFINITY));
+    }
+
+    #[test]
+    fn seeded_networks_are_reproducible() {
+        let config = Network::<f64>::new(vec![Layer::new(3, 4).unwrap(), Layer::new(4, 2).unwrap()]).unwrap().config();
+
+        let a: Network = Network::from_config_seeded(&config, 42).unwrap();
+        let b: Network = Network::from_config_seeded(&config, 42).unwrap();
+        let c: Network = Network::from_config_seeded(&config, 43).unwrap();
+
+        let data = |network: &Network| network.parameters().iter().map(|p| p.get_data()).collect::<Vec<f64>>();
+        assert_eq!(data(&a), data(&b));
+        assert_ne!(data(&a), data(&c));
+        assert_eq!(a.config(), config);
+    }
+
+    #[test]
+    fn leaky_relu_layers_keep_learning_from_negative_inputs() {
+        let activation = Activation::LeakyRelu { alpha: 0.05 };
+        let network: Network = Network::new(vec![Layer::dense(1, 1, activation, false).unwrap()]).unwrap();
+        network.layers[0].set_weights(&[vec![2.0]]);
+
+        let outputs = network.forward_values(&[-1.0]).unwrap();
+        assert_eq!(outputs[0].get_data(), -0.1);
+
+        outputs[0].run_grad();
+        assert_eq!(network.parameters()[0].get_gradient(), -0.05);
+        assert!(!network.layers[0].neurons[0].is_dead());
+
+        let config = network.config();
+        assert_eq!(Network::<f64>::from_config(&config).unwrap().layers[0].activation(), activation);
+        assert!(toml::to_string(&config).unwrap().contains("leaky_relu"));
+    }
+
+    #[test]
+    fn forward_rejects_mismatched_inputs() {
+        let network = Network::new(vec![Layer::new(3, 1).unwrap()]).unwrap();
+
+        assert_eq!(
+            network.forward(&[0.1, 0.2]),
+            Err(NetworkError::DimensionMismatch { expected: 3, found: 2 })
+        );
+    }
+
+    #[test]
+    fn invalid_networks_are_rejected() {
+        assert_eq!(Network::<f64>::new(vec![]).err(), Some(NetworkError::EmptyNetwork));
+        assert_eq!(Layer::<f64>::new(0, 2).err(), Some(NetworkError::EmptyLayer { inputs: 0, outputs: 2 }));
+
+        let layers: Vec<Layer> = vec![Layer::new(2, 3).unwrap(), Layer::new(4, 1).unwrap()];
+        assert_eq!(
+            Network::new(layers).err(),
+            Some(NetworkError::IncompatibleLayers { layer: 1, expected: 3, found: 4 })
+        );
+
+        // Networks assembled through the public field are checked when they are evaluated
+        let network = Network { layers: vec![Layer::new(2, 3).unwrap(), Layer::new(2, 1).unwrap()] };
+        assert!(matches!(network.forward(&[0.0, 0.0]), Err(NetworkError::IncompatibleLayers { .. })));
+    }
+
+    #[test]
+    fn f32_network_trains() {
+        let network: Network<f32> = Network::new(vec![
+            Layer::dense(2, 4, Activation::Relu, true).unwrap(),
+            Layer::dense(4, 1, Activation::Linear, true).unwrap(),
+        ]).unwrap();
+
+        let outputs = network.forward_values(&[0.5, -0.25]).unwrap();
+        outputs[0].run_grad();
+
+        let output_bias = network.layers[1].parameters().pop().unwrap();
+        assert_eq!(output_bias.get_gradient(), 1.0);
+
+        // Both precisions compute the same function up to rounding
+        let reference: Network = Network::from_config(&network.config()).unwrap();
+        for (source, target) in network.parameters().iter().zip(reference.parameters()) {
+            target.set_data(source.get_data() as f64);
+        }
+
+        let output = network.forward(&[0.5, -0.25]).unwrap()[0] as f64;
+        let expected = reference.forward(&[0.5, -0.25]).unwrap()[0];
+        assert!((output - expected).abs() < 1e-5);
+    }
+
+    #[test]
+    fn classifiers_predict_probabilities_and_classes() {
+        let network: Network = Network::new(vec![Layer::dense(2, 3, Activation::Linear, false).unwrap()]).unwrap();
+        network.layers[0].set_weights(&[vec![1.0, 0.0], vec![0.0, 1.0], vec![0.5, 0.5]]);
+
+        let inputs = vec![vec![2.0, 0.0], vec![0.0, 1.0], vec![1.0, 1.0]];
+        let probabilities = network.predict_proba(&inputs).unwrap();
+        for p in &probabilities {
+            assert!((p.iter().sum::<f64>() - 1.0).abs() < 1e-12);
+        }
+        assert!(probabilities[0][0] > probabilities[0][2] && probabilities[0][2] > probabilities[0][1]);
+
+        // All three logits are equal for the last input, which goes to the first class
+        assert_eq!(network.predict_classes(&inputs).unwrap(), vec![0, 1, 0]);
+
+        let binary: Network = Network::new(vec![Layer::dense(1, 1, Activation::Linear, false).unwrap()]).unwrap();
+        binary.layers[0].set_weights(&[vec![1.0]]);
+        let probabilities = binary.predict_proba(&[vec![0.0], vec![2.0]]).unwrap();
+        assert_eq!(probabilities[0], vec![0.5, 0.5]);
+        assert!((probabilities[1][1] - 1.0 / (1.0 + (-2.0f64).exp())).abs() < 1e-12);
+        assert_eq!(binary.predict_classes(&[vec![-1.0], vec![1.0]]).unwrap(), vec![0, 1]);
+
+        assert_eq!(
+            network.predict_proba(&[vec![1.0]]).err(),
+            Some(NetworkError::DimensionMismatch { expected: 2, found: 1 })
+        );
+
+        // Activated outputs aren't logits, so a softmax over them would be meaningless
+        let activated: Network = Network::new(vec![Layer::dense(2, 3, Activation::Softplus, false).unwrap()]).unwrap();
+        assert_eq!(
+            activated.predict_classes(&inputs).err(),
+            Some(NetworkError::NotLogits { activation: Activation::Softplus })
+        );
+    }
+
+    #[test]
+    fn forward_matches_graph_outputs() {
+        let network: Network = Network::new(vec![
+            Layer::dense(20, 16, Activation::Relu, true).unwrap(),
+            Layer::dense(16, 3, Activation::Linear, false).unwrap(),
+        ]).unwrap();
+
+        let inputs: Vec<f64> = (0..20).map(|i| (i as f64 - 10.0) / 7.0).collect();
+
+        let outputs = network.forward(&inputs).unwrap();
+        let values = network.forward_values(&inputs).unwrap();
+
+        for (output, value) in outputs.iter().zip(&values) {
+            assert!((output - value.get_data()).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn grad_report_summarises_layers() {
+        let network = Network::new(vec![
+            Layer::dense(2, 2, Activation::Relu, false).unwrap(),
+            Layer::dense(2, 1, Activation::Linear, false).unwrap(),
+        ]).unwrap();
+        network.layers[0].set_weights(&[vec![1.0, 1.0], vec![-1.0, -1.0]]);
+        network.layers[1].set_weights(&[vec![2.0, 4.0]]);
+
+        // The second hidden unit's pre-activation is negative, so it receives no gradient
+        let outputs = network.forward_values(&[1.0, 2.0]).unwrap();
+        outputs[0].run_grad();
+
+        let report = network.grad_report();
+        assert_eq!(report[0].dead_units, 1);
+        assert_eq!(report[0].units, 2);
+        assert_eq!(report[0].weights, Stats { min: -1.0, max: 1.0, mean: 0.0, std: 1.0 });
+        assert_eq!(report[0].gradients, Stats { min: 0.0, max: 4.0, mean: 1.5, std: 1.6583123951777 });
+        assert_eq!(report[1].dead_units, 0);
+        assert_eq!(
+            report[1].to_string(),
+            "weights min=2.0000 max=4.0000 mean=3.0000 std=1.0000 | gradients min=0.0000 max=3.0000 mean=1.5000 std=1.5000 | dead units 0/1"
+        );
+    }
+
+    #[test]
+    fn forward_hooks_see_activations() {
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        let mut network = Network::new(vec![
+            Layer::dense(2, 2, Activation::Relu, false).unwrap(),
+            Layer::dense(2, 1, Activation::Linear, false).unwrap(),
+        ]).unwrap();
+        network.layers[0].set_weights(&[vec![1.0, 1.0], vec![-1.0, -1.0]]);
+
+        let recorded = Rc::new(RefCell::new(Vec::new()));
+        let sink = Rc::clone(&recorded);
+        network.layers[0].register_forward_hook(move |activations| sink.borrow_mut().push(activations.to_vec()));
+
+        network.forward(&[1.0, 2.0]).unwrap();
+        network.forward_values(&[0.5, 0.5]).unwrap();
+        assert_eq!(*recorded.borrow(), vec![vec![3.0, 0.0], vec![1.0, 0.0]]);
+
+        network.layers[0].clear_forward_hooks();
+        network.forward(&[1.0, 2.0]).unwrap();
+        assert_eq!(recorded.borrow().len(), 2);
+    }
+
+    #[test]
+    fn summary_lists_layers_and_parameters() {
+        let network: Network = Network::new(vec![
+            Layer::dense(2, 4, Activation::Relu, true).unwrap(),
+            Layer::dense(4, 1, Activation::Linear, true).unwrap(),
+        ]).unwrap();
+
+        assert_eq!(network.num_parameters(), 17);
+        assert_eq!(
+            network.summary(),
+            "Layer  Type   Output shape  Activation  Params\n\
+             ==============================================\n\
+             0      dense  (4)           relu        12\n\
+             1      dense  (1)           linear      5\n\
+             ==============================================\n\
+             Total params: 17\n\
+             Trainable params: 17\n\
+             Non-trainable params: 0\n"
+        );
+    }
+
+    #[test]
+    fn frozen_layers_are_not_trained() {
+        use crate::optim::Sgd;
+        use crate::train::Trainer;
+
+        let network: Network = Network::new(vec![
+            Layer::dense(2, 3, Activation::Relu, true).unwrap(),
+            Layer::dense(3, 1, Activation::Linear, true).unwrap(),
+        ]).unwrap();
+        network.layers[0].freeze();
+
+        let frozen: Vec<f64> = network.layers[0].parameters().iter().map(|p| p.get_data()).collect();
+        let head: Vec<f64> = network.layers[1].parameters().iter().map(|p| p.get_data()).collect();
+
+        let dataset = vec![(vec![0.5, 1.0], vec![2.0]), (vec![-1.0, 0.5], vec![-1.0])];
+        Trainer::new(Sgd::new(0.1)).train_epoch(&network, &dataset).unwrap();
+
+        assert!(network.layers[0].is_frozen());
+        assert_eq!(network.layers[0].parameters().iter().map(|p| p.get_data()).collect::<Vec<f64>>(), frozen);
+        assert_ne!(network.layers[1].parameters().iter().map(|p| p.get_data()).collect::<Vec<f64>>(), head);
+        assert_eq!(network.num_trainable_parameters(), 4);
+        assert!(network.summary().ends_with("Trainable params: 4\nNon-trainable params: 9\n"));
+
+        network.layers[0].unfreeze();
+        assert_eq!(network.num_trainable_parameters(), 13);
+    }
+
+    #[test]
+    fn deep_clone_has_independent_parameters() {
+        let network: Network = Network::new(vec![
+            Layer::dense(2, 2, Activation::Relu, true).unwrap(),
+            Layer::dense(2, 1, Activation::Linear, false).unwrap(),
+        ]).unwrap();
+        network.layers[0].freeze();
+
+        let copy = network.deep_clone();
+        assert_eq!(copy.forward(&[0.3, -0.7]).unwrap(), network.forward(&[0.3, -0.7]).unwrap());
+        assert!(copy.layers[0].is_frozen());
+        assert_eq!(copy.config(), network.config());
+
+        copy.parameters().iter().for_each(|p| p.set_data(0.0));
+        assert!(network.parameters().iter().any(|p| p.get_data() != 0.0));
+    }
+
+    #[test]
+    fn ensembles_average_predictions_and_weights() {
+        let linear = |weights: Vec<f64>, bias: f64| {
+            let network: Network = Network::new(vec![Layer::dense(2, 1, Activation::Linear, true).unwrap()]).unwrap();
+            network.layers[0].set_weights(&[weights]);
+            network.layers[0].set_biases(&[bias]);
+
+            network
+        };
+        let networks = vec![linear(vec![1.0, 2.0], 0.0), linear(vec![3.0, -2.0], 1.0)];
+
+        assert_eq!(ensemble_average(&networks, &[1.0, 1.0]).unwrap(), vec![2.5]);
+
+        let average = Network::average_weights(&networks).unwrap();
+        assert_eq!(average.layers[0].weights(), vec![vec![2.0, 0.0]]);
+        assert_eq!(average.layers[0].biases(), Some(vec![0.5]));
+
+        let wider: Network = Network::new(vec![Layer::dense(2, 2, Activation::Linear, true).unwrap()]).unwrap();
+        let mismatched = vec![linear(vec![1.0, 2.0], 0.0), wider];
+        assert_eq!(Network::average_weights(&mismatched).err(), Some(NetworkError::ArchitectureMismatch { model: 1 }));
+        assert_eq!(ensemble_average(&mismatched, &[1.0, 1.0]), Err(NetworkError::ArchitectureMismatch { model: 1 }));
+        assert_eq!(ensemble_average::<f64>(&[], &[1.0]), Err(NetworkError::EmptyEnsemble));
+    }
+
+    #[test]
+    fn flat_params_round_trip_in_layer_order() {
+        let network: Network = Network::new(vec![
+            Layer::dense(2, 2, Activation::Relu, true).unwrap(),
+            Layer::dense(2, 1, Activation::Linear, false).unwrap(),
+        ]).unwrap();
+        network.layers[0].set_weights(&[vec![1.0, 2.0], vec![3.0, 4.0]]);
+        network.layers[0].set_biases(&[5.0, 6.0]);
+        network.layers[1].set_weights(&[vec![7.0, 8.0]]);
+
+        assert_eq!(network.get_flat_params(), vec![1.0, 2.0, 5.0, 3.0, 4.0, 6.0, 7.0, 8.0]);
+
+        let values: Vec<f64> = (0..8).map(|i| i as f64 * 0.5).collect();
+        network.set_flat_params(&values).unwrap();
+        assert_eq!(network.get_flat_params(), values);
+        assert_eq!(network.layers[1].weights(), vec![vec![3.0, 3.5]]);
+
+        assert_eq!(network.set_flat_params(&[1.0]), Err(NetworkError::DimensionMismatch { expected: 8, found: 1 }));
+        assert_eq!(network.get_flat_params(), values);
+    }
+
+    #[test]
+    fn dropout_and_batch_norm_only_change_while_training() {
+        crate::rand::global_seed(5);
+        let dropout: Network = Network::new(vec![Layer::dropout(1000, 0.25).unwrap()]).unwrap();
+        let inputs = vec![1.0; 1000];
+        assert_eq!(dropout.forward(&inputs).unwrap(), inputs);
+
+        // Training passes build a graph, which Network::forward skips with the fast-math feature
+        let train = |network: &Network, inputs: &[f64]| -> Vec<f64> {
+            network.forward_values(inputs).unwrap().iter().map(|output| output.get_data()).collect()
+        };
+
+        dropout.set_training(true);
+        let outputs = train(&dropout, &inputs);
+        crate::rand::clear_global_seed();
+        assert!(outputs.iter().all(|x| *x == 0.0 || (*x - 1.0 / 0.75).abs() < 1e-12));
+        let dropped = outputs.iter().filter(|x| **x == 0.0).count();
+        assert!((200..300).contains(&dropped), "{}", dropped);
+
+        // Each training pass moves the running statistics towards its sample
+        let network: Network = Network::new(vec![Layer::batch_norm(2, 0.5, 1e-5).unwrap()]).unwrap();
+        network.set_training(true);
+        train(&network, &[2.0, -1.0]);
+        train(&network, &[4.0, -1.0]);
+        let (means, variances) = network.layers[0].running_stats().unwrap();
+        assert_eq!((means.clone(), variances), (vec![2.5, -0.75], vec![3.0, 0.4375]));
+
+        network.set_training(false);
+        network.parameters()[0].set_data(2.0);
+        let outputs = network.forward(&[4.0, -0.75]).unwrap();
+        assert!((outputs[0] - 2.0 * 1.5 / (3.0f64 + 1e-5).sqrt()).abs() < 1e-12);
+        assert!(outputs[1].abs() < 1e-12);
+        assert_eq!(network.layers[0].running_stats().unwrap().0, means);
+
+        // The dense form used for inference applies the same map
+        let inference = crate::inference::InferenceNetwork::from_network(&network).forward(&[4.0, -0.75]).unwrap();
+        assert!(inference.iter().zip(&outputs).all(|(a, b)| (a - b).abs() < 1e-12));
+
+        // Gradients reach each feature's scale and shift
+        let output = &network.forward_values(&[4.0, -0.75]).unwrap()[0];
+        output.run_grad();
+        assert!(network.parameters().iter().take(2).all(|parameter| parameter.get_gradient() != 0.0));
+
+        assert!(matches!(Layer::<f64>::dropout(2, 1.0), Err(NetworkError::InvalidHyperparameter { .. })));
+        assert!(matches!(Layer::<f64>::batch_norm(2, 0.1, 0.0), Err(NetworkError::InvalidHyperparameter { .. })));
+    }
 }
@@ -0,0 +1,1012 @@
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::rc::Rc;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use crate::loss::Loss;
+use crate::network::{Network, NetworkError};
+use crate::scalar::Scalar;
+use crate::value::Value;
+
+/// Optimizer computes parameter updates from gradients. Trainer works with any implementation,
+/// so a custom optimizer only has to implement `update`; parameter groups, weight decay and
+/// frozen parameters are handled by step_groups.
+///
+/// Methods take `&self` so a trainer can share its optimizer across steps; optimizers which keep
+/// state between steps (e.g. momentum) hold it in a RefCell.
+pub trait Optimizer {
+    /// Returns the default learning rate, used by parameter groups which don't set their own.
+    fn learning_rate(&self) -> f64;
+
+    fn set_learning_rate(&mut self, learning_rate: f64);
+
+    /// Returns the new value of the parameter at `index` (its position in the network's
+    /// parameters, stable across steps), given its current value and gradient.
+    fn update(&self, index: usize, data: f64, gradient: f64, learning_rate: f64) -> f64;
+
+    /// Called before every step with the current values of all parameters. Does nothing by default.
+    fn begin_step(&self, _values: &[f64]) {}
+
+    /// Called after every step with the updated values of all parameters, which it may change
+    /// before they're written back. Does nothing by default.
+    fn end_step(&self, _values: &mut [f64]) {}
+
+    /// Returns the name recorded in checkpoints, which Trainer::resume checks before restoring a
+    /// run. Defaults to the type's name.
+    fn name(&self) -> &'static str {
+        std::any::type_name::<Self>()
+    }
+
+    /// Returns the optimizer's settings and its state between steps, which checkpoints save so
+    /// Trainer::resume can rebuild the optimizer as it was. Defaults to None, for optimizers
+    /// checkpoints can't restore.
+    fn state(&self) -> Option<OptimizerState> {
+        None
+    }
+}
+
+impl<O: Optimizer + ?Sized> Optimizer for Box<O> {
+    fn learning_rate(&self) -> f64 {
+        (**self).learning_rate()
+    }
+
+    fn set_learning_rate(&mut self, learning_rate: f64) {
+        (**self).set_learning_rate(learning_rate);
+    }
+
+    fn update(&self, index: usize, data: f64, gradient: f64, learning_rate: f64) -> f64 {
+        (**self).update(index, data, gradient, learning_rate)
+    }
+
+    fn begin_step(&self, values: &[f64]) {
+        (**self).begin_step(values);
+    }
+
+    fn end_step(&self, values: &mut [f64]) {
+        (**self).end_step(values);
+    }
+
+    fn name(&self) -> &'static str {
+        (**self).name()
+    }
+
+    fn state(&self) -> Option<OptimizerState> {
+        (**self).state()
+    }
+}
+
+/// OptimizerState describes one of the crate's optimizers together with its state between
+/// steps, as returned by Optimizer::state, so a checkpoint can rebuild it exactly as it was.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OptimizerState {
+    Sgd { learning_rate: f64 },
+
+    // slow is None until the first step
+    Lookahead { inner: Box<OptimizerState>, k: usize, alpha: f64, slow: Option<Vec<f64>>, steps: usize },
+
+    // average holds the running average over `count` steps
+    Swa { inner: Box<OptimizerState>, start: usize, frequency: usize, average: Vec<f64>, count: usize, steps: usize },
+}
+
+impl OptimizerState {
+    /// Rebuilds the optimizer with its state restored. A restored Swa has a new SwaAverage, so
+    /// read its average through Optimizer::state.
+    pub fn into_optimizer(self) -> Box<dyn Optimizer> {
+        match self {
+            OptimizerState::Sgd { learning_rate } => Box::new(Sgd::new(learning_rate)),
+            OptimizerState::Lookahead { inner, k, alpha, slow, steps } => {
+                let lookahead = Lookahead::new(inner.into_optimizer(), k, alpha);
+                *lookahead.slow.borrow_mut() = slow;
+                lookahead.steps.set(steps);
+
+                Box::new(lookahead)
+            }
+            OptimizerState::Swa { inner, start, frequency, average, count, steps } => {
+                let swa = Swa::new(inner.into_optimizer(), start, frequency);
+                *swa.average.0.borrow_mut() = (average, count);
+                swa.steps.set(steps);
+
+                Box::new(swa)
+            }
+        }
+    }
+}
+
+/// ParamSelector picks the parameters of a network a ParamGroup applies to.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ParamSelector {
+    All,
+    Weights,
+    Biases,
+    Layer(usize),
+}
+
+/// ParamGroup gives some of a network's parameters their own learning rate and weight decay,
+/// e.g. `ParamGroup::new(ParamSelector::Biases)` to exempt the biases from weight decay. Each
+/// parameter belongs to the first group which selects it; parameters no group selects use the
+/// optimizer's learning rate without weight decay.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ParamGroup {
+    pub selector: ParamSelector,
+    pub learning_rate: Option<f64>,
+
+    // weight_decay adds weight_decay * p to each parameter's gradient (L2 regularisation)
+    pub weight_decay: f64,
+}
+
+impl ParamGroup {
+    pub fn new(selector: ParamSelector) -> ParamGroup {
+        ParamGroup { selector, learning_rate: None, weight_decay: 0.0 }
+    }
+
+    pub fn with_learning_rate(mut self, learning_rate: f64) -> ParamGroup {
+        self.learning_rate = Some(learning_rate);
+        self
+    }
+
+    pub fn with_weight_decay(mut self, weight_decay: f64) -> ParamGroup {
+        self.weight_decay = weight_decay;
+        self
+    }
+
+    fn selects(&self, layer: usize, is_bias: bool) -> bool {
+        match self.selector {
+            ParamSelector::All => true,
+            ParamSelector::Weights => !is_bias,
+            ParamSelector::Biases => is_bias,
+            ParamSelector::Layer(index) => index == layer,
+        }
+    }
+}
+
+/// GroupSettings are the learning rate (None for the optimizer's) and weight decay of one parameter.
+pub type GroupSettings = (Option<f64>, f64);
+
+/// Returns the settings of each of the network's parameters under `groups`, in the order of
+/// Network::parameters, for step_groups.
+pub fn resolve_groups<T: Scalar>(network: &Network<T>, groups: &[ParamGroup]) -> Vec<GroupSettings> {
+    network
+        .parameter_layout()
+        .into_iter()
+        .map(|(layer, is_bias)| {
+            groups
+                .iter()
+                .find(|group| group.selects(layer, is_bias))
+                .map_or((None, 0.0), |group| (group.learning_rate, group.weight_decay))
+        })
+        .collect()
+}
+
+/// step_groups takes one optimizer step over `parameters`, each with the settings at the same
+/// index of `settings`, and returns how many parameters changed. Frozen parameters are skipped.
+pub fn step_groups<T: Scalar>(optimizer: &dyn Optimizer, parameters: &[Value<T>], settings: &[GroupSettings]) -> usize {
+    assert_eq!(parameters.len(), settings.len(), "expected settings for every parameter");
+
+    let current: Vec<f64> = parameters.iter().map(|parameter| parameter.get_data().to_f64()).collect();
+    optimizer.begin_step(&current);
+
+    let mut values: Vec<f64> = parameters
+        .iter()
+        .zip(settings)
+        .zip(current)
+        .enumerate()
+        .map(|(index, ((parameter, (learning_rate, weight_decay)), data))| {
+            if !parameter.requires_grad() {
+                return data;
+            }
+
+            let gradient = parameter.get_gradient() + weight_decay * data;
+            optimizer.update(index, data, gradient, learning_rate.unwrap_or(optimizer.learning_rate()))
+        })
+        .collect();
+    optimizer.end_step(&mut values);
+
+    let mut updated = 0;
+    for (parameter, value) in parameters.iter().zip(values) {
+        if parameter.get_data().to_f64() != value {
+            parameter.set_data(T::from_f64(value));
+            updated += 1;
+        }
+    }
+
+    updated
+}
+
+/// zero_grad resets the gradients of the parameters before the next backward pass,
+/// since run_grad accumulates into existing gradients.
+pub fn zero_grad<T: Scalar>(parameters: &[Value<T>]) {
+    for parameter in parameters {
+        parameter.set_gradient(0.0);
+    }
+}
+
+/// Sgd performs plain stochastic gradient descent updates: p = p - learning_rate * p.gradient
+pub struct Sgd {
+    pub learning_rate: f64,
+}
+
+impl Sgd {
+    pub fn new(learning_rate: f64) -> Sgd {
+        Sgd { learning_rate }
+    }
+
+    /// step updates each parameter using its accumulated gradient and returns how many
+    /// parameters were actually updated.
+    /// Parameters whose gradient is exactly zero are skipped, as their update would be a no-op,
+    /// and so are frozen parameters.
+    pub fn step<T: Scalar>(&self, parameters: &[Value<T>]) -> usize {
+        let mut updated = 0;
+
+        for parameter in parameters {
+            let gradient = parameter.get_gradient();
+            if gradient == 0.0 || !parameter.requires_grad() {
+                continue;
+            }
+
+            parameter.set_data(T::from_f64(parameter.get_data().to_f64() - self.learning_rate * gradient));
+            updated += 1;
+        }
+
+        updated
+    }
+
+    /// zero_grad resets the gradients of the parameters before the next backward pass,
+    /// since run_grad accumulates into existing gradients.
+    pub fn zero_grad<T: Scalar>(&self, parameters: &[Value<T>]) {
+        zero_grad(parameters)
+    }
+}
+
+impl Optimizer for Sgd {
+    fn learning_rate(&self) -> f64 {
+        self.learning_rate
+    }
+
+    fn set_learning_rate(&mut self, learning_rate: f64) {
+        self.learning_rate = learning_rate;
+    }
+
+    fn update(&self, _index: usize, data: f64, gradient: f64, learning_rate: f64) -> f64 {
+        data - learning_rate * gradient
+    }
+
+    fn name(&self) -> &'static str {
+        "sgd"
+    }
+
+    fn state(&self) -> Option<OptimizerState> {
+        Some(OptimizerState::Sgd { learning_rate: self.learning_rate })
+    }
+}
+
+/// Lookahead wraps another optimizer, which takes `k` fast steps ahead of a slow copy of the
+/// parameters. After every k steps the slow copy moves `alpha` of the way towards the fast
+/// parameters, which then restart from it: slow = slow + alpha * (fast - slow), fast = slow.
+/// This damps the oscillations of the inner optimizer at little extra cost.
+pub struct Lookahead<O> {
+    pub inner: O,
+    pub k: usize,
+    pub alpha: f64,
+
+    // slow holds the slow parameters, from the first step on
+    slow: RefCell<Option<Vec<f64>>>,
+    steps: Cell<usize>,
+}
+
+impl<O: Optimizer> Lookahead<O> {
+    pub fn new(inner: O, k: usize, alpha: f64) -> Lookahead<O> {
+        Lookahead { inner, k: k.max(1), alpha, slow: RefCell::new(None), steps: Cell::new(0) }
+    }
+}
+
+impl<O: Optimizer> Optimizer for Lookahead<O> {
+    fn learning_rate(&self) -> f64 {
+        self.inner.learning_rate()
+    }
+
+    fn set_learning_rate(&mut self, learning_rate: f64) {
+        self.inner.set_learning_rate(learning_rate);
+    }
+
+    fn update(&self, index: usize, data: f64, gradient: f64, learning_rate: f64) -> f64 {
+        self.inner.update(index, data, gradient, learning_rate)
+    }
+
+    fn begin_step(&self, values: &[f64]) {
+        self.inner.begin_step(values);
+        self.slow.borrow_mut().get_or_insert_with(|| values.to_vec());
+    }
+
+    fn end_step(&self, values: &mut [f64]) {
+        self.inner.end_step(values);
+
+        self.steps.set(self.steps.get() + 1);
+        if !self.steps.get().is_multiple_of(self.k) {
+            return;
+        }
+
+        if let Some(slow) = self.slow.borrow_mut().as_mut() {
+            for (slow, fast) in slow.iter_mut().zip(values.iter_mut()) {
+                *slow += self.alpha * (*fast - *slow);
+                *fast = *slow;
+            }
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        "lookahead"
+    }
+
+    fn state(&self) -> Option<OptimizerState> {
+        Some(OptimizerState::Lookahead {
+            inner: Box::new(self.inner.state()?),
+            k: self.k,
+            alpha: self.alpha,
+            slow: self.slow.borrow().clone(),
+            steps: self.steps.get(),
+        })
+    }
+}
+
+/// Swa wraps another optimizer and keeps a running average of the parameters (stochastic weight
+/// averaging), taken every `frequency` steps once `start` steps have passed. The average usually
+/// generalises better than the final parameters. Keep the SwaAverage returned by `average` to
+/// read it after the optimizer has been moved into a Trainer.
+pub struct Swa<O> {
+    pub inner: O,
+    pub start: usize,
+    pub frequency: usize,
+    average: SwaAverage,
+    steps: Cell<usize>,
+}
+
+/// SwaAverage is a shared handle to the running average of a Swa optimizer.
+#[derive(Debug, Clone, Default)]
+pub struct SwaAverage(Rc<RefCell<(Vec<f64>, usize)>>);
+
+impl SwaAverage {
+    /// Returns the averaged parameters, None if no step has been averaged yet.
+    pub fn values(&self) -> Option<Vec<f64>> {
+        let average = self.0.borrow();
+        (average.1 > 0).then(|| average.0.clone())
+    }
+
+    /// Returns the number of steps averaged so far.
+    pub fn count(&self) -> usize {
+        self.0.borrow().1
+    }
+
+    /// Copies the averaged parameters into `network`, in the order of Network::parameters.
+    /// Returns false, leaving the network unchanged, if nothing has been averaged yet.
+    pub fn apply<T: Scalar>(&self, network: &Network<T>) -> bool {
+        let Some(values) = self.values() else {
+            return false;
+        };
+
+        for (parameter, value) in network.parameters().iter().zip(values) {
+            parameter.set_data(T::from_f64(value));
+        }
+
+        true
+    }
+}
+
+impl<O: Optimizer> Swa<O> {
+    pub fn new(inner: O, start: usize, frequency: usize) -> Swa<O> {
+        Swa { inner, start, frequency: frequency.max(1), average: SwaAverage::default(), steps: Cell::new(0) }
+    }
+
+    /// Returns a handle to the running average.
+    pub fn average(&self) -> SwaAverage {
+        self.average.clone()
+    }
+}
+
+impl<O: Optimizer> Optimizer for Swa<O> {
+    fn learning_rate(&self) -> f64 {
+        self.inner.learning_rate()
+    }
+
+    fn set_learning_rate(&mut self, learning_rate: f64) {
+        self.inner.set_learning_rate(learning_rate);
+    }
+
+    fn update(&self, index: usize, data: f64, gradient: f64, learning_rate: f64) -> f64 {
+        self.inner.update(index, data, gradient, learning_rate)
+    }
+
+    fn begin_step(&self, values: &[f64]) {
+        self.inner.begin_step(values);
+    }
+
+    fn end_step(&self, values: &mut [f64]) {
+        self.inner.end_step(values);
+
+        let steps = self.steps.get() + 1;
+        self.steps.set(steps);
+        if steps < self.start || !(steps - self.start).is_multiple_of(self.frequency) {
+            return;
+        }
+
+        let (average, count) = &mut *self.average.0.borrow_mut();
+        average.resize(values.len(), 0.0);
+        *count += 1;
+        for (average, value) in average.iter_mut().zip(values.iter()) {
+            *average += (value - *average) / *count as f64;
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        "swa"
+    }
+
+    fn state(&self) -> Option<OptimizerState> {
+        let (average, count) = self.average.0.borrow().clone();
+
+        Some(OptimizerState::Swa {
+            inner: Box::new(self.inner.state()?),
+            start: self.start,
+            frequency: self.frequency,
+            average,
+            count,
+            steps: self.steps.get(),
+        })
+    }
+}
+
+/// ES optimizes a network without gradients with an evolution strategy: each step evaluates
+/// the loss at `population` random perturbations of the parameters with standard deviation
+/// `sigma`, and moves the parameters towards the perturbations which lowered it,
+/// p = p - learning_rate * Σ (loss_i * noise_i) / (population * sigma²).
+///
+/// Perturbations come in mirrored pairs (+noise and -noise), which cancels out most of the
+/// sampling noise in the estimate. Frozen parameters are never perturbed or updated.
+///
+/// ES keeps no state between steps besides `rng`. Passing a rand::SeedState, which serializes
+/// the position of its draws, lets a run be checkpointed and resumed exactly.
+pub struct ES {
+    pub population: usize,
+    pub sigma: f64,
+    pub learning_rate: f64,
+}
+
+impl ES {
+    pub fn new(population: usize, sigma: f64, learning_rate: f64) -> ES {
+        ES { population, sigma, learning_rate }
+    }
+
+    /// step evaluates `loss` on the perturbed copies of `network`, updates the network's
+    /// parameters and returns the mean loss of the population. An odd population is rounded up
+    /// to a whole number of mirrored pairs.
+    pub fn step<T: Scalar, R: Rng + ?Sized>(
+        &self,
+        network: &Network<T>,
+        loss: &mut dyn FnMut(&Network<T>) -> f64,
+        rng: &mut R,
+    ) -> f64 {
+        let parameters = network.parameters();
+        let pairs = self.population.div_ceil(2).max(1);
+
+        let mut estimate = vec![0.0; parameters.len()];
+        let mut total_loss = 0.0;
+
+        for _ in 0..pairs {
+            let candidate = network.deep_clone();
+            let noise = candidate.perturb_weights(self.sigma, rng);
+            let positive = loss(&candidate);
+
+            for ((parameter, original), noise) in candidate.parameters().iter().zip(&parameters).zip(&noise) {
+                parameter.set_data(T::from_f64(original.get_data().to_f64() - noise));
+            }
+            let negative = loss(&candidate);
+
+            for (estimate, noise) in estimate.iter_mut().zip(&noise) {
+                *estimate += (positive - negative) * noise;
+            }
+            total_loss += positive + negative;
+        }
+
+        let samples = (2 * pairs) as f64;
+        let scale = self.learning_rate / (samples * self.sigma * self.sigma);
+        for (parameter, estimate) in parameters.iter().zip(estimate) {
+            if parameter.requires_grad() {
+                parameter.set_data(T::from_f64(parameter.get_data().to_f64() - scale * estimate));
+            }
+        }
+
+        total_loss / samples
+    }
+}
+
+/// LBFGS minimizes a smooth function with the limited-memory BFGS method: it approximates the
+/// curvature of the function from the last `history` steps and gradients, and searches along
+/// the resulting direction for a step which decreases the function enough (backtracking
+/// line search on the Armijo condition).
+///
+/// Each iteration evaluates the function over all the data, so it suits full-batch training of
+/// small networks and other deterministic problems, where it usually needs far fewer iterations
+/// than gradient descent. It stops after `max_iterations`, once the gradient norm drops below
+/// `tolerance` times the initial gradient norm (or 1 if that's smaller), once a step changes the
+/// function by no more than `function_tolerance` times its magnitude (or 1 if that's smaller), or
+/// when the line search can't decrease the function any further.
+///
+/// The LbfgsReport of a run holds everything needed to continue it, so a run saved with the
+/// report (e.g. in a checkpoint) can be picked up again with resume.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LBFGS {
+    pub history: usize,
+    pub max_iterations: usize,
+    pub tolerance: f64,
+    pub function_tolerance: f64,
+}
+
+/// LbfgsReport describes the result of an LBFGS run.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LbfgsReport {
+    pub parameters: Vec<f64>,
+    pub loss: f64,
+    pub iterations: usize,
+
+    // converged is true when either stopping rule was met, or the line search failed so close to
+    // a minimum that a full step couldn't be expected to decrease the function measurably
+    pub converged: bool,
+
+    // history holds the last steps s = x' - x and gradient changes y = g' - g, oldest first, and
+    // gradient_scale the initial gradient norm the tolerance is relative to, see LBFGS::resume
+    pub history: Vec<(Vec<f64>, Vec<f64>)>,
+    pub gradient_scale: f64,
+}
+
+/// Objective returns the value and gradient of a function at a point.
+pub type Objective<'a> = dyn FnMut(&[f64]) -> (f64, Vec<f64>) + 'a;
+
+impl Default for LBFGS {
+    fn default() -> Self {
+        LBFGS { history: 10, max_iterations: 100, tolerance: 1e-8, function_tolerance: 1e-14 }
+    }
+}
+
+impl LBFGS {
+    pub fn new(history: usize, max_iterations: usize) -> LBFGS {
+        LBFGS { history, max_iterations, ..LBFGS::default() }
+    }
+
+    /// Minimizes `objective` starting from `initial`.
+    pub fn minimize(&self, initial: &[f64], objective: &mut Objective<'_>) -> LbfgsReport {
+        self.run(initial.to_vec(), Vec::new(), None, 0, objective)
+    }
+
+    /// Continues the run which returned `report` as if it had never stopped: from the report's
+    /// parameters and curvature history, until `max_iterations` iterations in total, counting
+    /// the report's.
+    pub fn resume(&self, report: &LbfgsReport, objective: &mut Objective<'_>) -> LbfgsReport {
+        self.run(report.parameters.clone(), report.history.clone(), Some(report.gradient_scale), report.iterations, objective)
+    }
+
+    fn run(
+        &self,
+        mut x: Vec<f64>,
+        history: Vec<(Vec<f64>, Vec<f64>)>,
+        gradient_scale: Option<f64>,
+        first_iteration: usize,
+        objective: &mut Objective<'_>,
+    ) -> LbfgsReport {
+        let dot = |a: &[f64], b: &[f64]| a.iter().zip(b).map(|(a, b)| a * b).sum::<f64>();
+
+        let (mut loss, mut gradient) = objective(&x);
+        let gradient_scale = gradient_scale.unwrap_or_else(|| dot(&gradient, &gradient).sqrt().max(1.0));
+        let gradient_tolerance = self.tolerance * gradient_scale;
+        let function_tolerance = |loss: f64| self.function_tolerance * loss.abs().max(1.0);
+
+        // The last steps s = x' - x and gradient changes y = g' - g, with 1 / (y · s)
+        let mut steps: VecDeque<(Vec<f64>, Vec<f64>, f64)> = VecDeque::with_capacity(self.history);
+        for (s, y) in history {
+            let rho = 1.0 / dot(&s, &y);
+            steps.push_back((s, y, rho));
+        }
+
+        let report = |parameters: Vec<f64>, loss: f64, iterations: usize, converged: bool, steps: &VecDeque<(Vec<f64>, Vec<f64>, f64)>| LbfgsReport {
+            parameters,
+            loss,
+            iterations,
+            converged,
+            history: steps.iter().map(|(s, y, _)| (s.clone(), y.clone())).collect(),
+            gradient_scale,
+        };
+
+        for iteration in first_iteration..self.max_iterations {
+            let gradient_norm = dot(&gradient, &gradient).sqrt();
+            if gradient_norm <= gradient_tolerance {
+                return report(x, loss, iteration, true, &steps);
+            }
+
+            // Two-loop recursion: direction = -H * gradient, with H the inverse Hessian estimate
+            let mut q = gradient.clone();
+            let mut alphas = Vec::with_capacity(steps.len());
+            for (s, y, rho) in steps.iter().rev() {
+                let alpha = rho * dot(s, &q);
+                q.iter_mut().zip(y).for_each(|(q, y)| *q -= alpha * y);
+                alphas.push(alpha);
+            }
+
+            let scale = match steps.back() {
+                Some((s, y, _)) => dot(s, y) / dot(y, y),
+                None => 1.0 / gradient_norm.max(1.0),
+            };
+            q.iter_mut().for_each(|q| *q *= scale);
+
+            for ((s, y, rho), alpha) in steps.iter().zip(alphas.into_iter().rev()) {
+                let beta = rho * dot(y, &q);
+                q.iter_mut().zip(s).for_each(|(q, s)| *q += (alpha - beta) * s);
+            }
+
+            let mut direction: Vec<f64> = q.into_iter().map(|q| -q).collect();
+            let mut slope = dot(&gradient, &direction);
+            if slope >= 0.0 {
+                // The estimate lost positive definiteness, start over from steepest descent
+                steps.clear();
+                direction = gradient.iter().map(|g| -g / gradient_norm.max(1.0)).collect();
+                slope = dot(&gradient, &direction);
+            }
+
+            // Backtracking line search for a sufficient decrease
+            let mut step = 1.0;
+            let mut accepted = None;
+            for _ in 0..40 {
+                let candidate: Vec<f64> = x.iter().zip(&direction).map(|(x, d)| x + step * d).collect();
+                let (candidate_loss, candidate_gradient) = objective(&candidate);
+                if candidate_loss <= loss + 1e-4 * step * slope {
+                    accepted = Some((candidate, candidate_loss, candidate_gradient));
+                    break;
+                }
+                step *= 0.5;
+            }
+
+            let Some((next, next_loss, next_gradient)) = accepted else {
+                // -slope is the decrease a full step would make to first order, which is about
+                // twice the distance to the minimum along a quasi-Newton direction
+                let converged = -slope <= function_tolerance(loss);
+                return report(x, loss, iteration, converged, &steps);
+            };
+
+            let s: Vec<f64> = next.iter().zip(&x).map(|(a, b)| a - b).collect();
+            let y: Vec<f64> = next_gradient.iter().zip(&gradient).map(|(a, b)| a - b).collect();
+            let curvature = dot(&s, &y);
+            if curvature > 1e-12 && self.history > 0 {
+                if steps.len() == self.history {
+                    steps.pop_front();
+                }
+                steps.push_back((s, y, 1.0 / curvature));
+            }
+
+            let decrease = loss - next_loss;
+            x = next;
+            loss = next_loss;
+            gradient = next_gradient;
+
+            if decrease <= function_tolerance(loss) {
+                return report(x, loss, iteration + 1, true, &steps);
+            }
+        }
+
+        let converged = dot(&gradient, &gradient).sqrt() <= gradient_tolerance;
+        report(x, loss, self.max_iterations.max(first_iteration), converged, &steps)
+    }
+
+    /// Trains `network` on the mean of `loss` over the whole of `dataset`, leaving it with the
+    /// best parameters found. Frozen parameters don't change.
+    pub fn train<T: Scalar>(&self, network: &Network<T>, dataset: &[(Vec<T>, Vec<T>)], loss: &Loss) -> Result<LbfgsReport, NetworkError> {
+        let initial = network.get_flat_params();
+
+        self.fit_network(network, dataset, loss, |objective| self.minimize(&initial, objective))
+    }
+
+    /// Like train, continuing the run which returned `previous` rather than starting over from the
+    /// network's parameters, see resume.
+    pub fn resume_training<T: Scalar>(
+        &self,
+        network: &Network<T>,
+        dataset: &[(Vec<T>, Vec<T>)],
+        loss: &Loss,
+        previous: &LbfgsReport,
+    ) -> Result<LbfgsReport, NetworkError> {
+        self.fit_network(network, dataset, loss, |objective| self.resume(previous, objective))
+    }
+
+    // fit_network runs `run` on the full batch loss of `network` and leaves the network with the
+    // parameters it found
+    fn fit_network<T: Scalar>(
+        &self,
+        network: &Network<T>,
+        dataset: &[(Vec<T>, Vec<T>)],
+        loss: &Loss,
+        run: impl FnOnce(&mut Objective<'_>) -> LbfgsReport,
+    ) -> Result<LbfgsReport, NetworkError> {
+        let parameters = network.parameters();
+        let mut failure = None;
+
+        let mut objective = |point: &[f64]| match full_batch_loss(network, &parameters, dataset, loss, point) {
+            Ok(result) => result,
+            Err(err) => {
+                failure.get_or_insert(err);
+                (f64::NAN, vec![0.0; point.len()])
+            }
+        };
+        let report = run(&mut objective);
+
+        if let Some(err) = failure {
+            return Err(err);
+        }
+        network.set_flat_params(&report.parameters)?;
+
+        Ok(report)
+    }
+}
+
+// full_batch_loss sets the network's parameters to `point` and returns the mean loss over
+// `dataset` with its gradient
+fn full_batch_loss<T: Scalar>(
+    network: &Network<T>,
+    parameters: &[Value<T>],
+    dataset: &[(Vec<T>, Vec<T>)],
+    loss: &Loss,
+    point: &[f64],
+) -> Result<(f64, Vec<f64>), NetworkError> {
+    network.set_flat_params(point)?;
+    zero_grad(parameters);
+
+    let mut total = 0.0;
+    for (input, target) in dataset {
+        let outputs = network.forward_values(input)?;
+        if outputs.len() != target.len() {
+            return Err(NetworkError::DimensionMismatch { expected: outputs.len(), found: target.len() });
+        }
+
+        let sample_loss = loss.compute(&outputs, target);
+        total += sample_loss.get_data().to_f64();
+        sample_loss.run_grad();
+    }
+
+    let samples = dataset.len().max(1) as f64;
+    let gradient = parameters
+        .iter()
+        .map(|parameter| if parameter.requires_grad() { parameter.get_gradient() / samples } else { 0.0 })
+        .collect();
+    zero_grad(parameters);
+
+    Ok((total / samples, gradient))
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use crate::network::{Activation, Layer, Network};
+    use crate::loss::Loss;
+    use crate::optim::{resolve_groups, step_groups, LbfgsReport, Lookahead, Optimizer, OptimizerState, ParamGroup, ParamSelector, Sgd, Swa, ES, LBFGS};
+    use crate::train::Trainer;
+    use crate::train::evaluate;
+    use crate::value::Value;
+
+    #[test]
+    fn step_skips_zero_gradients() {
+        let w = Value::new(2.0);
+        let b = Value::new(1.0);
+
+        let parameters = vec![w.clone(), b.clone()];
+
+        w.set_gradient(0.5);
+
+        let optimizer = Sgd::new(0.1);
+        let updated = optimizer.step(&parameters);
+
+        assert_eq!(updated, 1);
+        assert_eq!(w.get_data(), 1.95);
+        assert_eq!(b.get_data(), 1.0);
+
+        optimizer.zero_grad(&parameters);
+        assert_eq!(w.get_gradient(), 0.0);
+    }
+
+    #[test]
+    fn evolution_strategy_reduces_the_loss_without_gradients() {
+        let dataset: Vec<(Vec<f64>, Vec<f64>)> = (0..10)
+            .map(|i| {
+                let x = i as f64 / 10.0;
+                (vec![x], vec![3.0 * x - 1.0])
+            })
+            .collect();
+
+        let mut rng = StdRng::seed_from_u64(1);
+        let network: Network = Network::new(vec![Layer::dense_with_rng(1, 1, Activation::Linear, true, &mut rng).unwrap()]).unwrap();
+        let initial = evaluate(&network, &dataset).unwrap();
+
+        let es = ES::new(20, 0.05, 0.5);
+        let mut loss = |candidate: &Network| evaluate(candidate, &dataset).unwrap();
+        for _ in 0..200 {
+            es.step(&network, &mut loss, &mut rng);
+        }
+
+        let trained = evaluate(&network, &dataset).unwrap();
+        assert!(trained < initial / 10.0 && trained < 0.01, "loss went from {} to {}", initial, trained);
+        assert!(network.parameters().iter().all(|parameter| parameter.get_gradient() == 0.0));
+
+        // Perturbing returns the noise it added
+        let before: Vec<f64> = network.parameters().iter().map(|p| p.get_data()).collect();
+        let noise = network.perturb_weights(0.1, &mut rng);
+        for ((parameter, before), noise) in network.parameters().iter().zip(before).zip(noise) {
+            assert!((parameter.get_data() - (before + noise)).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn parameter_groups_set_learning_rates_and_decay() {
+        let network: Network = Network::new(vec![
+            Layer::dense(1, 1, Activation::Linear, true).unwrap(),
+            Layer::dense(1, 1, Activation::Linear, true).unwrap(),
+        ]).unwrap();
+        let parameters = network.parameters();
+        for parameter in &parameters {
+            parameter.set_data(1.0);
+            parameter.set_gradient(0.5);
+        }
+        network.layers[1].freeze();
+
+        // No decay on biases, a smaller learning rate for the first layer's weights
+        let groups = [
+            ParamGroup::new(ParamSelector::Biases),
+            ParamGroup::new(ParamSelector::Layer(0)).with_learning_rate(0.01).with_weight_decay(1.0),
+        ];
+        let settings = resolve_groups(&network, &groups);
+        assert_eq!(settings, vec![(Some(0.01), 1.0), (None, 0.0), (None, 0.0), (None, 0.0)]);
+
+        let optimizer: Box<dyn Optimizer> = Box::new(Sgd::new(0.1));
+        assert_eq!(step_groups(optimizer.as_ref(), &parameters, &settings), 2);
+
+        let data: Vec<f64> = parameters.iter().map(|p| p.get_data()).collect();
+        assert_eq!(data, vec![1.0 - 0.01 * 1.5, 1.0 - 0.1 * 0.5, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn lookahead_and_swa_wrap_any_optimizer() {
+        let p: Value<f64> = Value::new(0.0);
+        let parameters = vec![p.clone()];
+        let settings = vec![(None, 0.0)];
+
+        // A constant gradient of -1 moves the fast weights by 0.1 per step
+        let run = |optimizer: &dyn Optimizer, steps: usize| {
+            for _ in 0..steps {
+                p.set_gradient(-1.0);
+                step_groups(optimizer, &parameters, &settings);
+            }
+        };
+
+        // After two fast steps (0.2) the slow weights move halfway, to 0.1
+        let lookahead = Lookahead::new(Sgd::new(0.1), 2, 0.5);
+        run(&lookahead, 2);
+        assert!((p.get_data() - 0.1).abs() < 1e-12);
+        run(&lookahead, 3);
+        assert!((p.get_data() - 0.3).abs() < 1e-12);
+
+        // Averages the values after steps 2, 4 and 6: 0.2, 0.4 and 0.6
+        p.set_data(0.0);
+        let swa = Swa::new(Sgd::new(0.1), 2, 2);
+        let average = swa.average();
+        assert!(average.values().is_none());
+        run(&swa, 6);
+        assert_eq!(average.count(), 3);
+        assert!((average.values().unwrap()[0] - 0.4).abs() < 1e-12);
+
+        let network: Network = Network::new(vec![Layer::dense(1, 1, Activation::Linear, false).unwrap()]).unwrap();
+        assert!(average.apply(&network));
+        assert!((network.parameters()[0].get_data() - 0.4).abs() < 1e-12);
+    }
+
+    #[test]
+    fn optimizer_states_restore_wrapped_optimizers() {
+        let p: Value<f64> = Value::new(0.0);
+        let parameters = vec![p.clone()];
+        let settings = vec![(None, 0.0)];
+        let run = |optimizer: &dyn Optimizer, steps: usize| {
+            for step in 0..steps {
+                p.set_gradient(-1.0 - step as f64);
+                step_groups(optimizer, &parameters, &settings);
+            }
+        };
+
+        let optimizer = Lookahead::new(Swa::new(Sgd::new(0.1), 1, 2), 3, 0.5);
+        run(&optimizer, 5);
+        let state = optimizer.state().unwrap();
+        let restored: OptimizerState = serde_json::from_str(&serde_json::to_string(&state).unwrap()).unwrap();
+        assert_eq!(restored, state);
+        let restored = restored.into_optimizer();
+        assert_eq!(restored.name(), "lookahead");
+
+        let resumed_at = p.get_data();
+        run(&optimizer, 4);
+        let (expected, expected_state) = (p.get_data(), optimizer.state());
+        p.set_data(resumed_at);
+        run(&restored, 4);
+        assert_eq!(p.get_data(), expected);
+        assert_eq!(restored.state(), expected_state);
+
+        // Optimizers which don't describe themselves can't be restored, nor can wrappers of them
+        struct Opaque;
+        impl Optimizer for Opaque {
+            fn learning_rate(&self) -> f64 {
+                0.1
+            }
+
+            fn set_learning_rate(&mut self, _: f64) {}
+
+            fn update(&self, _: usize, data: f64, _: f64, _: f64) -> f64 {
+                data
+            }
+        }
+        assert!(Lookahead::new(Opaque, 2, 0.5).state().is_none());
+    }
+
+    #[test]
+    fn lbfgs_minimizes_the_rosenbrock_function() {
+        // f(x, y) = (1 - x)² + 100 (y - x²)², with its minimum at (1, 1)
+        let mut rosenbrock = |p: &[f64]| {
+            let (x, y) = (p[0], p[1]);
+            let loss = (1.0 - x).powi(2) + 100.0 * (y - x * x).powi(2);
+            (loss, vec![-2.0 * (1.0 - x) - 400.0 * x * (y - x * x), 200.0 * (y - x * x)])
+        };
+
+        let report = LBFGS::default().minimize(&[-1.2, 1.0], &mut rosenbrock);
+        assert!(report.converged, "{:?}", report);
+        assert!((report.parameters[0] - 1.0).abs() < 1e-6 && (report.parameters[1] - 1.0).abs() < 1e-6);
+        assert!(report.iterations < 60);
+    }
+
+    #[test]
+    fn lbfgs_resumes_interrupted_runs_exactly() {
+        let mut rosenbrock = |p: &[f64]| {
+            let (x, y) = (p[0], p[1]);
+            let loss = (1.0 - x).powi(2) + 100.0 * (y - x * x).powi(2);
+            (loss, vec![-2.0 * (1.0 - x) - 400.0 * x * (y - x * x), 200.0 * (y - x * x)])
+        };
+
+        let uninterrupted = LBFGS::new(5, 30).minimize(&[-1.2, 1.0], &mut rosenbrock);
+
+        let interrupted = LBFGS::new(5, 12).minimize(&[-1.2, 1.0], &mut rosenbrock);
+        assert_eq!(interrupted.iterations, 12);
+        assert_eq!(interrupted.history.len(), 5);
+        let saved: LbfgsReport = serde_json::from_str(&serde_json::to_string(&interrupted).unwrap()).unwrap();
+        assert_eq!(saved, interrupted);
+
+        let resumed = LBFGS::new(5, 30).resume(&saved, &mut rosenbrock);
+        assert_eq!(resumed, uninterrupted);
+    }
+
+    #[test]
+    fn lbfgs_trains_small_networks_faster_than_sgd() {
+        crate::rand::global_seed(3);
+        let dataset: Vec<(Vec<f64>, Vec<f64>)> = (0..20)
+            .map(|i| {
+                let x = i as f64 / 10.0 - 1.0;
+                (vec![x, x * x], vec![0.5 * x - 2.0 * x * x + 0.25])
+            })
+            .collect();
+        let network: Network = Network::new(vec![Layer::dense(2, 1, Activation::Linear, true).unwrap()]).unwrap();
+        crate::rand::clear_global_seed();
+        let baseline = network.deep_clone();
+
+        let report = LBFGS::new(5, 50).train(&network, &dataset, &Loss::Mse).unwrap();
+        assert!(report.converged && report.loss < 1e-12, "{:?}", report);
+        assert_eq!(network.get_flat_params(), report.parameters);
+        assert!(network.parameters().iter().all(|parameter| parameter.get_gradient() == 0.0));
+
+        let trainer = Trainer::new(Sgd::new(0.1));
+        for _ in 0..report.iterations {
+            trainer.train_epoch(&baseline, &dataset).unwrap();
+        }
+        assert!(evaluate(&baseline, &dataset).unwrap() > 1000.0 * report.loss);
+
+        let mismatched = vec![(vec![1.0, 1.0], vec![1.0, 2.0])];
+        assert!(LBFGS::default().train(&network, &mismatched, &Loss::Mse).is_err());
+    }
+}
@@ -0,0 +1,252 @@
+use crate::optim::LBFGS;
+use crate::value::Value;
+
+/// Method selects how minimize searches for a minimum.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Method {
+    // Lbfgs keeps the last `history` steps to estimate the curvature, see optim::LBFGS
+    Lbfgs { history: usize },
+    GradientDescent { learning_rate: f64 },
+}
+
+/// MinimizeConfig sets the method of minimize and when it stops: after `max_iterations`, or once
+/// the gradient norm drops below `tolerance`.
+///
+/// `bounds` optionally holds a (lower, upper) box constraint per variable, using infinities for
+/// unbounded sides. Gradient descent projects the point back into the box after each step; with
+/// L-BFGS, `f` is evaluated at the projection of each point, so the function is flat outside the
+/// box. Either way the minimum returned lies within the bounds.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MinimizeConfig {
+    pub method: Method,
+    pub max_iterations: usize,
+    pub tolerance: f64,
+    pub bounds: Option<Vec<(f64, f64)>>,
+}
+
+impl Default for MinimizeConfig {
+    fn default() -> Self {
+        MinimizeConfig {
+            method: Method::Lbfgs { history: 10 },
+            max_iterations: 200,
+            tolerance: 1e-8,
+            bounds: None,
+        }
+    }
+}
+
+/// Minimum is the point minimize stopped at.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Minimum {
+    pub x: Vec<f64>,
+    pub value: f64,
+    pub iterations: usize,
+
+    // converged is true when the gradient norm dropped below the tolerance
+    pub converged: bool,
+}
+
+/// minimize finds a local minimum of `f` starting from `init`. `f` builds its result from the
+/// Values it's given like any other expression, and its gradient comes from the backward pass,
+/// so any differentiable function can be minimized, not just the loss of a network:
+///
+/// ```
+/// use backprop::optimize::{minimize, MinimizeConfig};
+/// use backprop::value::Value;
+///
+/// // (x - 3)² + (y + 1)²
+/// let f = |p: &[Value<f64>]| {
+///     let a = &p[0] - &Value::constant(3.0);
+///     let b = &p[1] + &Value::constant(1.0);
+///     &(&a * &a) + &(&b * &b)
+/// };
+///
+/// let minimum = minimize(f, &[0.0, 0.0], &MinimizeConfig::default());
+/// assert!((minimum.x[0] - 3.0).abs() < 1e-6 && (minimum.x[1] + 1.0).abs() < 1e-6);
+/// ```
+///
+/// Panics if `bounds` doesn't have one pair per variable.
+pub fn minimize(f: impl Fn(&[Value<f64>]) -> Value<f64>, init: &[f64], config: &MinimizeConfig) -> Minimum {
+    let bounds = config.bounds.as_deref();
+    if let Some(bounds) = bounds {
+        assert_eq!(bounds.len(), init.len(), "expected one pair of bounds per variable");
+    }
+
+    let mut objective = |point: &[f64]| {
+        let Some(bounds) = bounds else {
+            return evaluate(&f, point);
+        };
+
+        // Clamping has no gradient outside the box
+        let mut projected = point.to_vec();
+        project(&mut projected, bounds);
+        let (value, mut gradient) = evaluate(&f, &projected);
+        for ((gradient, x), (lower, upper)) in gradient.iter_mut().zip(point).zip(bounds) {
+            if x < lower || x > upper {
+                *gradient = 0.0;
+            }
+        }
+
+        (value, gradient)
+    };
+
+    let mut init = init.to_vec();
+    if let Some(bounds) = bounds {
+        project(&mut init, bounds);
+    }
+
+    match config.method {
+        Method::Lbfgs { history } => {
+            let lbfgs = LBFGS { history, max_iterations: config.max_iterations, tolerance: config.tolerance, ..LBFGS::default() };
+            let mut report = lbfgs.minimize(&init, &mut objective);
+            let Some(bounds) = bounds else {
+                return Minimum { x: report.parameters, value: report.loss, iterations: report.iterations, converged: report.converged };
+            };
+
+            // A minimum on a bound keeps a gradient pointing out of the box, so only the
+            // projected gradient can tell whether it has converged
+            project(&mut report.parameters, bounds);
+            let (value, gradient) = objective(&report.parameters);
+            let converged = projected_gradient_norm(&report.parameters, &gradient, Some(bounds)) <= config.tolerance;
+            Minimum { x: report.parameters, value, iterations: report.iterations, converged }
+        }
+        Method::GradientDescent { learning_rate } => {
+            let mut x = init;
+            for iteration in 0..config.max_iterations {
+                let (value, gradient) = objective(&x);
+                if projected_gradient_norm(&x, &gradient, bounds) <= config.tolerance {
+                    return Minimum { x, value, iterations: iteration, converged: true };
+                }
+
+                x.iter_mut().zip(&gradient).for_each(|(x, g)| *x -= learning_rate * g);
+                if let Some(bounds) = bounds {
+                    project(&mut x, bounds);
+                }
+            }
+
+            let (value, gradient) = objective(&x);
+            let converged = projected_gradient_norm(&x, &gradient, bounds) <= config.tolerance;
+            Minimum { x, value, iterations: config.max_iterations, converged }
+        }
+    }
+}
+
+/// Clamps each variable of `x` into its (lower, upper) bounds.
+pub fn project(x: &mut [f64], bounds: &[(f64, f64)]) {
+    for (x, (lower, upper)) in x.iter_mut().zip(bounds) {
+        *x = x.clamp(*lower, *upper);
+    }
+}
+
+/// Returns a penalty for violating the constraint `g <= 0`: weight * max(0, g)², to add to the
+/// function passed to minimize. The penalty is 0 wherever the constraint holds, and larger
+/// weights enforce it more strictly.
+pub fn penalty_le(g: &Value<f64>, weight: f64) -> Value<f64> {
+    let violation = g.relu();
+    &(&violation * &violation) * &Value::constant(weight)
+}
+
+/// Returns a penalty for violating the constraint `h == 0`: weight * h².
+pub fn penalty_eq(h: &Value<f64>, weight: f64) -> Value<f64> {
+    &(h * h) * &Value::constant(weight)
+}
+
+/// Returns a penalty for `x` leaving [lower, upper], see penalty_le. Unlike the bounds of
+/// MinimizeConfig, the constraint is soft: x can leave the interval if that lowers the function
+/// by more than the penalty.
+pub fn penalty_bounds(x: &Value<f64>, lower: f64, upper: f64, weight: f64) -> Value<f64> {
+    let below = &Value::constant(lower) - x;
+    let above = x - &Value::constant(upper);
+
+    &penalty_le(&below, weight) + &penalty_le(&above, weight)
+}
+
+// projected_gradient_norm is the norm of the gradient, ignoring the components which push
+// variables at their bounds outside the box, since projection cancels those steps
+fn projected_gradient_norm(x: &[f64], gradient: &[f64], bounds: Option<&[(f64, f64)]>) -> f64 {
+    gradient
+        .iter()
+        .enumerate()
+        .map(|(i, g)| match bounds {
+            Some(bounds) if (x[i] <= bounds[i].0 && *g > 0.0) || (x[i] >= bounds[i].1 && *g < 0.0) => 0.0,
+            _ => g * g,
+        })
+        .sum::<f64>()
+        .sqrt()
+}
+
+// evaluate returns the value of `f` at `point` and its gradient
+fn evaluate(f: &impl Fn(&[Value<f64>]) -> Value<f64>, point: &[f64]) -> (f64, Vec<f64>) {
+    let inputs: Vec<Value<f64>> = point.iter().map(|x| Value::new(*x)).collect();
+
+    let output = f(&inputs);
+    output.run_grad();
+
+    (output.get_data(), inputs.iter().map(|input| input.get_gradient()).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::optimize::{minimize, penalty_bounds, penalty_eq, penalty_le, Method, MinimizeConfig};
+    use crate::value::Value;
+
+    #[test]
+    fn minimizes_arbitrary_expressions() {
+        // Rosenbrock: (1 - x)² + 100 (y - x²)², with its minimum at (1, 1)
+        let rosenbrock = |p: &[Value<f64>]| {
+            let a = &Value::constant(1.0) - &p[0];
+            let b = &p[1] - &(&p[0] * &p[0]);
+            &(&a * &a) + &(&(&b * &b) * &Value::constant(100.0))
+        };
+
+        let minimum = minimize(rosenbrock, &[-1.2, 1.0], &MinimizeConfig::default());
+        assert!(minimum.converged, "{:?}", minimum);
+        assert!((minimum.x[0] - 1.0).abs() < 1e-6 && (minimum.x[1] - 1.0).abs() < 1e-6);
+        assert!(minimum.value < 1e-12);
+
+        // The smallest value of e^x - 2x is at x = ln 2
+        let config = MinimizeConfig { method: Method::GradientDescent { learning_rate: 0.3 }, ..MinimizeConfig::default() };
+        let minimum = minimize(|p| &p[0].exp() - &(&p[0] * &Value::constant(2.0)), &[0.0], &config);
+        assert!(minimum.converged && (minimum.x[0] - 2f64.ln()).abs() < 1e-6);
+    }
+
+    #[test]
+    fn bounds_and_penalties_constrain_the_minimum() {
+        // (x - 3)² + (y + 1)², with the unconstrained minimum at (3, -1)
+        let f = |p: &[Value<f64>]| {
+            let a = &p[0] - &Value::constant(3.0);
+            let b = &p[1] + &Value::constant(1.0);
+            &(&a * &a) + &(&b * &b)
+        };
+
+        for method in [Method::Lbfgs { history: 5 }, Method::GradientDescent { learning_rate: 0.1 }] {
+            let config = MinimizeConfig { method, bounds: Some(vec![(0.0, 2.0), (0.0, f64::INFINITY)]), ..MinimizeConfig::default() };
+            let minimum = minimize(f, &[5.0, 5.0], &config);
+            assert!((minimum.x[0] - 2.0).abs() < 1e-6 && minimum.x[1].abs() < 1e-6, "{:?} {:?}", method, minimum);
+            assert!(minimum.converged, "{:?} {:?}", method, minimum);
+            assert!((minimum.value - 2.0).abs() < 1e-6);
+        }
+
+        // The line search can't make progress from a minimum on a bound, which still converges
+        let shifted = |p: &[Value<f64>]| {
+            let a = &p[0] + &Value::constant(1.0);
+            &a * &a
+        };
+        let config = MinimizeConfig { bounds: Some(vec![(0.0, f64::INFINITY)]), ..MinimizeConfig::default() };
+        let minimum = minimize(shifted, &[1.0], &config);
+        assert!(minimum.converged && minimum.x == vec![0.0] && minimum.value == 1.0, "{:?}", minimum);
+
+        // Soft constraints: x + y = 1 and y <= -2 with large weights land near (3, -2)
+        let penalized = |p: &[Value<f64>]| {
+            let sum = &(&p[0] + &p[1]) - &Value::constant(1.0);
+            let y_bound = &p[1] + &Value::constant(2.0);
+            &(&f(p) + &penalty_eq(&sum, 1e4)) + &penalty_le(&y_bound, 1e4)
+        };
+        let minimum = minimize(penalized, &[0.0, 0.0], &MinimizeConfig::default());
+        assert!((minimum.x[0] - 3.0).abs() < 1e-2 && (minimum.x[1] + 2.0).abs() < 1e-2, "{:?}", minimum);
+
+        let x = Value::new(5.0);
+        assert_eq!(penalty_bounds(&x, 0.0, 4.0, 2.0).get_data(), 2.0);
+        assert_eq!(penalty_bounds(&Value::new(1.0), 0.0, 4.0, 2.0).get_data(), 0.0);
+    }
+}
@@ -0,0 +1,256 @@
+use rayon::prelude::*;
+use crate::config::LayerConfig;
+use crate::network::{Activation, Layer, Network, NetworkError};
+use crate::sync_value::SyncValue;
+
+fn activate(activation: Activation, x: &SyncValue<f64>) -> SyncValue<f64> {
+    match activation {
+        Activation::Relu => x.relu(),
+        Activation::Linear => x.clone(),
+        Activation::LeakyRelu { alpha } => x.leaky_relu(alpha),
+        Activation::Softplus => x.softplus(),
+        Activation::Gelu => x.gelu(),
+        Activation::Elu => x.elu(),
+        Activation::Swish => x.swish(),
+    }
+}
+
+/// ParallelLayer is a thread-safe snapshot of a layer's parameters whose neurons are evaluated in parallel.
+pub struct ParallelLayer {
+    weights: Vec<Vec<SyncValue<f64>>>,
+    biases: Vec<Option<SyncValue<f64>>>,
+    activation: Activation,
+}
+
+impl ParallelLayer {
+    pub fn from_layer(layer: &Layer) -> ParallelLayer {
+        let weights = layer
+            .weights()
+            .iter()
+            .map(|row| row.iter().map(|w| SyncValue::new(*w)).collect())
+            .collect();
+
+        let biases = match layer.biases() {
+            Some(biases) => biases.into_iter().map(|b| Some(SyncValue::new(b))).collect(),
+            None => vec![None; layer.num_outputs() as usize],
+        };
+
+        ParallelLayer {
+            weights,
+            biases,
+            activation: layer.activation(),
+        }
+    }
+
+    /// Copies the layer's current weights and biases into the snapshot, reusing its nodes.
+    fn refresh(&self, layer: &Layer) {
+        let biases = layer.biases();
+        let data = layer
+            .weights()
+            .into_iter()
+            .enumerate()
+            .flat_map(|(index, row)| row.into_iter().chain(biases.as_ref().map(|biases| biases[index])));
+
+        for (snapshot, data) in self.parameters().iter().zip(data) {
+            snapshot.set_data(data);
+        }
+    }
+
+    /// Evaluates each neuron on a separate rayon task.
+    pub fn forward(&self, inputs: &[SyncValue<f64>]) -> Vec<SyncValue<f64>> {
+        self.weights
+            .par_iter()
+            .zip(self.biases.par_iter())
+            .map(|(weights, bias)| {
+                let weighted_sum = weights
+                    .iter()
+                    .zip(inputs)
+                    .map(|(w, input)| w * input)
+                    .fold(SyncValue::new(0.0), |acc, item| acc + item);
+
+                let weight_and_bias = match bias {
+                    Some(bias) => &weighted_sum + bias,
+                    None => weighted_sum,
+                };
+
+                activate(self.activation, &weight_and_bias)
+            })
+            .collect()
+    }
+
+    /// Returns handles to every weight and bias, in the same order as Layer::parameters.
+    pub fn parameters(&self) -> Vec<SyncValue<f64>> {
+        self.weights
+            .iter()
+            .zip(&self.biases)
+            .flat_map(|(weights, bias)| weights.iter().chain(bias).cloned())
+            .collect()
+    }
+}
+
+/// ParallelNetwork is a thread-safe snapshot of a network, evaluated layer by layer with the
+/// neurons of each layer running in parallel.
+///
+/// Gradients computed on the snapshot can be merged back into the source network with
+/// accumulate_gradients_into, so it can be used for training as well as inference. Taking the
+/// snapshot copies every parameter, so it's meant to be kept for a whole batch or epoch: after an
+/// optimizer step, refresh copies the updated parameters into the existing snapshot.
+///
+/// Dropout and batch normalisation layers are snapshotted as the dense maps they apply outside of
+/// training (see Layer::weights), so they don't drop inputs or receive gradients from the snapshot.
+pub struct ParallelNetwork {
+    pub layers: Vec<ParallelLayer>,
+}
+
+impl ParallelNetwork {
+    pub fn from_network(network: &Network) -> ParallelNetwork {
+        ParallelNetwork {
+            layers: network.layers.iter().map(ParallelLayer::from_layer).collect(),
+        }
+    }
+
+    /// Copies the current parameters of `network`, which must be the network the snapshot was
+    /// taken from, into the snapshot, e.g. after an optimizer step.
+    pub fn refresh(&self, network: &Network) {
+        for (parallel_layer, layer) in self.layers.iter().zip(&network.layers) {
+            parallel_layer.refresh(layer);
+        }
+    }
+
+    pub fn forward(&self, inputs: &[f64]) -> Vec<SyncValue<f64>> {
+        let mut result: Vec<SyncValue<f64>> = inputs.iter().map(|x| SyncValue::new(*x)).collect();
+
+        for layer in &self.layers {
+            result = layer.forward(&result);
+        }
+
+        result
+    }
+
+    /// Adds the gradients accumulated on the snapshot's parameters into the matching parameters of
+    /// `network`, skipping frozen ones, and resets the snapshot's gradients so the next backward
+    /// pass through it starts from zero.
+    pub fn accumulate_gradients_into(&self, network: &Network) {
+        for (parallel_layer, layer) in self.layers.iter().zip(&network.layers) {
+            let snapshot = parallel_layer.parameters();
+
+            if matches!(layer.config(), LayerConfig::Dense { .. }) {
+                for (sync_parameter, parameter) in snapshot.iter().zip(layer.parameters()) {
+                    sync_parameter.accumulate_into(&parameter);
+                }
+            }
+            for sync_parameter in &snapshot {
+                sync_parameter.set_gradient(0.0);
+            }
+        }
+    }
+}
+
+impl Network {
+    /// Performs the forward pass with the neurons of each layer evaluated in parallel. The
+    /// parameters are snapshotted on every call, so use forward_parallel_batch for several inputs.
+    pub fn forward_parallel(&self, inputs: &[f64]) -> Result<Vec<f64>, NetworkError> {
+        Ok(self.forward_parallel_batch(&[inputs.to_vec()])?.remove(0))
+    }
+
+    /// Like forward_parallel for each of `inputs`, snapshotting the parameters once for the batch.
+    pub fn forward_parallel_batch(&self, inputs: &[Vec<f64>]) -> Result<Vec<Vec<f64>>, NetworkError> {
+        for input in inputs {
+            self.check_inputs(input.len())?;
+        }
+
+        let snapshot = ParallelNetwork::from_network(self);
+
+        Ok(inputs
+            .iter()
+            .map(|input| snapshot.forward(input).iter().map(|output| output.get_data()).collect())
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::network::{Activation, Layer, Network};
+    use crate::parallel::ParallelNetwork;
+
+    fn network() -> Network {
+        Network{
+            layers: vec![
+                Layer::dense(3, 16, Activation::Relu, true).unwrap(),
+                Layer::dense(16, 2, Activation::Linear, false).unwrap(),
+            ],
+        }
+    }
+
+    #[test]
+    fn parallel_forward_matches_sequential() {
+        let network = network();
+        let inputs = vec![0.1, -0.2, 0.3];
+
+        let sequential = network.forward(&inputs).unwrap();
+        let parallel = network.forward_parallel(&inputs).unwrap();
+
+        for (a, b) in sequential.iter().zip(&parallel) {
+            assert!((a - b).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn parallel_gradients_match_sequential() {
+        let network = network();
+        let inputs = vec![0.5, 0.25, -1.0];
+
+        let parallel_network = ParallelNetwork::from_network(&network);
+        parallel_network.forward(&inputs)[0].run_grad();
+
+        let reference = Network::from_config(&network.config()).unwrap();
+        for (source, target) in network.parameters().iter().zip(reference.parameters()) {
+            target.set_data(source.get_data());
+        }
+        reference.forward_values(&inputs).unwrap()[0].run_grad();
+
+        parallel_network.accumulate_gradients_into(&network);
+
+        for (a, b) in network.parameters().iter().zip(reference.parameters()) {
+            assert!((a.get_gradient() - b.get_gradient()).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn snapshots_are_reused_and_skip_frozen_parameters() {
+        let network = network();
+        network.layers[0].freeze();
+        let inputs = [vec![0.5, 0.25, -1.0], vec![-0.1, 0.4, 0.2]];
+
+        let parallel = network.forward_parallel_batch(&inputs).unwrap();
+        for (input, outputs) in inputs.iter().zip(&parallel) {
+            for (a, b) in network.forward(input).unwrap().iter().zip(outputs) {
+                assert!((a - b).abs() < 1e-12);
+            }
+        }
+
+        // One snapshot serves both samples; merging moves its gradients into the network
+        let snapshot = ParallelNetwork::from_network(&network);
+        for input in &inputs {
+            snapshot.forward(input)[0].run_grad();
+            snapshot.accumulate_gradients_into(&network);
+        }
+        assert!(snapshot.layers.iter().flat_map(|layer| layer.parameters()).all(|p| p.get_gradient() == 0.0));
+
+        let reference = network.deep_clone();
+        for input in &inputs {
+            reference.forward_values(input).unwrap()[0].run_grad();
+        }
+        assert!(network.layers[0].parameters().iter().all(|p| p.get_gradient() == 0.0));
+        for (a, b) in network.parameters().iter().zip(reference.parameters()) {
+            assert!((a.get_gradient() - b.get_gradient()).abs() < 1e-12);
+        }
+
+        // After an update, refreshing the snapshot picks up the new parameters
+        for parameter in network.parameters() {
+            parameter.set_data(parameter.get_data() * 0.5);
+        }
+        snapshot.refresh(&network);
+        assert!((snapshot.forward(&inputs[0])[1].get_data() - network.forward(&inputs[0]).unwrap()[1]).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,30 @@
+// The prelude re-exports the types needed to build, train and differentiate models,
+// so user code only needs `use backprop::prelude::*;`.
+
+pub use crate::error::BackpropError;
+pub use crate::logging::{Logger, ProgressBar};
+pub use crate::loss::{cross_entropy, hinge, huber, mse, Loss};
+pub use crate::network::{ensemble_average, jacobian, Activation, Layer, Network, NetworkError};
+pub use crate::optim::{Lookahead, Optimizer, ParamGroup, ParamSelector, Sgd, Swa, ES, LBFGS};
+pub use crate::scalar::Scalar;
+pub use crate::train::{EarlyStopping, Trainer, TrainingHistory};
+pub use crate::value::Value;
+// Brings the value! and expr! macros into scope
+pub use crate::{expr, value};
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+
+    #[test]
+    fn prelude_covers_a_training_loop() {
+        let network: Network = Network::new(vec![Layer::dense(1, 1, Activation::Linear, true).unwrap()]).unwrap();
+
+        let outputs = network.forward_values(&[1.0]).unwrap();
+        let loss = mse(&outputs, &[3.0]);
+        loss.run_grad();
+
+        Sgd::new(0.1).step(&network.parameters());
+        assert!(Trainer::new(Sgd::new(0.1)).train_epoch(&network, &[(vec![1.0], vec![3.0])]).is_ok());
+    }
+}
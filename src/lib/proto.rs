@@ -0,0 +1,106 @@
+// Minimal protocol buffers encoder used to emit ONNX models and TensorBoard events without
+// generated code. Only the wire types needed by the exporters are supported.
+
+const WIRE_VARINT: u64 = 0;
+const WIRE_FIXED64: u64 = 1;
+const WIRE_LENGTH_DELIMITED: u64 = 2;
+const WIRE_FIXED32: u64 = 5;
+
+/// Message accumulates the encoded fields of a single protobuf message.
+#[derive(Default)]
+pub(crate) struct Message {
+    buf: Vec<u8>,
+}
+
+fn write_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+impl Message {
+    pub fn new() -> Message {
+        Message::default()
+    }
+
+    fn key(&mut self, field: u32, wire_type: u64) {
+        write_varint(&mut self.buf, ((field as u64) << 3) | wire_type);
+    }
+
+    pub fn varint(&mut self, field: u32, value: u64) -> &mut Message {
+        self.key(field, WIRE_VARINT);
+        write_varint(&mut self.buf, value);
+        self
+    }
+
+    pub fn fixed64(&mut self, field: u32, value: u64) -> &mut Message {
+        self.key(field, WIRE_FIXED64);
+        self.buf.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+
+    pub fn fixed32(&mut self, field: u32, value: u32) -> &mut Message {
+        self.key(field, WIRE_FIXED32);
+        self.buf.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+
+    pub fn double(&mut self, field: u32, value: f64) -> &mut Message {
+        self.fixed64(field, value.to_bits())
+    }
+
+    pub fn float(&mut self, field: u32, value: f32) -> &mut Message {
+        self.fixed32(field, value.to_bits())
+    }
+
+    pub fn bytes(&mut self, field: u32, value: &[u8]) -> &mut Message {
+        self.key(field, WIRE_LENGTH_DELIMITED);
+        write_varint(&mut self.buf, value.len() as u64);
+        self.buf.extend_from_slice(value);
+        self
+    }
+
+    pub fn string(&mut self, field: u32, value: &str) -> &mut Message {
+        self.bytes(field, value.as_bytes())
+    }
+
+    pub fn message(&mut self, field: u32, value: &Message) -> &mut Message {
+        self.bytes(field, &value.buf)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buf
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::proto::Message;
+
+    #[test]
+    fn encodes_fields() {
+        let mut inner = Message::new();
+        inner.string(2, "testing");
+
+        let mut message = Message::new();
+        message.varint(1, 150).message(3, &inner);
+
+        assert_eq!(message.as_bytes(), &[
+            0x08, 0x96, 0x01,
+            0x1a, 0x09, 0x12, 0x07, b't', b'e', b's', b't', b'i', b'n', b'g',
+        ]);
+    }
+
+    #[test]
+    fn encodes_fixed_width_fields() {
+        let mut message = Message::new();
+        message.double(1, 1.0).float(2, 0.5);
+
+        assert_eq!(message.as_bytes(), &[
+            0x09, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xf0, 0x3f,
+            0x15, 0x00, 0x00, 0x00, 0x3f,
+        ]);
+    }
+}
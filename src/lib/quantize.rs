@@ -0,0 +1,171 @@
+use crate::network::{Activation, Layer, Network, NetworkError};
+use crate::scalar::Scalar;
+
+/// QuantizedLayer stores a dense layer's weights as signed 8-bit integers, with one affine mapping
+/// for the whole layer: weight = scale * (quantized - zero_point). Biases are kept as f32, as they
+/// are few and quantizing them saves little.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuantizedLayer {
+    // weights holds one row of num_inputs values per output, row after row
+    weights: Vec<i8>,
+    biases: Option<Vec<f32>>,
+    scale: f32,
+    zero_point: i8,
+    num_inputs: usize,
+    activation: Activation,
+}
+
+impl QuantizedLayer {
+    /// Quantizes the layer's weights over the range [min, max] of its weights, extended to include 0
+    /// so it's represented exactly.
+    pub fn from_layer<T: Scalar>(layer: &Layer<T>) -> QuantizedLayer {
+        let weights: Vec<f64> = layer.weights().concat().iter().map(|w| w.to_f64()).collect();
+
+        let min = weights.iter().copied().fold(0.0, f64::min);
+        let max = weights.iter().copied().fold(0.0, f64::max);
+
+        // All-zero layers would have a zero scale, which any scale represents exactly
+        let scale = if max > min { (max - min) / 255.0 } else { 1.0 };
+        let zero_point = (-128.0 - min / scale).round().clamp(-128.0, 127.0);
+
+        let quantized = weights
+            .iter()
+            .map(|w| (w / scale + zero_point).round().clamp(-128.0, 127.0) as i8)
+            .collect();
+
+        QuantizedLayer {
+            weights: quantized,
+            biases: layer.biases().map(|biases| biases.iter().map(|b| b.to_f64() as f32).collect()),
+            scale: scale as f32,
+            zero_point: zero_point as i8,
+            num_inputs: layer.num_inputs() as usize,
+            activation: layer.activation(),
+        }
+    }
+
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+
+    pub fn zero_point(&self) -> i8 {
+        self.zero_point
+    }
+
+    pub fn num_inputs(&self) -> usize {
+        self.num_inputs
+    }
+
+    pub fn num_outputs(&self) -> usize {
+        self.weights.len() / self.num_inputs
+    }
+
+    /// Returns the weights mapped back to real values, one row per output.
+    pub fn dequantized_weights(&self) -> Vec<Vec<f64>> {
+        self.weights.chunks(self.num_inputs).map(|row| row.iter().map(|q| self.dequantize(*q)).collect()).collect()
+    }
+
+    fn dequantize(&self, quantized: i8) -> f64 {
+        self.scale as f64 * (quantized as f64 - self.zero_point as f64)
+    }
+
+    fn forward(&self, inputs: &[f64]) -> Vec<f64> {
+        self.weights
+            .chunks(self.num_inputs)
+            .enumerate()
+            .map(|(output, row)| {
+                // Subtracting the zero point before scaling keeps the sum in integer steps of `scale`
+                let sum: f64 = row.iter().zip(inputs).map(|(q, x)| (*q as f64 - self.zero_point as f64) * x).sum();
+                let bias = self.biases.as_ref().map_or(0.0, |biases| biases[output] as f64);
+
+                self.activation.apply_scalar(self.scale as f64 * sum + bias)
+            })
+            .collect()
+    }
+
+    /// Returns how many bytes the quantized weights, biases and quantization parameters take.
+    pub fn size_in_bytes(&self) -> usize {
+        let biases = self.biases.as_ref().map_or(0, |biases| biases.len() * 4);
+
+        self.weights.len() + biases + 4 + 1
+    }
+}
+
+/// QuantizedNetwork is an inference-only copy of a network with int8 weights, roughly 8 times
+/// smaller than its float64 original. It can't be trained, and its outputs differ slightly from
+/// the original's due to rounding.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuantizedNetwork {
+    pub layers: Vec<QuantizedLayer>,
+}
+
+impl QuantizedNetwork {
+    pub fn from_network<T: Scalar>(network: &Network<T>) -> QuantizedNetwork {
+        QuantizedNetwork {
+            layers: network.layers.iter().map(QuantizedLayer::from_layer).collect(),
+        }
+    }
+
+    /// Performs the forward pass, dequantizing the weights on the fly.
+    pub fn forward(&self, inputs: &[f64]) -> Result<Vec<f64>, NetworkError> {
+        let first = self.layers.first().ok_or(NetworkError::EmptyNetwork)?;
+        if inputs.len() != first.num_inputs() {
+            return Err(NetworkError::DimensionMismatch { expected: first.num_inputs(), found: inputs.len() });
+        }
+
+        let mut result = inputs.to_vec();
+        for layer in &self.layers {
+            result = layer.forward(&result);
+        }
+
+        Ok(result)
+    }
+
+    pub fn size_in_bytes(&self) -> usize {
+        self.layers.iter().map(|layer| layer.size_in_bytes()).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::network::{Activation, Layer, Network, NetworkError};
+    use crate::quantize::QuantizedLayer;
+
+    #[test]
+    fn weights_round_trip_within_half_a_step() {
+        let layer: Layer = Layer::dense(3, 2, Activation::Relu, true).unwrap();
+        layer.set_weights(&[vec![-0.8, 0.0, 0.35], vec![1.2, -0.05, 0.6]]);
+
+        let quantized = QuantizedLayer::from_layer(&layer);
+        assert!((quantized.scale() as f64 - 2.0 / 255.0).abs() < 1e-6);
+
+        let restored = quantized.dequantized_weights();
+        for (row, original) in restored.iter().zip(layer.weights()) {
+            for (w, expected) in row.iter().zip(original) {
+                assert!((w - expected).abs() <= quantized.scale() as f64 / 2.0 + 1e-6, "{} vs {}", w, expected);
+            }
+        }
+
+        // Zero is always exactly representable
+        assert_eq!(restored[0][1], 0.0);
+    }
+
+    #[test]
+    fn quantized_network_matches_the_original() {
+        let network: Network = Network::new(vec![
+            Layer::dense(4, 8, Activation::Relu, true).unwrap(),
+            Layer::dense(8, 2, Activation::Linear, true).unwrap(),
+        ]).unwrap();
+
+        let quantized = network.quantize_int8();
+        assert!(quantized.size_in_bytes() * 4 < network.num_parameters() * 8);
+
+        let input = [0.3, -0.7, 0.1, 0.9];
+        let expected = network.forward(&input).unwrap();
+        let outputs = quantized.forward(&input).unwrap();
+        for (output, expected) in outputs.iter().zip(&expected) {
+            assert!((output - expected).abs() < 0.05, "{} vs {}", output, expected);
+        }
+
+        assert_eq!(quantized.forward(&[1.0]), Err(NetworkError::DimensionMismatch { expected: 4, found: 1 }));
+    }
+}
@@ -0,0 +1,256 @@
+use std::cell::RefCell;
+use std::f64::consts::PI;
+use ::rand::{Rng, RngCore, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// SeedState is a seeded random number generator which remembers its seed, so a run can record
+/// the seed (e.g. in Experiment::seed) and replay exactly the same random draws later.
+///
+/// Pass it wherever an rng is taken (Layer::dense_with_rng, WeightedRandomSampler::indices,
+/// sample::softmax_sample, transforms), or install one for the whole thread with global_seed.
+///
+/// It serializes as its seed and the number of draws taken so far, so a checkpoint can save it
+/// and the restored state carries on with exactly the draws the original would have made next.
+/// It draws the same sequence as rand's StdRng seeded with seed_from_u64.
+#[derive(Debug, Clone)]
+pub struct SeedState {
+    seed: u64,
+    rng: ChaCha12Rng,
+}
+
+// SavedSeedState is how a SeedState is serialized: the position is the rng's word position, a
+// 68 bit number, kept as a string since JSON numbers can't hold it exactly
+#[derive(Serialize, Deserialize)]
+struct SavedSeedState {
+    seed: u64,
+    position: String,
+}
+
+impl Serialize for SeedState {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        SavedSeedState { seed: self.seed, position: self.rng.get_word_pos().to_string() }.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for SeedState {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<SeedState, D::Error> {
+        let saved = SavedSeedState::deserialize(deserializer)?;
+        let position: u128 = saved.position.parse().map_err(serde::de::Error::custom)?;
+
+        let mut state = SeedState::new(saved.seed);
+        state.rng.set_word_pos(position);
+
+        Ok(state)
+    }
+}
+
+impl SeedState {
+    pub fn new(seed: u64) -> SeedState {
+        SeedState { seed, rng: ChaCha12Rng::seed_from_u64(seed) }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Restarts the sequence of draws from the seed.
+    pub fn reset(&mut self) {
+        self.rng = ChaCha12Rng::seed_from_u64(self.seed);
+    }
+
+    /// Returns a new state seeded from this one, for an independent stream of draws (e.g. one for
+    /// initialisation and one for shuffling) which is still determined by the original seed.
+    pub fn fork(&mut self) -> SeedState {
+        SeedState::new(self.rng.next_u64())
+    }
+}
+
+impl RngCore for SeedState {
+    fn next_u32(&mut self) -> u32 {
+        self.rng.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.rng.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.rng.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), ::rand::Error> {
+        self.rng.try_fill_bytes(dest)
+    }
+}
+
+thread_local! {
+    static GLOBAL: RefCell<Option<SeedState>> = const { RefCell::new(None) };
+}
+
+/// Seeds the randomness used by this thread wherever no rng is passed explicitly, such as
+/// Layer::dense, Conv1d::new, SelfAttention::new and the recurrent layers, so the same seed
+/// builds the same networks.
+pub fn global_seed(seed: u64) {
+    GLOBAL.with(|global| *global.borrow_mut() = Some(SeedState::new(seed)));
+}
+
+/// Undoes global_seed, going back to the thread's entropy-seeded generator.
+pub fn clear_global_seed() {
+    GLOBAL.with(|global| *global.borrow_mut() = None);
+}
+
+/// Returns the seed set with global_seed, if any.
+pub fn current_seed() -> Option<u64> {
+    GLOBAL.with(|global| global.borrow().as_ref().map(SeedState::seed))
+}
+
+/// Returns a copy of the thread's global state, with the draws taken from it so far, if it has
+/// been seeded. Restoring it with set_global_state replays the draws which would have come next,
+/// e.g. when a checkpointed run is resumed.
+pub fn global_state() -> Option<SeedState> {
+    GLOBAL.with(|global| global.borrow().clone())
+}
+
+/// Installs `state` as the thread's global state, see global_state.
+pub fn set_global_state(state: SeedState) {
+    GLOBAL.with(|global| *global.borrow_mut() = Some(state));
+}
+
+/// GlobalRng draws from the state installed by global_seed, or from rand's thread_rng when
+/// the thread hasn't been seeded.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GlobalRng;
+
+/// Returns a handle to the thread's global generator, see GlobalRng.
+pub fn global_rng() -> GlobalRng {
+    GlobalRng
+}
+
+impl GlobalRng {
+    fn with<R>(&mut self, draw: impl FnOnce(&mut dyn RngCore) -> R) -> R {
+        GLOBAL.with(|global| match global.borrow_mut().as_mut() {
+            Some(state) => draw(state),
+            None => draw(&mut ::rand::thread_rng()),
+        })
+    }
+}
+
+impl RngCore for GlobalRng {
+    fn next_u32(&mut self) -> u32 {
+        self.with(|rng| rng.next_u32())
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.with(|rng| rng.next_u64())
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.with(|rng| rng.fill_bytes(dest))
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), ::rand::Error> {
+        self.with(|rng| rng.try_fill_bytes(dest))
+    }
+}
+
+/// gaussian draws from a normal distribution with mean 0 and the given standard deviation
+/// (Box-Muller transform).
+pub fn gaussian<R: Rng + ?Sized>(rng: &mut R, std_dev: f64) -> f64 {
+    if std_dev == 0.0 {
+        return 0.0;
+    }
+
+    let u1: f64 = rng.gen_range(f64::EPSILON..1.0);
+    let u2: f64 = rng.gen_range(0.0..1.0);
+
+    std_dev * (-2.0 * u1.ln()).sqrt() * (2.0 * PI * u2).cos()
+}
+
+#[cfg(test)]
+mod tests {
+    use ::rand::{Rng, RngCore};
+    use crate::attention::SelfAttention;
+    use crate::conv::Conv1d;
+    use crate::network::{Activation, Layer, Network};
+    use ::rand::rngs::StdRng;
+    use ::rand::SeedableRng;
+    use crate::rand::{clear_global_seed, current_seed, global_rng, global_seed, global_state, set_global_state, SeedState};
+
+    #[test]
+    fn seed_states_replay_and_fork() {
+        let mut state = SeedState::new(9);
+        let draws: Vec<u64> = (0..4).map(|_| state.next_u64()).collect();
+
+        state.reset();
+        assert_eq!((0..4).map(|_| state.next_u64()).collect::<Vec<_>>(), draws);
+
+        let mut first = state.fork();
+        let mut second = state.fork();
+        assert_ne!(first.seed(), second.seed());
+        assert_ne!(first.gen::<u64>(), second.gen::<u64>());
+
+        // The same draws as StdRng, which earlier runs recorded their seeds against
+        let mut std_rng = StdRng::seed_from_u64(9);
+        state.reset();
+        assert_eq!((0..4).map(|_| std_rng.next_u64()).collect::<Vec<_>>(), draws);
+    }
+
+    #[test]
+    fn seed_states_resume_from_where_they_were_saved() {
+        let mut state = SeedState::new(4);
+        // An odd number of 32 bit draws leaves the generator part way through a word pair
+        for _ in 0..7 {
+            state.next_u32();
+        }
+
+        let saved = serde_json::to_string(&state).unwrap();
+        let mut restored: SeedState = serde_json::from_str(&saved).unwrap();
+        assert_eq!(restored.seed(), 4);
+        for _ in 0..100 {
+            assert_eq!(restored.next_u32(), state.next_u32());
+        }
+
+        global_seed(4);
+        global_rng().next_u64();
+        let saved = global_state().unwrap();
+        let expected = global_rng().next_u64();
+
+        clear_global_seed();
+        assert!(global_state().is_none());
+        set_global_state(saved);
+        assert_eq!(global_rng().next_u64(), expected);
+        clear_global_seed();
+    }
+
+    #[test]
+    fn global_seed_makes_initialisation_reproducible() {
+        let build = || {
+            let network: Network = Network::new(vec![
+                Layer::dense(2, 3, Activation::Relu, true).unwrap(),
+                Layer::dense(3, 1, Activation::Linear, true).unwrap(),
+            ]).unwrap();
+            let conv: Conv1d<f64> = Conv1d::new(1, 2, 3, 1, 0).unwrap();
+            let attention = SelfAttention::new(2, 2).unwrap();
+
+            let mut parameters: Vec<f64> = network.parameters().iter().map(|p| p.get_data()).collect();
+            parameters.extend(conv.parameters().iter().map(|p| p.get_data()));
+            parameters.extend(attention.parameters().iter().flat_map(|p| p.data()));
+            parameters
+        };
+
+        global_seed(42);
+        assert_eq!(current_seed(), Some(42));
+        let first = build();
+
+        global_seed(42);
+        assert_eq!(build(), first);
+
+        global_seed(43);
+        assert_ne!(build(), first);
+
+        clear_global_seed();
+        assert_eq!(current_seed(), None);
+        assert_ne!(global_rng().next_u64(), global_rng().next_u64());
+    }
+}
@@ -0,0 +1,514 @@
+use rand::Rng;
+use crate::network::NetworkError;
+use crate::scalar::Scalar;
+use crate::value::Value;
+
+// Projection computes W x + U h + b for every hidden unit, the pre-activation of a recurrent cell
+// given its input x and previous hidden state h.
+struct Projection<T> {
+    input_weights: Vec<Vec<Value<T>>>,
+    hidden_weights: Vec<Vec<Value<T>>>,
+    biases: Vec<Value<T>>,
+}
+
+impl<T: Scalar> Projection<T> {
+    // Weights are drawn from uniform(-k, k) with k = 1/sqrt(hidden) rather than the dense layers'
+    // uniform(-1, 1), so the recurrent sums don't saturate tanh as the hidden size grows.
+    fn new(inputs: usize, hidden: usize) -> Projection<T> {
+        let mut rng = crate::rand::global_rng();
+        let bound = 1.0 / (hidden as f64).sqrt();
+
+        let mut matrix = |columns: usize| -> Vec<Vec<Value<T>>> {
+            (0..hidden)
+                .map(|_| (0..columns).map(|_| Value::new(T::from_f64(rng.gen_range(-bound..=bound)))).collect())
+                .collect()
+        };
+
+        Projection {
+            input_weights: matrix(inputs),
+            hidden_weights: matrix(hidden),
+            biases: (0..hidden).map(|_| Value::new(T::from_f64(0.0))).collect(),
+        }
+    }
+
+    fn apply(&self, input: &[Value<T>], state: &[Value<T>]) -> Vec<Value<T>> {
+        self.input_weights
+            .iter()
+            .zip(&self.hidden_weights)
+            .zip(&self.biases)
+            .map(|((input_weights, hidden_weights), bias)| {
+                input_weights
+                    .iter()
+                    .zip(input)
+                    .chain(hidden_weights.iter().zip(state))
+                    .map(|(weight, x)| weight * x)
+                    .fold(bias.clone(), |acc, item| acc + item)
+            })
+            .collect()
+    }
+
+    fn parameters(&self) -> Vec<Value<T>> {
+        let mut parameters: Vec<Value<T>> = self.input_weights.concat();
+        parameters.extend(self.hidden_weights.concat());
+        parameters.extend(self.biases.iter().cloned());
+
+        parameters
+    }
+}
+
+/// RnnCell is an Elman recurrent cell, computing its next hidden state from an input and its
+/// previous state as h' = tanh(W x + U h + b).
+///
+/// Running a cell over a sequence unrolls it into a single computation graph, so calling run_grad
+/// on a loss over its states performs backpropagation through time. Setting `truncate` to k detaches
+/// the state every k steps, which bounds how far back gradients flow on long sequences.
+pub struct RnnCell<T = f64> {
+    projection: Projection<T>,
+    num_inputs: u64,
+    hidden_size: u64,
+    pub truncate: Option<usize>,
+}
+
+impl<T: Scalar> RnnCell<T> {
+    /// Creates a cell, failing if it would have no inputs or no hidden units.
+    pub fn new(num_inputs: u64, hidden_size: u64) -> Result<RnnCell<T>, NetworkError> {
+        if num_inputs == 0 || hidden_size == 0 {
+            return Err(NetworkError::EmptyLayer { inputs: num_inputs, outputs: hidden_size });
+        }
+
+        Ok(RnnCell {
+            projection: Projection::new(num_inputs as usize, hidden_size as usize),
+            num_inputs,
+            hidden_size,
+            truncate: None,
+        })
+    }
+
+    pub fn num_inputs(&self) -> u64 {
+        self.num_inputs
+    }
+
+    pub fn hidden_size(&self) -> u64 {
+        self.hidden_size
+    }
+
+    /// Returns the all-zero state sequences start from.
+    pub fn initial_state(&self) -> Vec<Value<T>> {
+        (0..self.hidden_size).map(|_| Value::new(T::from_f64(0.0))).collect()
+    }
+
+    /// Computes the hidden state following `state` after reading `input`.
+    pub fn step(&self, input: &[Value<T>], state: &[Value<T>]) -> Result<Vec<Value<T>>, NetworkError> {
+        check_length(self.num_inputs, input.len())?;
+        check_length(self.hidden_size, state.len())?;
+
+        Ok(self.projection.apply(input, state).iter().map(|x| x.tanh()).collect())
+    }
+
+    /// Runs the cell over `inputs` from the initial state, returning the hidden state after each step.
+    pub fn forward_sequence(&self, inputs: &[Vec<T>]) -> Result<Vec<Vec<Value<T>>>, NetworkError> {
+        let mut states = Vec::with_capacity(inputs.len());
+        let mut state = self.initial_state();
+
+        for (index, input) in inputs.iter().enumerate() {
+            let input: Vec<Value<T>> = input.iter().map(|x| Value::constant(*x)).collect();
+            let next = self.step(&input, &state)?;
+
+            state = if truncates(self.truncate, index) { detach(&next) } else { next.clone() };
+            states.push(next);
+        }
+
+        Ok(states)
+    }
+
+    /// Returns handles to the input weights, recurrent weights and biases, in that order.
+    pub fn parameters(&self) -> Vec<Value<T>> {
+        self.projection.parameters()
+    }
+
+    pub fn num_parameters(&self) -> usize {
+        let hidden = self.hidden_size as usize;
+
+        hidden * (self.num_inputs as usize + hidden + 1)
+    }
+}
+
+/// LstmState is the hidden state and the cell state (the long-term memory) an LstmCell carries
+/// from one step to the next.
+#[derive(Clone)]
+pub struct LstmState<T = f64> {
+    pub hidden: Vec<Value<T>>,
+    pub cell: Vec<Value<T>>,
+}
+
+impl<T: Scalar> LstmState<T> {
+    /// Returns the same state as new leaf nodes, see Value::detach.
+    pub fn detach(&self) -> LstmState<T> {
+        LstmState {
+            hidden: detach(&self.hidden),
+            cell: detach(&self.cell),
+        }
+    }
+}
+
+/// LstmCell is a long short-term memory cell. Sigmoid input, forget and output gates decide what
+/// is written to, kept in and read from its cell state:
+///
+/// ```text
+/// i = σ(W_i x + U_i h + b_i)    f = σ(W_f x + U_f h + b_f)    o = σ(W_o x + U_o h + b_o)
+/// g = tanh(W_g x + U_g h + b_g)
+/// c' = f * c + i * g
+/// h' = o * tanh(c')
+/// ```
+///
+/// The forget gate's biases start at 1, so the cell initially keeps its memory. `truncate` works
+/// as for RnnCell.
+pub struct LstmCell<T = f64> {
+    input_gate: Projection<T>,
+    forget_gate: Projection<T>,
+    candidate: Projection<T>,
+    output_gate: Projection<T>,
+    num_inputs: u64,
+    hidden_size: u64,
+    pub truncate: Option<usize>,
+}
+
+impl<T: Scalar> LstmCell<T> {
+    /// Creates a cell, failing if it would have no inputs or no hidden units.
+    pub fn new(num_inputs: u64, hidden_size: u64) -> Result<LstmCell<T>, NetworkError> {
+        if num_inputs == 0 || hidden_size == 0 {
+            return Err(NetworkError::EmptyLayer { inputs: num_inputs, outputs: hidden_size });
+        }
+
+        let (inputs, hidden) = (num_inputs as usize, hidden_size as usize);
+        let forget_gate = Projection::new(inputs, hidden);
+        for bias in &forget_gate.biases {
+            bias.set_data(T::from_f64(1.0));
+        }
+
+        Ok(LstmCell {
+            input_gate: Projection::new(inputs, hidden),
+            forget_gate,
+            candidate: Projection::new(inputs, hidden),
+            output_gate: Projection::new(inputs, hidden),
+            num_inputs,
+            hidden_size,
+            truncate: None,
+        })
+    }
+
+    pub fn num_inputs(&self) -> u64 {
+        self.num_inputs
+    }
+
+    pub fn hidden_size(&self) -> u64 {
+        self.hidden_size
+    }
+
+    /// Returns the all-zero hidden and cell state sequences start from.
+    pub fn initial_state(&self) -> LstmState<T> {
+        let zeros = || (0..self.hidden_size).map(|_| Value::new(T::from_f64(0.0))).collect();
+
+        LstmState { hidden: zeros(), cell: zeros() }
+    }
+
+    /// Computes the state following `state` after reading `input`.
+    pub fn step(&self, input: &[Value<T>], state: &LstmState<T>) -> Result<LstmState<T>, NetworkError> {
+        check_length(self.num_inputs, input.len())?;
+        check_length(self.hidden_size, state.hidden.len())?;
+        check_length(self.hidden_size, state.cell.len())?;
+
+        let gate = |projection: &Projection<T>| -> Vec<Value<T>> {
+            projection.apply(input, &state.hidden).iter().map(|x| x.sigmoid()).collect()
+        };
+        let (input_gate, forget_gate, output_gate) = (gate(&self.input_gate), gate(&self.forget_gate), gate(&self.output_gate));
+        let candidate: Vec<Value<T>> = self.candidate.apply(input, &state.hidden).iter().map(|x| x.tanh()).collect();
+
+        let cell: Vec<Value<T>> = (0..self.hidden_size as usize)
+            .map(|j| &(&forget_gate[j] * &state.cell[j]) + &(&input_gate[j] * &candidate[j]))
+            .collect();
+        let hidden = output_gate.iter().zip(&cell).map(|(o, c)| o * &c.tanh()).collect();
+
+        Ok(LstmState { hidden, cell })
+    }
+
+    /// Runs the cell over `inputs` from the initial state, returning the hidden state after each step.
+    pub fn forward_sequence(&self, inputs: &[Vec<T>]) -> Result<Vec<Vec<Value<T>>>, NetworkError> {
+        let mut states = Vec::with_capacity(inputs.len());
+        let mut state = self.initial_state();
+
+        for (index, input) in inputs.iter().enumerate() {
+            let input: Vec<Value<T>> = input.iter().map(|x| Value::constant(*x)).collect();
+            let next = self.step(&input, &state)?;
+
+            states.push(next.hidden.clone());
+            state = if truncates(self.truncate, index) { next.detach() } else { next };
+        }
+
+        Ok(states)
+    }
+
+    /// Returns handles to the parameters of the input gate, forget gate, candidate and output gate,
+    /// in that order.
+    pub fn parameters(&self) -> Vec<Value<T>> {
+        [&self.input_gate, &self.forget_gate, &self.candidate, &self.output_gate]
+            .iter()
+            .flat_map(|projection| projection.parameters())
+            .collect()
+    }
+
+    pub fn num_parameters(&self) -> usize {
+        let hidden = self.hidden_size as usize;
+
+        4 * hidden * (self.num_inputs as usize + hidden + 1)
+    }
+}
+
+/// GruCell is a gated recurrent unit, a lighter alternative to the LSTM without a separate cell state:
+///
+/// ```text
+/// z = σ(W_z x + U_z h + b_z)    r = σ(W_r x + U_r h + b_r)
+/// n = tanh(W_n x + U_n (r * h) + b_n)
+/// h' = (1 - z) * n + z * h
+/// ```
+///
+/// The reset gate is applied before the recurrent weights, as in the original formulation.
+/// `truncate` works as for RnnCell.
+pub struct GruCell<T = f64> {
+    update_gate: Projection<T>,
+    reset_gate: Projection<T>,
+    candidate: Projection<T>,
+    num_inputs: u64,
+    hidden_size: u64,
+    pub truncate: Option<usize>,
+}
+
+impl<T: Scalar> GruCell<T> {
+    /// Creates a cell, failing if it would have no inputs or no hidden units.
+    pub fn new(num_inputs: u64, hidden_size: u64) -> Result<GruCell<T>, NetworkError> {
+        if num_inputs == 0 || hidden_size == 0 {
+            return Err(NetworkError::EmptyLayer { inputs: num_inputs, outputs: hidden_size });
+        }
+
+        let (inputs, hidden) = (num_inputs as usize, hidden_size as usize);
+
+        Ok(GruCell {
+            update_gate: Projection::new(inputs, hidden),
+            reset_gate: Projection::new(inputs, hidden),
+            candidate: Projection::new(inputs, hidden),
+            num_inputs,
+            hidden_size,
+            truncate: None,
+        })
+    }
+
+    pub fn num_inputs(&self) -> u64 {
+        self.num_inputs
+    }
+
+    pub fn hidden_size(&self) -> u64 {
+        self.hidden_size
+    }
+
+    /// Returns the all-zero state sequences start from.
+    pub fn initial_state(&self) -> Vec<Value<T>> {
+        (0..self.hidden_size).map(|_| Value::new(T::from_f64(0.0))).collect()
+    }
+
+    /// Computes the hidden state following `state` after reading `input`.
+    pub fn step(&self, input: &[Value<T>], state: &[Value<T>]) -> Result<Vec<Value<T>>, NetworkError> {
+        check_length(self.num_inputs, input.len())?;
+        check_length(self.hidden_size, state.len())?;
+
+        let update_gate: Vec<Value<T>> = self.update_gate.apply(input, state).iter().map(|x| x.sigmoid()).collect();
+        let reset_gate: Vec<Value<T>> = self.reset_gate.apply(input, state).iter().map(|x| x.sigmoid()).collect();
+
+        let reset_state: Vec<Value<T>> = reset_gate.iter().zip(state).map(|(r, h)| r * h).collect();
+        let candidate: Vec<Value<T>> = self.candidate.apply(input, &reset_state).iter().map(|x| x.tanh()).collect();
+
+        // (1 - z) * n + z * h, written as n + z * (h - n)
+        Ok((0..self.hidden_size as usize)
+            .map(|j| &candidate[j] + &(&update_gate[j] * &(&state[j] - &candidate[j])))
+            .collect())
+    }
+
+    /// Runs the cell over `inputs` from the initial state, returning the hidden state after each step.
+    pub fn forward_sequence(&self, inputs: &[Vec<T>]) -> Result<Vec<Vec<Value<T>>>, NetworkError> {
+        let mut states = Vec::with_capacity(inputs.len());
+        let mut state = self.initial_state();
+
+        for (index, input) in inputs.iter().enumerate() {
+            let input: Vec<Value<T>> = input.iter().map(|x| Value::constant(*x)).collect();
+            let next = self.step(&input, &state)?;
+
+            state = if truncates(self.truncate, index) { detach(&next) } else { next.clone() };
+            states.push(next);
+        }
+
+        Ok(states)
+    }
+
+    /// Returns handles to the parameters of the update gate, reset gate and candidate, in that order.
+    pub fn parameters(&self) -> Vec<Value<T>> {
+        [&self.update_gate, &self.reset_gate, &self.candidate]
+            .iter()
+            .flat_map(|projection| projection.parameters())
+            .collect()
+    }
+
+    pub fn num_parameters(&self) -> usize {
+        let hidden = self.hidden_size as usize;
+
+        3 * hidden * (self.num_inputs as usize + hidden + 1)
+    }
+}
+
+// Returns true when the state should be detached after the step at `index`.
+fn truncates(truncate: Option<usize>, index: usize) -> bool {
+    matches!(truncate, Some(steps) if (index + 1).is_multiple_of(steps))
+}
+
+fn detach<T: Scalar>(state: &[Value<T>]) -> Vec<Value<T>> {
+    state.iter().map(|h| h.detach()).collect()
+}
+
+fn check_length(expected: u64, found: usize) -> Result<(), NetworkError> {
+    if found != expected as usize {
+        return Err(NetworkError::DimensionMismatch { expected: expected as usize, found });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::loss::mse;
+    use crate::network::{Activation, Layer, Network, NetworkError};
+    use crate::optim::Sgd;
+    use crate::recurrent::{GruCell, LstmCell, RnnCell};
+    use crate::value::Value;
+
+    type Forward<'a> = &'a dyn Fn(&[Vec<f64>]) -> Vec<Vec<Value<f64>>>;
+
+    fn sequence() -> Vec<Vec<f64>> {
+        vec![vec![0.5, -1.0], vec![0.25, 0.75], vec![-0.5, 0.1]]
+    }
+
+    fn last_state_sum(forward: Forward) -> Value<f64> {
+        let states = forward(&sequence());
+
+        states[states.len() - 1].iter().fold(Value::new(0.0), |acc, h| acc + h.clone())
+    }
+
+    // Compares the gradients of the sum of the last state with central differences.
+    fn check_gradients(forward: Forward, parameters: &[Value<f64>]) {
+        last_state_sum(forward).run_grad();
+
+        let h = 1e-6;
+        for parameter in parameters {
+            let original = parameter.get_data();
+
+            parameter.set_data(original + h);
+            let plus = last_state_sum(forward).get_data();
+            parameter.set_data(original - h);
+            let minus = last_state_sum(forward).get_data();
+            parameter.set_data(original);
+
+            let numerical = (plus - minus) / (2.0 * h);
+            assert!((parameter.get_gradient() - numerical).abs() < 1e-6, "{} vs {}", parameter.get_gradient(), numerical);
+        }
+    }
+
+    // Trains the cell and a linear readout to output the first element of sequences of three ±1
+    // inputs, which the cell has to carry for two steps, returning the mean loss of the last epoch.
+    fn train_to_remember_first_input(forward: Forward, mut parameters: Vec<Value<f64>>, hidden: u64, epochs: usize) -> f64 {
+        let readout: Network = Network::new(vec![Layer::dense(hidden, 1, Activation::Linear, true).unwrap()]).unwrap();
+        parameters.extend(readout.parameters());
+
+        let sequences: Vec<Vec<Vec<f64>>> = (0..8)
+            .map(|i| (0..3).map(|step| vec![if (i >> step) & 1 == 1 { 1.0 } else { -1.0 }]).collect())
+            .collect();
+        let optimizer = Sgd::new(0.1);
+
+        let mut total = 0.0;
+        for _ in 0..epochs {
+            total = 0.0;
+            for sequence in &sequences {
+                optimizer.zero_grad(&parameters);
+
+                let states = forward(sequence);
+                let output = readout.forward_graph(&states[states.len() - 1]).unwrap();
+                let loss = mse(&output, &sequence[0]);
+                loss.run_grad();
+                optimizer.step(&parameters);
+
+                total += loss.get_data();
+            }
+        }
+
+        total / sequences.len() as f64
+    }
+
+    #[test]
+    fn bptt_matches_numerical_gradients() {
+        let cell: RnnCell = RnnCell::new(2, 3).unwrap();
+        assert_eq!(cell.parameters().len(), cell.num_parameters());
+
+        check_gradients(&|inputs| cell.forward_sequence(inputs).unwrap(), &cell.parameters());
+    }
+
+    #[test]
+    fn truncation_stops_gradients_at_the_boundary() {
+        let mut cell: RnnCell = RnnCell::new(2, 3).unwrap();
+
+        let states = cell.forward_sequence(&sequence()).unwrap();
+        states[2][0].run_grad();
+        assert!(states[0].iter().any(|h| h.get_gradient() != 0.0));
+
+        cell.truncate = Some(2);
+        let states = cell.forward_sequence(&sequence()).unwrap();
+        states[2][0].run_grad();
+        assert!(states[0].iter().chain(&states[1]).all(|h| h.get_gradient() == 0.0));
+    }
+
+    #[test]
+    fn step_rejects_mismatched_inputs() {
+        let cell: RnnCell = RnnCell::new(2, 3).unwrap();
+
+        let input = vec![Value::new(1.0)];
+        assert_eq!(cell.step(&input, &cell.initial_state()).err(), Some(NetworkError::DimensionMismatch { expected: 2, found: 1 }));
+        assert!(RnnCell::<f64>::new(0, 3).is_err());
+        assert!(LstmCell::<f64>::new(2, 0).is_err());
+        assert!(GruCell::<f64>::new(0, 0).is_err());
+    }
+
+    #[test]
+    fn rnn_remembers_the_first_input() {
+        let cell: RnnCell = RnnCell::new(1, 4).unwrap();
+
+        let loss = train_to_remember_first_input(&|inputs| cell.forward_sequence(inputs).unwrap(), cell.parameters(), 4, 150);
+        assert!(loss < 0.01, "loss {}", loss);
+    }
+
+    #[test]
+    fn lstm_gradients_and_memory() {
+        let cell: LstmCell = LstmCell::new(2, 3).unwrap();
+        assert_eq!(cell.parameters().len(), cell.num_parameters());
+        check_gradients(&|inputs| cell.forward_sequence(inputs).unwrap(), &cell.parameters());
+
+        let cell: LstmCell = LstmCell::new(1, 3).unwrap();
+        let loss = train_to_remember_first_input(&|inputs| cell.forward_sequence(inputs).unwrap(), cell.parameters(), 3, 80);
+        assert!(loss < 0.1, "loss {}", loss);
+    }
+
+    #[test]
+    fn gru_gradients_and_memory() {
+        let cell: GruCell = GruCell::new(2, 3).unwrap();
+        assert_eq!(cell.parameters().len(), cell.num_parameters());
+        check_gradients(&|inputs| cell.forward_sequence(inputs).unwrap(), &cell.parameters());
+
+        let cell: GruCell = GruCell::new(1, 3).unwrap();
+        let loss = train_to_remember_first_input(&|inputs| cell.forward_sequence(inputs).unwrap(), cell.parameters(), 3, 80);
+        assert!(loss < 0.1, "loss {}", loss);
+    }
+}
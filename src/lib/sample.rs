@@ -0,0 +1,85 @@
+use rand::Rng;
+use crate::inference::softmax;
+
+/// softmax_sample draws an index from the softmax of `logits / temperature`, e.g. the next
+/// character of a character-level model. Pass a seeded rng (such as rand's StdRng) to make a run
+/// reproducible.
+///
+/// Temperatures below 1 sharpen the distribution towards the largest logit and temperatures
+/// above 1 flatten it; a temperature of 0 always picks the largest logit. With `top_k`, only the
+/// k largest logits can be drawn, which cuts off the long tail of unlikely outputs.
+///
+/// Panics if there are no logits, the temperature is negative or NaN, or `top_k` is 0.
+pub fn softmax_sample<R: Rng + ?Sized>(logits: &[f64], temperature: f64, top_k: Option<usize>, rng: &mut R) -> usize {
+    assert!(!logits.is_empty(), "sampling needs at least one logit");
+    assert!(temperature >= 0.0, "temperature must be non-negative, got {}", temperature);
+    assert!(top_k != Some(0), "top_k must keep at least one logit");
+
+    // Candidates in order of decreasing logit, ties in index order
+    let mut candidates: Vec<usize> = (0..logits.len()).collect();
+    candidates.sort_by(|&a, &b| logits[b].total_cmp(&logits[a]));
+    if temperature == 0.0 {
+        return candidates[0];
+    }
+    candidates.truncate(top_k.unwrap_or(logits.len()));
+
+    let scaled: Vec<f64> = candidates.iter().map(|&index| logits[index] / temperature).collect();
+    let probabilities = softmax(&scaled);
+
+    let mut threshold: f64 = rng.gen();
+    for (index, probability) in candidates.iter().zip(&probabilities) {
+        if threshold < *probability {
+            return *index;
+        }
+        threshold -= probability;
+    }
+
+    // Rounding can leave the threshold just above the total probability
+    candidates[candidates.len() - 1]
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use crate::sample::softmax_sample;
+
+    #[test]
+    fn sampling_follows_temperature_and_top_k() {
+        let logits = [1.0, 3.0, 2.0, -1.0];
+        let mut rng = StdRng::seed_from_u64(42);
+
+        assert_eq!(softmax_sample(&logits, 0.0, None, &mut rng), 1);
+        assert_eq!(softmax_sample(&logits, 5.0, Some(1), &mut rng), 1);
+
+        let counts = |temperature: f64, top_k: Option<usize>, rng: &mut StdRng| {
+            let mut counts = [0; 4];
+            for _ in 0..4000 {
+                counts[softmax_sample(&logits, temperature, top_k, rng)] += 1;
+            }
+            counts
+        };
+
+        // P = softmax(logits) is about [0.09, 0.66, 0.24, 0.01]
+        let plain = counts(1.0, None, &mut rng);
+        assert!((2450..2850).contains(&plain[1]) && (800..1100).contains(&plain[2]));
+
+        let sharp = counts(0.25, None, &mut rng);
+        assert!(sharp[1] > 3800);
+
+        let top_two = counts(1.0, Some(2), &mut rng);
+        assert_eq!((top_two[0], top_two[3]), (0, 0));
+    }
+
+    #[test]
+    fn seeded_rngs_sample_reproducibly() {
+        let logits = [0.5, 0.1, 0.9, 0.3, 0.7];
+        let run = |seed: u64| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            (0..20).map(|_| softmax_sample(&logits, 1.5, Some(4), &mut rng)).collect::<Vec<_>>()
+        };
+
+        assert_eq!(run(7), run(7));
+        assert_ne!(run(7), run(8));
+    }
+}
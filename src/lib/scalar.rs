@@ -0,0 +1,34 @@
+use core::fmt;
+use core::ops::{Add, Sub, Mul, Div};
+
+/// Scalar is the numeric type a Value can hold.
+/// Gradients are always accumulated as f64, so a scalar only needs to convert to and from f64
+/// on top of the usual arithmetic operations.
+pub trait Scalar:
+    'static + Copy + PartialOrd + fmt::Display + fmt::Debug
+    + Add<Output = Self> + Sub<Output = Self> + Mul<Output = Self> + Div<Output = Self>
+{
+    fn from_f64(value: f64) -> Self;
+
+    fn to_f64(self) -> f64;
+}
+
+impl Scalar for f64 {
+    fn from_f64(value: f64) -> Self {
+        value
+    }
+
+    fn to_f64(self) -> f64 {
+        self
+    }
+}
+
+impl Scalar for f32 {
+    fn from_f64(value: f64) -> Self {
+        value as f32
+    }
+
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+}
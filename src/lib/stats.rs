@@ -0,0 +1,131 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashSet;
+use std::rc::{Rc, Weak};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use crate::scalar::Scalar;
+use crate::value::{InnerValue, Value, build_topological_graph};
+
+// LIVE_NODES counts the nodes alive across all threads
+static LIVE_NODES: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    // Nodes alive on this thread. Values aren't Send, so each node is created and dropped on the same thread
+    static THREAD_LIVE_NODES: Cell<usize> = const { Cell::new(0) };
+}
+
+pub(crate) fn node_created() {
+    LIVE_NODES.fetch_add(1, Ordering::Relaxed);
+    let _ = THREAD_LIVE_NODES.try_with(|count| count.set(count.get() + 1));
+}
+
+pub(crate) fn node_dropped() {
+    LIVE_NODES.fetch_sub(1, Ordering::Relaxed);
+
+    // Nodes held by other thread locals may be dropped after this one is destroyed
+    let _ = THREAD_LIVE_NODES.try_with(|count| count.set(count.get().saturating_sub(1)));
+}
+
+/// Returns the number of computation graph nodes currently alive in the process. A count which
+/// keeps growing across training steps means graphs from earlier steps are being kept alive.
+pub fn live_nodes() -> usize {
+    LIVE_NODES.load(Ordering::Relaxed)
+}
+
+/// Returns the number of nodes alive on the current thread, which unlike live_nodes isn't
+/// affected by graphs other threads build at the same time.
+pub fn thread_live_nodes() -> usize {
+    THREAD_LIVE_NODES.with(|count| count.get())
+}
+
+/// Drops `value` and returns the ids of the nodes of its graph which are still alive afterwards,
+/// i.e. those held by other handles (such as clones or a Network's parameters) or by a cycle.
+///
+/// The graph is tracked through weak references, so the check itself keeps nothing alive.
+pub fn retained_nodes<T: Scalar>(value: Value<T>) -> Vec<String> {
+    let nodes: Vec<Weak<RefCell<InnerValue<T>>>> = build_topological_graph(&value).iter().map(Rc::downgrade).collect();
+    drop(value);
+
+    nodes.iter().filter_map(Weak::upgrade).map(|node| node.borrow().id.clone()).collect()
+}
+
+/// Returns the ids along a cycle in the graph of `value`, starting and ending with the same node,
+/// or None if it has no cycles. Graphs built by the Value operations can't have cycles, but
+/// editing a node's ancestors by hand can create one, which leaks every node on it.
+pub fn find_cycle<T: Scalar>(value: &Value<T>) -> Option<Vec<String>> {
+    let mut done = HashSet::new();
+    let mut path = Vec::new();
+
+    find_cycle_from(value, &mut path, &mut done)
+}
+
+// find_cycle_from walks the ancestors of `node` depth first, with `path` holding the nodes being
+// visited: reaching one of them again closes a cycle
+fn find_cycle_from<T>(node: &Rc<RefCell<InnerValue<T>>>, path: &mut Vec<String>, done: &mut HashSet<String>) -> Option<Vec<String>> {
+    let id = node.borrow().id.clone();
+    if let Some(start) = path.iter().position(|visiting| *visiting == id) {
+        let mut cycle = path[start..].to_vec();
+        cycle.push(id);
+
+        return Some(cycle);
+    }
+    if done.contains(&id) {
+        return None;
+    }
+
+    path.push(id);
+    for ancestor in node.borrow().ancestors.iter() {
+        if let Some(cycle) = find_cycle_from(ancestor, path, done) {
+            return Some(cycle);
+        }
+    }
+    done.insert(path.pop().unwrap());
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+    use crate::stats::{find_cycle, retained_nodes, thread_live_nodes};
+    use crate::value::Value;
+
+    #[test]
+    fn counts_live_nodes() {
+        let before = thread_live_nodes();
+
+        let x = Value::new(2.0);
+        let y = &(&x * &x) + &Value::new(1.0);
+        assert_eq!(thread_live_nodes(), before + 4);
+
+        let copy = y.clone();
+        drop(y);
+        assert_eq!(thread_live_nodes(), before + 4);
+
+        drop(copy);
+        assert_eq!(thread_live_nodes(), before + 1);
+        drop(x);
+        assert_eq!(thread_live_nodes(), before);
+    }
+
+    #[test]
+    fn finds_retained_nodes_and_cycles() {
+        let x = Value::new_with_id(2.0, "x");
+        let y = &(&x * &Value::new(3.0)) + &Value::new(1.0);
+        assert!(find_cycle(&y).is_none());
+        assert_eq!(retained_nodes(y), vec!["x".to_string()]);
+
+        let a = Value::new_with_id(1.0, "a");
+        let b = &a * &Value::new(2.0);
+        b.borrow_mut().id = "b".to_string();
+        a.borrow_mut().ancestors.push(Rc::clone(&b));
+
+        assert_eq!(find_cycle(&b), Some(vec!["b".to_string(), "a".to_string(), "b".to_string()]));
+
+        // Breaking the cycle lets both nodes go
+        let before = thread_live_nodes();
+        a.borrow_mut().ancestors.clear();
+        drop(a);
+        assert_eq!(retained_nodes(b), Vec::<String>::new());
+        assert_eq!(thread_live_nodes(), before - 3);
+    }
+}
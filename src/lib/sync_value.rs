@@ -0,0 +1,297 @@
+use std::ops::{Add, Sub, Mul, Div, Deref};
+use std::fmt;
+use std::sync::{Arc, RwLock};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::collections::HashSet;
+use crate::scalar::Scalar;
+use crate::value::{Value, ValueOp};
+
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// SyncInnerValue is the thread-safe counterpart of InnerValue.
+/// Ancestors are shared through Arc<RwLock<_>> so graphs can be built and differentiated from several threads.
+#[derive(Debug)]
+pub struct SyncInnerValue<T> {
+    pub data: T,
+    pub ancestors: Vec<Arc<RwLock<SyncInnerValue<T>>>>,
+    pub gradient: f64,
+    pub operation: ValueOp,
+    pub id: String,
+}
+
+/// SyncValue is a thread-safe variant of Value which is Send and Sync.
+/// It supports the same auto-differentiable operations, at the cost of locking on every access.
+///
+/// A typical use is evaluating the samples of a batch concurrently: parameters are shared between
+/// threads as SyncValues, each thread builds and differentiates its own graph, and the backward passes
+/// accumulate into the shared parameters' gradients.
+#[derive(Debug, Clone)]
+pub struct SyncValue<T>(Arc<RwLock<SyncInnerValue<T>>>);
+
+impl<T> Deref for SyncValue<T> {
+    type Target = Arc<RwLock<SyncInnerValue<T>>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for SyncValue<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let inner = self.read().unwrap();
+
+        write!(f, "id={} data={} grad={} op={}", inner.id, inner.data, inner.gradient, inner.operation.to_str())
+    }
+}
+
+impl<T: Scalar + Send + Sync> SyncValue<T> {
+    pub fn new(data: T) -> SyncValue<T> {
+        let id = format!("syncvalueid_{}", NEXT_ID.fetch_add(1, Ordering::Relaxed));
+
+        SyncValue(Arc::new(RwLock::new(SyncInnerValue {
+            id,
+            data,
+            gradient: 0.0,
+            ancestors: vec![],
+            operation: ValueOp::None,
+        })))
+    }
+
+    /// Creates a thread-safe leaf holding a copy of the value's data, e.g. to share network parameters between threads.
+    pub fn from_value(value: &Value<T>) -> SyncValue<T> {
+        SyncValue::new(value.get_data())
+    }
+
+    /// Adds the gradient accumulated on this node into the gradient of a Value, merging the result of
+    /// concurrent backward passes back into the single threaded graph. Frozen values and constants
+    /// are left unchanged, as a backward pass through the Value itself would leave them.
+    pub fn accumulate_into(&self, value: &Value<T>) {
+        if !value.requires_grad() || value.is_constant() {
+            return;
+        }
+
+        value.set_gradient(value.get_gradient() + self.get_gradient());
+    }
+
+    fn from_operation(data: T, ancestors: Vec<Arc<RwLock<SyncInnerValue<T>>>>, operation: ValueOp) -> SyncValue<T> {
+        let value = SyncValue::new(data);
+        {
+            let mut inner = value.write().unwrap();
+            inner.ancestors = ancestors;
+            inner.operation = operation;
+        }
+
+        value
+    }
+
+    pub fn get_data(&self) -> T {
+        self.read().unwrap().data
+    }
+
+    pub fn set_data(&self, data: T) {
+        self.write().unwrap().data = data;
+    }
+
+    pub fn get_gradient(&self) -> f64 {
+        self.read().unwrap().gradient
+    }
+
+    pub fn set_gradient(&self, gradient: f64) {
+        self.write().unwrap().gradient = gradient;
+    }
+
+    pub fn get_id(&self) -> String {
+        self.read().unwrap().id.clone()
+    }
+
+    /// relu applies the rectified linear unit max(0, x) to the value.
+    pub fn relu(&self) -> SyncValue<T> {
+        let data = self.get_data().to_f64();
+
+        SyncValue::from_operation(T::from_f64(data.max(0.0)), vec![Arc::clone(self)], ValueOp::Relu)
+    }
+
+    /// leaky_relu applies x for positive x and alpha * x otherwise, see Value::leaky_relu.
+    pub fn leaky_relu(&self, alpha: f64) -> SyncValue<T> {
+        let data = self.get_data().to_f64();
+        let slope = SyncValue::new(T::from_f64(alpha));
+
+        SyncValue::from_operation(
+            T::from_f64(if data > 0.0 { data } else { alpha * data }),
+            vec![Arc::clone(self), slope.0],
+            ValueOp::LeakyRelu,
+        )
+    }
+
+    /// softplus applies ln(1 + e^x), see Value::softplus.
+    pub fn softplus(&self) -> SyncValue<T> {
+        self.unary(ValueOp::Softplus)
+    }
+
+    /// gelu applies the tanh approximation of the Gaussian error linear unit, see Value::gelu.
+    pub fn gelu(&self) -> SyncValue<T> {
+        self.unary(ValueOp::Gelu)
+    }
+
+    /// elu applies the exponential linear unit, see Value::elu.
+    pub fn elu(&self) -> SyncValue<T> {
+        self.unary(ValueOp::Elu)
+    }
+
+    /// swish applies x * sigmoid(x), see Value::swish.
+    pub fn swish(&self) -> SyncValue<T> {
+        self.unary(ValueOp::Swish)
+    }
+
+    fn unary(&self, operation: ValueOp) -> SyncValue<T> {
+        let data = self.get_data().to_f64();
+
+        SyncValue::from_operation(T::from_f64(operation.apply_unary(data)), vec![Arc::clone(self)], operation)
+    }
+
+    // backward propagates this node's gradient to its ancestors, using the same rules as Value::backward.
+    fn backward(&self) {
+        let val = self.read().unwrap();
+        let gradient = val.gradient;
+
+        let data = |node: &Arc<RwLock<SyncInnerValue<T>>>| -> f64 { node.read().unwrap().data.to_f64() };
+        let accumulate = |node: &Arc<RwLock<SyncInnerValue<T>>>, delta: f64| node.write().unwrap().gradient += delta;
+
+        match val.operation {
+            ValueOp::Addition => {
+                accumulate(&val.ancestors[0], gradient);
+                accumulate(&val.ancestors[1], gradient);
+            }
+            ValueOp::Subtraction => {
+                accumulate(&val.ancestors[0], gradient);
+                accumulate(&val.ancestors[1], -gradient);
+            }
+            ValueOp::Multiplication => {
+                let left_data = data(&val.ancestors[0]);
+                let right_data = data(&val.ancestors[1]);
+
+                accumulate(&val.ancestors[0], right_data * gradient);
+                accumulate(&val.ancestors[1], left_data * gradient);
+            }
+            ValueOp::Division => {
+                let left_data = data(&val.ancestors[0]);
+                let right_data = data(&val.ancestors[1]);
+
+                accumulate(&val.ancestors[0], (1.0 / right_data) * gradient);
+                accumulate(&val.ancestors[1], -(left_data / (right_data * right_data)) * gradient);
+            }
+            // The gradient only flows through inputs which were positive in the forward pass
+            ValueOp::Relu if data(&val.ancestors[0]) > 0.0 => {
+                accumulate(&val.ancestors[0], gradient);
+            }
+            ValueOp::LeakyRelu => {
+                let slope = if data(&val.ancestors[0]) > 0.0 { 1.0 } else { data(&val.ancestors[1]) };
+                accumulate(&val.ancestors[0], slope * gradient);
+            }
+            operation if operation.is_unary() => {
+                accumulate(&val.ancestors[0], operation.unary_derivative(data(&val.ancestors[0])) * gradient);
+            }
+            _ => ()
+        }
+    }
+
+    /// run_grad performs the backward pass from this node, see Value::run_grad.
+    pub fn run_grad(&self) {
+        self.set_gradient(1.0);
+
+        let mut seen = HashSet::new();
+        let mut topological_graph = vec![];
+        order_nodes_topologically(self, &mut seen, &mut topological_graph);
+
+        for node in topological_graph.iter().rev() {
+            node.backward();
+        }
+    }
+}
+
+// Nodes are identified by their allocation, so ids don't need to be unique across threads.
+fn order_nodes_topologically<T>(value: &SyncValue<T>, seen: &mut HashSet<usize>, nodes: &mut Vec<SyncValue<T>>) {
+    if !seen.insert(Arc::as_ptr(value) as usize) {
+        return;
+    }
+
+    let ancestors: Vec<_> = value.read().unwrap().ancestors.iter().map(|ancestor| SyncValue(Arc::clone(ancestor))).collect();
+    for ancestor in ancestors.iter() {
+        order_nodes_topologically(ancestor, seen, nodes);
+    }
+
+    nodes.push(SyncValue(Arc::clone(value)));
+}
+
+macro_rules! impl_sync_op {
+    ($trait:ident, $method:ident, $op:tt, $value_op:expr) => {
+        impl<T: Scalar + Send + Sync> $trait for &SyncValue<T> {
+            type Output = SyncValue<T>;
+
+            fn $method(self, rhs: Self) -> Self::Output {
+                let result = self.get_data() $op rhs.get_data();
+
+                SyncValue::from_operation(result, vec![Arc::clone(self), Arc::clone(rhs)], $value_op)
+            }
+        }
+
+        impl<T: Scalar + Send + Sync> $trait for SyncValue<T> {
+            type Output = SyncValue<T>;
+
+            fn $method(self, rhs: Self) -> Self::Output {
+                &self $op &rhs
+            }
+        }
+    };
+}
+
+impl_sync_op!(Add, add, +, ValueOp::Addition);
+impl_sync_op!(Sub, sub, -, ValueOp::Subtraction);
+impl_sync_op!(Mul, mul, *, ValueOp::Multiplication);
+impl_sync_op!(Div, div, /, ValueOp::Division);
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use crate::sync_value::SyncValue;
+    use crate::value::Value;
+
+    #[test]
+    fn chained_operations_match_value() {
+        let a = &SyncValue::new(2.0);
+        let b = &SyncValue::new(3.0);
+
+        let c = a + b;
+        let d = &c * &(b - a);
+        let z = &d / b;
+
+        z.run_grad();
+
+        assert_eq!(z.get_data(), 5.0 / 3.0);
+        assert_eq!((a.get_gradient() * 100.0).round() / 100.0, -1.33);
+        assert_eq!((b.get_gradient() * 100.0).round() / 100.0, 1.44);
+    }
+
+    #[test]
+    fn concurrent_backward_passes_merge_gradients() {
+        let weight = Value::new(0.5);
+        let shared_weight = SyncValue::from_value(&weight);
+
+        let batch = vec![1.0, 2.0, 3.0, 4.0];
+
+        thread::scope(|scope| {
+            for x in &batch {
+                let w = shared_weight.clone();
+                scope.spawn(move || {
+                    let y = (&w * &SyncValue::new(*x)).relu();
+                    y.run_grad();
+                });
+            }
+        });
+
+        assert_eq!(shared_weight.get_gradient(), 10.0);
+
+        shared_weight.accumulate_into(&weight);
+        assert_eq!(weight.get_gradient(), 10.0);
+    }
+}
@@ -0,0 +1,669 @@
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::fmt;
+use std::ops::Deref;
+use std::rc::Rc;
+use crate::kernels::{matmul_add, transpose};
+
+/// TensorOp is the operation which produced a tensor. Reductions remove the axis they reduce;
+/// element-wise operations broadcast their operands, see Tensor::add.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TensorOp {
+    Addition,
+    Subtraction,
+    Multiplication,
+    Division,
+    MatMul,
+    Sum { axis: usize },
+    Mean { axis: usize },
+    Max { axis: usize },
+    Exp,
+    Transpose,
+    Reshape,
+    None,
+}
+
+impl TensorOp {
+    pub fn to_str(&self) -> &'static str {
+        match self {
+            TensorOp::Addition => "+",
+            TensorOp::Subtraction => "-",
+            TensorOp::Multiplication => "*",
+            TensorOp::Division => "/",
+            TensorOp::MatMul => "matmul",
+            TensorOp::Sum { .. } => "sum",
+            TensorOp::Mean { .. } => "mean",
+            TensorOp::Max { .. } => "max",
+            TensorOp::Exp => "exp",
+            TensorOp::Transpose => "transpose",
+            TensorOp::Reshape => "reshape",
+            TensorOp::None => "none",
+        }
+    }
+}
+
+/// TensorNode is a node of a tensor computation graph: an n-dimensional array of f64 stored in
+/// row-major order, with a gradient of the same shape.
+///
+/// Where a Value graph has a node per scalar, a tensor graph has a node per array, so a batched
+/// operation is one node whose backward rule loops over the whole array.
+#[derive(Debug)]
+pub struct TensorNode {
+    pub data: Vec<f64>,
+
+    // shape lists the size of each axis, outermost first. Scalars have an empty shape.
+    pub shape: Vec<usize>,
+
+    pub gradient: Vec<f64>,
+
+    // ancestors are the tensors passed as inputs to the operation which produced this one
+    pub ancestors: Vec<Rc<RefCell<TensorNode>>>,
+
+    pub operation: TensorOp,
+}
+
+/// Tensor is a handle to a node of a tensor computation graph, see TensorNode. Cloning a tensor
+/// clones the handle, not the data.
+#[derive(Debug, Clone)]
+pub struct Tensor(Rc<RefCell<TensorNode>>);
+
+impl Deref for Tensor {
+    type Target = Rc<RefCell<TensorNode>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+/// TensorError represents data which doesn't fit a shape, or an operation which can't be applied
+/// to the shapes of its operands.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TensorError {
+    // The data doesn't hold as many elements as the shape describes.
+    ShapeMismatch { len: usize, shape: Vec<usize> },
+
+    // The axis isn't less than the rank of the tensor.
+    InvalidAxis { axis: usize, rank: usize },
+
+    // The mean or max of an axis of length zero is undefined.
+    EmptyAxis { axis: usize },
+
+    // The shapes of an element-wise operation's operands can't be broadcast together, or those of
+    // a matrix product don't line up.
+    IncompatibleShapes { left: Vec<usize>, right: Vec<usize> },
+
+    // The operation needs a tensor of another rank, e.g. transpose needs a matrix.
+    RankMismatch { expected: usize, found: usize },
+}
+
+impl fmt::Display for TensorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TensorError::ShapeMismatch { len, shape } => write!(f, "{} elements don't fit shape {:?}", len, shape),
+            TensorError::InvalidAxis { axis, rank } => write!(f, "axis {} is out of range for a tensor of rank {}", axis, rank),
+            TensorError::EmptyAxis { axis } => write!(f, "axis {} is empty", axis),
+            TensorError::IncompatibleShapes { left, right } => write!(f, "shapes {:?} and {:?} can't be broadcast together", left, right),
+            TensorError::RankMismatch { expected, found } => write!(f, "expected a tensor of rank {}, got rank {}", expected, found),
+        }
+    }
+}
+
+impl std::error::Error for TensorError {}
+
+impl Tensor {
+    /// Creates a leaf tensor holding `data` in row-major order.
+    pub fn new(data: Vec<f64>, shape: &[usize]) -> Result<Tensor, TensorError> {
+        if data.len() != shape.iter().product::<usize>() {
+            return Err(TensorError::ShapeMismatch { len: data.len(), shape: shape.to_vec() });
+        }
+
+        Ok(Tensor::from_operation(data, shape.to_vec(), vec![], TensorOp::None))
+    }
+
+    pub fn scalar(data: f64) -> Tensor {
+        Tensor::from_operation(vec![data], vec![], vec![], TensorOp::None)
+    }
+
+    pub fn zeros(shape: &[usize]) -> Tensor {
+        Tensor::from_operation(vec![0.0; shape.iter().product()], shape.to_vec(), vec![], TensorOp::None)
+    }
+
+    fn from_operation(data: Vec<f64>, shape: Vec<usize>, ancestors: Vec<Rc<RefCell<TensorNode>>>, operation: TensorOp) -> Tensor {
+        let gradient = vec![0.0; data.len()];
+
+        Tensor(Rc::new(RefCell::new(TensorNode { data, shape, gradient, ancestors, operation })))
+    }
+
+    pub fn shape(&self) -> Vec<usize> {
+        self.borrow().shape.clone()
+    }
+
+    /// Returns the number of axes.
+    pub fn rank(&self) -> usize {
+        self.borrow().shape.len()
+    }
+
+    /// Returns the number of elements.
+    pub fn len(&self) -> usize {
+        self.borrow().data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.borrow().data.is_empty()
+    }
+
+    pub fn data(&self) -> Vec<f64> {
+        self.borrow().data.clone()
+    }
+
+    pub fn gradient(&self) -> Vec<f64> {
+        self.borrow().gradient.clone()
+    }
+
+    pub fn zero_grad(&self) {
+        self.borrow_mut().gradient.fill(0.0);
+    }
+
+    /// Adds `other` element-wise, broadcasting the two shapes against each other as NumPy does:
+    /// aligned from the last axis, each pair of sizes must be equal or one of them 1, and missing
+    /// leading axes count as 1. The result has the larger size along each axis, e.g. adding a
+    /// bias of shape [outputs] to a batch of shape [batch, outputs].
+    ///
+    /// The gradient of a broadcast operand is summed over the axes it was repeated along, so it
+    /// keeps the operand's shape.
+    pub fn add(&self, other: &Tensor) -> Result<Tensor, TensorError> {
+        self.elementwise(other, TensorOp::Addition)
+    }
+
+    /// Subtracts `other` element-wise, broadcasting as add does.
+    pub fn sub(&self, other: &Tensor) -> Result<Tensor, TensorError> {
+        self.elementwise(other, TensorOp::Subtraction)
+    }
+
+    /// Multiplies by `other` element-wise, broadcasting as add does.
+    pub fn mul(&self, other: &Tensor) -> Result<Tensor, TensorError> {
+        self.elementwise(other, TensorOp::Multiplication)
+    }
+
+    /// Divides by `other` element-wise, broadcasting as add does.
+    pub fn div(&self, other: &Tensor) -> Result<Tensor, TensorError> {
+        self.elementwise(other, TensorOp::Division)
+    }
+
+    fn elementwise(&self, other: &Tensor, operation: TensorOp) -> Result<Tensor, TensorError> {
+        let (data, shape) = {
+            let (left, right) = (self.borrow(), other.borrow());
+            let shape = broadcast_shape(&left.shape, &right.shape)
+                .ok_or_else(|| TensorError::IncompatibleShapes { left: left.shape.clone(), right: right.shape.clone() })?;
+
+            let left_indices = broadcast_indices(&left.shape, &shape);
+            let right_indices = broadcast_indices(&right.shape, &shape);
+            let data = left_indices
+                .iter()
+                .zip(&right_indices)
+                .map(|(&l, &r)| apply_elementwise(operation, left.data[l], right.data[r]))
+                .collect();
+
+            (data, shape)
+        };
+
+        Ok(Tensor::from_operation(data, shape, vec![Rc::clone(self), Rc::clone(other)], operation))
+    }
+
+    /// Multiplies the matrix (`m` x `k`) by `other` (`k` x `n`), e.g. a batch of inputs by a layer's
+    /// weights. Both operands must have rank 2.
+    ///
+    /// The backward pass computes dA = dC B^T and dB = A^T dC with the same blocked kernel as the
+    /// forward pass, see kernels::matmul_add, transposing B and A once up front.
+    pub fn matmul(&self, other: &Tensor) -> Result<Tensor, TensorError> {
+        let (data, shape) = {
+            let (left, right) = (self.borrow(), other.borrow());
+            let (m, k, n) = match (left.shape.as_slice(), right.shape.as_slice()) {
+                (&[m, k], &[k2, n]) if k == k2 => (m, k, n),
+                _ => return Err(TensorError::IncompatibleShapes { left: left.shape.clone(), right: right.shape.clone() }),
+            };
+
+            let mut data = vec![0.0; m * n];
+            matmul_add(&mut data, &left.data, &right.data, m, k, n);
+
+            (data, vec![m, n])
+        };
+
+        Ok(Tensor::from_operation(data, shape, vec![Rc::clone(self), Rc::clone(other)], TensorOp::MatMul))
+    }
+
+    /// Sums the elements along `axis`, removing it from the shape.
+    pub fn sum(&self, axis: usize) -> Result<Tensor, TensorError> {
+        self.reduce(TensorOp::Sum { axis }, axis)
+    }
+
+    /// Averages the elements along `axis`, removing it from the shape.
+    pub fn mean(&self, axis: usize) -> Result<Tensor, TensorError> {
+        self.reduce(TensorOp::Mean { axis }, axis)
+    }
+
+    /// Takes the largest element along `axis`, removing it from the shape. The gradient flows to
+    /// the first largest element only.
+    pub fn max(&self, axis: usize) -> Result<Tensor, TensorError> {
+        self.reduce(TensorOp::Max { axis }, axis)
+    }
+
+    fn reduce(&self, operation: TensorOp, axis: usize) -> Result<Tensor, TensorError> {
+        let (data, shape) = {
+            let inner = self.borrow();
+            if axis >= inner.shape.len() {
+                return Err(TensorError::InvalidAxis { axis, rank: inner.shape.len() });
+            }
+
+            let (outer, len, rest) = split_at_axis(&inner.shape, axis);
+            if len == 0 && operation != (TensorOp::Sum { axis }) {
+                return Err(TensorError::EmptyAxis { axis });
+            }
+
+            let mut data = Vec::with_capacity(outer * rest);
+            for o in 0..outer {
+                for i in 0..rest {
+                    let along = (0..len).map(|k| inner.data[(o * len + k) * rest + i]);
+
+                    data.push(match operation {
+                        TensorOp::Max { .. } => along.fold(f64::NEG_INFINITY, f64::max),
+                        TensorOp::Mean { .. } => along.sum::<f64>() / len as f64,
+                        _ => along.sum(),
+                    });
+                }
+            }
+
+            let mut shape = inner.shape.clone();
+            shape.remove(axis);
+
+            (data, shape)
+        };
+
+        Ok(Tensor::from_operation(data, shape, vec![Rc::clone(self)], operation))
+    }
+
+    /// Applies e^x element-wise.
+    pub fn exp(&self) -> Tensor {
+        let (data, shape) = {
+            let inner = self.borrow();
+            (inner.data.iter().map(|x| x.exp()).collect(), inner.shape.clone())
+        };
+
+        Tensor::from_operation(data, shape, vec![Rc::clone(self)], TensorOp::Exp)
+    }
+
+    /// Returns the transpose of a matrix, e.g. to multiply by K^T without copying K by hand.
+    pub fn transpose(&self) -> Result<Tensor, TensorError> {
+        let (data, shape) = {
+            let inner = self.borrow();
+            let &[rows, cols] = inner.shape.as_slice() else {
+                return Err(TensorError::RankMismatch { expected: 2, found: inner.shape.len() });
+            };
+
+            (transpose(&inner.data, rows, cols), vec![cols, rows])
+        };
+
+        Ok(Tensor::from_operation(data, shape, vec![Rc::clone(self)], TensorOp::Transpose))
+    }
+
+    /// Returns the elements in the same row-major order with a new shape holding as many elements,
+    /// e.g. [n] to [n, 1] so a reduction broadcasts back against the tensor it reduced.
+    pub fn reshape(&self, shape: &[usize]) -> Result<Tensor, TensorError> {
+        let data = self.data();
+        if data.len() != shape.iter().product::<usize>() {
+            return Err(TensorError::ShapeMismatch { len: data.len(), shape: shape.to_vec() });
+        }
+
+        Ok(Tensor::from_operation(data, shape.to_vec(), vec![Rc::clone(self)], TensorOp::Reshape))
+    }
+
+    /// Turns the elements along `axis` into probabilities which sum to 1, as e^x_i / Σ e^x_j.
+    /// Like value::softmax, the largest element is subtracted first as a constant, so large
+    /// inputs don't overflow; elements of -inf get a probability of 0.
+    pub fn softmax(&self, axis: usize) -> Result<Tensor, TensorError> {
+        let mut kept = self.shape();
+        let largest = self.max(axis)?.data();
+        kept[axis] = 1;
+
+        let exps = self.sub(&Tensor::new(largest, &kept)?)?.exp();
+        exps.div(&exps.sum(axis)?.reshape(&kept)?)
+    }
+
+    /// Backpropagates through the graph rooted at this tensor, accumulating gradients like
+    /// Value::run_grad. A root with more than one element is seeded with ones, which gives the
+    /// gradients of the sum of its elements.
+    pub fn run_grad(&self) {
+        self.borrow_mut().gradient.fill(1.0);
+
+        let mut seen = HashSet::new();
+        let mut order = Vec::new();
+        order_topologically(self, &mut seen, &mut order);
+
+        for node in order.iter().rev() {
+            backward(&node.borrow());
+        }
+    }
+}
+
+fn apply_elementwise(operation: TensorOp, left: f64, right: f64) -> f64 {
+    match operation {
+        TensorOp::Addition => left + right,
+        TensorOp::Subtraction => left - right,
+        TensorOp::Multiplication => left * right,
+        _ => left / right,
+    }
+}
+
+// broadcast_shape returns the shape two operands broadcast to, or None if they can't be
+fn broadcast_shape(left: &[usize], right: &[usize]) -> Option<Vec<usize>> {
+    let rank = left.len().max(right.len());
+    let size = |shape: &[usize], axis: usize| if axis + shape.len() < rank { 1 } else { shape[axis + shape.len() - rank] };
+
+    (0..rank)
+        .map(|axis| match (size(left, axis), size(right, axis)) {
+            (l, r) if l == r || r == 1 => Some(l),
+            (1, r) => Some(r),
+            _ => None,
+        })
+        .collect()
+}
+
+// broadcast_indices maps each element of a tensor of shape `to` to the element of an operand of
+// shape `from` it was broadcast from
+fn broadcast_indices(from: &[usize], to: &[usize]) -> Vec<usize> {
+    // The operand's stride along each axis of `to`, zero along the axes it's repeated over
+    let offset = to.len() - from.len();
+    let mut strides = vec![0; to.len()];
+    let mut stride = 1;
+    for axis in (0..from.len()).rev() {
+        if from[axis] != 1 {
+            strides[axis + offset] = stride;
+        }
+        stride *= from[axis];
+    }
+
+    let mut indices = Vec::with_capacity(to.iter().product());
+    let mut position = vec![0; to.len()];
+    for _ in 0..to.iter().product::<usize>() {
+        indices.push(position.iter().zip(&strides).map(|(p, s)| p * s).sum());
+
+        // Advance the position like an odometer, last axis fastest
+        for axis in (0..to.len()).rev() {
+            position[axis] += 1;
+            if position[axis] < to[axis] {
+                break;
+            }
+            position[axis] = 0;
+        }
+    }
+
+    indices
+}
+
+// split_at_axis returns the number of elements before, along and after `axis`, so element k of
+// the axis at position (o, i) of the other axes is at (o * len + k) * rest + i.
+fn split_at_axis(shape: &[usize], axis: usize) -> (usize, usize, usize) {
+    (shape[..axis].iter().product(), shape[axis], shape[axis + 1..].iter().product())
+}
+
+fn order_topologically(node: &Rc<RefCell<TensorNode>>, seen: &mut HashSet<*const RefCell<TensorNode>>, order: &mut Vec<Rc<RefCell<TensorNode>>>) {
+    if !seen.insert(Rc::as_ptr(node)) {
+        return;
+    }
+
+    for ancestor in node.borrow().ancestors.iter() {
+        order_topologically(ancestor, seen, order);
+    }
+    order.push(Rc::clone(node));
+}
+
+// backward accumulates the gradients of the node's ancestors from its own gradient
+fn backward(node: &TensorNode) {
+    match node.operation {
+        TensorOp::Addition | TensorOp::Subtraction | TensorOp::Multiplication | TensorOp::Division => {
+            // Both operands may be the same tensor, so their gradients are summed up before either
+            // is borrowed mutably
+            let (left_gradient, right_gradient) = {
+                let (left, right) = (node.ancestors[0].borrow(), node.ancestors[1].borrow());
+                let left_indices = broadcast_indices(&left.shape, &node.shape);
+                let right_indices = broadcast_indices(&right.shape, &node.shape);
+
+                let mut left_gradient = vec![0.0; left.data.len()];
+                let mut right_gradient = vec![0.0; right.data.len()];
+                for (j, gradient) in node.gradient.iter().enumerate() {
+                    let (l, r) = (left_indices[j], right_indices[j]);
+                    let (a, b) = (left.data[l], right.data[r]);
+
+                    let (da, db) = match node.operation {
+                        TensorOp::Addition => (1.0, 1.0),
+                        TensorOp::Subtraction => (1.0, -1.0),
+                        TensorOp::Multiplication => (b, a),
+                        _ => (1.0 / b, -a / (b * b)),
+                    };
+                    left_gradient[l] += da * gradient;
+                    right_gradient[r] += db * gradient;
+                }
+
+                (left_gradient, right_gradient)
+            };
+
+            for (ancestor, gradient) in node.ancestors.iter().zip([left_gradient, right_gradient]) {
+                for (total, delta) in ancestor.borrow_mut().gradient.iter_mut().zip(gradient) {
+                    *total += delta;
+                }
+            }
+        }
+        TensorOp::MatMul => {
+            // As with element-wise operations, both operands may be the same tensor
+            let (left_gradient, right_gradient) = {
+                let (left, right) = (node.ancestors[0].borrow(), node.ancestors[1].borrow());
+                let (m, k, n) = (left.shape[0], left.shape[1], right.shape[1]);
+
+                let mut left_gradient = vec![0.0; m * k];
+                matmul_add(&mut left_gradient, &node.gradient, &transpose(&right.data, k, n), m, n, k);
+                let mut right_gradient = vec![0.0; k * n];
+                matmul_add(&mut right_gradient, &transpose(&left.data, m, k), &node.gradient, k, m, n);
+
+                (left_gradient, right_gradient)
+            };
+
+            for (ancestor, gradient) in node.ancestors.iter().zip([left_gradient, right_gradient]) {
+                for (total, delta) in ancestor.borrow_mut().gradient.iter_mut().zip(gradient) {
+                    *total += delta;
+                }
+            }
+        }
+        TensorOp::Sum { axis } | TensorOp::Mean { axis } | TensorOp::Max { axis } => {
+            let mut ancestor = node.ancestors[0].borrow_mut();
+            let (outer, len, rest) = split_at_axis(&ancestor.shape, axis);
+            let scale = if matches!(node.operation, TensorOp::Mean { .. }) { 1.0 / len as f64 } else { 1.0 };
+
+            for o in 0..outer {
+                for i in 0..rest {
+                    let gradient = node.gradient[o * rest + i];
+                    let index = |k: usize| (o * len + k) * rest + i;
+
+                    if let TensorOp::Max { .. } = node.operation {
+                        let selected = (0..len).find(|&k| ancestor.data[index(k)] == node.data[o * rest + i]).unwrap_or(0);
+                        ancestor.gradient[index(selected)] += gradient;
+                    } else {
+                        for k in 0..len {
+                            ancestor.gradient[index(k)] += scale * gradient;
+                        }
+                    }
+                }
+            }
+        }
+        TensorOp::Exp => {
+            let mut ancestor = node.ancestors[0].borrow_mut();
+            for ((total, gradient), y) in ancestor.gradient.iter_mut().zip(&node.gradient).zip(&node.data) {
+                *total += gradient * y;
+            }
+        }
+        TensorOp::Transpose => {
+            let mut ancestor = node.ancestors[0].borrow_mut();
+            let (rows, cols) = (node.shape[0], node.shape[1]);
+            for (total, delta) in ancestor.gradient.iter_mut().zip(transpose(&node.gradient, rows, cols)) {
+                *total += delta;
+            }
+        }
+        TensorOp::Reshape => {
+            let mut ancestor = node.ancestors[0].borrow_mut();
+            for (total, delta) in ancestor.gradient.iter_mut().zip(&node.gradient) {
+                *total += delta;
+            }
+        }
+        TensorOp::None => (),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tensor::{broadcast_shape, Tensor, TensorError};
+
+    fn matrix() -> Tensor {
+        Tensor::new(vec![1.0, 5.0, 3.0, 4.0, 2.0, 6.0], &[2, 3]).unwrap()
+    }
+
+    #[test]
+    fn reductions_remove_their_axis() {
+        let x = matrix();
+
+        let rows = x.sum(1).unwrap();
+        assert_eq!((rows.shape(), rows.data()), (vec![2], vec![9.0, 12.0]));
+
+        let columns = x.mean(0).unwrap();
+        assert_eq!((columns.shape(), columns.data()), (vec![3], vec![2.5, 3.5, 4.5]));
+
+        let largest = x.max(1).unwrap();
+        assert_eq!(largest.data(), vec![5.0, 6.0]);
+
+        let total = rows.sum(0).unwrap();
+        assert_eq!((total.shape(), total.data()), (vec![], vec![21.0]));
+
+        assert_eq!(x.sum(2).err(), Some(TensorError::InvalidAxis { axis: 2, rank: 2 }));
+        assert_eq!(Tensor::zeros(&[2, 0]).max(1).err(), Some(TensorError::EmptyAxis { axis: 1 }));
+        assert_eq!(Tensor::zeros(&[2, 0]).sum(1).unwrap().data(), vec![0.0, 0.0]);
+        assert_eq!(
+            Tensor::new(vec![1.0, 2.0], &[3]).err(),
+            Some(TensorError::ShapeMismatch { len: 2, shape: vec![3] })
+        );
+    }
+
+    #[test]
+    fn reductions_backpropagate() {
+        let x = matrix();
+
+        // The batch mean of each row's largest element
+        let loss = x.max(1).unwrap().mean(0).unwrap();
+        loss.run_grad();
+        assert_eq!(x.gradient(), vec![0.0, 0.5, 0.0, 0.0, 0.0, 0.5]);
+
+        x.zero_grad();
+        x.mean(1).unwrap().sum(0).unwrap().run_grad();
+        assert_eq!(x.gradient(), vec![1.0 / 3.0; 6]);
+
+        // Non-scalar roots are seeded with ones
+        x.zero_grad();
+        x.sum(0).unwrap().run_grad();
+        assert_eq!(x.gradient(), vec![1.0; 6]);
+    }
+
+    #[test]
+    fn elementwise_ops_broadcast() {
+        assert_eq!(broadcast_shape(&[2, 3], &[3]), Some(vec![2, 3]));
+        assert_eq!(broadcast_shape(&[4, 1, 3], &[2, 1]), Some(vec![4, 2, 3]));
+        assert_eq!(broadcast_shape(&[], &[2]), Some(vec![2]));
+        assert_eq!(broadcast_shape(&[2, 3], &[2]), None);
+
+        // A batch of two samples plus a bias per output
+        let batch = matrix();
+        let bias = Tensor::new(vec![10.0, 20.0, 30.0], &[3]).unwrap();
+        let shifted = batch.add(&bias).unwrap();
+        assert_eq!(shifted.shape(), vec![2, 3]);
+        assert_eq!(shifted.data(), vec![11.0, 25.0, 33.0, 14.0, 22.0, 36.0]);
+
+        let column = Tensor::new(vec![2.0, 4.0], &[2, 1]).unwrap();
+        assert_eq!(batch.div(&column).unwrap().data(), vec![0.5, 2.5, 1.5, 1.0, 0.5, 1.5]);
+        assert_eq!(batch.sub(&Tensor::scalar(1.0)).unwrap().data(), vec![0.0, 4.0, 2.0, 3.0, 1.0, 5.0]);
+
+        assert_eq!(
+            batch.mul(&column.sum(1).unwrap()).err(),
+            Some(TensorError::IncompatibleShapes { left: vec![2, 3], right: vec![2] })
+        );
+    }
+
+    #[test]
+    fn broadcast_gradients_are_reduced() {
+        let batch = matrix();
+        let bias = Tensor::new(vec![1.0, -1.0, 0.5], &[3]).unwrap();
+        let scale = Tensor::new(vec![2.0, 4.0], &[2, 1]).unwrap();
+
+        // sum((x + b) * s / s) - sum(x * x)
+        let scaled = batch.add(&bias).unwrap().mul(&scale).unwrap().div(&scale).unwrap();
+        let loss = scaled.sub(&batch.mul(&batch).unwrap()).unwrap().sum(1).unwrap().sum(0).unwrap();
+        loss.run_grad();
+
+        // The bias is repeated over the batch, so its gradient is summed over it
+        assert_eq!(bias.gradient(), vec![2.0, 2.0, 2.0]);
+        assert_eq!(scale.shape(), vec![2, 1]);
+        assert!(scale.gradient().iter().all(|gradient| gradient.abs() < 1e-12));
+        assert_eq!(batch.gradient(), batch.data().iter().map(|x| 1.0 - 2.0 * x).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn matmul_backpropagates_through_both_operands() {
+        // A batch of two samples with three features through a layer of two outputs
+        let x = matrix();
+        let w = Tensor::new(vec![0.5, -1.0, 2.0, 0.0, -0.5, 1.0], &[3, 2]).unwrap();
+        let b = Tensor::new(vec![1.0, -1.0], &[2]).unwrap();
+
+        let y = x.matmul(&w).unwrap().add(&b).unwrap();
+        assert_eq!(y.shape(), vec![2, 2]);
+        assert_eq!(y.data(), vec![10.0, 1.0, 4.0, 1.0]);
+
+        // With dC all ones, dW = X^T 1 holds each feature's sum, and dX = 1 W^T each weight row's sum
+        y.run_grad();
+        assert_eq!(w.gradient(), vec![5.0, 5.0, 7.0, 7.0, 9.0, 9.0]);
+        assert_eq!(x.gradient(), vec![-0.5, 2.0, 0.5, -0.5, 2.0, 0.5]);
+        assert_eq!(b.gradient(), vec![2.0, 2.0]);
+
+        // d sum(A A) / dA = 1 A^T + A^T 1 for a square matrix used twice
+        let a = Tensor::new(vec![1.0, 2.0, 3.0, 4.0], &[2, 2]).unwrap();
+        a.matmul(&a).unwrap().run_grad();
+        assert_eq!(a.gradient(), vec![7.0, 11.0, 9.0, 13.0]);
+
+        assert_eq!(
+            x.matmul(&x).err(),
+            Some(TensorError::IncompatibleShapes { left: vec![2, 3], right: vec![2, 3] })
+        );
+        assert!(x.matmul(&b).is_err());
+    }
+
+    #[test]
+    fn softmax_backpropagates_through_exp_reshape_and_transpose() {
+        // Rows of logits, the second shifted by a constant and transposed into columns first
+        let x = Tensor::new(vec![1000.0, 1000.0 + 2.0f64.ln(), 0.0, 1.0], &[2, 2]).unwrap();
+        let columns = x.transpose().unwrap();
+        assert_eq!(columns.data(), vec![1000.0, 0.0, 1000.0 + 2.0f64.ln(), 1.0]);
+
+        let probabilities = columns.softmax(0).unwrap();
+        let first = probabilities.data();
+        assert!((first[0] - 1.0 / 3.0).abs() < 1e-12 && (first[2] - 2.0 / 3.0).abs() < 1e-12);
+
+        // d p0 / d x0 = p0 (1 - p0), d p0 / d x1 = -p0 p1, through the mask of the other entries
+        let mask = Tensor::new(vec![1.0, 0.0, 0.0, 0.0], &[2, 2]).unwrap();
+        probabilities.mul(&mask).unwrap().run_grad();
+        let gradient = x.gradient();
+        assert!((gradient[0] - 2.0 / 9.0).abs() < 1e-12 && (gradient[1] + 2.0 / 9.0).abs() < 1e-12);
+        assert_eq!(&gradient[2..], &[0.0, 0.0]);
+
+        let flat = Tensor::new(vec![1.0, 2.0, 3.0], &[3]).unwrap();
+        let column = flat.reshape(&[3, 1]).unwrap();
+        column.exp().sum(0).unwrap().run_grad();
+        assert_eq!(flat.gradient(), vec![1.0f64.exp(), 2.0f64.exp(), 3.0f64.exp()]);
+
+        assert_eq!(flat.reshape(&[2, 2]).err(), Some(TensorError::ShapeMismatch { len: 3, shape: vec![2, 2] }));
+        assert_eq!(flat.transpose().err(), Some(TensorError::RankMismatch { expected: 2, found: 1 }));
+    }
+}
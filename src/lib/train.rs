@@ -0,0 +1,1145 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use serde::{Deserialize, Serialize};
+use crate::archive::{self, Entry};
+use crate::config::NetworkConfig;
+use crate::data::stream::StreamingDataset;
+use crate::experiment::ExperimentError;
+use crate::interop::{self, InteropError, NdArray};
+use crate::logging::{Logger, Progress};
+use crate::loss::{self, Loss};
+use crate::network::{Network, NetworkError};
+use crate::optim::{self, Optimizer, OptimizerState, ParamGroup, Sgd};
+use crate::rand::{self, gaussian, SeedState};
+use crate::scalar::Scalar;
+use crate::value::{GradPlan, Value};
+
+/// Trainer fits a network to a dataset of (input, target) pairs with mini-batch gradient descent,
+/// minimising `loss` (the mean squared error by default).
+///
+/// Only one sample's computation graph is alive at a time: each backward pass adds into the
+/// parameters' gradients, which are averaged and applied once per optimizer step. Setting
+/// `accumulate_steps` above 1 accumulates several mini-batches before each step, giving an
+/// effective batch size of `batch_size * accumulate_steps` without holding more graphs in memory.
+///
+/// Any Optimizer can drive the updates, and `param_groups` give parts of the network their own
+/// learning rate and weight decay, see ParamGroup.
+///
+/// Setting `early_stopping` stops fit_with_validation once the validation loss stops improving.
+///
+/// Setting `differential_privacy` trains with DP-SGD, see DifferentialPrivacy.
+///
+/// Setting `checkpoint_path` makes fit write a checkpoint there at the end of every epoch, from
+/// which Trainer::resume continues the run after an interruption.
+///
+/// The network is switched to training mode (see Network::set_training) for the optimizer steps
+/// and back to inference mode after them, so validation losses see the network as inference will.
+///
+/// With `static_graph` (the default), networks whose graph doesn't depend on the data (see
+/// Network::has_static_graph) trained on the mean squared error compile one sample's graph at the
+/// start of each epoch and rerun it for every sample, rather than allocating a fresh graph each
+/// time. The results are the same either way.
+pub struct Trainer {
+    pub optimizer: Box<dyn Optimizer>,
+    pub param_groups: Vec<ParamGroup>,
+    pub loss: Loss,
+    pub batch_size: usize,
+    pub accumulate_steps: usize,
+    pub early_stopping: Option<EarlyStopping>,
+    pub differential_privacy: Option<DifferentialPrivacy>,
+    pub checkpoint_path: Option<PathBuf>,
+    pub static_graph: bool,
+
+    // initial_epoch is the number of epochs already completed, which fit skips, and
+    // initial_history their history, which fit extends
+    pub initial_epoch: usize,
+    pub initial_history: TrainingHistory,
+}
+
+/// EarlyStopping ends training once the validation loss hasn't improved by more than `min_delta`
+/// for `patience` consecutive epochs. With `restore_best_weights`, the network is left with the
+/// parameters from the epoch with the lowest validation loss rather than those from the last epoch.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EarlyStopping {
+    pub patience: usize,
+    pub min_delta: f64,
+    pub restore_best_weights: bool,
+}
+
+impl EarlyStopping {
+    pub fn new(patience: usize) -> EarlyStopping {
+        EarlyStopping {
+            patience,
+            min_delta: 0.0,
+            restore_best_weights: true,
+        }
+    }
+}
+
+/// DifferentialPrivacy makes each optimizer step differentially private (DP-SGD): every sample's
+/// gradient is clipped to an L2 norm of at most `max_grad_norm`, bounding how much any one sample
+/// can move the parameters, and gaussian noise with standard deviation
+/// `noise_multiplier * max_grad_norm` is added to the sum of the clipped gradients before it's
+/// averaged. The noise is drawn from rand::global_rng, so rand::global_seed makes it reproducible.
+///
+/// Each sample's gradient has to be read on its own, so steps take a little longer.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct DifferentialPrivacy {
+    pub max_grad_norm: f64,
+    pub noise_multiplier: f64,
+}
+
+impl DifferentialPrivacy {
+    // clip_into adds the gradients of the sample just backpropagated to `sum`, scaled down to
+    // max_grad_norm if needed, and resets them for the next sample
+    fn clip_into<T: Scalar>(&self, parameters: &[Value<T>], sum: &mut [f64]) {
+        let norm = parameters.iter().map(|p| p.get_gradient().powi(2)).sum::<f64>().sqrt();
+        let scale = if norm > self.max_grad_norm { self.max_grad_norm / norm } else { 1.0 };
+
+        for (parameter, sum) in parameters.iter().zip(sum.iter_mut()) {
+            *sum += parameter.get_gradient() * scale;
+            parameter.set_gradient(0.0);
+        }
+    }
+
+    // add_noise sets the gradients to the noisy sum of the clipped ones and resets the sum
+    fn add_noise<T: Scalar>(&self, parameters: &[Value<T>], sum: &mut [f64]) {
+        let std_dev = self.noise_multiplier * self.max_grad_norm;
+        let mut rng = rand::global_rng();
+
+        for (parameter, sum) in parameters.iter().zip(sum.iter_mut()) {
+            parameter.set_gradient(*sum + gaussian(&mut rng, std_dev));
+            *sum = 0.0;
+        }
+    }
+}
+
+impl Trainer {
+    pub fn new(optimizer: impl Optimizer + 'static) -> Trainer {
+        Trainer {
+            optimizer: Box::new(optimizer),
+            param_groups: Vec::new(),
+            loss: Loss::Mse,
+            batch_size: 1,
+            accumulate_steps: 1,
+            early_stopping: None,
+            differential_privacy: None,
+            checkpoint_path: None,
+            static_graph: true,
+            initial_epoch: 0,
+            initial_history: TrainingHistory::default(),
+        }
+    }
+
+    /// Restores a run from a checkpoint written by fit or save_checkpoint, returning a trainer set
+    /// up to continue after the checkpointed epoch and the network with its checkpointed weights.
+    /// Calling fit with the original number of epochs then trains only the remaining ones.
+    ///
+    /// The optimizer is rebuilt with its state between steps (e.g. Lookahead's slow weights or
+    /// Swa's average) from Optimizer::state; checkpoints of optimizers which don't return one
+    /// fail with ExperimentError::UnsupportedOptimizer. When the checkpointed run had seeded the
+    /// thread's global rng, it's restored with the draws taken so far, so shuffling and DP-SGD
+    /// noise continue as they would have. Resumed runs then match uninterrupted ones exactly.
+    ///
+    /// Early stopping state isn't saved, so patience starts over when a run is resumed.
+    pub fn resume<T: Scalar>(path: impl AsRef<Path>) -> Result<(Trainer, Network<T>), ExperimentError> {
+        let bytes = fs::read(&path)?;
+        let entries = archive::read_entries(&bytes).map_err(ExperimentError::InvalidBundle)?;
+
+        let entry = |name: &str| {
+            entries
+                .iter()
+                .find(|entry| entry.name == name)
+                .map(|entry| entry.data.as_slice())
+                .ok_or_else(|| ExperimentError::InvalidBundle(format!("missing {}", name)))
+        };
+
+        let state: CheckpointState = serde_json::from_slice(entry(CHECKPOINT_ENTRY)?)
+            .map_err(|err| ExperimentError::InvalidBundle(format!("failed to decode checkpoint: {}", err)))?;
+        // Checkpoints written before optimizer states were saved always used Sgd
+        let optimizer = match state.optimizer_state {
+            Some(optimizer) => optimizer.into_optimizer(),
+            None if state.optimizer == sgd_name() => Box::new(Sgd::new(state.learning_rate)),
+            None => return Err(ExperimentError::UnsupportedOptimizer(state.optimizer)),
+        };
+
+        let config = String::from_utf8_lossy(entry(CONFIG_ENTRY)?);
+        let config = NetworkConfig::from_toml_str(&config)
+            .map_err(|err| ExperimentError::InvalidBundle(format!("failed to decode config: {}", err)))?;
+        let network = Network::from_config(&config)?;
+
+        let parameters = interop::parse_npy(entry(PARAMETERS_ENTRY)?)?;
+        let expected = vec![network.num_parameters()];
+        if parameters.shape != expected {
+            return Err(InteropError::ShapeMismatch { name: PARAMETERS_ENTRY.to_string(), expected, found: parameters.shape }.into());
+        }
+        for (parameter, data) in network.parameters().iter().zip(parameters.data) {
+            parameter.set_data(T::from_f64(data));
+        }
+
+        // Checkpoints written before batch normalisation layers existed don't have their statistics
+        if let Ok(bytes) = entry(RUNNING_STATS_ENTRY) {
+            set_running_stats(&network, interop::parse_npy(bytes)?)?;
+        }
+
+        if let Some(rng) = state.rng {
+            rand::set_global_state(rng);
+        }
+
+        let mut trainer = Trainer { optimizer, ..Trainer::new(Sgd::new(state.learning_rate)) };
+        trainer.param_groups = state.param_groups;
+        trainer.loss = state.loss;
+        trainer.batch_size = state.batch_size;
+        trainer.accumulate_steps = state.accumulate_steps;
+        trainer.differential_privacy = state.differential_privacy;
+        trainer.checkpoint_path = Some(path.as_ref().to_path_buf());
+        trainer.initial_epoch = state.epoch;
+        trainer.initial_history = state.history;
+
+        Ok((trainer, network))
+    }
+
+    /// Writes the network, this trainer's settings and optimizer state, the thread's global rng
+    /// state, the number of completed epochs and their history to a checkpoint archive, see resume.
+    pub fn save_checkpoint<T: Scalar>(
+        &self,
+        network: &Network<T>,
+        epoch: usize,
+        history: &TrainingHistory,
+        path: impl AsRef<Path>,
+    ) -> Result<(), ExperimentError> {
+        let state = CheckpointState {
+            crate_version: env!("CARGO_PKG_VERSION").to_string(),
+            epoch,
+            optimizer: self.optimizer.name().to_string(),
+            learning_rate: self.optimizer.learning_rate(),
+            optimizer_state: self.optimizer.state(),
+            param_groups: self.param_groups.clone(),
+            loss: self.loss.clone(),
+            batch_size: self.batch_size,
+            accumulate_steps: self.accumulate_steps,
+            differential_privacy: self.differential_privacy,
+            rng: rand::global_state(),
+            history: history.clone(),
+        };
+
+        let state = serde_json::to_vec_pretty(&state)
+            .map_err(|err| ExperimentError::InvalidBundle(format!("failed to encode checkpoint: {}", err)))?;
+        let config = network
+            .config()
+            .to_toml_string()
+            .map_err(|err| ExperimentError::InvalidBundle(format!("failed to encode config: {}", err)))?;
+        let parameters = NdArray {
+            shape: vec![network.num_parameters()],
+            data: network.parameters().iter().map(|p| p.get_data().to_f64()).collect(),
+        };
+        let running_stats = running_stats(network);
+        let running_stats = NdArray { shape: vec![running_stats.len()], data: running_stats };
+
+        let entries = vec![
+            Entry { name: CHECKPOINT_ENTRY.to_string(), data: state },
+            Entry { name: CONFIG_ENTRY.to_string(), data: config.into_bytes() },
+            Entry { name: PARAMETERS_ENTRY.to_string(), data: interop::write_npy(&parameters) },
+            Entry { name: RUNNING_STATS_ENTRY.to_string(), data: interop::write_npy(&running_stats) },
+        ];
+
+        // Writing next to the checkpoint and renaming keeps the previous checkpoint intact if
+        // the run is interrupted mid-write
+        let path = path.as_ref();
+        let partial = path.with_extension("partial");
+        fs::write(&partial, archive::write_entries(&entries))?;
+        fs::rename(partial, path)?;
+
+        Ok(())
+    }
+
+    /// Returns the number of samples which contribute to each optimizer step.
+    pub fn effective_batch_size(&self) -> usize {
+        self.batch_size.max(1) * self.accumulate_steps.max(1)
+    }
+
+    /// train_epoch makes a single pass over `dataset` in order and returns the mean loss of its samples.
+    ///
+    /// The gradients applied at each step are the mean over the samples of that step, so
+    /// accumulating k mini-batches of size n produces the same update as a single batch of size n * k.
+    /// When the dataset doesn't divide evenly, the last step averages over the remaining samples.
+    pub fn train_epoch<T: Scalar>(&self, network: &Network<T>, dataset: &[(Vec<T>, Vec<T>)]) -> Result<f64, NetworkError> {
+        self.run_epoch(network, dataset, &mut |_, _, _| {})
+    }
+
+    /// fit trains for `epochs` passes over `dataset`, reporting progress to `logger` after every
+    /// optimizer step and epoch, and returns the per-epoch history of the run.
+    pub fn fit<T: Scalar>(
+        &self,
+        network: &Network<T>,
+        dataset: &[(Vec<T>, Vec<T>)],
+        epochs: usize,
+        logger: &mut dyn Logger,
+    ) -> Result<TrainingHistory, NetworkError> {
+        self.fit_with_validation(network, dataset, &[], epochs, logger)
+    }
+
+    /// Like fit, but also evaluates the mean loss over `validation` at the end of every epoch.
+    /// The validation samples never contribute to the gradients. When `early_stopping` is set,
+    /// training may end before `epochs`, see EarlyStopping.
+    pub fn fit_with_validation<T: Scalar>(
+        &self,
+        network: &Network<T>,
+        dataset: &[(Vec<T>, Vec<T>)],
+        validation: &[(Vec<T>, Vec<T>)],
+        epochs: usize,
+        logger: &mut dyn Logger,
+    ) -> Result<TrainingHistory, NetworkError> {
+        let batches = dataset.len().div_ceil(self.effective_batch_size());
+        let start = Instant::now();
+
+        let mut progress = Progress {
+            epoch: 0,
+            epochs,
+            batch: 0,
+            batches,
+            loss: 0.0,
+            val_loss: None,
+            learning_rate: self.optimizer.learning_rate(),
+            grad_norm: 0.0,
+            elapsed: start.elapsed(),
+            eta: start.elapsed(),
+        };
+
+        let mut history = self.initial_history.clone();
+
+        // The lowest validation loss so far, with the parameters which produced it
+        let mut best_loss = f64::INFINITY;
+        let mut best_parameters: Option<Vec<T>> = None;
+        let mut epochs_without_improvement = 0;
+
+        for epoch in self.initial_epoch + 1..=epochs {
+            progress.epoch = epoch;
+            progress.val_loss = None;
+
+            let loss = self.run_epoch(network, dataset, &mut |batch, loss, grad_norm| {
+                progress.batch = batch;
+                progress.loss = loss;
+                progress.grad_norm = grad_norm;
+                progress.elapsed = start.elapsed();
+
+                // Estimate the remaining time from the average time per step so far in this run
+                let completed = ((epoch - 1) * batches + batch) as u32;
+                let timed = completed - (self.initial_epoch * batches) as u32;
+                let remaining = (epochs * batches) as u32 - completed;
+                progress.eta = progress.elapsed / timed * remaining;
+
+                logger.on_batch_end(&progress);
+            })?;
+
+            history.train_loss.push(loss);
+            history.record("learning_rate", self.optimizer.learning_rate());
+
+            if !validation.is_empty() {
+                let val_loss = evaluate_with(network, validation, &self.loss)?;
+                history.val_loss.push(val_loss);
+                progress.val_loss = Some(val_loss);
+            }
+
+            progress.batch = batches;
+            progress.loss = loss;
+            progress.elapsed = start.elapsed();
+            logger.on_epoch_end(&progress);
+
+            if let Some(path) = &self.checkpoint_path {
+                self.save_checkpoint(network, epoch, &history, path)
+                    .map_err(|err| NetworkError::Checkpoint(err.to_string()))?;
+            }
+
+            let (Some(early_stopping), Some(val_loss)) = (&self.early_stopping, progress.val_loss) else {
+                continue;
+            };
+
+            if val_loss < best_loss - early_stopping.min_delta {
+                best_loss = val_loss;
+                history.best_epoch = Some(epoch);
+                epochs_without_improvement = 0;
+
+                if early_stopping.restore_best_weights {
+                    best_parameters = Some(network.parameters().iter().map(|p| p.get_data()).collect());
+                }
+            } else {
+                epochs_without_improvement += 1;
+                if epochs_without_improvement >= early_stopping.patience {
+                    break;
+                }
+            }
+        }
+
+        if let Some(best_parameters) = best_parameters {
+            for (parameter, data) in network.parameters().iter().zip(best_parameters) {
+                parameter.set_data(data);
+            }
+        }
+
+        Ok(history)
+    }
+
+    /// Like train_epoch, for a dataset read as training goes rather than held in memory. The stream
+    /// is reset first, so each call makes a full pass over it.
+    pub fn train_epoch_stream(&self, network: &Network, stream: &mut dyn StreamingDataset) -> Result<f64, NetworkError> {
+        stream.reset().map_err(|err| NetworkError::Dataset(err.to_string()))?;
+
+        let size = self.effective_batch_size();
+        let steps = std::iter::from_fn(|| stream.next_batch(size)).map(|step| step.map_err(|err| NetworkError::Dataset(err.to_string())));
+
+        self.run_steps(network, steps, &mut |_, _, _| {})
+    }
+
+    /// Returns the gradient of the loss of each sample of `batch` on its own, with one value per
+    /// parameter in the order of Network::parameters, e.g. to find the samples which pull the
+    /// parameters furthest or to compute influence scores. Each sample is backpropagated through
+    /// its own graph; the parameters' gradients are left as they were.
+    pub fn per_sample_grads<T: Scalar>(&self, network: &Network<T>, batch: &[(Vec<T>, Vec<T>)]) -> Result<Vec<Vec<f64>>, NetworkError> {
+        let parameters = network.parameters();
+        let saved: Vec<f64> = parameters.iter().map(|p| p.get_gradient()).collect();
+
+        let gradients = batch
+            .iter()
+            .map(|(input, target)| {
+                let outputs = network.forward_values(input)?;
+                if outputs.len() != target.len() {
+                    return Err(NetworkError::DimensionMismatch { expected: outputs.len(), found: target.len() });
+                }
+
+                optim::zero_grad(&parameters);
+                self.loss.compute(&outputs, target).run_grad();
+
+                Ok(parameters.iter().map(|p| p.get_gradient()).collect())
+            })
+            .collect();
+
+        for (parameter, gradient) in parameters.iter().zip(saved) {
+            parameter.set_gradient(gradient);
+        }
+
+        gradients
+    }
+
+    // run_epoch trains on `dataset` once, calling `on_step` after every step with the number of
+    // optimizer steps taken so far, the mean loss of the samples seen so far and the norm of the
+    // gradients applied by the step.
+    fn run_epoch<T: Scalar>(
+        &self,
+        network: &Network<T>,
+        dataset: &[(Vec<T>, Vec<T>)],
+        on_step: &mut dyn FnMut(usize, f64, f64),
+    ) -> Result<f64, NetworkError> {
+        self.run_steps(network, dataset.chunks(self.effective_batch_size()).map(Ok), on_step)
+    }
+
+    // run_steps takes an optimizer step for each batch of `steps`, see run_epoch, with the network
+    // in training mode
+    fn run_steps<T: Scalar, B: AsRef<[(Vec<T>, Vec<T>)]>>(
+        &self,
+        network: &Network<T>,
+        steps: impl Iterator<Item = Result<B, NetworkError>>,
+        on_step: &mut dyn FnMut(usize, f64, f64),
+    ) -> Result<f64, NetworkError> {
+        network.set_training(true);
+        let loss = self.take_steps(network, steps, on_step);
+        network.set_training(false);
+
+        loss
+    }
+
+    fn take_steps<T: Scalar, B: AsRef<[(Vec<T>, Vec<T>)]>>(
+        &self,
+        network: &Network<T>,
+        steps: impl Iterator<Item = Result<B, NetworkError>>,
+        on_step: &mut dyn FnMut(usize, f64, f64),
+    ) -> Result<f64, NetworkError> {
+        let parameters = network.parameters();
+        let settings = optim::resolve_groups(network, &self.param_groups);
+        optim::zero_grad(&parameters);
+
+        let mut total_loss = 0.0;
+        let mut seen = 0;
+
+        // With differential privacy, the clipped per-sample gradients of the current step
+        let mut clipped_sum = self.differential_privacy.map(|_| vec![0.0; parameters.len()]);
+
+        let static_graph = if self.static_graph { StaticGraph::compile(network, &self.loss) } else { None };
+
+        for (i, step) in steps.enumerate() {
+            let step = step?;
+            let step = step.as_ref();
+
+            for (input, target) in step {
+                total_loss += match &static_graph {
+                    Some(graph) => graph.run(network, input, target)?,
+                    None => {
+                        let outputs = network.forward_values(input)?;
+                        if outputs.len() != target.len() {
+                            return Err(NetworkError::DimensionMismatch { expected: outputs.len(), found: target.len() });
+                        }
+
+                        let loss = self.loss.compute(&outputs, target);
+                        loss.run_grad();
+
+                        loss.get_data().to_f64()
+                    }
+                };
+
+                if let (Some(privacy), Some(sum)) = (&self.differential_privacy, clipped_sum.as_mut()) {
+                    privacy.clip_into(&parameters, sum);
+                }
+            }
+
+            if let (Some(privacy), Some(sum)) = (&self.differential_privacy, clipped_sum.as_mut()) {
+                privacy.add_noise(&parameters, sum);
+            }
+
+            // Average the summed gradients over the samples of this step
+            let samples = step.len() as f64;
+            let mut squared_norm = 0.0;
+            for parameter in &parameters {
+                let gradient = parameter.get_gradient() / samples;
+                parameter.set_gradient(gradient);
+                squared_norm += gradient * gradient;
+            }
+
+            optim::step_groups(self.optimizer.as_ref(), &parameters, &settings);
+            optim::zero_grad(&parameters);
+
+            seen += step.len();
+            on_step(i + 1, total_loss / seen as f64, squared_norm.sqrt());
+        }
+
+        if seen == 0 {
+            return Ok(0.0);
+        }
+
+        Ok(total_loss / seen as f64)
+    }
+}
+
+// StaticGraph is one sample's forward pass and loss compiled once, which every sample of an epoch
+// reruns with its own inputs and targets, see Trainer::static_graph.
+struct StaticGraph<T> {
+    inputs: Vec<Value<T>>,
+    targets: Vec<Value<T>>,
+    plan: GradPlan<T>,
+}
+
+impl<T: Scalar> StaticGraph<T> {
+    // Returns None when the network or the loss build a different graph for different data, like
+    // cross entropy, which shifts the logits by their maximum and skips classes with no target.
+    fn compile(network: &Network<T>, loss: &Loss) -> Option<StaticGraph<T>> {
+        if !network.has_static_graph() || *loss != Loss::Mse {
+            return None;
+        }
+
+        let num_inputs = network.layers[0].num_inputs() as usize;
+        let inputs: Vec<Value<T>> = (0..num_inputs).map(|_| Value::constant(T::from_f64(0.0))).collect();
+        let outputs = network.forward_graph(&inputs).ok()?;
+        let targets: Vec<Value<T>> = outputs.iter().map(|_| Value::constant(T::from_f64(0.0))).collect();
+
+        let plan = GradPlan::compile(&loss::mse_values(&outputs, &targets));
+
+        Some(StaticGraph { inputs, targets, plan })
+    }
+
+    // Runs the forward and backward pass of a sample, returning its loss.
+    fn run(&self, network: &Network<T>, input: &[T], target: &[T]) -> Result<f64, NetworkError> {
+        network.check_inputs(input.len())?;
+        if target.len() != self.targets.len() {
+            return Err(NetworkError::DimensionMismatch { expected: self.targets.len(), found: target.len() });
+        }
+
+        for (value, data) in self.inputs.iter().zip(input).chain(self.targets.iter().zip(target)) {
+            value.set_data(*data);
+        }
+        self.plan.run();
+
+        Ok(self.plan.root().get_data().to_f64())
+    }
+}
+
+/// evaluate returns the mean squared error of `network` over `dataset` without touching any gradients.
+pub fn evaluate<T: Scalar>(network: &Network<T>, dataset: &[(Vec<T>, Vec<T>)]) -> Result<f64, NetworkError> {
+    evaluate_with(network, dataset, &Loss::Mse)
+}
+
+/// Like evaluate, with the mean of `loss` rather than the squared error.
+pub fn evaluate_with<T: Scalar>(network: &Network<T>, dataset: &[(Vec<T>, Vec<T>)], loss: &Loss) -> Result<f64, NetworkError> {
+    if dataset.is_empty() {
+        return Ok(0.0);
+    }
+
+    let mut total_loss = 0.0;
+    for (input, target) in dataset {
+        let outputs = network.forward_values(input)?;
+        if outputs.len() != target.len() {
+            return Err(NetworkError::DimensionMismatch { expected: outputs.len(), found: target.len() });
+        }
+
+        total_loss += loss.compute(&outputs, target).get_data().to_f64();
+    }
+
+    Ok(total_loss / dataset.len() as f64)
+}
+
+const CHECKPOINT_ENTRY: &str = "checkpoint.json";
+const CONFIG_ENTRY: &str = "config.toml";
+const PARAMETERS_ENTRY: &str = "parameters.npy";
+const RUNNING_STATS_ENTRY: &str = "running_stats.npy";
+
+// running_stats returns the running means and variances of the network's batch normalisation
+// layers, layer by layer, which checkpoints save next to the parameters
+fn running_stats<T: Scalar>(network: &Network<T>) -> Vec<f64> {
+    network
+        .layers
+        .iter()
+        .filter_map(|layer| layer.running_stats())
+        .flat_map(|(means, variances)| means.into_iter().chain(variances))
+        .collect()
+}
+
+// set_running_stats restores the statistics returned by running_stats, checking their length first
+fn set_running_stats<T: Scalar>(network: &Network<T>, stats: NdArray) -> Result<(), InteropError> {
+    let expected = vec![running_stats(network).len()];
+    if stats.shape != expected {
+        return Err(InteropError::ShapeMismatch { name: RUNNING_STATS_ENTRY.to_string(), expected, found: stats.shape });
+    }
+
+    let mut stats = stats.data.as_slice();
+    for layer in &network.layers {
+        if let Some((means, _)) = layer.running_stats() {
+            let (means, rest) = stats.split_at(means.len());
+            let (variances, rest) = rest.split_at(means.len());
+            layer.set_running_stats(means, variances);
+            stats = rest;
+        }
+    }
+
+    Ok(())
+}
+
+// CheckpointState holds the non-network parts of a checkpoint.
+#[derive(Serialize, Deserialize)]
+struct CheckpointState {
+    crate_version: String,
+    epoch: usize,
+
+    // Checkpoints written before the optimizer was recorded always used Sgd
+    #[serde(default = "sgd_name")]
+    optimizer: String,
+    learning_rate: f64,
+    #[serde(default)]
+    optimizer_state: Option<OptimizerState>,
+    #[serde(default)]
+    param_groups: Vec<ParamGroup>,
+
+    // Checkpoints written before the loss was configurable always used Mse
+    #[serde(default)]
+    loss: Loss,
+    batch_size: usize,
+    accumulate_steps: usize,
+
+    // Resuming a DP-SGD run without its clipping and noise would void its privacy guarantee
+    #[serde(default)]
+    differential_privacy: Option<DifferentialPrivacy>,
+
+    // rng is None when the run drew from thread_rng, which can't be replayed
+    #[serde(default)]
+    rng: Option<SeedState>,
+    history: TrainingHistory,
+}
+
+fn sgd_name() -> String {
+    Sgd::new(0.0).name().to_string()
+}
+
+/// TrainingHistory records the per-epoch losses and metrics of a training run, so learning curves
+/// can be plotted after the fact. `val_loss` is empty when training ran without a validation set.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TrainingHistory {
+    pub train_loss: Vec<f64>,
+    pub val_loss: Vec<f64>,
+    pub metrics: BTreeMap<String, Vec<f64>>,
+
+    // best_epoch is the 1-based epoch with the lowest validation loss, tracked with early stopping
+    pub best_epoch: Option<usize>,
+}
+
+impl TrainingHistory {
+    /// Returns the number of epochs recorded.
+    pub fn epochs(&self) -> usize {
+        self.train_loss.len()
+    }
+
+    /// Appends `value` to the series for the metric `name`.
+    pub fn record(&mut self, name: &str, value: f64) {
+        self.metrics.entry(name.to_string()).or_default().push(value);
+    }
+
+    /// Formats the history as a table with one row per epoch: the epoch number, train_loss,
+    /// val_loss when there's a validation set, then each metric in name order. Metrics with fewer
+    /// values than there are epochs leave their remaining cells empty.
+    pub fn to_delimited(&self, delimiter: char) -> String {
+        let mut columns: Vec<(&str, &[f64])> = vec![("train_loss", &self.train_loss)];
+        if !self.val_loss.is_empty() {
+            columns.push(("val_loss", &self.val_loss));
+        }
+        columns.extend(self.metrics.iter().map(|(name, values)| (name.as_str(), values.as_slice())));
+
+        let separator = delimiter.to_string();
+
+        let mut header = vec!["epoch"];
+        header.extend(columns.iter().map(|(name, _)| *name));
+        let mut output = header.join(&separator);
+        output.push('\n');
+
+        for epoch in 0..self.epochs() {
+            let mut row = vec![(epoch + 1).to_string()];
+            row.extend(columns.iter().map(|(_, values)| values.get(epoch).map(|v| v.to_string()).unwrap_or_default()));
+
+            output.push_str(&row.join(&separator));
+            output.push('\n');
+        }
+
+        output
+    }
+
+    /// Writes the history as a comma separated file, see to_delimited.
+    pub fn write_csv(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_delimited(','))
+    }
+
+    /// Writes the history as a tab separated file, see to_delimited.
+    pub fn write_tsv(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_delimited('\t'))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::stream::Generator;
+    use crate::data::class_weights;
+    use crate::experiment::ExperimentError;
+    use crate::logging::{Logger, Progress, Silent};
+    use crate::loss::Loss;
+    use crate::network::{Activation, Layer, Network, NetworkError};
+    use crate::optim::{Lookahead, Optimizer, ParamGroup, ParamSelector, Sgd, Swa};
+    use crate::rand;
+    use crate::train::{evaluate, DifferentialPrivacy, EarlyStopping, Trainer, TrainingHistory};
+
+    fn dataset() -> Vec<(Vec<f64>, Vec<f64>)> {
+        (0..8)
+            .map(|i| {
+                let x = i as f64 / 8.0;
+                (vec![x, 1.0 - x], vec![2.0 * x - 0.5])
+            })
+            .collect()
+    }
+
+    #[test]
+    fn accumulation_matches_larger_batches() {
+        let network = Network::new(vec![
+            Layer::dense(2, 4, Activation::Relu, true).unwrap(),
+            Layer::dense(4, 1, Activation::Linear, true).unwrap(),
+        ]).unwrap();
+        let accumulated = network.deep_clone();
+
+        let mut trainer = Trainer::new(Sgd::new(0.1));
+        trainer.batch_size = 4;
+        trainer.train_epoch(&network, &dataset()).unwrap();
+
+        let mut accumulating_trainer = Trainer::new(Sgd::new(0.1));
+        accumulating_trainer.batch_size = 2;
+        accumulating_trainer.accumulate_steps = 2;
+        assert_eq!(accumulating_trainer.effective_batch_size(), 4);
+        accumulating_trainer.train_epoch(&accumulated, &dataset()).unwrap();
+
+        for (a, b) in network.parameters().iter().zip(accumulated.parameters()) {
+            assert!((a.get_data() - b.get_data()).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn static_graphs_train_like_rebuilt_ones() {
+        let network = Network::new(vec![
+            Layer::dense(2, 4, Activation::Gelu, true).unwrap(),
+            Layer::dense(4, 1, Activation::Linear, true).unwrap(),
+        ]).unwrap();
+        let rebuilt = network.deep_clone();
+        network.parameters()[0].set_requires_grad(false);
+        rebuilt.parameters()[0].set_requires_grad(false);
+
+        let mut trainer = Trainer::new(Sgd::new(0.1));
+        trainer.batch_size = 3;
+        let loss = trainer.train_epoch(&network, &dataset()).unwrap();
+
+        trainer.static_graph = false;
+        assert_eq!(trainer.train_epoch(&rebuilt, &dataset()).unwrap(), loss);
+        for (a, b) in network.parameters().iter().zip(rebuilt.parameters()) {
+            assert_eq!(a.get_data(), b.get_data());
+        }
+
+        let mismatched = vec![(vec![0.5, 0.5], vec![1.0, 2.0])];
+        trainer.static_graph = true;
+        assert!(matches!(
+            trainer.train_epoch(&network, &mismatched),
+            Err(NetworkError::DimensionMismatch { expected: 1, found: 2 })
+        ));
+    }
+
+    #[test]
+    fn training_reduces_loss() {
+        let network = Network::new(vec![Layer::dense(2, 1, Activation::Linear, true).unwrap()]).unwrap();
+
+        let mut trainer = Trainer::new(Sgd::new(0.5));
+        trainer.batch_size = 2;
+        trainer.accumulate_steps = 2;
+
+        let first = trainer.train_epoch(&network, &dataset()).unwrap();
+        let mut last = first;
+        for _ in 0..50 {
+            last = trainer.train_epoch(&network, &dataset()).unwrap();
+        }
+
+        assert!(last < first / 10.0, "loss went from {} to {}", first, last);
+    }
+
+    #[test]
+    fn streamed_epochs_match_in_memory_ones() {
+        let network = Network::new(vec![Layer::dense(2, 1, Activation::Linear, true).unwrap()]).unwrap();
+        let streamed = network.deep_clone();
+
+        let mut trainer = Trainer::new(Sgd::new(0.5));
+        trainer.batch_size = 3;
+
+        let data = dataset();
+        let mut stream = Generator::new(|i| data.get(i).cloned());
+        for _ in 0..3 {
+            let loss = trainer.train_epoch(&network, &dataset()).unwrap();
+            assert_eq!(trainer.train_epoch_stream(&streamed, &mut stream).unwrap(), loss);
+        }
+        assert_eq!(
+            network.parameters().iter().map(|p| p.get_data()).collect::<Vec<_>>(),
+            streamed.parameters().iter().map(|p| p.get_data()).collect::<Vec<_>>()
+        );
+
+        let path = std::env::temp_dir().join("backprop_train_stream.csv");
+        std::fs::write(&path, "1,2,3
+4,five,6
+").unwrap();
+        let mut stream = crate::data::stream::CsvStream::open(&path, 1).unwrap();
+        assert!(matches!(trainer.train_epoch_stream(&streamed, &mut stream), Err(NetworkError::Dataset(_))));
+    }
+
+    #[derive(Default)]
+    struct Recorder {
+        batches: Vec<(usize, usize)>,
+        epochs: Vec<Progress>,
+    }
+
+    impl Logger for Recorder {
+        fn on_batch_end(&mut self, progress: &Progress) {
+            self.batches.push((progress.epoch, progress.batch));
+        }
+
+        fn on_epoch_end(&mut self, progress: &Progress) {
+            self.epochs.push(progress.clone());
+        }
+    }
+
+    #[test]
+    fn fit_reports_progress() {
+        let network = Network::new(vec![Layer::dense(2, 1, Activation::Linear, true).unwrap()]).unwrap();
+
+        let mut trainer = Trainer::new(Sgd::new(0.5));
+        trainer.batch_size = 3;
+
+        let mut recorder = Recorder::default();
+        let history = trainer.fit(&network, &dataset(), 2, &mut recorder).unwrap();
+
+        assert_eq!(recorder.batches, vec![(1, 1), (1, 2), (1, 3), (2, 1), (2, 2), (2, 3)]);
+        assert_eq!(recorder.epochs.len(), 2);
+        assert_eq!(recorder.epochs[1].batches, 3);
+        assert_eq!(recorder.epochs[1].learning_rate, 0.5);
+        assert_eq!(recorder.epochs.iter().map(|progress| progress.loss).collect::<Vec<f64>>(), history.train_loss);
+        assert_eq!(recorder.epochs[1].eta, std::time::Duration::ZERO);
+    }
+
+    #[test]
+    fn history_tracks_validation_loss() {
+        let network = Network::new(vec![Layer::dense(2, 1, Activation::Linear, true).unwrap()]).unwrap();
+        let samples = dataset();
+        let (train, validation) = samples.split_at(6);
+
+        let trainer = Trainer::new(Sgd::new(0.5));
+        let history = network.train(&trainer, train, validation, 20).unwrap();
+
+        assert_eq!(history.epochs(), 20);
+        assert_eq!(history.val_loss.len(), 20);
+        assert_eq!(history.metrics["learning_rate"], vec![0.5; 20]);
+        assert!(history.val_loss[19] < history.val_loss[0]);
+    }
+
+    #[test]
+    fn history_formats_as_csv_and_tsv() {
+        let mut history = TrainingHistory {
+            train_loss: vec![0.5, 0.25],
+            ..Default::default()
+        };
+        history.record("accuracy", 0.75);
+
+        assert_eq!(history.to_delimited(','), "epoch,train_loss,accuracy\n1,0.5,0.75\n2,0.25,\n");
+
+        history.val_loss = vec![0.75, 0.5];
+        assert_eq!(history.to_delimited('\t'), "epoch\ttrain_loss\tval_loss\taccuracy\n1\t0.5\t0.75\t0.75\n2\t0.25\t0.5\t\n");
+    }
+
+    #[test]
+    fn early_stopping_restores_best_weights() {
+        let network = Network::new(vec![Layer::dense(2, 1, Activation::Linear, true).unwrap()]).unwrap();
+
+        // The validation targets disagree with the training ones, so the validation loss
+        // bottoms out early and then rises as the network fits the training data
+        let train = dataset();
+        let validation: Vec<(Vec<f64>, Vec<f64>)> = train.iter().map(|(x, y)| (x.clone(), vec![y[0] + 1.0])).collect();
+        network.parameters().iter().for_each(|p| p.set_data(0.5));
+
+        let mut trainer = Trainer::new(Sgd::new(0.1));
+        trainer.early_stopping = Some(EarlyStopping::new(3));
+
+        let history = trainer.fit_with_validation(&network, &train, &validation, 100, &mut Silent).unwrap();
+        let best_epoch = history.best_epoch.unwrap();
+
+        assert!(history.epochs() < 100);
+        assert_eq!(history.epochs(), best_epoch + 3);
+
+        let best_loss = history.val_loss[best_epoch - 1];
+        assert!(history.val_loss.iter().all(|loss| *loss >= best_loss));
+        assert!((evaluate(&network, &validation).unwrap() - best_loss).abs() < 1e-12);
+    }
+
+    #[test]
+    fn resumed_runs_match_uninterrupted_ones() {
+        let path = std::env::temp_dir().join("backprop_resume.ckpt");
+        let network = Network::new(vec![
+            Layer::dense(2, 3, Activation::Relu, true).unwrap(),
+            Layer::batch_norm(3, 0.1, 1e-5).unwrap(),
+            Layer::dense(3, 1, Activation::Linear, false).unwrap(),
+        ]).unwrap();
+        let uninterrupted = network.deep_clone();
+
+        let mut trainer = Trainer::new(Sgd::new(0.1));
+        trainer.batch_size = 3;
+        trainer.checkpoint_path = Some(path.clone());
+        trainer.fit(&network, &dataset(), 2, &mut Silent).unwrap();
+
+        let (resumed, restored): (Trainer, Network) = Trainer::resume(&path).unwrap();
+        assert_eq!((resumed.initial_epoch, resumed.batch_size), (2, 3));
+        let history = resumed.fit(&restored, &dataset(), 5, &mut Silent).unwrap();
+
+        trainer.checkpoint_path = None;
+        let expected = trainer.fit(&uninterrupted, &dataset(), 5, &mut Silent).unwrap();
+
+        assert_eq!(history, expected);
+        assert_eq!(restored.forward(&[0.25, 0.75]).unwrap(), uninterrupted.forward(&[0.25, 0.75]).unwrap());
+
+        // The resumed run kept checkpointing, up to its last epoch
+        let (finished, _): (Trainer, Network) = Trainer::resume(&path).unwrap();
+        assert_eq!(finished.initial_epoch, 5);
+        assert_eq!(finished.initial_history, expected);
+    }
+
+    #[test]
+    fn class_weights_keep_rare_classes_from_collapsing() {
+        // Nine samples of class 0 and one of class 1 with the same input, so only the biases
+        // decide the predicted probabilities
+        let labels = [0, 0, 0, 0, 0, 0, 0, 0, 0, 1];
+        let dataset: Vec<(Vec<f64>, Vec<f64>)> = labels
+            .iter()
+            .map(|&label| (vec![0.0], if label == 0 { vec![1.0, 0.0] } else { vec![0.0, 1.0] }))
+            .collect();
+
+        let rare_probability = |loss: Loss| {
+            let network = Network::new(vec![Layer::dense(1, 2, Activation::Linear, true).unwrap()]).unwrap();
+            let mut trainer = Trainer::new(Sgd::new(0.5));
+            trainer.batch_size = dataset.len();
+            trainer.loss = loss;
+            trainer.fit(&network, &dataset, 300, &mut Silent).unwrap();
+
+            network.predict_proba(&[vec![0.0]]).unwrap()[0][1]
+        };
+
+        assert!((rare_probability(Loss::CrossEntropy { class_weights: None }) - 0.1).abs() < 0.01);
+
+        let class_weights = Some(class_weights(&labels, 2).unwrap());
+        assert!((rare_probability(Loss::CrossEntropy { class_weights }) - 0.5).abs() < 0.01);
+    }
+
+    #[test]
+    fn differential_privacy_clips_each_sample() {
+        let build = || {
+            let network: Network = Network::new(vec![Layer::dense(2, 1, Activation::Linear, true).unwrap()]).unwrap();
+            for parameter in network.parameters() {
+                parameter.set_data(0.0);
+            }
+            network
+        };
+
+        // Without clipping or noise, DP-SGD takes the same steps as plain training
+        let mut trainer = Trainer::new(Sgd::new(0.1));
+        trainer.batch_size = 4;
+        let (plain, private) = (build(), build());
+        trainer.train_epoch(&plain, &dataset()).unwrap();
+        trainer.differential_privacy = Some(DifferentialPrivacy { max_grad_norm: 1e9, noise_multiplier: 0.0 });
+        trainer.train_epoch(&private, &dataset()).unwrap();
+        assert!(plain.weights_approx_eq(&private, 1e-12));
+
+        // A single step moves the parameters by at most learning_rate * max_grad_norm
+        let clipped = build();
+        trainer.differential_privacy = Some(DifferentialPrivacy { max_grad_norm: 0.01, noise_multiplier: 0.0 });
+        trainer.train_epoch(&clipped, &dataset()[..4]).unwrap();
+        let moved = clipped.parameters().iter().map(|p| p.get_data().powi(2)).sum::<f64>().sqrt();
+        assert!(moved > 0.0 && moved <= 0.1 * 0.01 + 1e-12);
+
+        // Noise makes the steps differ between runs
+        trainer.differential_privacy = Some(DifferentialPrivacy { max_grad_norm: 1.0, noise_multiplier: 1.0 });
+        let (first, second) = (build(), build());
+        trainer.train_epoch(&first, &dataset()).unwrap();
+        trainer.train_epoch(&second, &dataset()).unwrap();
+        assert!(!first.weights_approx_eq(&second, 1e-9));
+    }
+
+    #[test]
+    fn resumed_runs_keep_param_groups_and_reject_unsaved_optimizers() {
+        let path = std::env::temp_dir().join("backprop_resume_groups.ckpt");
+        let network: Network = Network::new(vec![Layer::dense(2, 1, Activation::Linear, true).unwrap()]).unwrap();
+
+        let mut trainer = Trainer::new(Sgd::new(0.1));
+        trainer.param_groups = vec![ParamGroup::new(ParamSelector::Weights).with_learning_rate(0.01).with_weight_decay(0.5)];
+        trainer.save_checkpoint(&network, 1, &TrainingHistory::default(), &path).unwrap();
+
+        let (resumed, _): (Trainer, Network) = Trainer::resume(&path).unwrap();
+        assert_eq!(resumed.param_groups, trainer.param_groups);
+
+        // An optimizer without a saved state can't be rebuilt, so resuming would change the run
+        struct Momentum;
+        impl Optimizer for Momentum {
+            fn learning_rate(&self) -> f64 {
+                0.1
+            }
+
+            fn set_learning_rate(&mut self, _: f64) {}
+
+            fn update(&self, _: usize, data: f64, gradient: f64, learning_rate: f64) -> f64 {
+                data - learning_rate * gradient
+            }
+
+            fn name(&self) -> &'static str {
+                "momentum"
+            }
+        }
+        let trainer = Trainer::new(Lookahead::new(Momentum, 5, 0.5));
+        trainer.save_checkpoint(&network, 1, &TrainingHistory::default(), &path).unwrap();
+        assert!(matches!(
+            Trainer::resume::<f64>(&path),
+            Err(ExperimentError::UnsupportedOptimizer(name)) if name == "lookahead"
+        ));
+    }
+
+    #[test]
+    fn resumed_runs_restore_optimizer_state_and_rng() {
+        let path = std::env::temp_dir().join("backprop_resume_state.ckpt");
+        let build = || {
+            rand::global_seed(11);
+            Network::new(vec![
+                Layer::dense(2, 3, Activation::Gelu, true).unwrap(),
+                Layer::dense(3, 1, Activation::Linear, false).unwrap(),
+            ])
+            .unwrap()
+        };
+        let trainer = || {
+            let mut trainer = Trainer::new(Lookahead::new(Swa::new(Sgd::new(0.1), 2, 1), 3, 0.5));
+            trainer.batch_size = 3;
+            trainer.differential_privacy = Some(DifferentialPrivacy { max_grad_norm: 1.0, noise_multiplier: 0.5 });
+            trainer
+        };
+
+        let uninterrupted: Network = build();
+        let uninterrupted_trainer = trainer();
+        let expected = uninterrupted_trainer.fit(&uninterrupted, &dataset(), 4, &mut Silent).unwrap();
+
+        let network: Network = build();
+        let mut interrupted = trainer();
+        interrupted.checkpoint_path = Some(path.clone());
+        interrupted.fit(&network, &dataset(), 2, &mut Silent).unwrap();
+
+        // Draws made between the interruption and the resumption don't affect the resumed run
+        rand::global_seed(12);
+        let (resumed, restored): (Trainer, Network) = Trainer::resume(&path).unwrap();
+        let history = resumed.fit(&restored, &dataset(), 4, &mut Silent).unwrap();
+        rand::clear_global_seed();
+
+        assert_eq!(history, expected);
+        assert_eq!(restored.get_flat_params(), uninterrupted.get_flat_params());
+        assert_eq!(resumed.optimizer.state(), uninterrupted_trainer.optimizer.state());
+    }
+
+    #[test]
+    fn resumed_runs_keep_differential_privacy() {
+        let path = std::env::temp_dir().join("backprop_resume_dp.ckpt");
+        let network: Network = Network::new(vec![Layer::dense(2, 1, Activation::Linear, true).unwrap()]).unwrap();
+        let uninterrupted = network.deep_clone();
+
+        // Without noise the clipped steps are deterministic, so they can be compared
+        let mut trainer = Trainer::new(Sgd::new(0.1));
+        trainer.batch_size = 4;
+        trainer.differential_privacy = Some(DifferentialPrivacy { max_grad_norm: 0.01, noise_multiplier: 0.0 });
+        trainer.checkpoint_path = Some(path.clone());
+        trainer.fit(&network, &dataset(), 1, &mut Silent).unwrap();
+
+        let (resumed, restored): (Trainer, Network) = Trainer::resume(&path).unwrap();
+        assert_eq!(resumed.differential_privacy, trainer.differential_privacy);
+        let history = resumed.fit(&restored, &dataset(), 3, &mut Silent).unwrap();
+
+        trainer.checkpoint_path = None;
+        assert_eq!(history, trainer.fit(&uninterrupted, &dataset(), 3, &mut Silent).unwrap());
+        assert!(restored.weights_approx_eq(&uninterrupted, 1e-12));
+    }
+
+    #[test]
+    fn per_sample_gradients_sum_to_the_batch_gradient() {
+        let network = Network::new(vec![
+            Layer::dense(2, 3, Activation::Relu, true).unwrap(),
+            Layer::dense(3, 1, Activation::Linear, false).unwrap(),
+        ]).unwrap();
+        let batch = &dataset()[..3];
+        let trainer = Trainer::new(Sgd::new(0.1));
+
+        network.parameters()[0].set_gradient(7.0);
+        let gradients = trainer.per_sample_grads(&network, batch).unwrap();
+        assert_eq!(gradients.len(), 3);
+        assert!(gradients.iter().all(|gradient| gradient.len() == network.num_parameters()));
+        assert_eq!(network.parameters()[0].get_gradient(), 7.0);
+
+        // Backpropagating the samples into the same gradients accumulates their sum
+        let parameters = network.parameters();
+        for parameter in &parameters {
+            parameter.set_gradient(0.0);
+        }
+        for (input, target) in batch {
+            crate::loss::mse(&network.forward_values(input).unwrap(), target).run_grad();
+        }
+        for (index, parameter) in parameters.iter().enumerate() {
+            let sum: f64 = gradients.iter().map(|gradient| gradient[index]).sum();
+            assert!((parameter.get_gradient() - sum).abs() < 1e-12);
+        }
+
+        assert!(matches!(trainer.per_sample_grads(&network, &[(vec![0.0, 0.0], vec![1.0, 2.0])]), Err(NetworkError::DimensionMismatch { .. })));
+    }
+}
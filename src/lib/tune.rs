@@ -0,0 +1,286 @@
+use std::fmt;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use crate::logging::Silent;
+use crate::network::{Activation, Layer, Network, NetworkError};
+use crate::optim::Sgd;
+use crate::train::{Trainer, TrainingHistory};
+
+type Dataset = [(Vec<f64>, Vec<f64>)];
+
+/// SearchSpace lists the candidate values of each hyperparameter. Every combination of a learning
+/// rate, a set of hidden layer sizes and an activation is a possible trial.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchSpace {
+    pub learning_rates: Vec<f64>,
+    pub hidden_sizes: Vec<Vec<u64>>,
+    pub activations: Vec<Activation>,
+}
+
+impl SearchSpace {
+    /// Returns every combination of the space, in order.
+    pub fn grid(&self) -> Vec<TrialConfig> {
+        let mut configs = Vec::new();
+
+        for learning_rate in &self.learning_rates {
+            for hidden_sizes in &self.hidden_sizes {
+                for activation in &self.activations {
+                    configs.push(TrialConfig {
+                        learning_rate: *learning_rate,
+                        hidden_sizes: hidden_sizes.clone(),
+                        activation: *activation,
+                    });
+                }
+            }
+        }
+
+        configs
+    }
+}
+
+/// TrialConfig is one point of a SearchSpace.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrialConfig {
+    pub learning_rate: f64,
+    pub hidden_sizes: Vec<u64>,
+    pub activation: Activation,
+}
+
+impl TrialConfig {
+    /// Builds a freshly initialised network with a hidden layer per entry of `hidden_sizes`, each
+    /// using `activation`, followed by a linear output layer.
+    pub fn network(&self, inputs: u64, outputs: u64) -> Result<Network, NetworkError> {
+        let mut layers = Vec::with_capacity(self.hidden_sizes.len() + 1);
+
+        let mut previous = inputs;
+        for size in &self.hidden_sizes {
+            layers.push(Layer::dense(previous, *size, self.activation, true)?);
+            previous = *size;
+        }
+        layers.push(Layer::dense(previous, outputs, Activation::Linear, true)?);
+
+        Network::new(layers)
+    }
+}
+
+impl fmt::Display for TrialConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "learning_rate={} hidden_sizes={:?} activation={}",
+            self.learning_rate,
+            self.hidden_sizes,
+            self.activation.to_str(),
+        )
+    }
+}
+
+/// Strategy decides which combinations of the search space are tried.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Strategy {
+    // Grid tries combinations in order, until the budget runs out.
+    Grid,
+
+    // Random tries distinct combinations in a random order determined by the seed.
+    Random { seed: u64 },
+}
+
+/// Trial is the outcome of training one configuration. `score` is the final validation loss, or
+/// the final training loss when there's no validation set; lower is better.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trial {
+    pub config: TrialConfig,
+    pub history: TrainingHistory,
+    pub score: f64,
+}
+
+/// TuneResult holds every trial which was run, along with the best one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TuneResult {
+    pub best: Trial,
+    pub trials: Vec<Trial>,
+}
+
+/// Tuner searches a SearchSpace for the configuration which trains best on a dataset. Each trial
+/// trains a new network for `epochs` epochs with mini-batches of `batch_size`, and at most `budget`
+/// trials are run.
+pub struct Tuner {
+    pub space: SearchSpace,
+    pub strategy: Strategy,
+    pub budget: usize,
+    pub epochs: usize,
+    pub batch_size: usize,
+}
+
+impl Tuner {
+    pub fn new(space: SearchSpace, budget: usize) -> Tuner {
+        Tuner {
+            space,
+            strategy: Strategy::Grid,
+            budget,
+            epochs: 10,
+            batch_size: 1,
+        }
+    }
+
+    /// Returns the configurations which will be tried, in order.
+    pub fn configs(&self) -> Vec<TrialConfig> {
+        let mut configs = self.space.grid();
+
+        if let Strategy::Random { seed } = self.strategy {
+            configs.shuffle(&mut StdRng::seed_from_u64(seed));
+        }
+
+        configs.truncate(self.budget);
+        configs
+    }
+
+    /// Runs the trials one after another, scoring them on `validation` (pass an empty slice to
+    /// score on the training loss instead).
+    pub fn run(&self, dataset: &Dataset, validation: &Dataset) -> Result<TuneResult, TuneError> {
+        let trials = self
+            .configs()
+            .into_iter()
+            .map(|config| self.trial(config, dataset, validation))
+            .collect::<Result<Vec<Trial>, TuneError>>()?;
+
+        best_of(trials)
+    }
+
+    /// Like run, but runs the trials concurrently on rayon's thread pool.
+    #[cfg(feature = "parallel")]
+    pub fn run_parallel(&self, dataset: &Dataset, validation: &Dataset) -> Result<TuneResult, TuneError> {
+        use rayon::prelude::*;
+
+        let trials = self
+            .configs()
+            .into_par_iter()
+            .map(|config| self.trial(config, dataset, validation))
+            .collect::<Result<Vec<Trial>, TuneError>>()?;
+
+        best_of(trials)
+    }
+
+    fn trial(&self, config: TrialConfig, dataset: &Dataset, validation: &Dataset) -> Result<Trial, TuneError> {
+        let (inputs, outputs) = match dataset.first() {
+            Some((input, target)) => (input.len() as u64, target.len() as u64),
+            None => return Err(TuneError::EmptyDataset),
+        };
+
+        let network = config.network(inputs, outputs)?;
+
+        let mut trainer = Trainer::new(Sgd::new(config.learning_rate));
+        trainer.batch_size = self.batch_size;
+
+        let history = trainer.fit_with_validation(&network, dataset, validation, self.epochs, &mut Silent)?;
+        let losses = if validation.is_empty() { &history.train_loss } else { &history.val_loss };
+        let score = losses.last().copied().unwrap_or(f64::INFINITY);
+
+        Ok(Trial { config, history, score })
+    }
+}
+
+// Picks the trial with the lowest score, treating NaN scores (diverged trials) as the worst.
+fn best_of(trials: Vec<Trial>) -> Result<TuneResult, TuneError> {
+    let score = |trial: &Trial| if trial.score.is_nan() { f64::INFINITY } else { trial.score };
+
+    let best = trials
+        .iter()
+        .min_by(|a, b| score(a).total_cmp(&score(b)))
+        .cloned()
+        .ok_or(TuneError::EmptySearchSpace)?;
+
+    Ok(TuneResult { best, trials })
+}
+
+/// TuneError represents a failure to run a hyperparameter search.
+#[derive(Debug, PartialEq)]
+pub enum TuneError {
+    // The search space or the budget allows no trials.
+    EmptySearchSpace,
+
+    // There are no training samples to infer the network's input and output sizes from.
+    EmptyDataset,
+
+    Network(NetworkError),
+}
+
+impl fmt::Display for TuneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TuneError::EmptySearchSpace => write!(f, "the search space has no configurations to try"),
+            TuneError::EmptyDataset => write!(f, "the dataset is empty"),
+            TuneError::Network(err) => write!(f, "network error: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for TuneError {}
+
+impl From<NetworkError> for TuneError {
+    fn from(err: NetworkError) -> Self {
+        TuneError::Network(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::toy::linear_regression;
+    use crate::network::Activation;
+    use crate::tune::{SearchSpace, Strategy, TuneError, Tuner};
+
+    fn space() -> SearchSpace {
+        SearchSpace {
+            learning_rates: vec![0.001, 0.1],
+            hidden_sizes: vec![vec![], vec![4]],
+            activations: vec![Activation::Relu, Activation::Linear],
+        }
+    }
+
+    #[test]
+    fn strategies_pick_configurations() {
+        let mut tuner = Tuner::new(space(), 3);
+        assert_eq!(space().grid().len(), 8);
+        assert_eq!(tuner.configs(), space().grid()[..3].to_vec());
+
+        tuner.strategy = Strategy::Random { seed: 7 };
+        let random = tuner.configs();
+        assert_eq!(random.len(), 3);
+        assert_eq!(random, tuner.configs());
+        assert!(random.iter().all(|config| space().grid().contains(config)));
+    }
+
+    #[test]
+    fn finds_the_better_learning_rate() {
+        let dataset = linear_regression(32, &[1.5, -2.0], 0.3, 0.0, 1);
+        let validation = linear_regression(16, &[1.5, -2.0], 0.3, 0.0, 2);
+
+        let mut tuner = Tuner::new(space(), 8);
+        tuner.space.hidden_sizes = vec![vec![]];
+        tuner.space.activations = vec![Activation::Linear];
+        tuner.epochs = 20;
+
+        let result = tuner.run(&dataset, &validation).unwrap();
+        assert_eq!(result.trials.len(), 2);
+        assert_eq!(result.best.config.learning_rate, 0.1);
+        assert_eq!(result.best.history.val_loss.len(), 20);
+        assert!(result.best.score < result.trials[0].score);
+
+        tuner.budget = 0;
+        assert_eq!(tuner.run(&dataset, &validation).err(), Some(TuneError::EmptySearchSpace));
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn parallel_search_runs_every_trial() {
+        let dataset = linear_regression(16, &[1.0], 0.0, 0.0, 3);
+
+        let mut tuner = Tuner::new(space(), 8);
+        tuner.epochs = 2;
+
+        let result = tuner.run_parallel(&dataset, &[]).unwrap();
+        assert_eq!(result.trials.len(), 8);
+        assert_eq!(result.trials.iter().map(|trial| trial.config.clone()).collect::<Vec<_>>(), space().grid());
+    }
+}
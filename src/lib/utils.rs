@@ -1,22 +1,81 @@
-use std::collections::HashMap;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fmt;
-use crate::value::{Value, build_topological_graph};
-use std::ops::{Mul, Div};
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::rc::Rc;
+use crate::network::Network;
+use crate::value::{InnerValue, Value, ValueOp, build_topological_graph};
+use crate::scalar::Scalar;
+use serde_json::json;
 
 
 /// Generates a GraphViz DOT format string for the computation graph
 /// rooted at `value`.
-pub fn to_dot_string<T>(value: &Value<T>) -> String
-where T: Div<Output=T> + Copy + 'static + Mul<f64, Output = f64> + Into<f64> + From<f64> + fmt::Display + fmt::Debug
-{
+pub fn to_dot_string<T: Scalar>(value: &Value<T>) -> String {
+    to_dot_string_with_options(value, &DotOptions::default())
+}
+
+/// DotOptions controls which nodes to_dot_string_with_options draws and how they're labelled,
+/// to keep the output readable for large graphs. The default options draw every node.
+#[derive(Debug, Clone, Default)]
+pub struct DotOptions {
+    // max_depth drops nodes more than this many operations away from the root
+    pub max_depth: Option<usize>,
+
+    // hide_constants drops nodes created with Value::constant and leaf nodes without a label, e.g.
+    // literals and inputs, keeping labelled leaves such as parameters
+    pub hide_constants: bool,
+
+    // precision is the number of decimal places for data and gradients, by default data is printed
+    // in full and gradients with 4 decimal places
+    pub precision: Option<usize>,
+
+    // color_by_gradient fills nodes with a heatmap of their gradient magnitude, relative to the
+    // largest gradient drawn
+    pub color_by_gradient: bool,
+}
+
+/// Generates a GraphViz DOT format string for the computation graph rooted at `value`, see DotOptions.
+pub fn to_dot_string_with_options<T: Scalar>(value: &Value<T>, options: &DotOptions) -> String {
     let topo = build_topological_graph(value);
 
+    // 1) Work out each node's distance from the root, walking the topological order backwards
+    let mut depths: HashMap<String, usize> = HashMap::new();
+    depths.insert(value.get_id(), 0);
+    for node in topo.iter().rev() {
+        let inner = node.borrow();
+        let depth = match depths.get(&inner.id) {
+            Some(depth) => *depth,
+            None => continue,
+        };
+
+        for ancestor in &inner.ancestors {
+            let ancestor_depth = depths.entry(ancestor.borrow().id.clone()).or_insert(depth + 1);
+            *ancestor_depth = (*ancestor_depth).min(depth + 1);
+        }
+    }
+
+    let is_drawn = |inner: &InnerValue<T>| {
+        let within_depth = options.max_depth.is_none_or(|max_depth| depths[&inner.id] <= max_depth);
+        let is_constant = inner.operation == ValueOp::Const || (inner.ancestors.is_empty() && inner.label.is_none());
+
+        within_depth && !(options.hide_constants && is_constant)
+    };
+
     // 2) Assign each node an integer ID for labeling
     let mut id_map = HashMap::new();
     for (i, node) in topo.iter().enumerate() {
         id_map.insert(node.borrow().id.clone(), i);
     }
 
+    let max_gradient = topo
+        .iter()
+        .filter(|node| is_drawn(&node.borrow()))
+        .map(|node| node.borrow().gradient.abs())
+        .fold(0.0, f64::max);
+
     // 3) Start building the DOT string
     let mut output = String::new();
     output.push_str("digraph G {\n");
@@ -24,24 +83,61 @@ where T: Div<Output=T> + Copy + 'static + Mul<f64, Output = f64> + Into<f64> + F
 
     // 4) For each node in the topological order, create:
     //    - A node label showing data, gradient, operation and id
-    //    - Edges from each ancestor -> this node
+    //    - Edges from each drawn ancestor -> this node
     for (i, node) in topo.iter().enumerate() {
         let inner = node.borrow();
+        if !is_drawn(&inner) {
+            continue;
+        }
 
-        // Build a label for this node.
-        let label = format!(
-            "data={} | grad={:.4} | operation={} |id={}",
-            inner.data,
-            inner.gradient,
-            inner.operation.to_str(),
-            inner.id,
-        );
+        // Build a label for this node, led by its own label when it has one.
+        let (data, gradient) = match options.precision {
+            Some(precision) => (
+                format!("{:.*}", precision, inner.data.to_f64()),
+                format!("{:.*}", precision, inner.gradient),
+            ),
+            None => (inner.data.to_string(), format!("{:.4}", inner.gradient)),
+        };
+
+        // Constants never have a gradient, so it's left out
+        let mut label = if inner.operation == ValueOp::Const {
+            format!("data={} | operation=const |id={}", data, inner.id)
+        } else {
+            format!(
+                "data={} | grad={} | operation={} |id={}",
+                data,
+                gradient,
+                inner.operation.to_str(),
+                inner.id,
+            )
+        };
+        if let Some(node_label) = &inner.label {
+            label = format!("{} | {}", node_label, label);
+        }
+
+        // Shade from white (no gradient) to red (the largest gradient). Constants are dashed
+        let dashed = inner.operation == ValueOp::Const;
+        let style = if options.color_by_gradient {
+            let intensity = if max_gradient > 0.0 { inner.gradient.abs() / max_gradient } else { 0.0 };
+            let shade = (255.0 * (1.0 - intensity)).round() as u8;
+            let style = if dashed { "\"filled,dashed\"" } else { "filled" };
+
+            format!(", style={}, fillcolor=\"#ff{:02x}{:02x}\"", style, shade, shade)
+        } else if dashed {
+            ", style=dashed".to_string()
+        } else {
+            String::new()
+        };
 
         // Create the node line, e.g.:  N0 [label="data=5 | grad=0.00 | ..."];
-        output.push_str(&format!("  N{} [shape=record, label=\"{}\"];\n", i, label));
+        output.push_str(&format!("  N{} [shape=record, label=\"{}\"{}];\n", i, label, style));
 
         // For each ancestor, create an edge: ancestor -> node
         for ancestor in &inner.ancestors {
+            if !is_drawn(&ancestor.borrow()) {
+                continue;
+            }
+
             let anc_id = id_map[&ancestor.borrow().id.clone()];
             
             // Draw arrow ancestor -> current node
@@ -53,17 +149,607 @@ where T: Div<Output=T> + Copy + 'static + Mul<f64, Output = f64> + Into<f64> + F
     output
 }
 
-pub fn write_graphiz_dot_file<T>(value: &Value<T>, output_name: &'static str)
-where T: Div<Output=T> + Copy + 'static + Mul<f64, Output = f64> + Into<f64> + From<f64> + fmt::Display + fmt::Debug
-{
+/// Generates a GraphViz DOT format string showing the layer topology of a network: one box per layer
+/// annotated with its shape, activation and parameter count. Unlike `to_dot_string`, which draws every
+/// scalar operation, this stays readable for networks of any size.
+pub fn network_to_dot<T: Scalar>(network: &Network<T>) -> String {
+    let mut output = String::new();
+    output.push_str("digraph G {\n");
+    output.push_str("  rankdir=\"LR\";\n");
+
+    let inputs = network.layers.first().map(|layer| layer.num_inputs()).unwrap_or(0);
+    output.push_str(&format!("  input [shape=record, label=\"input | shape=({})\"];\n", inputs));
+
+    let mut previous = "input".to_string();
+    for (i, layer) in network.layers.iter().enumerate() {
+        let label = format!(
+            "layer {} | {} | shape=({} -\\> {}) | activation={} | params={}",
+            i,
+            layer.layer_type(),
+            layer.num_inputs(),
+            layer.num_outputs(),
+            layer.activation().to_str(),
+            layer.num_parameters(),
+        );
+
+        output.push_str(&format!("  L{} [shape=record, label=\"{}\"];\n", i, label));
+        output.push_str(&format!("  {} -> L{};\n", previous, i));
+
+        previous = format!("L{}", i);
+    }
+
+    output.push_str("}\n");
+    output
+}
+
+/// Generates a Mermaid `graph LR` flowchart for the computation graph rooted at `value`.
+/// The output can be pasted into a `mermaid` code block in GitHub markdown to render the graph
+/// without needing Graphviz.
+pub fn to_mermaid_string<T: Scalar>(value: &Value<T>) -> String {
+    let topo = build_topological_graph(value);
+
+    let mut id_map = HashMap::new();
+    for (i, node) in topo.iter().enumerate() {
+        id_map.insert(node.borrow().id.clone(), i);
+    }
+
+    let mut output = String::new();
+    output.push_str("graph LR\n");
+
+    for (i, node) in topo.iter().enumerate() {
+        let inner = node.borrow();
+
+        // Quotes would terminate the label, so they're replaced with Mermaid's entity code
+        let label = format!(
+            "data={} | grad={:.4} | operation={} | id={}",
+            inner.data,
+            inner.gradient,
+            inner.operation.to_str(),
+            inner.id,
+        ).replace('"', "#quot;");
+
+        output.push_str(&format!("  N{}[\"{}\"]\n", i, label));
+
+        for ancestor in &inner.ancestors {
+            let anc_id = id_map[&ancestor.borrow().id];
+
+            output.push_str(&format!("  N{} --> N{}\n", anc_id, i));
+        }
+    }
+
+    output
+}
+
+/// Generates a JSON document describing the computation graph rooted at `value`, for use in web
+/// viewers such as D3 or Cytoscape which don't require Graphviz to be installed.
+/// The document has a `nodes` list (id, data, grad, op and ancestor ids, in topological order)
+/// and an `edges` list of `{source, target}` pairs pointing from each ancestor to the node it produced.
+pub fn to_json_graph<T: Scalar>(value: &Value<T>) -> String {
+    let topo = build_topological_graph(value);
+
+    let mut nodes = Vec::with_capacity(topo.len());
+    let mut edges = Vec::new();
+
+    for node in topo.iter() {
+        let inner = node.borrow();
+        let data = inner.data.to_f64();
+
+        let ancestors: Vec<String> = inner.ancestors.iter().map(|ancestor| ancestor.borrow().id.clone()).collect();
+
+        for ancestor in &ancestors {
+            edges.push(json!({
+                "source": ancestor,
+                "target": inner.id,
+            }));
+        }
+
+        nodes.push(json!({
+            "id": inner.id,
+            "data": data,
+            "grad": inner.gradient,
+            "op": inner.operation.to_str(),
+            "ancestors": ancestors,
+        }));
+    }
+
+    json!({
+        "nodes": nodes,
+        "edges": edges,
+    }).to_string()
+}
+
+/// Writes the DOT string for the computation graph rooted at `value` to `path`.
+pub fn write_graphiz_dot_file<T: Scalar>(value: &Value<T>, path: impl AsRef<Path>) -> io::Result<()> {
     let dot_str = to_dot_string(value);
-    std::fs::write(output_name, dot_str).unwrap();
+    std::fs::write(path, dot_str)
+}
+
+/// GraphStats summarises the size and shape of a computation graph.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GraphStats {
+    pub node_count: usize,
+
+    // depth is the length of the longest chain of operations from a leaf to the root
+    pub depth: usize,
+
+    // op_counts maps each operation (see ValueOp::to_str) to the number of nodes it produced
+    pub op_counts: BTreeMap<&'static str, usize>,
+
+    // memory_bytes is an estimate of the heap memory held by the graph's nodes
+    pub memory_bytes: usize,
+}
+
+impl fmt::Display for GraphStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "nodes: {}", self.node_count)?;
+        writeln!(f, "depth: {}", self.depth)?;
+        writeln!(f, "memory: ~{:.1} KiB", self.memory_bytes as f64 / 1024.0)?;
+        writeln!(f, "operations:")?;
+
+        for (op, count) in &self.op_counts {
+            writeln!(f, "  {}: {}", op, count)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Computes statistics for the computation graph rooted at `value`, which is useful to see how large
+/// a graph has grown, e.g. when a training loop slows down.
+pub fn graph_stats<T: Scalar>(value: &Value<T>) -> GraphStats {
+    let topo = build_topological_graph(value);
+
+    let mut depths: HashMap<String, usize> = HashMap::new();
+    let mut op_counts = BTreeMap::new();
+    let mut memory_bytes = 0;
+
+    for node in topo.iter() {
+        let inner = node.borrow();
+
+        // Ancestors come before their descendants in topological order, so their depth is already known.
+        let depth = inner
+            .ancestors
+            .iter()
+            .map(|ancestor| depths[&ancestor.borrow().id] + 1)
+            .max()
+            .unwrap_or(0);
+        depths.insert(inner.id.clone(), depth);
+
+        *op_counts.entry(inner.operation.to_str()).or_insert(0) += 1;
+
+        // Each node is an Rc allocation (strong and weak counts plus the RefCell) with heap-allocated
+        // ancestor references and id.
+        memory_bytes += 2 * std::mem::size_of::<usize>()
+            + std::mem::size_of::<RefCell<InnerValue<T>>>()
+            + inner.ancestors.capacity() * std::mem::size_of::<Rc<RefCell<InnerValue<T>>>>()
+            + inner.id.capacity();
+    }
+
+    GraphStats {
+        node_count: topo.len(),
+        depth: depths[&value.get_id()],
+        op_counts,
+        memory_bytes,
+    }
+}
+
+/// GraphDifference is one way two computation graphs differ, see diff_graphs. `path` locates the
+/// node from the root, as the ancestor index taken at each step, e.g. "root.1.0".
+#[derive(Debug, Clone, PartialEq)]
+pub enum GraphDifference {
+    // The nodes were produced by different operations; their ancestors aren't compared.
+    Operation { path: String, left: &'static str, right: &'static str },
+
+    // The nodes have a different number of ancestors. Ancestors present in both are still compared.
+    Ancestors { path: String, left: usize, right: usize },
+
+    Data { path: String, left: f64, right: f64 },
+    Gradient { path: String, left: f64, right: f64 },
+}
+
+impl fmt::Display for GraphDifference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GraphDifference::Operation { path, left, right } => write!(f, "{}: operation {} vs {}", path, left, right),
+            GraphDifference::Ancestors { path, left, right } => write!(f, "{}: {} ancestors vs {}", path, left, right),
+            GraphDifference::Data { path, left, right } => write!(f, "{}: data {} vs {}", path, left, right),
+            GraphDifference::Gradient { path, left, right } => write!(f, "{}: gradient {} vs {}", path, left, right),
+        }
+    }
+}
+
+/// Compares the computation graphs rooted at `a` and `b` node by node, walking both from the
+/// root through ancestors in order. Node ids are ignored, so graphs built separately from the
+/// same expression have no differences. Data and gradients are compared with an absolute
+/// `tolerance`.
+///
+/// A pair of nodes reached by several paths, e.g. a node shared by several descendants, is
+/// compared once, and its differences are reported under the first path reaching it.
+pub fn diff_graphs<T: Scalar>(a: &Value<T>, b: &Value<T>, tolerance: f64) -> Vec<GraphDifference> {
+    let mut differences = Vec::new();
+    diff_nodes(a, b, "root".to_string(), tolerance, &mut HashSet::new(), &mut differences);
+
+    differences
+}
+
+// NodePair identifies a pair of nodes compared by diff_nodes
+type NodePair<T> = (*const RefCell<InnerValue<T>>, *const RefCell<InnerValue<T>>);
+
+fn diff_nodes<T: Scalar>(
+    a: &Rc<RefCell<InnerValue<T>>>,
+    b: &Rc<RefCell<InnerValue<T>>>,
+    path: String,
+    tolerance: f64,
+    visited: &mut HashSet<NodePair<T>>,
+    differences: &mut Vec<GraphDifference>,
+) {
+    if !visited.insert((Rc::as_ptr(a), Rc::as_ptr(b))) {
+        return;
+    }
+    let (a, b) = (a.borrow(), b.borrow());
+
+    if a.operation != b.operation {
+        differences.push(GraphDifference::Operation { path, left: a.operation.to_str(), right: b.operation.to_str() });
+        return;
+    }
+
+    let (left, right) = (a.data.to_f64(), b.data.to_f64());
+    if (left - right).abs() > tolerance {
+        differences.push(GraphDifference::Data { path: path.clone(), left, right });
+    }
+    if (a.gradient - b.gradient).abs() > tolerance {
+        differences.push(GraphDifference::Gradient { path: path.clone(), left: a.gradient, right: b.gradient });
+    }
+    if a.ancestors.len() != b.ancestors.len() {
+        differences.push(GraphDifference::Ancestors { path: path.clone(), left: a.ancestors.len(), right: b.ancestors.len() });
+    }
+
+    for (index, (a, b)) in a.ancestors.iter().zip(&b.ancestors).enumerate() {
+        diff_nodes(a, b, format!("{}.{}", path, index), tolerance, visited, differences);
+    }
+}
+
+type Node<T> = Rc<RefCell<InnerValue<T>>>;
+
+// A constant is a frozen leaf: it never receives a gradient, so folding it changes nothing upstream
+fn is_constant<T>(node: &Node<T>) -> bool {
+    let node = node.borrow();
+    node.ancestors.is_empty() && !node.requires_grad
+}
+
+fn constant<T: Scalar>(data: T) -> Node<T> {
+    Rc::clone(&Value::constant(data))
+}
+
+// rebuild creates a fresh node applying `operation` to `ancestors`, with its data computed
+fn rebuild<T: Scalar>(operation: ValueOp, ancestors: Vec<Node<T>>, label: Option<String>) -> Value<T> {
+    let value = Value::new(ancestors[0].borrow().data);
+    {
+        let mut inner = value.borrow_mut();
+        inner.operation = operation;
+        inner.ancestors = ancestors;
+        inner.label = label;
+    }
+    value.recompute();
+
+    value
+}
+
+/// Returns a compacted copy of the graph rooted at `value`, so the original graph, and every
+/// intermediate result it keeps alive, can be dropped:
+///
+/// - only nodes contributing to `value` are copied
+/// - operations whose operands are all constants (see Value::constant, or leaves frozen with
+///   Value::set_requires_grad) are folded into a single constant
+/// - chains adding constants, e.g. (x + c1) + c2, are fused into x + (c1 + c2)
+///
+/// Leaves other than constants are shared with the original graph rather than copied, so
+/// backpropagating through the pruned graph still reaches the parameters. Its data matches the
+/// original up to rounding from the reordered additions; gradients and hooks aren't copied.
+pub fn prune<T: Scalar>(value: &Value<T>) -> Value<T> {
+    let mut copies: HashMap<String, Node<T>> = HashMap::new();
+    let mut root = value.clone();
+
+    for node in build_topological_graph(value) {
+        let inner = node.borrow();
+        if inner.ancestors.is_empty() {
+            copies.insert(inner.id.clone(), Rc::clone(&node));
+            continue;
+        }
+
+        let ancestors: Vec<Node<T>> = inner.ancestors.iter().map(|ancestor| Rc::clone(&copies[&ancestor.borrow().id])).collect();
+
+        let copy = if ancestors.iter().all(is_constant) {
+            Value::constant(rebuild(inner.operation, ancestors, None).get_data())
+        } else {
+            match fuse_constant_addition(inner.operation, &ancestors) {
+                Some((rest, sum)) => rebuild(ValueOp::Addition, vec![rest, sum], inner.label.clone()),
+                None => rebuild(inner.operation, ancestors, inner.label.clone()),
+            }
+        };
+
+        copies.insert(inner.id.clone(), Rc::clone(&copy));
+        root = copy;
+    }
+
+    root
+}
+
+// For an addition of a constant and another addition of a constant, returns the non-constant
+// operand of the inner addition and a constant holding the sum of both constants.
+fn fuse_constant_addition<T: Scalar>(operation: ValueOp, ancestors: &[Node<T>]) -> Option<(Node<T>, Node<T>)> {
+    if operation != ValueOp::Addition {
+        return None;
+    }
+
+    let (outer_constant, inner) = match (is_constant(&ancestors[0]), is_constant(&ancestors[1])) {
+        (true, false) => (&ancestors[0], &ancestors[1]),
+        (false, true) => (&ancestors[1], &ancestors[0]),
+        _ => return None,
+    };
+
+    let inner = inner.borrow();
+    if inner.operation != ValueOp::Addition {
+        return None;
+    }
+
+    let (inner_constant, rest) = match (is_constant(&inner.ancestors[0]), is_constant(&inner.ancestors[1])) {
+        (true, false) => (&inner.ancestors[0], &inner.ancestors[1]),
+        (false, true) => (&inner.ancestors[1], &inner.ancestors[0]),
+        _ => return None,
+    };
+
+    let sum = constant(outer_constant.borrow().data + inner_constant.borrow().data);
+
+    Some((Rc::clone(rest), sum))
+}
+
+/// GraphFormat is an image format a computation graph can be rendered to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GraphFormat {
+    Svg,
+    Png,
+}
+
+impl GraphFormat {
+    pub fn to_str(&self) -> &'static str {
+        match self {
+            GraphFormat::Svg => "svg",
+            GraphFormat::Png => "png",
+        }
+    }
+}
+
+/// RenderError represents a failure to render a computation graph to an image.
+#[derive(Debug)]
+pub enum RenderError {
+    Io(io::Error),
+
+    // The `dot` binary isn't installed and the requested format has no built-in fallback.
+    GraphvizNotFound(GraphFormat),
+
+    // `dot` exited unsuccessfully, with its stderr output.
+    Graphviz(String),
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RenderError::Io(err) => write!(f, "io error: {}", err),
+            RenderError::GraphvizNotFound(format) => write!(
+                f,
+                "rendering to {} requires the Graphviz `dot` binary, install Graphviz or render to svg instead",
+                format.to_str()
+            ),
+            RenderError::Graphviz(stderr) => write!(f, "dot failed: {}", stderr),
+        }
+    }
+}
+
+impl std::error::Error for RenderError {}
+
+impl From<io::Error> for RenderError {
+    fn from(err: io::Error) -> Self {
+        RenderError::Io(err)
+    }
+}
+
+// Layout constants for the built-in SVG renderer
+const SVG_NODE_WIDTH: usize = 200;
+const SVG_NODE_HEIGHT: usize = 72;
+const SVG_COLUMN_GAP: usize = 80;
+const SVG_ROW_GAP: usize = 24;
+const SVG_MARGIN: usize = 20;
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// Lays out the computation graph in columns from left to right, where each node's column is one
+/// past the furthest of its ancestors, and draws it as an SVG document.
+fn to_svg_string<T: Scalar>(value: &Value<T>) -> String {
+    let topo = build_topological_graph(value);
+
+    // Ancestors always come before their descendants in topological order, so a single pass assigns columns.
+    let mut positions: HashMap<String, (usize, usize)> = HashMap::new();
+    let mut column_sizes: Vec<usize> = Vec::new();
+
+    for node in topo.iter() {
+        let inner = node.borrow();
+        let column = inner
+            .ancestors
+            .iter()
+            .map(|ancestor| positions[&ancestor.borrow().id].0 + 1)
+            .max()
+            .unwrap_or(0);
+
+        if column_sizes.len() <= column {
+            column_sizes.resize(column + 1, 0);
+        }
+        positions.insert(inner.id.clone(), (column, column_sizes[column]));
+        column_sizes[column] += 1;
+    }
+
+    let coordinates = |(column, row): (usize, usize)| {
+        (
+            SVG_MARGIN + column * (SVG_NODE_WIDTH + SVG_COLUMN_GAP),
+            SVG_MARGIN + row * (SVG_NODE_HEIGHT + SVG_ROW_GAP),
+        )
+    };
+
+    let rows = column_sizes.iter().copied().max().unwrap_or(0);
+    let width = 2 * SVG_MARGIN + column_sizes.len() * (SVG_NODE_WIDTH + SVG_COLUMN_GAP) - SVG_COLUMN_GAP;
+    let height = 2 * SVG_MARGIN + rows * (SVG_NODE_HEIGHT + SVG_ROW_GAP) - SVG_ROW_GAP;
+
+    let mut output = String::new();
+    output.push_str(&format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" font-family=\"monospace\" font-size=\"12\">\n",
+        width, height
+    ));
+    output.push_str("  <defs><marker id=\"arrow\" markerWidth=\"10\" markerHeight=\"10\" refX=\"10\" refY=\"5\" orient=\"auto\"><path d=\"M0,0 L10,5 L0,10 z\"/></marker></defs>\n");
+
+    for node in topo.iter() {
+        let inner = node.borrow();
+        let (x, y) = coordinates(positions[&inner.id]);
+
+        for ancestor in &inner.ancestors {
+            let (ax, ay) = coordinates(positions[&ancestor.borrow().id]);
+
+            output.push_str(&format!(
+                "  <line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"black\" marker-end=\"url(#arrow)\"/>\n",
+                ax + SVG_NODE_WIDTH, ay + SVG_NODE_HEIGHT / 2, x, y + SVG_NODE_HEIGHT / 2
+            ));
+        }
+
+        output.push_str(&format!(
+            "  <rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"white\" stroke=\"black\"/>\n",
+            x, y, SVG_NODE_WIDTH, SVG_NODE_HEIGHT
+        ));
+
+        let lines = [
+            format!("data={}", inner.data),
+            format!("grad={:.4}", inner.gradient),
+            format!("operation={}", inner.operation.to_str()),
+            format!("id={}", inner.id),
+        ];
+        for (line_number, line) in lines.iter().enumerate() {
+            output.push_str(&format!(
+                "  <text x=\"{}\" y=\"{}\">{}</text>\n",
+                x + 8, y + 18 + line_number * 16, escape_xml(line)
+            ));
+        }
+    }
+
+    output.push_str("</svg>\n");
+    output
+}
+
+/// Renders the computation graph rooted at `value` directly to an image file.
+///
+/// The Graphviz `dot` binary is used when it's installed. Otherwise SVG output falls back to a
+/// built-in layout, while PNG output returns `RenderError::GraphvizNotFound`.
+pub fn render_graph<T: Scalar>(value: &Value<T>, path: impl AsRef<Path>, format: GraphFormat) -> Result<(), RenderError> {
+    let spawned = Command::new("dot")
+        .arg(format!("-T{}", format.to_str()))
+        .arg("-o")
+        .arg(path.as_ref())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn();
+
+    let mut child = match spawned {
+        Ok(child) => child,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            return match format {
+                GraphFormat::Svg => Ok(std::fs::write(path, to_svg_string(value))?),
+                GraphFormat::Png => Err(RenderError::GraphvizNotFound(format)),
+            };
+        }
+        Err(err) => return Err(err.into()),
+    };
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(to_dot_string(value).as_bytes())?;
+    }
+
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(RenderError::Graphviz(String::from_utf8_lossy(&output.stderr).trim().to_string()));
+    }
+
+    Ok(())
+}
+
+/// TraceStep records one operation of a forward pass: the node it produced, the data of each of
+/// its operands and its result.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceStep {
+    pub id: String,
+    pub label: Option<String>,
+    pub operation: &'static str,
+
+    // operands holds the name (label, or id when unlabelled) and data of each ancestor, in order
+    pub operands: Vec<(String, f64)>,
+    pub result: f64,
+}
+
+/// Trace is the ordered log of the operations which produced a value, see trace_forward.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Trace {
+    pub steps: Vec<TraceStep>,
+}
+
+impl Trace {
+    /// Writes the trace to `path`, one operation per line.
+    pub fn write(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        std::fs::write(path, self.to_string())
+    }
+}
+
+impl fmt::Display for Trace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, step) in self.steps.iter().enumerate() {
+            let operands: Vec<String> = step.operands.iter().map(|(name, data)| format!("{}={}", name, data)).collect();
+            let name = step.label.as_ref().unwrap_or(&step.id);
+
+            writeln!(f, "{:>4}  {} = {}({}) -> {}", index, name, step.operation, operands.join(", "), step.result)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Lists every operation which contributed to `value`, in the order they were evaluated: each
+/// operation comes after the operations producing its operands, and `value` itself comes last.
+/// Leaves (parameters, inputs and constants) only appear as operands.
+pub fn trace_forward<T: Scalar>(value: &Value<T>) -> Trace {
+    let name = |node: &InnerValue<T>| node.label.clone().unwrap_or_else(|| node.id.clone());
+
+    let steps = build_topological_graph(value)
+        .iter()
+        .map(|node| node.borrow())
+        .filter(|node| !node.ancestors.is_empty())
+        .map(|node| TraceStep {
+            id: node.id.clone(),
+            label: node.label.clone(),
+            operation: node.operation.to_str(),
+            operands: node.ancestors.iter().map(|ancestor| {
+                let ancestor = ancestor.borrow();
+                (name(&ancestor), ancestor.data.to_f64())
+            }).collect(),
+            result: node.data.to_f64(),
+        })
+        .collect();
+
+    Trace { steps }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::value::{Value};
-    use crate::utils::{write_graphiz_dot_file};
+    use crate::network::{Activation, Layer, Network};
+    use crate::utils::{diff_graphs, graph_stats, prune, network_to_dot, render_graph, to_dot_string, to_dot_string_with_options, to_json_graph, to_mermaid_string, to_svg_string, trace_forward, write_graphiz_dot_file, DotOptions, GraphDifference, GraphFormat};
     
     #[test]
     fn render_topological_graph() {
@@ -75,6 +761,275 @@ mod tests {
         let d = &c * b;       // d = c * b = 12
         let z = &d / a;       // z = d / a = 3
 
-        write_graphiz_dot_file(&z, "graph.dot");
+        // Paths can be built at runtime, e.g. for per-epoch dumps
+        for epoch in 0..2 {
+            let path = std::env::temp_dir().join(format!("backprop_graph_epoch_{}.dot", epoch));
+            write_graphiz_dot_file(&z, &path).unwrap();
+
+            assert_eq!(std::fs::read_to_string(&path).unwrap(), to_dot_string(&z));
+        }
+
+        let missing_directory = std::env::temp_dir().join("backprop_missing_directory").join("graph.dot");
+        assert!(write_graphiz_dot_file(&z, missing_directory).is_err());
+    }
+
+    #[test]
+    fn dot_output_shows_labels() {
+        let w = Value::new(0.5).with_label("w1");
+        let x = Value::new(2.0);
+
+        let loss = (&w * &x).with_label("loss");
+        assert_eq!(loss.get_label().as_deref(), Some("loss"));
+        assert_eq!(x.get_label(), None);
+
+        let dot = to_dot_string(&loss);
+        assert!(dot.contains("label=\"w1 | data=0.5 |"));
+        assert!(dot.contains("label=\"loss | data=1 |"));
+        assert!(dot.contains("label=\"data=2 |"));
+    }
+
+    #[test]
+    fn dot_options_filter_and_format_nodes() {
+        let w = Value::new_with_id(0.5, "w").with_label("w");
+        let x = Value::new_with_id(2.0, "x");
+        let b = Value::new_with_id(1.0, "b").with_label("b");
+
+        let y = &(&w * &x) + &b;
+        y.borrow_mut().id = "y".to_string();
+        y.run_grad();
+
+        assert_eq!(to_dot_string_with_options(&y, &DotOptions::default()), to_dot_string(&y));
+
+        let options = DotOptions { hide_constants: true, precision: Some(1), ..DotOptions::default() };
+        let dot = to_dot_string_with_options(&y, &options);
+        assert!(!dot.contains("id=x"));
+        assert!(dot.contains("w | data=0.5 | grad=2.0 |"));
+        assert_eq!(dot.matches("->").count(), 3);
+
+        let scaled = &w * &Value::constant(4.0);
+        let dot = to_dot_string(&scaled);
+        assert!(dot.contains("label=\"data=4 | operation=const |id=") && dot.contains("style=dashed"));
+        assert_eq!(to_dot_string_with_options(&scaled, &options).matches("->").count(), 1);
+
+        let options = DotOptions { max_depth: Some(1), color_by_gradient: true, ..DotOptions::default() };
+        let dot = to_dot_string_with_options(&y, &options);
+        assert!(!dot.contains("id=w") && !dot.contains("id=x"));
+        assert!(dot.contains("id=y\", style=filled, fillcolor=\"#ff0000\""));
+
+        // x has the smallest gradient, a quarter of w's
+        let dot = to_dot_string_with_options(&y, &DotOptions { color_by_gradient: true, ..DotOptions::default() });
+        assert!(dot.contains("id=x\", style=filled, fillcolor=\"#ffbfbf\""));
+        assert!(dot.contains("id=w\", style=filled, fillcolor=\"#ff0000\""));
+    }
+
+    #[test]
+    fn render_json_graph() {
+        let a = &Value::new_with_id(4.0, "a");
+        let b = &Value::new_with_id(2.0, "b");
+
+        let c = a * b;
+        c.borrow_mut().id = "c".to_string();
+
+        c.run_grad();
+
+        let graph: serde_json::Value = serde_json::from_str(&to_json_graph(&c)).unwrap();
+
+        let nodes = graph["nodes"].as_array().unwrap();
+        assert_eq!(nodes.len(), 3);
+        assert_eq!(nodes[2]["id"], "c");
+        assert_eq!(nodes[2]["op"], "*");
+        assert_eq!(nodes[2]["data"], 8.0);
+        assert_eq!(nodes[2]["ancestors"], serde_json::json!(["a", "b"]));
+        assert_eq!(nodes[0]["grad"], 2.0);
+
+        let edges = graph["edges"].as_array().unwrap();
+        assert_eq!(edges.len(), 2);
+        assert_eq!(edges[0], serde_json::json!({"source": "a", "target": "c"}));
+    }
+
+    #[test]
+    fn render_mermaid_graph() {
+        let a = &Value::new_with_id(4.0, "a");
+        let b = &Value::new_with_id(2.0, "b");
+
+        let c = a + b;
+        c.borrow_mut().id = "c".to_string();
+
+        let mermaid = to_mermaid_string(&c);
+
+        assert_eq!(mermaid, concat!(
+            "graph LR\n",
+            "  N0[\"data=4 | grad=0.0000 | operation=none | id=a\"]\n",
+            "  N1[\"data=2 | grad=0.0000 | operation=none | id=b\"]\n",
+            "  N2[\"data=6 | grad=0.0000 | operation=+ | id=c\"]\n",
+            "  N0 --> N2\n",
+            "  N1 --> N2\n",
+        ));
+    }
+
+    #[test]
+    fn render_svg_without_graphviz() {
+        let a = &Value::new_with_id(4.0, "a");
+        let b = &Value::new_with_id(2.0, "b");
+
+        let c = a * b;
+        let d = &c + a;
+
+        let svg = to_svg_string(&d);
+
+        assert!(svg.starts_with("<svg"));
+        assert_eq!(svg.matches("<rect").count(), 4);
+        assert_eq!(svg.matches("<line").count(), 4);
+        assert!(svg.contains("id=a"));
+    }
+
+    #[test]
+    fn render_graph_to_svg_file() {
+        let path = std::env::temp_dir().join("backprop_render_graph.svg");
+
+        let a = &Value::new(4.0);
+        let b = &Value::new(2.0);
+        let c = a / b;
+
+        render_graph(&c, &path, GraphFormat::Svg).unwrap();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert!(contents.contains("<svg"));
+    }
+
+    #[test]
+    fn render_network_architecture() {
+        let network: Network = Network{
+            layers: vec![
+                Layer::dense(3, 4, Activation::Relu, true).unwrap(),
+                Layer::dense(4, 1, Activation::Linear, false).unwrap(),
+            ],
+        };
+
+        let dot = network_to_dot(&network);
+
+        assert!(dot.contains("input [shape=record, label=\"input | shape=(3)\"];"));
+        assert!(dot.contains("L0 [shape=record, label=\"layer 0 | dense | shape=(3 -\\> 4) | activation=relu | params=16\"];"));
+        assert!(dot.contains("L1 [shape=record, label=\"layer 1 | dense | shape=(4 -\\> 1) | activation=linear | params=4\"];"));
+        assert!(dot.contains("input -> L0;"));
+        assert!(dot.contains("L0 -> L1;"));
+    }
+
+    #[test]
+    fn compute_graph_stats() {
+        let a = &Value::new(4.0);
+        let b = &Value::new(2.0);
+
+        let c = a + b;       // c = a + b = 6
+        let d = &c * b;       // d = c * b = 12
+        let z = &d / a;       // z = d / a = 3
+
+        let stats = graph_stats(&z);
+
+        assert_eq!(stats.node_count, 5);
+        assert_eq!(stats.depth, 3);
+        assert_eq!(stats.op_counts["none"], 2);
+        assert_eq!(stats.op_counts["+"], 1);
+        assert_eq!(stats.op_counts["*"], 1);
+        assert_eq!(stats.op_counts["/"], 1);
+        assert!(stats.memory_bytes > 0);
+
+        assert!(stats.to_string().starts_with("nodes: 5\ndepth: 3\n"));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn trace_lists_operations_in_evaluation_order() {
+        let w = Value::new(3.0);
+        w.borrow_mut().label = Some("w".to_string());
+        let x = Value::new(2.0);
+        x.borrow_mut().label = Some("x".to_string());
+
+        let product = &w * &x;
+        let y = (&product + &w).relu();
+        y.borrow_mut().label = Some("y".to_string());
+
+        let trace = trace_forward(&y);
+        let operations: Vec<&str> = trace.steps.iter().map(|step| step.operation).collect();
+        assert_eq!(operations, vec!["*", "+", "relu"]);
+
+        assert_eq!(trace.steps[0].operands, vec![("w".to_string(), 3.0), ("x".to_string(), 2.0)]);
+        assert_eq!(trace.steps[1].operands[0], (product.borrow().id.clone(), 6.0));
+        assert_eq!(trace.steps[2].result, 9.0);
+
+        let path = std::env::temp_dir().join("backprop_trace.txt");
+        trace.write(&path).unwrap();
+        let written = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(written.lines().count(), 3);
+        assert!(written.lines().next().unwrap().ends_with("*(w=3, x=2) -> 6"));
+        assert!(written.lines().last().unwrap().contains("y = relu("));
+    }
+
+    #[test]
+    fn diff_reports_structural_and_numerical_differences() {
+        let build = |x: f64, extra: bool| {
+            let x = Value::new(x);
+            let y = &(&x * &Value::new(2.0)) + &Value::new(1.0);
+            let y = if extra { y.relu() } else { y.tanh() };
+            y.run_grad();
+            y
+        };
+
+        // Same expression, separately built nodes
+        assert!(diff_graphs(&build(0.5, true), &build(0.5, true), 1e-12).is_empty());
+
+        let differences = diff_graphs(&build(0.5, true), &build(0.5 + 1e-3, true), 1e-6);
+        assert_eq!(differences.len(), 5);
+        assert!(matches!(&differences[0], GraphDifference::Data { path, .. } if path == "root"));
+        assert!(matches!(&differences[3], GraphDifference::Data { path, .. } if path == "root.0.0.0"));
+        // The constant 2 receives x as its gradient
+        assert!(matches!(&differences[4], GraphDifference::Gradient { path, .. } if path == "root.0.0.1"));
+
+        let differences = diff_graphs(&build(0.5, true), &build(0.5, false), 1e-6);
+        assert_eq!(differences, vec![GraphDifference::Operation { path: "root".to_string(), left: "relu", right: "tanh" }]);
+
+        let a = Value::new(1.0) + Value::new(2.0);
+        let b = Value::new(3.0).relu();
+        assert_eq!(
+            diff_graphs(&a, &b, 0.0),
+            vec![GraphDifference::Operation { path: "root".to_string(), left: "+", right: "relu" }]
+        );
+        assert_eq!(differences[0].to_string(), "root: operation relu vs tanh");
+
+        // Doubling 64 times reaches the leaf through 2^64 paths, but each pair is compared once
+        let doubled = |x: f64| (0..64).fold(Value::new(x), |y, _| &y + &y);
+        assert!(diff_graphs(&doubled(1.0), &doubled(1.0), 0.0).is_empty());
+        let differences = diff_graphs(&doubled(1.0), &doubled(2.0), 0.0);
+        assert_eq!(differences.len(), 65);
+        assert!(matches!(&differences[64], GraphDifference::Data { path, .. } if path.matches(".0").count() == 64));
+    }
+
+    #[test]
+    fn prune_folds_constants_and_keeps_parameters() {
+        let constant = Value::<f64>::constant;
+
+        let w = Value::new(1.5);
+        let x = Value::new(-2.0);
+
+        // ((w * x + 1) + 2) + (3 * 4), where 3 * 4 folds and the additions fuse
+        let shifted = &(&(&w * &x) + &constant(1.0)) + &constant(2.0);
+        let y = (&shifted + &(&constant(3.0) * &constant(4.0))).tanh();
+
+        let pruned = prune(&y);
+        assert!((pruned.get_data() - y.get_data()).abs() < 1e-12);
+        assert!(graph_stats(&pruned).node_count < graph_stats(&y).node_count);
+        assert_eq!(graph_stats(&pruned).node_count, 6);
+
+        // Gradients still reach the original parameters
+        y.run_grad();
+        let expected = (w.get_gradient(), x.get_gradient());
+        w.set_gradient(0.0);
+        x.set_gradient(0.0);
+        pruned.run_grad();
+        assert!((w.get_gradient() - expected.0).abs() < 1e-12);
+        assert!((x.get_gradient() - expected.1).abs() < 1e-12);
+
+        // Unfrozen literals may be trained, so they're left alone
+        let z = &(&w + &Value::new(1.0)) + &Value::new(2.0);
+        assert_eq!(graph_stats(&prune(&z)).node_count, graph_stats(&z).node_count);
+    }
+}
@@ -1,20 +1,85 @@
 use std::ops::{Add, Sub, Mul, Div, Deref};
 use std::fmt;
 use std::rc::Rc;
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
 use rand::Rng;
+use serde::de::{self, Deserializer};
+use serde::ser::Serializer;
+use serde::{Deserialize, Serialize};
+use crate::error::BackpropError;
+use crate::graph::Graph;
+use crate::kernels;
+use crate::scalar::Scalar;
+
+thread_local! {
+    // Whether produced data and gradients are checked for NaN/Inf, see Value::enable_anomaly_detection
+    static ANOMALY_DETECTION: Cell<bool> = const { Cell::new(false) };
+
+    // The tapes recording on this thread, innermost last, see Tape
+    static TAPES: RefCell<Vec<Recording>> = const { RefCell::new(Vec::new()) };
+}
+
+pub(crate) fn anomaly_detection_enabled() -> bool {
+    ANOMALY_DETECTION.with(|enabled| enabled.get())
+}
+
+// accumulate_gradient adds `delta` to the node's gradient, unless the node is frozen.
+fn accumulate_gradient<T>(node: &Rc<RefCell<InnerValue<T>>>, delta: f64) {
+    let mut inner = node.borrow_mut();
+    if inner.requires_grad && inner.operation != ValueOp::Const {
+        inner.gradient += delta;
+    }
+}
+
+fn describe_ancestors<T: fmt::Display + fmt::Debug>(ancestors: &[Rc<RefCell<InnerValue<T>>>]) -> String {
+    ancestors
+        .iter()
+        .map(|ancestor| format!("({})", ancestor.borrow()))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
 
 /// ValueOp represents an arithmetic operation that can be performed on 1 or more Value types.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum ValueOp {
     Addition,
     Subtraction,
     Multiplication,
     Division,
+    Relu,
+    // LeakyRelu keeps its negative slope as a constant second ancestor, so ValueOp stays hashable
+    LeakyRelu,
+    Softplus,
+    Gelu,
+    Elu,
+    Swish,
+    Exp,
+    Ln,
+    Sqrt,
+    Abs,
+    Tanh,
+    Sigmoid,
+    Max,
+    Min,
+    // Dot holds two equally long vectors as its ancestors, one after the other, see value::dot
+    Dot,
+    // Axpy holds a, x and y as its ancestors, see value::axpy
+    Axpy,
+    // Const marks a leaf which never receives a gradient, see Value::constant
+    Const,
     None,
 }
 
+// GELU is computed with its tanh approximation, 0.5x(1 + tanh(sqrt(2/pi)(x + 0.044715x^3)))
+const GELU_SCALE: f64 = 0.7978845608028654;
+const GELU_CUBIC: f64 = 0.044715;
+
+fn sigmoid(x: f64) -> f64 {
+    1.0 / (1.0 + (-x).exp())
+}
+
 impl ValueOp {
     pub fn to_str(&self) -> &'static str {
         match self{
@@ -22,9 +87,204 @@ impl ValueOp {
             ValueOp::Subtraction => "-",
             ValueOp::Multiplication => "*",
             ValueOp::Division => "/",
+            ValueOp::Relu => "relu",
+            ValueOp::LeakyRelu => "leaky_relu",
+            ValueOp::Softplus => "softplus",
+            ValueOp::Gelu => "gelu",
+            ValueOp::Elu => "elu",
+            ValueOp::Swish => "swish",
+            ValueOp::Exp => "exp",
+            ValueOp::Ln => "ln",
+            ValueOp::Sqrt => "sqrt",
+            ValueOp::Abs => "abs",
+            ValueOp::Tanh => "tanh",
+            ValueOp::Sigmoid => "sigmoid",
+            ValueOp::Max => "max",
+            ValueOp::Min => "min",
+            ValueOp::Dot => "dot",
+            ValueOp::Axpy => "axpy",
+            ValueOp::Const => "const",
             ValueOp::None => "none",
         } 
     }
+
+    /// Returns true for the element-wise operations whose result and derivative only depend on their
+    /// single ancestor, see apply_unary and unary_derivative.
+    pub fn is_unary(&self) -> bool {
+        matches!(
+            self,
+            ValueOp::Softplus
+                | ValueOp::Gelu
+                | ValueOp::Elu
+                | ValueOp::Swish
+                | ValueOp::Exp
+                | ValueOp::Ln
+                | ValueOp::Sqrt
+                | ValueOp::Abs
+                | ValueOp::Tanh
+                | ValueOp::Sigmoid
+        )
+    }
+
+    // Returns true if a node of this operation can have `count` ancestors, so backward can index
+    // them without panicking.
+    fn accepts_ancestors(&self, count: usize) -> bool {
+        match self {
+            ValueOp::Addition
+            | ValueOp::Subtraction
+            | ValueOp::Multiplication
+            | ValueOp::Division
+            | ValueOp::Max
+            | ValueOp::Min
+            | ValueOp::LeakyRelu => count == 2,
+            ValueOp::Relu => count == 1,
+            operation if operation.is_unary() => count == 1,
+            ValueOp::Dot => count.is_multiple_of(2),
+            ValueOp::Axpy => count == 3,
+            _ => count == 0,
+        }
+    }
+
+    // Returns the index of the ancestor picked by Max or Min, preferring the left one on ties.
+    fn selected(&self, left: f64, right: f64) -> usize {
+        let left_wins = match self {
+            ValueOp::Min => left <= right,
+            _ => left >= right,
+        };
+
+        if left_wins { 0 } else { 1 }
+    }
+
+    /// Applies a unary operation to `x`. Operations which aren't unary return `x` unchanged.
+    pub fn apply_unary(&self, x: f64) -> f64 {
+        match self {
+            // ln(1 + e^x), which is x to within f64 precision for large x and would otherwise overflow
+            ValueOp::Softplus if x > 30.0 => x,
+            ValueOp::Softplus => x.exp().ln_1p(),
+            ValueOp::Gelu => 0.5 * x * (1.0 + (GELU_SCALE * (x + GELU_CUBIC * x.powi(3))).tanh()),
+            ValueOp::Elu if x > 0.0 => x,
+            ValueOp::Elu => x.exp_m1(),
+            ValueOp::Swish => x * sigmoid(x),
+            ValueOp::Exp => x.exp(),
+            ValueOp::Ln => x.ln(),
+            ValueOp::Sqrt => x.sqrt(),
+            ValueOp::Abs => x.abs(),
+            ValueOp::Tanh => x.tanh(),
+            ValueOp::Sigmoid => sigmoid(x),
+            _ => x,
+        }
+    }
+
+    /// Returns the derivative of a unary operation at `x`.
+    pub fn unary_derivative(&self, x: f64) -> f64 {
+        match self {
+            ValueOp::Softplus => sigmoid(x),
+            ValueOp::Gelu => {
+                let tanh = (GELU_SCALE * (x + GELU_CUBIC * x.powi(3))).tanh();
+                0.5 * (1.0 + tanh) + 0.5 * x * (1.0 - tanh * tanh) * GELU_SCALE * (1.0 + 3.0 * GELU_CUBIC * x * x)
+            }
+            ValueOp::Elu if x > 0.0 => 1.0,
+            ValueOp::Elu => x.exp(),
+            ValueOp::Swish => {
+                let s = sigmoid(x);
+                s + x * s * (1.0 - s)
+            }
+            ValueOp::Exp => x.exp(),
+            ValueOp::Ln => 1.0 / x,
+            ValueOp::Sqrt => 0.5 / x.sqrt(),
+            // abs isn't differentiable at 0, where 0 is used as the subgradient
+            ValueOp::Abs if x == 0.0 => 0.0,
+            ValueOp::Abs => x.signum(),
+            ValueOp::Tanh => 1.0 - x.tanh().powi(2),
+            ValueOp::Sigmoid => sigmoid(x) * (1.0 - sigmoid(x)),
+            _ => 1.0,
+        }
+    }
+
+    /// Returns the result of the operation applied to the data of its ancestors, `operands`, as
+    /// when the node was created. Leaves have no operands and can't be evaluated.
+    pub fn evaluate<T: Scalar>(&self, operands: &[T]) -> T {
+        match self {
+            ValueOp::Addition => operands[0] + operands[1],
+            ValueOp::Subtraction => operands[0] - operands[1],
+            ValueOp::Multiplication => operands[0] * operands[1],
+            ValueOp::Division => operands[0] / operands[1],
+            ValueOp::Relu => T::from_f64(operands[0].to_f64().max(0.0)),
+            ValueOp::LeakyRelu => {
+                let (x, alpha) = (operands[0].to_f64(), operands[1].to_f64());
+                T::from_f64(if x > 0.0 { x } else { alpha * x })
+            }
+            ValueOp::Max | ValueOp::Min => operands[self.selected(operands[0].to_f64(), operands[1].to_f64())],
+            ValueOp::Dot => {
+                let (left, right) = operands.split_at(operands.len() / 2);
+                kernels::dot(left, right)
+            }
+            ValueOp::Axpy => operands[0] * operands[1] + operands[2],
+            operation => T::from_f64(operation.apply_unary(operands[0].to_f64())),
+        }
+    }
+
+    /// Propagates `gradient`, the gradient of a node with this operation and `count` ancestors,
+    /// to the ancestors: `accumulate` is called with the index of each ancestor and its share of
+    /// the gradient, and `operand` returns the data of the ancestor at an index.
+    pub fn backpropagate(
+        &self,
+        count: usize,
+        operand: impl Fn(usize) -> f64,
+        gradient: f64,
+        mut accumulate: impl FnMut(usize, f64),
+    ) {
+        match self {
+            ValueOp::Addition => {
+                accumulate(0, 1.0 * gradient);
+                accumulate(1, 1.0 * gradient);
+            }
+            ValueOp::Subtraction => {
+                accumulate(0, 1.0 * gradient);
+                accumulate(1, -gradient);
+            }
+            ValueOp::Multiplication => {
+                let (left, right) = (operand(0), operand(1));
+
+                accumulate(0, right * gradient);
+                accumulate(1, left * gradient);
+            }
+            ValueOp::Division => {
+                let (left, right) = (operand(0), operand(1));
+
+                accumulate(0, (1.0 / right) * gradient);
+                accumulate(1, -(left / (right * right)) * gradient);
+            }
+            // The gradient only flows through inputs which were positive in the forward pass
+            ValueOp::Relu if operand(0) > 0.0 => accumulate(0, gradient),
+            // Negative inputs pass on the gradient scaled by the slope; the slope itself isn't trained
+            ValueOp::LeakyRelu => {
+                let slope = if operand(0) > 0.0 { 1.0 } else { operand(1) };
+                accumulate(0, slope * gradient);
+            }
+            // The whole gradient goes to the selected ancestor, which is the left one on ties
+            ValueOp::Max | ValueOp::Min => accumulate(self.selected(operand(0), operand(1)), gradient),
+            ValueOp::Dot => {
+                let half = count / 2;
+
+                for index in 0..half {
+                    let (left, right) = (operand(index), operand(half + index));
+
+                    accumulate(index, right * gradient);
+                    accumulate(half + index, left * gradient);
+                }
+            }
+            ValueOp::Axpy => {
+                let (a, x) = (operand(0), operand(1));
+
+                accumulate(0, x * gradient);
+                accumulate(1, a * gradient);
+                accumulate(2, gradient);
+            }
+            operation if operation.is_unary() => accumulate(0, operation.unary_derivative(operand(0)) * gradient),
+            _ => (),
+        }
+    }
 }
 
 /// InnerValue represents the inner contents of a Value object in a computation graph.
@@ -57,6 +317,18 @@ pub struct InnerValue<T> {
     pub operation: ValueOp,
 
     pub id: String,
+
+    // label is an optional human readable name for the node (e.g. "w1", "bias" or "loss"),
+    // shown in visualisations in place of the randomly generated id.
+    pub label: Option<String>,
+
+    // backward_hooks are called with the node's gradient during run_grad, see Value::register_backward_hook
+    pub backward_hooks: Vec<Rc<dyn Fn(f64)>>,
+
+    // requires_grad is false for frozen nodes, which the backward pass doesn't accumulate gradients into
+    pub requires_grad: bool,
+
+    _tracker: NodeTracker,
 }
 
 impl<T: fmt::Debug> fmt::Debug for InnerValue<T> {
@@ -67,32 +339,62 @@ impl<T: fmt::Debug> fmt::Debug for InnerValue<T> {
             .field("ancestors", &self.ancestors)
             .field("gradient", &self.gradient)
             .field("operation", &self.operation)
+            .field("label", &self.label)
+            .field("backward_hooks", &self.backward_hooks.len())
+            .field("requires_grad", &self.requires_grad)
             .finish()
     }
 }
 
+// NodeTracker counts live nodes for the stats module when the `instrumentation` feature is
+// enabled. Otherwise it's zero-sized and does nothing.
+struct NodeTracker;
+
+impl NodeTracker {
+    fn new() -> NodeTracker {
+        #[cfg(feature = "instrumentation")]
+        crate::stats::node_created();
+
+        NodeTracker
+    }
+}
+
+impl Clone for NodeTracker {
+    fn clone(&self) -> NodeTracker {
+        NodeTracker::new()
+    }
+}
+
+#[cfg(feature = "instrumentation")]
+impl Drop for NodeTracker {
+    fn drop(&mut self) {
+        crate::stats::node_dropped();
+    }
+}
+
+/// Display prints a compact single line summary of the node, without its ancestors.
+/// Use Value::dump_tree to print the graph of ancestors.
 impl<T: fmt::Display + fmt::Debug> fmt::Display for InnerValue<T> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_struct("InnerValue")
-            .field("id", &self.id)
-            .field("data", &self.data)
-            .field("ancestors", &self.ancestors)
-            .field("gradient", &self.gradient)
-            .field("operation", &self.operation)
-            .finish()
+        write!(
+            f,
+            "id={} data={} grad={} op={}",
+            self.id,
+            self.data,
+            self.gradient,
+            self.operation.to_str(),
+        )
     }
 }
 
 /// Value is a tuple struct which wraps an InnerValue
 /// It provides support for auto-differentiable mathematical operations.
 #[derive(Debug, Clone)]
-pub struct Value<T>(Rc<RefCell<InnerValue<T>>>);
+pub struct Value<T>(pub(crate) Rc<RefCell<InnerValue<T>>>);
 
 impl<T: fmt::Display + fmt::Debug> fmt::Display for Value<T> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_struct("Value")
-            .field("innerValue", &self.0.borrow())
-            .finish()
+        write!(f, "{}", self.0.borrow())
     }
 }
 
@@ -104,9 +406,7 @@ impl<T> Deref for Value<T> {
     }
 }
 
-impl<T> Value<T>
-where T: Copy + Mul<f64, Output = f64> + Div<T, Output = T> + Into<f64> + From<f64> + fmt::Display + fmt::Debug  + 'static
-{
+impl<T: Scalar> Value<T> {
     fn generate_id() -> String {
         let mut rng = rand::thread_rng();
 
@@ -125,9 +425,34 @@ where T: Copy + Mul<f64, Output = f64> + Div<T, Output = T> + Into<f64> + From<f
             gradient: 0.0,
             ancestors: vec![],
             operation: ValueOp::None,
+            label: None,
+            backward_hooks: vec![],
+            requires_grad: true,
+            _tracker: NodeTracker::new(),
         };
 
-        Value(Rc::new(RefCell::new(inner_value)))
+        let value = Value(Rc::new(RefCell::new(inner_value)));
+        Tape::record(&value);
+
+        value
+    }
+
+    /// constant creates a leaf which never receives a gradient, such as a dataset input or a fixed
+    /// coefficient. Backpropagation skips it, and it's drawn dashed in DOT output.
+    pub fn constant(data: T) -> Value<T> {
+        let value = Value::new(data);
+        {
+            let mut inner = value.borrow_mut();
+            inner.operation = ValueOp::Const;
+            inner.requires_grad = false;
+        }
+
+        value
+    }
+
+    /// Returns true for nodes created with Value::constant.
+    pub fn is_constant(&self) -> bool {
+        self.borrow().operation == ValueOp::Const
     }
 
     pub fn new_from_ref(data: &T) -> Value<T> {
@@ -146,44 +471,64 @@ where T: Copy + Mul<f64, Output = f64> + Div<T, Output = T> + Into<f64> + From<f
     // given node relative to the ancestor.
     pub fn backward(&self) {
         let val = self.borrow();
-
-        match val.operation{
-            ValueOp::Addition => {
-                let left_ancestor = &val.ancestors[0];
-                let right_ancestor = &val.ancestors[1];
-
-                left_ancestor.borrow_mut().gradient += 1.0 * val.gradient;
-                right_ancestor.borrow_mut().gradient += 1.0 * val.gradient;
-            },
-            ValueOp::Subtraction => {
-                let left_ancestor = &val.ancestors[0];
-                let right_ancestor = &val.ancestors[1];
-
-                left_ancestor.borrow_mut().gradient += 1.0 * val.gradient;
-                right_ancestor.borrow_mut().gradient -= 1.0 * val.gradient;
+        let operand = |index: usize| -> f64 { val.ancestors[index].borrow().data.to_f64() };
+
+        val.operation.backpropagate(val.ancestors.len(), operand, val.gradient, |index, delta| {
+            accumulate_gradient(&val.ancestors[index], delta)
+        });
+
+        if anomaly_detection_enabled() {
+            for ancestor in &val.ancestors {
+                let gradient = ancestor.borrow().gradient;
+                if !gradient.is_finite() {
+                    panic!(
+                        "anomaly detected: backward pass of node {} (operation {}) produced a non-finite gradient ({}) for ancestor {}, ancestors: [{}]",
+                        val.id,
+                        val.operation.to_str(),
+                        gradient,
+                        ancestor.borrow().id,
+                        describe_ancestors(&val.ancestors),
+                    );
+                }
             }
-            ValueOp::Multiplication=> {
-                let left_ancestor = &val.ancestors[0];
-                let right_ancestor = &val.ancestors[1];
+        }
+    }
+
+    // check_anomaly panics if anomaly detection is enabled and the node's data is NaN or infinite.
+    pub(crate) fn check_anomaly(&self) {
+        if !anomaly_detection_enabled() {
+            return;
+        }
 
-                let left_ancestor_data: f64 = left_ancestor.borrow().data.into();
-                let right_ancestor_data: f64 = right_ancestor.borrow().data.into();
+        let inner = self.borrow();
+        let data: f64 = inner.data.to_f64();
+
+        if !data.is_finite() {
+            panic!(
+                "anomaly detected: node {} (operation {}) produced {}, ancestors: [{}]",
+                inner.id,
+                inner.operation.to_str(),
+                inner.data,
+                describe_ancestors(&inner.ancestors),
+            );
+        }
+    }
 
-                left_ancestor.borrow_mut().gradient += right_ancestor_data * val.gradient;
-                right_ancestor.borrow_mut().gradient += left_ancestor_data * val.gradient;
+    // recompute sets the node's data from its ancestors' current data, as the operation which
+    // created it did. Leaves are left unchanged.
+    pub(crate) fn recompute(&self) {
+        let data = {
+            let inner = self.borrow();
+            if inner.ancestors.is_empty() {
+                return;
             }
-            ValueOp::Division => {
-                let left_ancestor = &val.ancestors[0];
-                let right_ancestor = &val.ancestors[1];
 
-                let left_ancestor_data: f64 = left_ancestor.borrow().data.into();
-                let right_ancestor_data: f64 = right_ancestor.borrow().data.into();
+            let operands: Vec<T> = inner.ancestors.iter().map(|ancestor| ancestor.borrow().data).collect();
+            inner.operation.evaluate(&operands)
+        };
 
-                left_ancestor.borrow_mut().gradient += (1.0/right_ancestor_data) * val.gradient;
-                right_ancestor.borrow_mut().gradient -= (left_ancestor_data/(right_ancestor_data * right_ancestor_data)) * val.gradient;
-            }
-            _ => ()
-        }
+        self.borrow_mut().data = data;
+        self.check_anomaly();
     }
 
     /// run_grad builds a topological graph of computations and then performs the backpropagation algorithm
@@ -201,14 +546,238 @@ where T: Copy + Mul<f64, Output = f64> + Div<T, Output = T> + Into<f64> + From<f
         // Compute the gradient for nodes in the graph
         for node in reversed_topological_graph {
             let node_as_value = Value(Rc::clone(node));
+
+            // Every node which depends on this one has already been visited, so its gradient is final
+            node_as_value.call_backward_hooks();
             node_as_value.backward();
         }
     }
 
+    /// Registers `hook` to be called with this node's gradient whenever run_grad reaches it,
+    /// e.g. to record the gradients flowing through an intermediate value. The gradient passed
+    /// is the node's accumulated gradient once every node depending on it has been processed.
+    pub fn register_backward_hook(&self, hook: impl Fn(f64) + 'static) {
+        self.borrow_mut().backward_hooks.push(Rc::new(hook));
+    }
+
+    pub fn clear_backward_hooks(&self) {
+        self.borrow_mut().backward_hooks.clear();
+    }
+
+    /// Freezes (false) or unfreezes (true) the node. The backward pass doesn't accumulate any
+    /// gradient into a frozen node, and optimizers leave frozen parameters unchanged.
+    pub fn set_requires_grad(&self, requires_grad: bool) {
+        self.borrow_mut().requires_grad = requires_grad;
+    }
+
+    pub fn requires_grad(&self) -> bool {
+        self.borrow().requires_grad
+    }
+
+    pub(crate) fn call_backward_hooks(&self) {
+        // Clone the hooks out so they can inspect the value without a borrow being held
+        let (hooks, gradient) = {
+            let inner = self.borrow();
+            if inner.backward_hooks.is_empty() {
+                return;
+            }
+
+            (inner.backward_hooks.clone(), inner.gradient)
+        };
+
+        for hook in hooks {
+            hook(gradient);
+        }
+    }
+
+    /// relu applies the rectified linear unit max(0, x) to the value.
+    pub fn relu(&self) -> Value<T> {
+        let data: f64 = self.get_data().to_f64();
+        let value = Value::new(T::from_f64(data.max(0.0)));
+
+        value.borrow_mut().ancestors.push(Rc::clone(self));
+        value.borrow_mut().operation = ValueOp::Relu;
+
+        value.check_anomaly();
+
+        value
+    }
+
+    /// leaky_relu applies x for positive x and alpha * x otherwise. Unlike relu, units with negative
+    /// inputs still receive a gradient, so they can't die.
+    pub fn leaky_relu(&self, alpha: f64) -> Value<T> {
+        let data: f64 = self.get_data().to_f64();
+        let value = Value::new(T::from_f64(if data > 0.0 { data } else { alpha * data }));
+
+        value.borrow_mut().ancestors.push(Rc::clone(self));
+        value.borrow_mut().ancestors.push(Value::constant(T::from_f64(alpha)).0);
+        value.borrow_mut().operation = ValueOp::LeakyRelu;
+
+        value.check_anomaly();
+
+        value
+    }
+
+    /// softplus applies ln(1 + e^x), a smooth approximation of relu.
+    pub fn softplus(&self) -> Value<T> {
+        self.unary(ValueOp::Softplus)
+    }
+
+    /// gelu applies the Gaussian error linear unit, using its tanh approximation.
+    pub fn gelu(&self) -> Value<T> {
+        self.unary(ValueOp::Gelu)
+    }
+
+    /// elu applies the exponential linear unit: x for positive x, and e^x - 1 otherwise.
+    pub fn elu(&self) -> Value<T> {
+        self.unary(ValueOp::Elu)
+    }
+
+    /// swish applies x * sigmoid(x), also known as SiLU.
+    pub fn swish(&self) -> Value<T> {
+        self.unary(ValueOp::Swish)
+    }
+
+    /// exp returns e raised to the value.
+    pub fn exp(&self) -> Value<T> {
+        self.unary(ValueOp::Exp)
+    }
+
+    /// ln returns the natural logarithm of the value.
+    pub fn ln(&self) -> Value<T> {
+        self.unary(ValueOp::Ln)
+    }
+
+    /// log returns the logarithm of the value in the given base, computed as ln(x) / ln(base).
+    pub fn log(&self, base: f64) -> Value<T> {
+        &self.ln() / &Value::constant(T::from_f64(base.ln()))
+    }
+
+    /// sqrt returns the square root of the value.
+    pub fn sqrt(&self) -> Value<T> {
+        self.unary(ValueOp::Sqrt)
+    }
+
+    /// abs returns |x|. Its gradient at 0 is taken to be 0.
+    pub fn abs(&self) -> Value<T> {
+        self.unary(ValueOp::Abs)
+    }
+
+    /// tanh applies the hyperbolic tangent, squashing the value into (-1, 1).
+    pub fn tanh(&self) -> Value<T> {
+        self.unary(ValueOp::Tanh)
+    }
+
+    /// sigmoid applies the logistic function 1 / (1 + e^-x), squashing the value into (0, 1).
+    pub fn sigmoid(&self) -> Value<T> {
+        self.unary(ValueOp::Sigmoid)
+    }
+
+    /// detach returns a new leaf node holding the same data. Gradients computed through the
+    /// returned node stop there instead of flowing back into this node's graph, e.g. to truncate
+    /// backpropagation through time.
+    pub fn detach(&self) -> Value<T> {
+        Value::new(self.get_data())
+    }
+
+    /// approx_eq returns true when the data of both values differ by at most `eps`. NaN is never
+    /// approximately equal to anything.
+    pub fn approx_eq(&self, other: &Value<T>, eps: f64) -> bool {
+        let (a, b) = (self.get_data().to_f64(), other.get_data().to_f64());
+
+        a == b || (a - b).abs() <= eps
+    }
+
+    /// max returns the larger of the two values. The gradient flows only to the larger one, or to
+    /// self on ties.
+    pub fn max(&self, other: &Value<T>) -> Value<T> {
+        self.select(other, ValueOp::Max)
+    }
+
+    /// min returns the smaller of the two values. The gradient flows only to the smaller one, or to
+    /// self on ties.
+    pub fn min(&self, other: &Value<T>) -> Value<T> {
+        self.select(other, ValueOp::Min)
+    }
+
+    /// clamp limits the value to [lo, hi]. The gradient only flows through when the value is within
+    /// the range.
+    pub fn clamp(&self, lo: f64, hi: f64) -> Value<T> {
+        let lo = Value::constant(T::from_f64(lo));
+        let hi = Value::constant(T::from_f64(hi));
+
+        // On ties with a bound, min and max keep the gradient on the clamped value
+        self.max(&lo).min(&hi)
+    }
+
+    fn select(&self, other: &Value<T>, operation: ValueOp) -> Value<T> {
+        let (left, right) = (self.get_data(), other.get_data());
+        let selected = [left, right][operation.selected(left.to_f64(), right.to_f64())];
+        let value = Value::new(selected);
+
+        value.borrow_mut().ancestors.append(&mut vec![Rc::clone(self), Rc::clone(other)]);
+        value.borrow_mut().operation = operation;
+
+        value.check_anomaly();
+
+        value
+    }
+
+    fn unary(&self, operation: ValueOp) -> Value<T> {
+        let data: f64 = self.get_data().to_f64();
+        let value = Value::new(T::from_f64(operation.apply_unary(data)));
+
+        value.borrow_mut().ancestors.push(Rc::clone(self));
+        value.borrow_mut().operation = operation;
+
+        value.check_anomaly();
+
+        value
+    }
+
+    /// try_div divides by `rhs`, returning an error instead of silently producing Inf or NaN
+    /// when the denominator is zero or the result isn't finite.
+    pub fn try_div(&self, rhs: &Value<T>) -> Result<Value<T>, BackpropError> {
+        let denominator: f64 = rhs.get_data().to_f64();
+        if denominator == 0.0 {
+            return Err(BackpropError::DivisionByZero {
+                numerator: self.get_id(),
+                denominator: rhs.get_id(),
+            });
+        }
+
+        let value = self / rhs;
+
+        let data: f64 = value.get_data().to_f64();
+        if !data.is_finite() {
+            return Err(BackpropError::NonFinite {
+                id: value.get_id(),
+                operation: ValueOp::Division.to_str(),
+                data,
+            });
+        }
+
+        Ok(value)
+    }
+
+    /// dump_tree renders the node and its ancestors as an indented tree, one node per line.
+    /// Ancestors deeper than `depth_limit` levels below this node are elided, as the full tree
+    /// repeats shared nodes and grows quickly for large graphs.
+    pub fn dump_tree(&self, depth_limit: usize) -> String {
+        let mut output = String::new();
+        dump_node(self, 0, depth_limit, &mut output);
+
+        output
+    }
+
     pub fn get_data(&self) -> T {
         self.borrow().data
     }
 
+    pub fn set_data(&self, data: T) {
+        self.borrow_mut().data = data;
+    }
+
     pub fn get_gradient(&self) -> f64 {
         self.borrow().gradient
     }
@@ -221,63 +790,721 @@ where T: Copy + Mul<f64, Output = f64> + Div<T, Output = T> + Into<f64> + From<f
         self.borrow().id.clone()
     }
 
-    pub fn clear_gradient(&self) {
-        self.borrow_mut().gradient = 0.0;
+    /// with_label sets the node's label and returns the node, e.g. `Value::new(0.5).with_label("w1")`.
+    pub fn with_label(self, label: &str) -> Value<T> {
+        self.set_label(label);
+
+        self
+    }
+
+    pub fn set_label(&self, label: &str) {
+        self.borrow_mut().label = Some(label.to_string());
+    }
+
+    pub fn get_label(&self) -> Option<String> {
+        self.borrow().label.clone()
+    }
+
+    pub fn clear_gradient(&self) {
+        self.borrow_mut().gradient = 0.0;
+
+        // todo: clear gradients on ancestors before removing references
+        self.borrow_mut().ancestors.clear();
+    }
+
+    /// differentiate computes the gradient of this node with respect to each of `wrt`, returning the
+    /// gradients as Values.
+    ///
+    /// Unlike run_grad, which accumulates raw f64 gradients on the nodes, the backward rules are
+    /// recorded as new operations on the graph. The returned gradients can therefore be differentiated
+    /// again (with run_grad or differentiate), e.g. for second derivatives or Hessian-vector products.
+    /// Ancestors that don't contribute to this node get a gradient of zero.
+    pub fn differentiate(&self, wrt: &[Value<T>]) -> Vec<Value<T>> {
+        let topological_graph = build_topological_graph(self);
+
+        let mut gradients: HashMap<String, Value<T>> = HashMap::new();
+        gradients.insert(self.get_id(), Value::constant(T::from_f64(1.0)));
+
+        let one = || Value::constant(T::from_f64(1.0));
+        let accumulate = |gradients: &mut HashMap<String, Value<T>>, node: &Value<T>, gradient: Value<T>| {
+            let id = node.get_id();
+            let total = match gradients.remove(&id) {
+                Some(existing) => &existing + &gradient,
+                None => gradient,
+            };
+
+            gradients.insert(id, total);
+        };
+
+        for node in topological_graph.iter().rev() {
+            let node = Value(Rc::clone(node));
+            let gradient = match gradients.get(&node.get_id()) {
+                Some(gradient) => gradient.clone(),
+                None => continue,
+            };
+
+            let (operation, ancestors) = {
+                let inner = node.borrow();
+                let ancestors: Vec<Value<T>> = inner.ancestors.iter().map(|a| Value(Rc::clone(a))).collect();
+
+                (inner.operation, ancestors)
+            };
+
+            match operation {
+                ValueOp::Addition => {
+                    accumulate(&mut gradients, &ancestors[0], gradient.clone());
+                    accumulate(&mut gradients, &ancestors[1], gradient);
+                }
+                ValueOp::Subtraction => {
+                    accumulate(&mut gradients, &ancestors[0], gradient.clone());
+                    accumulate(&mut gradients, &ancestors[1], &Value::constant(T::from_f64(0.0)) - &gradient);
+                }
+                ValueOp::Multiplication => {
+                    accumulate(&mut gradients, &ancestors[0], &gradient * &ancestors[1]);
+                    accumulate(&mut gradients, &ancestors[1], &gradient * &ancestors[0]);
+                }
+                ValueOp::Division => {
+                    let (left, right) = (&ancestors[0], &ancestors[1]);
+
+                    accumulate(&mut gradients, left, &gradient / right);
+
+                    let right_gradient = &(&gradient * left) / &(right * right);
+                    accumulate(&mut gradients, right, &Value::constant(T::from_f64(0.0)) - &right_gradient);
+                }
+                ValueOp::Relu => {
+                    // The derivative of relu is a step function, which is constant almost everywhere
+                    let ancestor_data: f64 = ancestors[0].get_data().to_f64();
+                    let mask = Value::constant(T::from_f64(if ancestor_data > 0.0 { 1.0 } else { 0.0 }));
+
+                    accumulate(&mut gradients, &ancestors[0], &gradient * &mask);
+                }
+                ValueOp::LeakyRelu => {
+                    let ancestor_data: f64 = ancestors[0].get_data().to_f64();
+                    let slope = if ancestor_data > 0.0 { Value::constant(T::from_f64(1.0)) } else { ancestors[1].clone() };
+
+                    accumulate(&mut gradients, &ancestors[0], &gradient * &slope);
+                }
+                ValueOp::Max | ValueOp::Min => {
+                    let selected = operation.selected(ancestors[0].get_data().to_f64(), ancestors[1].get_data().to_f64());
+                    accumulate(&mut gradients, &ancestors[selected], gradient);
+                }
+                ValueOp::Dot => {
+                    let (left, right) = ancestors.split_at(ancestors.len() / 2);
+
+                    for (left, right) in left.iter().zip(right) {
+                        accumulate(&mut gradients, left, &gradient * right);
+                        accumulate(&mut gradients, right, &gradient * left);
+                    }
+                }
+                ValueOp::Axpy => {
+                    accumulate(&mut gradients, &ancestors[0], &gradient * &ancestors[1]);
+                    accumulate(&mut gradients, &ancestors[1], &gradient * &ancestors[0]);
+                    accumulate(&mut gradients, &ancestors[2], gradient);
+                }
+                // The derivatives of the smooth unary operations are built from Values, mostly from
+                // the node's own output, so differentiating the gradients again accounts for their curvature
+                ValueOp::Exp => {
+                    accumulate(&mut gradients, &ancestors[0], &gradient * &node);
+                }
+                ValueOp::Ln => {
+                    accumulate(&mut gradients, &ancestors[0], &gradient / &ancestors[0]);
+                }
+                ValueOp::Sqrt => {
+                    let doubled = &node + &node;
+                    accumulate(&mut gradients, &ancestors[0], &gradient / &doubled);
+                }
+                ValueOp::Tanh => {
+                    // tanh' = 1 - y²
+                    let derivative = &one() - &(&node * &node);
+                    accumulate(&mut gradients, &ancestors[0], &gradient * &derivative);
+                }
+                ValueOp::Sigmoid => {
+                    // sigmoid' = y(1 - y)
+                    let derivative = &node * &(&one() - &node);
+                    accumulate(&mut gradients, &ancestors[0], &gradient * &derivative);
+                }
+                ValueOp::Softplus => {
+                    // softplus' = sigmoid(x)
+                    accumulate(&mut gradients, &ancestors[0], &gradient * &ancestors[0].sigmoid());
+                }
+                ValueOp::Swish => {
+                    // swish' = s + y(1 - s) with s = sigmoid(x)
+                    let s = ancestors[0].sigmoid();
+                    let derivative = &s + &(&node * &(&one() - &s));
+                    accumulate(&mut gradients, &ancestors[0], &gradient * &derivative);
+                }
+                ValueOp::Elu => {
+                    // elu' is 1 for positive inputs and e^x = y + 1 otherwise
+                    let derivative = if ancestors[0].get_data().to_f64() > 0.0 { one() } else { &node + &one() };
+                    accumulate(&mut gradients, &ancestors[0], &gradient * &derivative);
+                }
+                ValueOp::Gelu => {
+                    // With u = k(x + cx³) and t = tanh(u), gelu' = 0.5(1 + t) + 0.5x(1 - t²)k(1 + 3cx²)
+                    let x = &ancestors[0];
+                    let scale = Value::constant(T::from_f64(GELU_SCALE));
+                    let cubic = Value::constant(T::from_f64(GELU_CUBIC));
+                    let half = Value::constant(T::from_f64(0.5));
+                    let three = Value::constant(T::from_f64(3.0));
+
+                    let squared = x * x;
+                    let t = (&scale * &(x + &(&cubic * &(&squared * x)))).tanh();
+                    let slope = &scale * &(&one() + &(&three * &(&cubic * &squared)));
+                    let derivative = &half * &(&(&one() + &t) + &(&(x * &(&one() - &(&t * &t))) * &slope));
+
+                    accumulate(&mut gradients, &ancestors[0], &gradient * &derivative);
+                }
+                ValueOp::Abs => {
+                    // Like relu, abs has a piecewise constant derivative
+                    let ancestor_data: f64 = ancestors[0].get_data().to_f64();
+                    let sign = Value::constant(T::from_f64(ValueOp::Abs.unary_derivative(ancestor_data)));
+
+                    accumulate(&mut gradients, &ancestors[0], &gradient * &sign);
+                }
+                ValueOp::Const | ValueOp::None => (),
+            }
+        }
+
+        wrt.iter()
+            .map(|value| gradients.get(&value.get_id()).cloned().unwrap_or_else(|| Value::constant(T::from_f64(0.0))))
+            .collect()
+    }
+
+    /// grad_wrt returns the gradient of this node with respect to each of `wrt`.
+    /// Unlike run_grad, the gradients stored on the nodes are left untouched, so it can be called for
+    /// several roots sharing the same graph (e.g. each output of a network).
+    pub fn grad_wrt(&self, wrt: &[Value<T>]) -> Vec<f64> {
+        self.differentiate(wrt).iter().map(|gradient| gradient.get_data().to_f64()).collect()
+    }
+}
+
+impl Value<f64> {
+    /// enable_anomaly_detection makes every operation check its result, and every backward step check
+    /// the gradients it produces, for NaN or infinite values on the current thread.
+    /// When one is found, it panics with the offending node's id, operation and ancestor values.
+    /// This slows down every operation, so it's meant for debugging diverging training runs.
+    pub fn enable_anomaly_detection() {
+        ANOMALY_DETECTION.with(|enabled| enabled.set(true));
+    }
+
+    pub fn disable_anomaly_detection() {
+        ANOMALY_DETECTION.with(|enabled| enabled.set(false));
+    }
+
+    pub fn is_anomaly_detection_enabled() -> bool {
+        anomaly_detection_enabled()
+    }
+}
+
+/// order_nodes_topologically builds a topological order for nodes based on their dependencies.
+pub fn build_topological_graph<T: Scalar>(value: &Value<T>) -> Vec<Rc<RefCell<InnerValue<T>>>> {
+    let mut seen_nodes: HashMap<String, bool> = HashMap::new();
+
+    order_nodes_topologically(value, &mut seen_nodes)
+}
+
+/// run_grad_multi backpropagates from several roots at once, starting each root's gradient at the
+/// matching entry of `seed_gradients`, in a single pass over their combined graph. Seeding every
+/// root with 1.0 gives the gradients of their sum, e.g. of per-output losses, and seeding the
+/// outputs of a network with a vector v gives the vector-Jacobian product v^T J.
+///
+/// As with run_grad, gradients are accumulated into the nodes rather than replacing them.
+///
+/// # Panics
+///
+/// Panics if `roots` and `seed_gradients` have different lengths.
+pub fn run_grad_multi<T: Scalar>(roots: &[Value<T>], seed_gradients: &[f64]) {
+    assert_eq!(roots.len(), seed_gradients.len(), "run_grad_multi needs one seed gradient per root");
+
+    for root in roots {
+        root.set_gradient(0.0);
+    }
+    // A root passed twice is seeded twice
+    for (root, seed) in roots.iter().zip(seed_gradients) {
+        root.set_gradient(root.get_gradient() + seed);
+    }
+
+    // Appending each root's unseen ancestors keeps ancestors before descendants across all roots
+    let mut seen_nodes: HashMap<String, bool> = HashMap::new();
+    let topological_graph: Vec<Rc<RefCell<InnerValue<T>>>> =
+        roots.iter().flat_map(|root| order_nodes_topologically(root, &mut seen_nodes)).collect();
+
+    for node in topological_graph.iter().rev() {
+        let node_as_value = Value(Rc::clone(node));
+
+        node_as_value.call_backward_hooks();
+        node_as_value.backward();
+    }
+}
+
+/// GradPlan is a compiled backward pass: the graph compiled once into an arena (see Graph), so
+/// repeated passes over a graph whose structure doesn't change skip rebuilding and sorting it.
+///
+/// A training loop can build its graph once, then each step update the inputs and parameters
+/// with set_data and call run, which recomputes every node's data from its ancestors and
+/// backpropagates through the arena.
+pub struct GradPlan<T> {
+    graph: RefCell<Graph<T>>,
+}
+
+impl<T: Scalar> GradPlan<T> {
+    pub fn compile(root: &Value<T>) -> GradPlan<T> {
+        GradPlan {
+            graph: RefCell::new(Graph::compile(root)),
+        }
+    }
+
+    /// Returns the number of nodes in the plan.
+    pub fn len(&self) -> usize {
+        self.graph.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.graph.borrow().is_empty()
+    }
+
+    pub fn root(&self) -> Value<T> {
+        self.graph.borrow().root().clone()
+    }
+
+    /// Recomputes the data of every operation from its ancestors, picking up leaves changed with
+    /// set_data since the graph was built or last run.
+    pub fn forward(&self) {
+        self.graph.borrow_mut().forward();
+    }
+
+    /// Backpropagates from the root like run_grad. The gradients of intermediate nodes are reset
+    /// first, as they belong to the previous pass; leaves keep accumulating, as with run_grad, so
+    /// parameters still need zeroing between steps.
+    pub fn backward(&self) {
+        self.graph.borrow_mut().backward();
+    }
+
+    /// Runs forward then backward.
+    pub fn run(&self) {
+        self.graph.borrow_mut().run();
+    }
+}
+
+/// dot returns a single node holding the dot product of `values` and `coefficients`, e.g. a
+/// weighted sum of features with fixed weights. Building it takes one node rather than the two
+/// per element of multiplying and summing the values one by one, and backpropagating through it
+/// is a single pass over the elements.
+///
+/// # Panics
+///
+/// Panics if `values` and `coefficients` have different lengths.
+pub fn dot<T: Scalar>(values: &[Value<T>], coefficients: &[T]) -> Value<T> {
+    let coefficients: Vec<Value<T>> = coefficients.iter().map(|c| Value::constant(*c)).collect();
+
+    dot_values(values, &coefficients)
+}
+
+/// dot_values returns a single node holding the dot product of `left` and `right`, such as a
+/// neuron's weights and inputs, with gradients flowing to both sides. See dot.
+///
+/// # Panics
+///
+/// Panics if `left` and `right` have different lengths.
+pub fn dot_values<T: Scalar>(left: &[Value<T>], right: &[Value<T>]) -> Value<T> {
+    assert_eq!(left.len(), right.len(), "dot product of vectors with different lengths");
+
+    let left_data: Vec<T> = left.iter().map(|v| v.get_data()).collect();
+    let right_data: Vec<T> = right.iter().map(|v| v.get_data()).collect();
+    let value = Value::new(kernels::dot(&left_data, &right_data));
+
+    {
+        let mut inner = value.borrow_mut();
+        inner.ancestors = left.iter().chain(right).map(|v| Rc::clone(v)).collect();
+        inner.operation = ValueOp::Dot;
+    }
+    value.check_anomaly();
+
+    value
+}
+
+/// axpy returns `a * x + y` for each pair of `xs` and `ys`, with one node per element rather
+/// than a multiplication and an addition. `a` is a constant.
+///
+/// # Panics
+///
+/// Panics if `xs` and `ys` have different lengths.
+pub fn axpy<T: Scalar>(a: T, xs: &[Value<T>], ys: &[Value<T>]) -> Vec<Value<T>> {
+    assert_eq!(xs.len(), ys.len(), "axpy of vectors with different lengths");
+
+    let a = Value::constant(a);
+    xs.iter()
+        .zip(ys)
+        .map(|(x, y)| {
+            let value = Value::new(a.get_data() * x.get_data() + y.get_data());
+            {
+                let mut inner = value.borrow_mut();
+                inner.ancestors = vec![Rc::clone(&a), Rc::clone(x), Rc::clone(y)];
+                inner.operation = ValueOp::Axpy;
+            }
+            value.check_anomaly();
+
+            value
+        })
+        .collect()
+}
+
+/// softmax turns logits into probabilities which sum to 1, as e^x_i / Σ e^x_j.
+/// The largest logit is subtracted first (as a constant, which doesn't change the result or its
+/// gradients) so large logits don't overflow. See inference::softmax for raw data.
+///
+/// # Panics
+///
+/// Panics if there are no logits.
+pub fn softmax<T: Scalar>(logits: &[Value<T>]) -> Vec<Value<T>> {
+    assert!(!logits.is_empty(), "softmax needs at least one logit");
+
+    let max = logits.iter().map(|x| x.get_data().to_f64()).fold(f64::NEG_INFINITY, f64::max);
+    let shift = Value::constant(T::from_f64(max));
+
+    let exps: Vec<Value<T>> = logits.iter().map(|x| (x - &shift).exp()).collect();
+    let sum = exps.iter().fold(Value::constant(T::from_f64(0.0)), |acc, e| acc + e.clone());
+
+    exps.iter().map(|e| e / &sum).collect()
+}
+
+// TapeNode lets a tape sever nodes regardless of their scalar type
+trait TapeNode {
+    fn sever(&self);
+}
+
+impl<T> TapeNode for RefCell<InnerValue<T>> {
+    fn sever(&self) {
+        // Without ancestors the node's operation has nothing to apply to, so it becomes a leaf;
+        // constants have no ancestors to begin with and stay constant
+        let mut inner = self.borrow_mut();
+        inner.ancestors.clear();
+        if inner.operation != ValueOp::Const {
+            inner.operation = ValueOp::None;
+        }
+    }
+}
+
+type Recording = Rc<RefCell<Vec<Rc<dyn TapeNode>>>>;
+
+/// Tape owns every node created on its thread while it's alive, and frees them together when
+/// dropped, e.g. a request's forward and backward pass in a long-running service:
+///
+/// ```ignore
+/// let tape = Tape::new();
+/// let loss = network.loss(&inputs, &targets)?;
+/// loss.run_grad();
+/// drop(tape); // the graph is freed here, even if `loss` is still held somewhere
+/// ```
+///
+/// Dropping the tape cuts every recorded node off from its ancestors, so a leftover handle keeps
+/// at most its own node alive (as a leaf holding its last data) rather than the graph behind it.
+/// This has the effect of ancestors referring upwards through weak references owned by the tape,
+/// without every traversal having to upgrade them. Nodes created before the tape, such as network
+/// parameters, aren't recorded and keep their data and gradients.
+///
+/// Tapes nest: nodes are recorded by the most recently created tape still alive.
+pub struct Tape {
+    nodes: Recording,
+}
+
+impl Tape {
+    pub fn new() -> Tape {
+        let nodes = Recording::default();
+        TAPES.with(|tapes| tapes.borrow_mut().push(Rc::clone(&nodes)));
+
+        Tape { nodes }
+    }
+
+    /// Returns the number of nodes recorded so far.
+    pub fn len(&self) -> usize {
+        self.nodes.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.borrow().is_empty()
+    }
+
+    fn record<T: Scalar>(value: &Value<T>) {
+        // try_with fails while the thread is shutting down, when nothing is recording anymore
+        let _ = TAPES.try_with(|tapes| {
+            if let Some(nodes) = tapes.borrow().last() {
+                nodes.borrow_mut().push(Rc::clone(value) as Rc<dyn TapeNode>);
+            }
+        });
+    }
+}
+
+impl Default for Tape {
+    fn default() -> Tape {
+        Tape::new()
+    }
+}
+
+impl Drop for Tape {
+    fn drop(&mut self) {
+        let _ = TAPES.try_with(|tapes| tapes.borrow_mut().retain(|nodes| !Rc::ptr_eq(nodes, &self.nodes)));
+
+        for node in self.nodes.borrow().iter() {
+            node.sever();
+        }
+    }
+}
+
+/// order_nodes_topologically returns a topologically ordered set of ancestor nodes for a given node.
+fn order_nodes_topologically<T: Scalar>(value: &Value<T>, seen_nodes: &mut HashMap<String, bool>) -> Vec<Rc<RefCell<InnerValue<T>>>> {
+    let mut nodes = vec![];
+
+    let value_id = value.get_id();
+
+    if seen_nodes.contains_key(&value_id) {
+        return nodes;
+    }
+
+    // Mark the node as seen incase its referenced in any of its own ancestors.
+    seen_nodes.insert(value_id, true);
+
+    for ancestor in value.borrow().ancestors.iter(){
+        // Process the dependencies for this ancestor node
+        let ancestor_as_value = Value(Rc::clone(ancestor));
+        let mut ancestor_dependencies = order_nodes_topologically(&ancestor_as_value, seen_nodes);
+
+        // Add the ancestor's dependencies to the list
+        nodes.append(&mut ancestor_dependencies);
+    }
+
+    // Add the node to the list after processing its ancestors
+    nodes.push(Rc::clone(value));
+
+    nodes
+}
+
+fn dump_node<T: fmt::Display + fmt::Debug>(node: &Rc<RefCell<InnerValue<T>>>, depth: usize, depth_limit: usize, output: &mut String) {
+    let inner = node.borrow();
+    let indent = "  ".repeat(depth);
+
+    output.push_str(&format!("{}{}\n", indent, inner));
+
+    if inner.ancestors.is_empty() {
+        return;
+    }
+
+    if depth == depth_limit {
+        output.push_str(&format!("{}  ... ({} ancestors)\n", indent, inner.ancestors.len()));
+        return;
+    }
+
+    for ancestor in &inner.ancestors {
+        dump_node(ancestor, depth + 1, depth_limit, output);
+    }
+}
+
+pub fn print_topological_graph<T: Scalar>(topological_graph: Vec<Rc<RefCell<InnerValue<T>>>>) {
+    for item in topological_graph {
+        println!("{}", item.borrow());
+    }
+}
+
+impl From<f64> for Value<f64> {
+    fn from(data: f64) -> Self {
+        Value::new(data)
+    }
+}
+
+impl From<f32> for Value<f64> {
+    fn from(data: f32) -> Self {
+        Value::new(data as f64)
+    }
+}
 
-        // todo: clear gradients on ancestors before removing references
-        self.borrow_mut().ancestors.clear();
+impl From<i32> for Value<f64> {
+    fn from(data: i32) -> Self {
+        Value::new(data as f64)
     }
 }
 
-/// order_nodes_topologically builds a topological order for nodes based on their dependencies.
-pub fn build_topological_graph<T>(value: &Value<T>) -> Vec<Rc<RefCell<InnerValue<T>>>>
-where T: Div<Output=T> + Copy + 'static + Mul<f64, Output = f64> + Into<f64> + From<f64> + fmt::Display + fmt::Debug {
-    let mut seen_nodes: HashMap<String, bool> = HashMap::new();
+// SerializedNode is a graph node with its ancestors replaced by their ids.
+#[derive(Serialize, Deserialize)]
+struct SerializedNode<T> {
+    id: String,
+    data: T,
+    gradient: f64,
+    operation: ValueOp,
+    ancestors: Vec<String>,
 
-    order_nodes_topologically(value, &mut seen_nodes)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    label: Option<String>,
+
+    #[serde(default = "requires_grad_default")]
+    requires_grad: bool,
 }
 
-/// order_nodes_topologically returns a topologically ordered set of ancestor nodes for a given node.
-fn order_nodes_topologically<T>(value: &Value<T>, seen_nodes: &mut HashMap<String, bool>) -> Vec<Rc<RefCell<InnerValue<T>>>>
-where T: Div<Output=T> + Copy + 'static + Mul<f64, Output = f64> + Into<f64> + From<f64> + fmt::Display + fmt::Debug
-{
-    let mut nodes = vec![];
+fn requires_grad_default() -> bool {
+    true
+}
 
-    let value_id = value.get_id();
+// SerializedGraph lists every node of a graph in topological order, ending with the root.
+#[derive(Serialize, Deserialize)]
+struct SerializedGraph<T> {
+    nodes: Vec<SerializedNode<T>>,
+}
 
-    if seen_nodes.contains_key(&value_id) {
-        return nodes;
+/// A Value serializes as the whole graph which produced it: a `nodes` list in topological order,
+/// ending with the value itself, where each node refers to its ancestors by id. Deserializing
+/// rebuilds the graph with shared ancestors shared again, keeping ids, data, gradients, labels and
+/// frozen state. Backward hooks aren't serialized.
+impl<T: Scalar + Serialize> Serialize for Value<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let nodes = build_topological_graph(self)
+            .iter()
+            .map(|node| {
+                let node = node.borrow();
+
+                SerializedNode {
+                    id: node.id.clone(),
+                    data: node.data,
+                    gradient: node.gradient,
+                    operation: node.operation,
+                    ancestors: node.ancestors.iter().map(|ancestor| ancestor.borrow().id.clone()).collect(),
+                    label: node.label.clone(),
+                    requires_grad: node.requires_grad,
+                }
+            })
+            .collect();
+
+        SerializedGraph { nodes }.serialize(serializer)
     }
+}
 
-    // Mark the node as seen incase its referenced in any of its own ancestors.
-    seen_nodes.insert(value_id, true);
+impl<'de, T: Scalar + Deserialize<'de>> Deserialize<'de> for Value<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Value<T>, D::Error> {
+        let graph = SerializedGraph::<T>::deserialize(deserializer)?;
+
+        let mut nodes: HashMap<String, Rc<RefCell<InnerValue<T>>>> = HashMap::with_capacity(graph.nodes.len());
+        let mut root = None;
+
+        for node in graph.nodes {
+            let ancestors = node
+                .ancestors
+                .iter()
+                .map(|id| nodes.get(id).cloned().ok_or_else(|| de::Error::custom(format!("node {} refers to unknown ancestor {}", node.id, id))))
+                .collect::<Result<Vec<_>, D::Error>>()?;
+            if !node.operation.accepts_ancestors(ancestors.len()) {
+                return Err(de::Error::custom(format!(
+                    "node {} has {} ancestors, which doesn't fit operation {}",
+                    node.id,
+                    ancestors.len(),
+                    node.operation.to_str()
+                )));
+            }
 
-    for ancestor in value.borrow().ancestors.iter(){
-        // Process the dependencies for this ancestor node
-        let ancestor_as_value = Value(Rc::clone(ancestor));
-        let mut ancestor_dependencies = order_nodes_topologically(&ancestor_as_value, seen_nodes);
+            let inner = Rc::new(RefCell::new(InnerValue {
+                id: node.id.clone(),
+                data: node.data,
+                gradient: node.gradient,
+                ancestors,
+                operation: node.operation,
+                label: node.label,
+                backward_hooks: vec![],
+                requires_grad: node.requires_grad,
+                _tracker: NodeTracker::new(),
+            }));
+
+            nodes.insert(node.id, Rc::clone(&inner));
+            root = Some(inner);
+        }
 
-        // Add the ancestor's dependencies to the list
-        nodes.append(&mut ancestor_dependencies);
+        root.map(Value).ok_or_else(|| de::Error::custom("graph has no nodes"))
     }
+}
 
-    // Add the node to the list after processing its ancestors
-    nodes.push(Rc::clone(value));
-
-    nodes
+/// value! creates a constant `Value<f64>` from an integer or float literal (or any expression
+/// with a From conversion), e.g. `value!(3)` or `value!(0.5)`.
+#[macro_export]
+macro_rules! value {
+    ($data:expr) => {
+        $crate::value::Value::<f64>::from($data)
+    };
 }
 
-pub fn print_topological_graph<T>(topological_graph: Vec<Rc<RefCell<InnerValue<T>>>>)
-where T: Div<Output=T> + Copy + 'static + Mul<f64, Output = f64> + Into<f64> + From<f64> + fmt::Display + fmt::Debug
-{
-    for item in topological_graph {
-        println!("{}", item.borrow());
-    }
+/// expr! builds a computation graph from a list of `name = expression;` assignments, binding each
+/// name to a `Value<f64>` labelled with the name, so graphs don't need manual `with_label` calls:
+///
+/// ```
+/// use backprop::expr;
+///
+/// expr! {
+///     a = 2.0;
+///     b = 3.0;
+///     z = (a + b) * b;
+/// }
+///
+/// z.run_grad();
+/// assert_eq!(z.get_label().as_deref(), Some("z"));
+/// assert_eq!(b.get_gradient(), 8.0);
+/// ```
+///
+/// Expressions may use `+`, `-`, `*`, `/`, parentheses, numeric literals and previously defined
+/// Values. Identifiers are cloned rather than moved, so a name used several times refers to the same
+/// node and its gradient accumulates every use. A name can be assigned again, e.g. `y = y * x;`,
+/// which binds it to a new node; nodes keep their own unique ids, so both stay in the graph.
+///
+/// An assignment of a lone literal, such as `a = 2.0;`, creates a trainable leaf, while literals
+/// within an expression become constants.
+#[macro_export]
+macro_rules! expr {
+    () => {};
+
+    ($name:ident = $literal:literal ; $($rest:tt)*) => {
+        let $name: $crate::value::Value<f64> = $crate::value::Value::<f64>::from($literal);
+        $name.set_label(stringify!($name));
+
+        $crate::expr!($($rest)*);
+    };
+
+    // Collect the tokens of the expression up to the next `;`
+    ($name:ident = $($rest:tt)+) => {
+        $crate::expr!(@assign $name [] $($rest)+);
+    };
+    (@assign $name:ident [$($expression:tt)+] ; $($rest:tt)*) => {
+        let $name: $crate::value::Value<f64> = $crate::expr!(@munch [] $($expression)+);
+        $name.set_label(stringify!($name));
+
+        $crate::expr!($($rest)*);
+    };
+    (@assign $name:ident [$($expression:tt)*] $next:tt $($rest:tt)*) => {
+        $crate::expr!(@assign $name [$($expression)* $next] $($rest)*)
+    };
+
+    // Rewrite the expression: identifiers are cloned, literals become constants and
+    // parenthesised groups are rewritten recursively
+    (@munch [$($out:tt)*]) => {
+        $($out)*
+    };
+    (@munch [$($out:tt)*] ( $($group:tt)+ ) $($rest:tt)*) => {
+        $crate::expr!(@munch [$($out)* ($crate::expr!(@munch [] $($group)+))] $($rest)*)
+    };
+    // Operators are matched first, as `literal` would also match `- 2` as a negative literal
+    (@munch [$($out:tt)*] + $($rest:tt)*) => {
+        $crate::expr!(@munch [$($out)* +] $($rest)*)
+    };
+    (@munch [$($out:tt)*] - $($rest:tt)*) => {
+        $crate::expr!(@munch [$($out)* -] $($rest)*)
+    };
+    (@munch [$($out:tt)*] * $($rest:tt)*) => {
+        $crate::expr!(@munch [$($out)* *] $($rest)*)
+    };
+    (@munch [$($out:tt)*] / $($rest:tt)*) => {
+        $crate::expr!(@munch [$($out)* /] $($rest)*)
+    };
+    (@munch [$($out:tt)*] $literal:literal $($rest:tt)*) => {
+        $crate::expr!(@munch [$($out)* $crate::value::Value::<f64>::constant($literal as f64)] $($rest)*)
+    };
+    (@munch [$($out:tt)*] $variable:ident $($rest:tt)*) => {
+        $crate::expr!(@munch [$($out)* $variable.clone()] $($rest)*)
+    };
 }
 
-impl<T> Add for Value<T>
-where T: Add<Output=T> + Copy + 'static + Mul<f64, Output = f64> + Div<T, Output = T> + Into<f64> + From<f64> + fmt::Display + fmt::Debug
-{
+impl<T: Scalar> Add for Value<T> {
     type Output = Self;
 
     fn add(self, rhs: Self) -> Self::Output {
@@ -290,13 +1517,13 @@ where T: Add<Output=T> + Copy + 'static + Mul<f64, Output = f64> + Div<T, Output
 
         value.borrow_mut().operation = ValueOp::Addition;
         
+        value.check_anomaly();
+
         value
     }
 }
 
-impl<T> Add for &Value<T>
-where T: Add<Output=T> + Copy + 'static + Mul<f64, Output = f64> + Div<T, Output = T> + Into<f64> + From<f64> + fmt::Display + fmt::Debug
-{
+impl<T: Scalar> Add for &Value<T> {
     type Output = Value<T>;
 
     fn add(self, rhs: Self) -> Self::Output {
@@ -309,14 +1536,14 @@ where T: Add<Output=T> + Copy + 'static + Mul<f64, Output = f64> + Div<T, Output
 
         value.borrow_mut().operation = ValueOp::Addition;
 
+        value.check_anomaly();
+
         value
     }
 }
 
 
-impl<T> Sub for Value<T>
-where T: Sub<Output=T> + Copy + 'static + Mul<f64, Output = f64> + Into<f64> + Div<T, Output = T> + From<f64> + fmt::Display + fmt::Debug
-{
+impl<T: Scalar> Sub for Value<T> {
     type Output = Self;
 
     fn sub(self, rhs: Self) -> Self::Output {
@@ -329,14 +1556,14 @@ where T: Sub<Output=T> + Copy + 'static + Mul<f64, Output = f64> + Into<f64> + D
 
         value.borrow_mut().operation = ValueOp::Subtraction;
 
+        value.check_anomaly();
+
         value
     }
 }
 
 
-impl<T> Sub for &Value<T>
-where T: Sub<Output=T> + Copy + 'static + Mul<f64, Output = f64> + Div<T, Output = T> + Into<f64> + From<f64> + fmt::Display + fmt::Debug
-{
+impl<T: Scalar> Sub for &Value<T> {
     type Output = Value<T>;
 
     fn sub(self, rhs: Self) -> Self::Output {
@@ -349,13 +1576,13 @@ where T: Sub<Output=T> + Copy + 'static + Mul<f64, Output = f64> + Div<T, Output
 
         value.borrow_mut().operation = ValueOp::Subtraction;
 
+        value.check_anomaly();
+
         value
     }
 }
 
-impl<T> Mul for Value<T>
-where T: Mul<Output=T> + Copy + 'static + Mul<f64, Output = f64> + Div<T, Output = T> + Into<f64> + From<f64> + fmt::Display + fmt::Debug
-{
+impl<T: Scalar> Mul for Value<T> {
     type Output = Self;
 
     fn mul(self, rhs: Self) -> Self::Output {
@@ -368,13 +1595,13 @@ where T: Mul<Output=T> + Copy + 'static + Mul<f64, Output = f64> + Div<T, Output
 
         value.borrow_mut().operation = ValueOp::Multiplication;
 
+        value.check_anomaly();
+
         value
     }
 }
 
-impl<T> Mul for &Value<T>
-where T: Mul<Output=T> + Copy + 'static + Mul<f64, Output = f64> + Div<T, Output = T> + Into<f64> + From<f64> + fmt::Display + fmt::Debug
-{
+impl<T: Scalar> Mul for &Value<T> {
     type Output = Value<T>;
 
     fn mul(self, rhs: Self) -> Self::Output {
@@ -387,13 +1614,13 @@ where T: Mul<Output=T> + Copy + 'static + Mul<f64, Output = f64> + Div<T, Output
 
         value.borrow_mut().operation = ValueOp::Multiplication;
 
+        value.check_anomaly();
+
         value
     }
 }
 
-impl<T> Div for Value<T>
-where T: Div<Output=T> + Copy + 'static + Mul<f64, Output = f64> + Into<f64> + From<f64> + fmt::Display + fmt::Debug
-{
+impl<T: Scalar> Div for Value<T> {
     type Output = Self;
 
     fn div(self, rhs: Self) -> Self::Output {
@@ -406,13 +1633,13 @@ where T: Div<Output=T> + Copy + 'static + Mul<f64, Output = f64> + Into<f64> + F
 
         value.borrow_mut().operation = ValueOp::Division;
 
+        value.check_anomaly();
+
         value
     }
 }
 
-impl<T> Div for &Value<T>
-where T: Div<Output=T> + Copy + 'static + Mul<f64, Output = f64> + Into<f64> + From<f64> + fmt::Display + fmt::Debug
-{
+impl<T: Scalar> Div for &Value<T> {
     type Output = Value<T>;
 
     fn div(self, rhs: Self) -> Self::Output {
@@ -425,13 +1652,17 @@ where T: Div<Output=T> + Copy + 'static + Mul<f64, Output = f64> + Into<f64> + F
 
         value.borrow_mut().operation = ValueOp::Division;
 
+        value.check_anomaly();
+
         value
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::value::{build_topological_graph, Value};
+    use std::rc::Rc;
+    use crate::error::BackpropError;
+    use crate::value::{axpy, build_topological_graph, dot, dot_values, run_grad_multi, softmax, GradPlan, Tape, Value};
 
     #[test]
     fn simple_addition_on_values(){
@@ -635,14 +1866,660 @@ mod tests {
 
         // Backward pass checks
         assert_eq!(z.borrow().gradient, 1.0, "z.gradient should be 1.0");
-        assert_eq!(round_to_places(c.borrow().gradient, 2), 0.33, "c.gradient should be 0.33");
-        assert_eq!(round_to_places(b.borrow().gradient, 2), 1.44, "b.gradient should be 1.44");
-        assert_eq!(round_to_places(a.borrow().gradient, 2), -1.33, "a.gradient should be −1.33");
+        assert!((c.borrow().gradient - 1.0 / 3.0).abs() < 1e-12, "c.gradient should be 1/3");
+        assert!((b.borrow().gradient - 13.0 / 9.0).abs() < 1e-12, "b.gradient should be 13/9");
+        assert!((a.borrow().gradient + 4.0 / 3.0).abs() < 1e-12, "a.gradient should be -4/3");
+    }
+
+    #[test]
+    fn relu_on_values(){
+        let x = &Value::new(3.0);
+        let w = &Value::new(-2.0);
+
+        let y = x.relu();
+        let z = w.relu();
+
+        assert_eq!(y.get_data(), 3.0);
+        assert_eq!(z.get_data(), 0.0);
+
+        y.run_grad();
+        z.run_grad();
+
+        assert_eq!(x.get_gradient(), 1.0);
+        assert_eq!(w.get_gradient(), 0.0);
     }
 
-    fn round_to_places(value: f64, places: u32) -> f64 {
-        let factor = 10f64.powi(places as i32);
-        (value * factor).round() / factor
+    #[test]
+    fn display_is_compact(){
+        let a = Value::new_with_id(2.0, "a");
+        let b = Value::new_with_id(3.0, "b");
+
+        let c = &a * &b;
+        c.borrow_mut().id = "c".to_string();
+        c.run_grad();
+
+        assert_eq!(c.to_string(), "id=c data=6 grad=1 op=*");
+        assert_eq!(a.to_string(), "id=a data=2 grad=3 op=none");
+    }
+
+    #[test]
+    fn dump_tree_respects_depth_limit(){
+        let a = Value::new_with_id(2.0, "a");
+        let b = Value::new_with_id(3.0, "b");
+
+        let c = &a + &b;
+        c.borrow_mut().id = "c".to_string();
+
+        let d = &c * &a;
+        d.borrow_mut().id = "d".to_string();
+
+        assert_eq!(d.dump_tree(5), concat!(
+            "id=d data=10 grad=0 op=*\n",
+            "  id=c data=5 grad=0 op=+\n",
+            "    id=a data=2 grad=0 op=none\n",
+            "    id=b data=3 grad=0 op=none\n",
+            "  id=a data=2 grad=0 op=none\n",
+        ));
+
+        assert_eq!(d.dump_tree(0), concat!(
+            "id=d data=10 grad=0 op=*\n",
+            "  ... (2 ancestors)\n",
+        ));
+    }
+
+    #[test]
+    fn differentiate_twice(){
+        let x = Value::new(2.0);
+
+        // y = x^3, dy/dx = 3x^2 = 12, d2y/dx2 = 6x = 12
+        let y = &(&x * &x) * &x;
+
+        let dy_dx = y.differentiate(std::slice::from_ref(&x)).pop().unwrap();
+        assert_eq!(dy_dx.get_data(), 12.0);
+
+        let d2y_dx2 = dy_dx.differentiate(std::slice::from_ref(&x)).pop().unwrap();
+        assert_eq!(d2y_dx2.get_data(), 12.0);
+
+        // The gradient graph can also be driven by run_grad
+        dy_dx.run_grad();
+        assert_eq!(x.get_gradient(), 12.0);
+    }
+
+    #[test]
+    fn second_derivatives_of_unary_operations_match_finite_differences(){
+        type Operation = fn(&Value<f64>) -> Value<f64>;
+        let operations: [Operation; 10] = [
+            Value::tanh,
+            Value::sigmoid,
+            Value::softplus,
+            Value::gelu,
+            Value::elu,
+            Value::swish,
+            Value::abs,
+            Value::exp,
+            Value::ln,
+            Value::sqrt,
+        ];
+
+        for operation in operations {
+            for x in [-1.3, 0.5, 2.0] {
+                let input: Value<f64> = Value::new(x);
+                let output = operation(&input);
+                if !output.get_data().is_finite() {
+                    continue;
+                }
+
+                let first = output.differentiate(std::slice::from_ref(&input)).pop().unwrap();
+                let second = first.differentiate(std::slice::from_ref(&input)).pop().unwrap();
+
+                // Central differences of the first derivative
+                let derivative = |x: f64| {
+                    let input = Value::new(x);
+                    operation(&input).grad_wrt(std::slice::from_ref(&input))[0]
+                };
+                let h = 1e-5;
+                let numerical = (derivative(x + h) - derivative(x - h)) / (2.0 * h);
+
+                let name = output.borrow().operation.to_str();
+                assert!((first.get_data() - derivative(x)).abs() < 1e-12, "{} at {}", name, x);
+                assert!((second.get_data() - numerical).abs() < 1e-6, "{} at {}: {} vs {}", name, x, second.get_data(), numerical);
+            }
+        }
+
+        // d²/dx² tanh(x) = -2 tanh(x)(1 - tanh²(x))
+        let x: Value<f64> = Value::new(0.5);
+        let first = x.tanh().differentiate(std::slice::from_ref(&x)).pop().unwrap();
+        let second = first.differentiate(std::slice::from_ref(&x)).pop().unwrap();
+        assert!((second.get_data() + 0.727).abs() < 1e-3);
+    }
+
+    #[test]
+    fn differentiate_matches_run_grad(){
+        let a = Value::new(2.0);
+        let b = Value::new(3.0);
+        let unused = Value::new(7.0);
+
+        let c = &a + &b;
+        let d = &c * &(&b - &a);
+        let z = &(&d / &b).relu();
+
+        let gradients = z.differentiate(&[a.clone(), b.clone(), unused.clone()]);
+
+        z.run_grad();
+
+        assert_eq!(gradients[0].get_data(), a.get_gradient());
+        assert_eq!(gradients[1].get_data(), b.get_gradient());
+        assert_eq!(gradients[2].get_data(), 0.0);
+    }
+
+    #[test]
+    fn grad_wrt_leaves_gradients_untouched(){
+        let x = Value::new(3.0);
+        let w = Value::new(4.0);
+
+        let y = &x * &w;
+        let z = &x + &w;
+
+        assert_eq!(y.grad_wrt(&[x.clone(), w.clone()]), vec![4.0, 3.0]);
+        assert_eq!(z.grad_wrt(&[x.clone(), w.clone()]), vec![1.0, 1.0]);
+
+        assert_eq!(x.get_gradient(), 0.0);
+        assert_eq!(w.get_gradient(), 0.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "(operation /) produced inf, ancestors: [(id=x data=1 grad=0 op=none), (id=y data=0 grad=0 op=none)]")]
+    fn anomaly_detection_in_forward_pass(){
+        Value::enable_anomaly_detection();
+
+        let x = Value::new_with_id(1.0, "x");
+        let y = Value::new_with_id(0.0, "y");
+
+        let _ = &x / &y;
+    }
+
+    #[test]
+    #[should_panic(expected = "produced a non-finite gradient (inf) for ancestor x")]
+    fn anomaly_detection_in_backward_pass(){
+        let x = Value::new_with_id(0.0, "x");
+        let y = Value::new_with_id(0.0, "y");
+
+        // 0 / 0 is NaN, which is only caught once detection is enabled
+        let z: Value<f64> = &x / &y;
+        assert!(z.get_data().is_nan());
+
+        Value::enable_anomaly_detection();
+        assert!(Value::is_anomaly_detection_enabled());
+
+        z.run_grad();
+    }
+
+    #[test]
+    fn anomaly_detection_is_disabled_by_default(){
+        assert!(!Value::is_anomaly_detection_enabled());
+
+        let z: Value<f64> = &Value::new(1.0) / &Value::new(0.0);
+        assert!(z.get_data().is_infinite());
+    }
+
+    #[test]
+    fn try_div_reports_division_by_zero(){
+        let x = Value::new_with_id(1.0, "x");
+        let y = Value::new_with_id(0.0, "y");
+        let w = Value::new(4.0);
+
+        assert_eq!(x.try_div(&y).unwrap_err(), BackpropError::DivisionByZero {
+            numerator: "x".to_string(),
+            denominator: "y".to_string(),
+        });
+
+        let z = w.try_div(&Value::new(2.0)).unwrap();
+        assert_eq!(z.get_data(), 2.0);
+
+        let overflow = Value::new(f64::MAX).try_div(&Value::new(0.5));
+        assert!(matches!(overflow, Err(BackpropError::NonFinite { .. })));
+    }
+
+    #[test]
+    fn f32_values(){
+        let x = Value::new(3.0f32);
+        let w = Value::new(-2.0f32);
+
+        let y = (&(&x * &w) + &x).relu();
+        assert_eq!(y.get_data(), 0.0f32);
+
+        let z = &(&x * &x) / &w;
+        assert_eq!(z.get_data(), -4.5f32);
+
+        z.run_grad();
+        assert_eq!(x.get_gradient(), -3.0);
+        assert_eq!(z.grad_wrt(std::slice::from_ref(&w)), vec![-2.25]);
+    }
+
+    #[test]
+    fn from_conversions_and_value_macro(){
+        let x = Value::new(2.0);
+
+        let y = x.clone() + 3.into();
+        assert_eq!(y.get_data(), 5.0);
+
+        let z = &y * &value!(2);
+        assert_eq!(z.get_data(), 10.0);
+        assert_eq!(value!(0.25).get_data(), 0.25);
+        assert_eq!(Value::from(1.5f32).get_data(), 1.5);
+
+        z.run_grad();
+        assert_eq!(x.get_gradient(), 2.0);
+    }
+
+    #[test]
+    fn expr_macro_builds_labelled_graph(){
+        let w = Value::new_with_id(0.5, "w");
+
+        expr! {
+            x = 4;
+            y = (x * w + 1.0) / x;
+            loss = (y - 2) * (y - 2);
+        }
+
+        assert_eq!(y.get_data(), 0.75);
+        assert_eq!(loss.get_data(), 1.5625);
+        assert_eq!(y.get_label().as_deref(), Some("y"));
+        assert_eq!(loss.get_label().as_deref(), Some("loss"));
+
+        // d loss / dw = 2 * (y - 2) * x / x
+        loss.run_grad();
+        assert_eq!(w.get_gradient(), -2.5);
+        assert_eq!(x.get_label().as_deref(), Some("x"));
+        assert!(!x.is_constant());
+    }
+
+    #[test]
+    fn expr_macro_keeps_reassigned_names_apart(){
+        expr! {
+            x = 3.0;
+            y = x * x;
+            y = y * x;
+        }
+
+        // y = x³, so dy/dx = 3x²
+        y.run_grad();
+        assert_eq!(y.get_data(), 27.0);
+        assert_eq!(x.get_gradient(), 27.0);
+        assert_eq!(build_topological_graph(&y).len(), 3);
+    }
+
+    #[test]
+    fn backward_hooks_receive_final_gradients(){
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        let x: Value<f64> = Value::new(3.0);
+        let h = &x * &x;
+        let y = &h + &x;
+
+        // dy/dh = 1, dy/dx = 2x + 1
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let (h_sink, x_sink) = (Rc::clone(&seen), Rc::clone(&seen));
+        h.register_backward_hook(move |gradient| h_sink.borrow_mut().push(("h", gradient)));
+        x.register_backward_hook(move |gradient| x_sink.borrow_mut().push(("x", gradient)));
+
+        y.run_grad();
+        assert_eq!(*seen.borrow(), vec![("h", 1.0), ("x", 7.0)]);
+
+        x.clear_backward_hooks();
+        y.run_grad();
+        assert_eq!(seen.borrow().len(), 3);
+    }
+
+    #[test]
+    fn frozen_values_receive_no_gradient(){
+        let w: Value<f64> = Value::new(2.0);
+        let x: Value<f64> = Value::new(3.0);
+        let y = &w * &x;
+
+        w.set_requires_grad(false);
+        y.run_grad();
+
+        assert!(!w.requires_grad());
+        assert_eq!(w.get_gradient(), 0.0);
+        assert_eq!(x.get_gradient(), 2.0);
+    }
+
+    #[test]
+    fn leaky_relu_passes_scaled_gradients(){
+        let x: Value<f64> = Value::new(-2.0);
+        let y: Value<f64> = Value::new(3.0);
+
+        let output = &x.leaky_relu(0.1) + &y.leaky_relu(0.1);
+        assert!((output.get_data() - 2.8).abs() < 1e-12);
+
+        output.run_grad();
+        assert_eq!(x.get_gradient(), 0.1);
+        assert_eq!(y.get_gradient(), 1.0);
+
+        assert_eq!(output.grad_wrt(&[x.clone(), y.clone()]), vec![0.1, 1.0]);
+    }
+
+    #[test]
+    fn log_sqrt_and_abs_gradients(){
+        let x: Value<f64> = Value::new(4.0);
+
+        let output = &(&x.ln() + &x.sqrt()) + &x.log(2.0);
+        assert!((output.get_data() - (4.0f64.ln() + 2.0 + 2.0)).abs() < 1e-12);
+
+        output.run_grad();
+        let expected = 1.0 / 4.0 + 0.25 + 1.0 / (4.0 * 2.0f64.ln());
+        assert!((x.get_gradient() - expected).abs() < 1e-12);
+
+        // d²/dx² of ln(x) + sqrt(x) = -1/x² - 1/(4x^1.5)
+        let ln_sqrt = &x.ln() + &x.sqrt();
+        let first = ln_sqrt.differentiate(std::slice::from_ref(&x));
+        let second = first[0].grad_wrt(std::slice::from_ref(&x));
+        assert!((second[0] - (-1.0 / 16.0 - 1.0 / 32.0)).abs() < 1e-12);
+
+        for (input, gradient) in [(-3.0, -1.0), (0.0, 0.0), (2.0, 1.0)] {
+            let value: Value<f64> = Value::new(input);
+            let abs = value.abs();
+            abs.run_grad();
+
+            assert_eq!(abs.get_data(), f64::abs(input));
+            assert_eq!(value.get_gradient(), gradient);
+        }
+    }
+
+    #[test]
+    fn detached_values_stop_gradients(){
+        let x: Value<f64> = Value::new(0.5);
+        let hidden = x.tanh();
+        let output = &hidden * &hidden.detach();
+
+        output.run_grad();
+        let tanh = 0.5f64.tanh();
+        assert!((x.get_gradient() - tanh * (1.0 - tanh * tanh)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn max_min_and_clamp_route_gradients(){
+        let a: Value<f64> = Value::new(2.0);
+        let b: Value<f64> = Value::new(-1.0);
+
+        let output = &a.max(&b) + &(&a.min(&b) * &Value::new(3.0));
+        assert_eq!(output.get_data(), -1.0);
+
+        output.run_grad();
+        assert_eq!(a.get_gradient(), 1.0);
+        assert_eq!(b.get_gradient(), 3.0);
+        assert_eq!(output.grad_wrt(&[a.clone(), b.clone()]), vec![1.0, 3.0]);
+
+        for (input, expected, gradient) in [(-2.0, -1.0, 0.0), (0.5, 0.5, 1.0), (1.0, 1.0, 1.0), (4.0, 1.0, 0.0)] {
+            let x: Value<f64> = Value::new(input);
+            let clamped = x.clamp(-1.0, 1.0);
+            clamped.run_grad();
+
+            assert_eq!(clamped.get_data(), expected);
+            assert_eq!(x.get_gradient(), gradient);
+        }
     }
-}
 
+    #[test]
+    fn smooth_activations_match_numerical_gradients(){
+        type Activation = fn(&Value<f64>) -> Value<f64>;
+        let activations: [(&str, Activation); 7] = [
+            ("softplus", Value::softplus),
+            ("gelu", Value::gelu),
+            ("elu", Value::elu),
+            ("swish", Value::swish),
+            ("tanh", Value::tanh),
+            ("sigmoid", Value::sigmoid),
+            ("exp", Value::exp),
+        ];
+
+        for (name, activation) in activations {
+            for x in [-2.5, -0.3, 0.4, 1.7] {
+                let input: Value<f64> = Value::new(x);
+                let output = activation(&input);
+                output.run_grad();
+
+                let h = 1e-6;
+                let numerical = (activation(&Value::new(x + h)).get_data() - activation(&Value::new(x - h)).get_data()) / (2.0 * h);
+
+                assert_eq!(output.borrow().operation.to_str(), name);
+                assert!((input.get_gradient() - numerical).abs() < 1e-6, "{} at {}: {} vs {}", name, x, input.get_gradient(), numerical);
+                assert!((output.grad_wrt(std::slice::from_ref(&input))[0] - input.get_gradient()).abs() < 1e-12);
+            }
+        }
+
+        assert_eq!(Value::new(1000.0f64).softplus().get_data(), 1000.0);
+        assert!((Value::new(-1.0f64).elu().get_data() - ((-1.0f64).exp() - 1.0)).abs() < 1e-12);
+        assert!((Value::new(1.0f64).gelu().get_data() - 0.8411919906).abs() < 1e-9);
+    }
+
+    #[test]
+    fn approx_eq_compares_data_within_eps() {
+        let a = Value::new(1.0);
+        let b = Value::new(1.0 + 1e-9);
+
+        assert!(a.approx_eq(&b, 1e-8));
+        assert!(!a.approx_eq(&b, 1e-10));
+        assert!(Value::new(f64::INFINITY).approx_eq(&Value::new(f64::INFINITY), 0.0));
+        assert!(!Value::new(f64::NAN).approx_eq(&Value::new(f64::NAN), 1.0));
+    }
+
+    #[test]
+    fn graphs_round_trip_through_serde() {
+        let a = Value::new(2.0);
+        a.borrow_mut().label = Some("a".to_string());
+        let b = Value::new(-3.0);
+        b.set_requires_grad(false);
+
+        // a is shared by both branches
+        let y = (&a * &b).tanh() + a.exp();
+        y.run_grad();
+
+        let json = serde_json::to_string(&y).unwrap();
+        let restored: Value<f64> = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(restored.get_id(), y.get_id());
+        assert_eq!(restored.get_data(), y.get_data());
+
+        let original = build_topological_graph(&y);
+        let copy = build_topological_graph(&restored);
+        assert_eq!(copy.len(), original.len());
+        for (copy, original) in copy.iter().zip(&original) {
+            let (copy, original) = (copy.borrow(), original.borrow());
+            assert_eq!((&copy.id, copy.data, copy.gradient, copy.operation), (&original.id, original.data, original.gradient, original.operation));
+            assert_eq!((&copy.label, copy.requires_grad), (&original.label, original.requires_grad));
+        }
+
+        // The shared ancestor is rebuilt once, so a fresh backward pass reaches it through both branches
+        let restored_a = copy.iter().find(|node| node.borrow().label.as_deref() == Some("a")).unwrap().clone();
+        copy.iter().for_each(|node| node.borrow_mut().gradient = 0.0);
+        restored.run_grad();
+        assert!((restored_a.borrow().gradient - a.get_gradient()).abs() < 1e-12);
+
+        let dangling = r#"{"nodes": [{"id": "y", "data": 1.0, "gradient": 0.0, "operation": "relu", "ancestors": ["x"]}]}"#;
+        assert!(serde_json::from_str::<Value<f64>>(dangling).unwrap_err().to_string().contains("unknown ancestor x"));
+        assert!(serde_json::from_str::<Value<f64>>(r#"{"nodes": []}"#).is_err());
+
+        // An operation with the wrong number of ancestors would panic in the backward pass
+        let childless = r#"{"nodes":[{"id":"y","data":1.0,"gradient":0.0,"operation":"addition","ancestors":[]}]}"#;
+        assert!(serde_json::from_str::<Value<f64>>(childless).unwrap_err().to_string().contains("node y has 0 ancestors"));
+    }
+
+    #[test]
+    fn dot_and_axpy_match_their_expansions() {
+        let xs: Vec<Value<f64>> = vec![Value::new(1.5), Value::new(-2.0), Value::new(0.5)];
+        let ws = vec![Value::new(0.2), Value::new(0.4), Value::new(-1.0)];
+
+        let fused = dot_values(&ws, &xs).tanh();
+        let expanded = (&(&(&ws[0] * &xs[0]) + &(&ws[1] * &xs[1])) + &(&ws[2] * &xs[2])).tanh();
+        assert_eq!(build_topological_graph(&fused).len(), 8);
+        assert!((fused.get_data() - expanded.get_data()).abs() < 1e-12);
+
+        fused.run_grad();
+        let fused_gradients: Vec<f64> = xs.iter().chain(&ws).map(|v| v.get_gradient()).collect();
+        for v in xs.iter().chain(&ws) {
+            v.set_gradient(0.0);
+        }
+        expanded.run_grad();
+        for (v, fused) in xs.iter().chain(&ws).zip(fused_gradients) {
+            assert!((v.get_gradient() - fused).abs() < 1e-12);
+        }
+
+        // Fixed coefficients don't receive gradients, and the node is differentiable again
+        let weighted = dot(&xs, &[2.0, 3.0, 4.0]);
+        assert_eq!(weighted.get_data(), -1.0);
+        assert_eq!(weighted.grad_wrt(&xs), vec![2.0, 3.0, 4.0]);
+
+        let ys = vec![Value::new(1.0), Value::new(1.0), Value::new(1.0)];
+        let shifted = axpy(2.0, &xs, &ys);
+        assert_eq!(shifted.iter().map(|v| v.get_data()).collect::<Vec<_>>(), vec![4.0, -3.0, 2.0]);
+        assert_eq!(shifted[0].grad_wrt(&[xs[0].clone(), ys[0].clone()]), vec![2.0, 1.0]);
+
+        // Both recompute from their ancestors' current data
+        xs[0].set_data(0.0);
+        GradPlan::compile(&shifted[0]).forward();
+        GradPlan::compile(&weighted).forward();
+        assert_eq!((shifted[0].get_data(), weighted.get_data()), (1.0, -4.0));
+    }
+
+    #[test]
+    fn softmax_is_stable_and_differentiable() {
+        let logits: Vec<Value<f64>> = vec![Value::new(1000.0), Value::new(1000.0 + 2.0f64.ln())];
+        let probabilities = softmax(&logits);
+
+        assert!((probabilities[0].get_data() - 1.0 / 3.0).abs() < 1e-12);
+        assert!((probabilities[1].get_data() - 2.0 / 3.0).abs() < 1e-12);
+
+        // d p0 / d x0 = p0 (1 - p0), d p0 / d x1 = -p0 p1
+        probabilities[0].run_grad();
+        assert!((logits[0].get_gradient() - 2.0 / 9.0).abs() < 1e-12);
+        assert!((logits[1].get_gradient() + 2.0 / 9.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn run_grad_multi_sums_seeded_roots() {
+        let x: Value<f64> = Value::new(2.0);
+        let y = Value::new(-1.0);
+
+        // u depends on v, so v gets its seed plus what flows back from u
+        let v = &x * &y;
+        let u = (&v + &x).tanh();
+        run_grad_multi(&[u.clone(), v.clone()], &[0.5, 2.0]);
+        assert_eq!(v.get_gradient(), 2.0 + 0.5 * (1.0 - u.get_data().powi(2)));
+
+        // The gradients of 0.5u + 2v = 0.5 tanh(xy + x) + 2xy
+        let slope = 1.0 - u.get_data().powi(2);
+        assert!((x.get_gradient() - (0.5 * slope * (y.get_data() + 1.0) + 2.0 * y.get_data())).abs() < 1e-12);
+        assert!((y.get_gradient() - (0.5 * slope * x.get_data() + 2.0 * x.get_data())).abs() < 1e-12);
+    }
+
+    #[test]
+    fn constants_never_receive_gradients() {
+        let w: Value<f64> = Value::new(3.0);
+        let x = Value::constant(2.0);
+        assert!(x.is_constant() && !w.is_constant());
+
+        let y = (&w * &x).tanh();
+        y.run_grad();
+        assert_eq!(x.get_gradient(), 0.0);
+        assert_eq!(w.get_gradient(), 2.0 * (1.0 - y.get_data().powi(2)));
+
+        // Even when unfrozen, a constant stays out of the backward pass
+        x.set_requires_grad(true);
+        y.run_grad();
+        assert_eq!(x.get_gradient(), 0.0);
+    }
+
+    #[test]
+    fn literals_are_constants() {
+        let x: Value<f64> = Value::new(0.5);
+        let logits = [x.clone(), Value::new(2.0)];
+
+        let mut roots = vec![x.clamp(0.0, 1.0), x.log(2.0), x.leaky_relu(0.1)];
+        roots.extend(softmax(&logits));
+
+        // The only leaves which aren't constants are the ones passed in
+        for root in roots {
+            for node in build_topological_graph(&root) {
+                let node = Value(node);
+                if node.borrow().ancestors.is_empty() && !Rc::ptr_eq(&node, &logits[0]) && !Rc::ptr_eq(&node, &logits[1]) {
+                    assert!(node.is_constant(), "{} isn't a constant", node);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn dropping_a_tape_frees_its_graph() {
+        let w = Value::new(3.0);
+        let weak_intermediate;
+
+        let tape = Tape::new();
+        let loss = {
+            let x = Value::new(2.0);
+            let product = &w * &x;
+            weak_intermediate = Rc::downgrade(&product);
+
+            (&product + &Value::new(1.0)).tanh()
+        };
+        loss.run_grad();
+        assert_eq!(tape.len(), 5);
+
+        // Nested tapes take over recording until they're dropped
+        let inner = Tape::new();
+        let _ = &loss * &loss;
+        assert_eq!((tape.len(), inner.len()), (5, 1));
+        drop(inner);
+        let _ = &loss * &loss;
+        assert_eq!(tape.len(), 6);
+
+        let gradient = w.get_gradient();
+        drop(tape);
+
+        // The root outlives the tape as a leaf, the rest of the graph is gone
+        assert!(weak_intermediate.upgrade().is_none());
+        assert!(loss.borrow().ancestors.is_empty());
+        assert_eq!(loss.get_data(), 7.0_f64.tanh());
+        assert_eq!((w.get_data(), w.get_gradient()), (3.0, gradient));
+
+        // The leftover handle can still be differentiated and built on like any other leaf
+        loss.run_grad();
+        assert_eq!(loss.get_gradient(), 1.0);
+        let doubled = &loss + &loss;
+        doubled.run_grad();
+        // The gradient accumulates onto the 1 from the previous pass
+        assert_eq!((doubled.get_data(), loss.get_gradient()), (2.0 * 7.0_f64.tanh(), 3.0));
+        assert_eq!(w.get_gradient(), gradient);
+    }
+
+    #[test]
+    fn grad_plans_match_fresh_graphs() {
+        type Build = fn(&Value<f64>, &Value<f64>, &Value<f64>) -> Value<f64>;
+        let build: Build = |w, x, b| {
+            let z = &(w * x) + b;
+            &z.tanh().max(&z.leaky_relu(0.1)) / &(x.abs() + Value::new(1.0))
+        };
+
+        let (w, x, b) = (Value::new(0.5), Value::new(-1.5), Value::new(0.25));
+        let plan = GradPlan::compile(&build(&w, &x, &b));
+
+        for (wd, xd, bd) in [(0.5, -1.5, 0.25), (-2.0, 0.75, 1.0), (1.25, 2.0, -3.0)] {
+            w.set_data(wd);
+            x.set_data(xd);
+            b.set_data(bd);
+            [&w, &x, &b].iter().for_each(|leaf| leaf.set_gradient(0.0));
+            plan.run();
+
+            let (fw, fx, fb) = (Value::new(wd), Value::new(xd), Value::new(bd));
+            let fresh = build(&fw, &fx, &fb);
+            fresh.run_grad();
+
+            assert_eq!(plan.root().get_data(), fresh.get_data());
+            for (leaf, expected) in [(&w, &fw), (&x, &fx), (&b, &fb)] {
+                assert!((leaf.get_gradient() - expected.get_gradient()).abs() < 1e-12);
+            }
+        }
+    }
+}
@@ -0,0 +1,175 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use rand::Rng;
+use crate::interop::{self, NdArray};
+use crate::network::{Network, NetworkError};
+use crate::rand::gaussian;
+use crate::scalar::Scalar;
+use crate::train::evaluate;
+
+/// Grid is the range of step sizes along each direction of a loss surface: `steps` evenly
+/// spaced values from `min` to `max` inclusive.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Grid {
+    pub min: f64,
+    pub max: f64,
+    pub steps: usize,
+}
+
+impl Grid {
+    pub fn new(min: f64, max: f64, steps: usize) -> Grid {
+        Grid { min, max, steps }
+    }
+
+    /// Returns the grid's values in increasing order.
+    pub fn values(&self) -> Vec<f64> {
+        match self.steps {
+            0 => Vec::new(),
+            1 => vec![self.min],
+            steps => (0..steps).map(|i| self.min + (self.max - self.min) * i as f64 / (steps - 1) as f64).collect(),
+        }
+    }
+}
+
+/// LossSurface holds the loss at each point of a 2D slice of parameter space, `losses[i][j]`
+/// being the loss at parameters + alphas[i] * direction1 + betas[j] * direction2.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LossSurface {
+    pub alphas: Vec<f64>,
+    pub betas: Vec<f64>,
+    pub losses: Vec<Vec<f64>>,
+}
+
+impl LossSurface {
+    /// Returns the surface as CSV with one `alpha,beta,loss` row per point.
+    pub fn to_csv(&self) -> String {
+        let mut output = String::from("alpha,beta,loss\n");
+        for (alpha, row) in self.alphas.iter().zip(&self.losses) {
+            for (beta, loss) in self.betas.iter().zip(row) {
+                output.push_str(&format!("{},{},{}\n", alpha, beta, loss));
+            }
+        }
+
+        output
+    }
+
+    pub fn write_csv(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_csv())
+    }
+
+    /// Writes the losses as a NumPy `.npy` array of shape (alphas, betas), e.g. for
+    /// matplotlib's contour with the alphas and betas of the grid.
+    pub fn write_npy(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let array = NdArray {
+            shape: vec![self.alphas.len(), self.betas.len()],
+            data: self.losses.iter().flatten().copied().collect(),
+        };
+
+        fs::write(path, interop::write_npy(&array))
+    }
+}
+
+/// loss_surface evaluates the mean squared error of `network` over `dataset` at every point
+/// parameters + alpha * direction1 + beta * direction2 of `grid` x `grid`, where the directions
+/// have one value per parameter in the order of Network::parameters. The network itself is
+/// left unchanged.
+///
+/// Plotting the surface around a trained network along two random_direction()s shows how sharp
+/// or flat the minimum it found is.
+pub fn loss_surface<T: Scalar>(
+    network: &Network<T>,
+    dataset: &[(Vec<T>, Vec<T>)],
+    direction1: &[f64],
+    direction2: &[f64],
+    grid: &Grid,
+) -> Result<LossSurface, NetworkError> {
+    let expected = network.num_parameters();
+    for direction in [direction1, direction2] {
+        if direction.len() != expected {
+            return Err(NetworkError::DimensionMismatch { expected, found: direction.len() });
+        }
+    }
+
+    let origin = network.get_flat_params();
+    let probe = network.deep_clone();
+
+    let alphas = grid.values();
+    let betas = grid.values();
+
+    let mut losses = Vec::with_capacity(alphas.len());
+    for alpha in &alphas {
+        let mut row = Vec::with_capacity(betas.len());
+        for beta in &betas {
+            let point: Vec<f64> = (0..expected).map(|i| origin[i] + alpha * direction1[i] + beta * direction2[i]).collect();
+            probe.set_flat_params(&point)?;
+            row.push(evaluate(&probe, dataset)?);
+        }
+        losses.push(row);
+    }
+
+    Ok(LossSurface { alphas, betas, losses })
+}
+
+/// Returns a random direction in parameter space, filter-normalised as in Li et al. (2018):
+/// the part of the direction for each neuron has the same norm as that neuron's parameters,
+/// so surfaces of networks with different weight scales can be compared.
+pub fn random_direction<T: Scalar, R: Rng + ?Sized>(network: &Network<T>, rng: &mut R) -> Vec<f64> {
+    network
+        .layers
+        .iter()
+        .flat_map(|layer| layer.neuron_parameters())
+        .flat_map(|neuron| {
+            let direction: Vec<f64> = neuron.iter().map(|_| gaussian(rng, 1.0)).collect();
+
+            let norm = |values: &mut dyn Iterator<Item = f64>| values.map(|x| x * x).sum::<f64>().sqrt();
+            let neuron_norm = norm(&mut neuron.iter().map(|p| p.get_data().to_f64()));
+            let direction_norm = norm(&mut direction.iter().copied());
+            let scale = if direction_norm > 0.0 { neuron_norm / direction_norm } else { 0.0 };
+
+            direction.into_iter().map(move |x| x * scale)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use crate::network::{Activation, Layer, Network};
+    use crate::viz::{loss_surface, random_direction, Grid};
+
+    #[test]
+    fn surface_is_evaluated_around_the_parameters() {
+        // y = w * x with w = 1, so the loss at w + a + 0 * b is a² * mean(x²)
+        let network: Network = Network::new(vec![Layer::dense(1, 1, Activation::Linear, false).unwrap()]).unwrap();
+        network.parameters()[0].set_data(1.0);
+        let dataset = vec![(vec![1.0], vec![1.0]), (vec![2.0], vec![2.0])];
+
+        let surface = loss_surface(&network, &dataset, &[1.0], &[0.0], &Grid::new(-1.0, 1.0, 3)).unwrap();
+        assert_eq!(surface.alphas, vec![-1.0, 0.0, 1.0]);
+        assert_eq!(surface.losses, vec![vec![2.5; 3], vec![0.0; 3], vec![2.5; 3]]);
+        assert_eq!(network.parameters()[0].get_data(), 1.0);
+
+        assert!(surface.to_csv().starts_with("alpha,beta,loss\n-1,-1,2.5\n-1,0,2.5\n"));
+        assert!(loss_surface(&network, &dataset, &[1.0, 2.0], &[0.0], &Grid::new(-1.0, 1.0, 3)).is_err());
+
+        let path = std::env::temp_dir().join("backprop_surface.npy");
+        surface.write_npy(&path).unwrap();
+        let array = crate::interop::parse_npy(&std::fs::read(&path).unwrap()).unwrap();
+        assert_eq!(array.shape, vec![3, 3]);
+    }
+
+    #[test]
+    fn random_directions_are_filter_normalised() {
+        let network: Network = Network::new(vec![Layer::dense(3, 2, Activation::Relu, true).unwrap()]).unwrap();
+        let direction = random_direction(&network, &mut StdRng::seed_from_u64(4));
+        assert_eq!(direction.len(), network.num_parameters());
+
+        let parameters = network.get_flat_params();
+        for (neuron, weights) in direction.chunks(4).zip(parameters.chunks(4)) {
+            let norm = |values: &[f64]| values.iter().map(|x| x * x).sum::<f64>().sqrt();
+            assert!((norm(neuron) - norm(weights)).abs() < 1e-9);
+        }
+    }
+}
@@ -0,0 +1,151 @@
+use wasm_bindgen::prelude::*;
+use crate::config::{LayerConfig, NetworkConfig};
+use crate::data::Samples;
+use crate::network::{Activation, Network, NetworkError};
+use crate::optim::Sgd;
+use crate::train::Trainer;
+
+/// WasmNetwork wraps a dense network for JavaScript, taking and returning flat f64 arrays
+/// (Float64Array on the JS side).
+///
+/// Networks are initialised from an explicit seed rather than platform entropy, so a demo
+/// can reproduce the same run on every page load.
+#[wasm_bindgen]
+pub struct WasmNetwork {
+    network: Network,
+}
+
+#[wasm_bindgen]
+impl WasmNetwork {
+    /// Creates a network with `sizes[0]` inputs and one dense layer per following size. Hidden
+    /// layers use `activation` (e.g. "relu", "gelu"), the last layer is linear.
+    #[wasm_bindgen(constructor)]
+    pub fn new(sizes: &[u32], activation: &str, seed: u32) -> Result<WasmNetwork, JsError> {
+        let activation = parse_activation(activation).ok_or_else(|| JsError::new(&format!("unknown activation {}", activation)))?;
+
+        Ok(WasmNetwork { network: build(sizes, activation, seed)? })
+    }
+
+    #[wasm_bindgen(js_name = numInputs)]
+    pub fn num_inputs(&self) -> usize {
+        self.network.layers[0].num_inputs() as usize
+    }
+
+    #[wasm_bindgen(js_name = numOutputs)]
+    pub fn num_outputs(&self) -> usize {
+        self.network.layers[self.network.layers.len() - 1].num_outputs() as usize
+    }
+
+    pub fn predict(&self, inputs: &[f64]) -> Result<Vec<f64>, JsError> {
+        Ok(self.network.forward(inputs)?)
+    }
+
+    /// Trains with mini-batch SGD on the mean squared error. `inputs` and `targets` hold one
+    /// sample after another, numInputs and numOutputs values each. Returns the mean loss of
+    /// every epoch.
+    pub fn train(&self, inputs: &[f64], targets: &[f64], epochs: u32, learning_rate: f64, batch_size: u32) -> Result<Vec<f64>, JsError> {
+        let dataset = samples(inputs, targets, self.num_inputs(), self.num_outputs())?;
+
+        let mut trainer = Trainer::new(Sgd::new(learning_rate));
+        trainer.batch_size = batch_size as usize;
+
+        // Trainer::fit measures elapsed time with Instant, which isn't available in browsers
+        let losses = (0..epochs).map(|_| trainer.train_epoch(&self.network, &dataset)).collect::<Result<_, _>>()?;
+
+        Ok(losses)
+    }
+
+    /// Returns the current value of every parameter, layer by layer, e.g. to draw the weights.
+    pub fn parameters(&self) -> Vec<f64> {
+        self.network.parameters().iter().map(|p| p.get_data()).collect()
+    }
+}
+
+fn parse_activation(name: &str) -> Option<Activation> {
+    let activations = [
+        Activation::Relu,
+        Activation::Linear,
+        Activation::LeakyRelu { alpha: 0.01 },
+        Activation::Softplus,
+        Activation::Gelu,
+        Activation::Elu,
+        Activation::Swish,
+    ];
+
+    activations.into_iter().find(|activation| activation.to_str() == name)
+}
+
+fn build(sizes: &[u32], activation: Activation, seed: u32) -> Result<Network, NetworkError> {
+    let last = sizes.len().saturating_sub(2);
+    let layers = sizes
+        .windows(2)
+        .enumerate()
+        .map(|(index, pair)| LayerConfig::Dense {
+            inputs: pair[0] as u64,
+            outputs: pair[1] as u64,
+            activation: if index == last { Activation::Linear } else { activation },
+            bias: true,
+        })
+        .collect();
+
+    Network::from_config_seeded(&NetworkConfig { layers }, seed as u64)
+}
+
+// samples splits flat inputs and targets into (input, target) pairs, failing unless both hold
+// the same number of samples
+fn samples(inputs: &[f64], targets: &[f64], num_inputs: usize, num_outputs: usize) -> Result<Samples, NetworkError> {
+    let count = targets.len() / num_outputs;
+    if inputs.len() != count * num_inputs {
+        return Err(NetworkError::IncompatibleShape { inputs: inputs.len(), shape: vec![count, num_inputs] });
+    }
+    if targets.len() != count * num_outputs {
+        return Err(NetworkError::IncompatibleShape { inputs: targets.len(), shape: vec![count, num_outputs] });
+    }
+
+    Ok(inputs.chunks(num_inputs).map(|x| x.to_vec()).zip(targets.chunks(num_outputs).map(|y| y.to_vec())).collect())
+}
+
+// JsError can only be built inside a JS runtime, so these tests stick to the success paths of the
+// exported API and the plain Rust helpers behind it
+#[cfg(test)]
+mod tests {
+    use crate::network::{Activation, NetworkError};
+    use crate::wasm::{build, parse_activation, samples, WasmNetwork};
+
+    #[test]
+    fn same_seed_builds_the_same_network() {
+        let a = WasmNetwork::new(&[2, 4, 1], "gelu", 7).ok().unwrap();
+        let b = WasmNetwork::new(&[2, 4, 1], "gelu", 7).ok().unwrap();
+        let c = WasmNetwork::new(&[2, 4, 1], "gelu", 8).ok().unwrap();
+
+        assert_eq!(a.parameters(), b.parameters());
+        assert_ne!(a.parameters(), c.parameters());
+        assert_eq!((a.num_inputs(), a.num_outputs()), (2, 1));
+        assert_eq!(a.network.layers[1].activation(), Activation::Linear);
+
+        assert_eq!(parse_activation("leaky_relu"), Some(Activation::LeakyRelu { alpha: 0.01 }));
+        assert_eq!(parse_activation("sigmoid"), None);
+        assert_eq!(build(&[3], Activation::Relu, 0).err(), Some(NetworkError::EmptyNetwork));
+    }
+
+    #[test]
+    fn trains_on_flat_arrays() {
+        let network = WasmNetwork::new(&[1, 1], "linear", 1).ok().unwrap();
+
+        // y = 2x + 1
+        let inputs = [-1.0, 0.0, 1.0, 2.0];
+        let targets = [-1.0, 1.0, 3.0, 5.0];
+        let losses = network.train(&inputs, &targets, 100, 0.05, 1).ok().unwrap();
+
+        assert_eq!(losses.len(), 100);
+        assert!(losses[99] < 1e-4 && losses[99] < losses[0]);
+
+        let prediction = network.predict(&[3.0]).ok().unwrap();
+        assert!((prediction[0] - 7.0).abs() < 0.05);
+
+        assert_eq!(
+            samples(&inputs, &targets[..3], 1, 1),
+            Err(NetworkError::IncompatibleShape { inputs: 4, shape: vec![3, 1] })
+        );
+    }
+}
@@ -1,16 +1,203 @@
-use backprop::{network};
-
-fn main (){
-    let network = network::Network{
-        layers: vec![
-            network::Layer::new(3, 4),
-            network::Layer::new(4, 5),
-            network::Layer::new(5, 100),
-            network::Layer::new(100, 1),
-        ],
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::process::ExitCode;
+use backprop::config::{OptimizerConfig, TrainingConfig};
+use backprop::data::csv;
+use backprop::experiment::{export_bundle, import_bundle, Experiment};
+use backprop::logging::ProgressBar;
+use backprop::network::Network;
+use backprop::optim::Sgd;
+use backprop::train::Trainer;
+use backprop::utils::{network_to_dot, write_graphiz_dot_file};
+use backprop::value::Value;
+
+const USAGE: &str = "usage:
+    backprop train --config <model.toml>
+    backprop predict --model <model.bin> --input <data.csv> --output <preds.csv>
+    backprop viz --model <model.bin> --out <arch.dot> [--sample x1,x2,...]";
+
+// Returns the value following `flag` in `args`, e.g. `--config model.toml`.
+fn flag_value<'a>(args: &'a [String], flag: &str) -> Option<&'a str> {
+    args.iter()
+        .position(|arg| arg == flag)
+        .and_then(|i| args.get(i + 1))
+        .map(|value| value.as_str())
+}
+
+fn required_flag<'a>(args: &'a [String], flag: &str) -> Result<&'a str, Box<dyn Error>> {
+    flag_value(args, flag).ok_or_else(|| format!("missing {}\n\n{}", flag, USAGE).into())
+}
+
+// Trains the network described by a TrainingConfig and writes the model bundle and metrics.
+fn train(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let config = TrainingConfig::load(required_flag(args, "--config")?)?;
+
+    let network: Network = Network::from_config(&config.network)?;
+    let samples = csv::read(&config.dataset.path)?.samples(config.dataset.targets)?;
+
+    let optimizer = match config.optimizer {
+        OptimizerConfig::Sgd { learning_rate } => Sgd::new(learning_rate),
     };
-    
-    let inputs = vec![0.1, 0.2, 0.3];
-    
-    let _ = network.forward(&inputs);
-}
\ No newline at end of file
+
+    let mut trainer = Trainer::new(optimizer);
+    trainer.batch_size = config.training.batch_size;
+    trainer.accumulate_steps = config.training.accumulate_steps;
+    trainer.differential_privacy = config.training.differential_privacy;
+
+    let history = trainer.fit(&network, &samples, config.training.epochs, &mut ProgressBar::new())?;
+    history.write_csv(&config.output.metrics)?;
+
+    let mut experiment = Experiment::new(network);
+    experiment.metrics.insert("loss".to_string(), history.train_loss);
+    export_bundle(&experiment, &config.output.model)?;
+
+    println!("wrote {} and {}", config.output.model.display(), config.output.metrics.display());
+
+    Ok(())
+}
+
+// Runs a saved network over every row of a CSV file and writes one row of outputs per input row.
+fn predict(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let (model, input, output) = (
+        required_flag(args, "--model")?,
+        required_flag(args, "--input")?,
+        required_flag(args, "--output")?,
+    );
+
+    let experiment = import_bundle(model)?;
+    let table = csv::read(input)?;
+
+    let predictions = table
+        .rows
+        .iter()
+        .map(|row| experiment.network.forward(row))
+        .collect::<Result<Vec<Vec<f64>>, _>>()?;
+
+    let outputs = predictions.first().map_or(0, |prediction| prediction.len());
+    let header: Vec<String> = (1..=outputs).map(|i| format!("y{}", i)).collect();
+    csv::write(output, Some(&header), &predictions)?;
+
+    println!("wrote {} predictions to {}", predictions.len(), output);
+
+    Ok(())
+}
+
+// Writes the layer topology of a saved network as DOT. When `--sample` is given, the scalar computation
+// graph of one forward pass over the sample is also written, one file per network output.
+fn viz(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let (model, out) = (required_flag(args, "--model")?, required_flag(args, "--out")?);
+    let sample = flag_value(args, "--sample")
+        .map(|sample| {
+            sample
+                .split(',')
+                .map(|field| field.trim().parse::<f64>().map_err(|_| format!("--sample: {:?} is not a number", field.trim())))
+                .collect::<Result<Vec<f64>, String>>()
+        })
+        .transpose()?;
+
+    let experiment = import_bundle(model)?;
+    fs::write(out, network_to_dot(&experiment.network))?;
+    println!("wrote {}", out);
+
+    let Some(sample) = sample else {
+        return Ok(());
+    };
+
+    let inputs: Vec<Value<f64>> = sample
+        .iter()
+        .enumerate()
+        .map(|(i, x)| Value::new(*x).with_label(&format!("x{}", i + 1)))
+        .collect();
+    let outputs = experiment.network.forward_graph(&inputs)?;
+
+    let stem = Path::new(out).with_extension("");
+    for (i, output) in outputs.iter().enumerate() {
+        output.set_label(&format!("y{}", i + 1));
+
+        let path = if outputs.len() == 1 {
+            stem.with_extension("sample.dot")
+        } else {
+            stem.with_extension(format!("sample{}.dot", i + 1))
+        };
+
+        write_graphiz_dot_file(output, &path)?;
+        println!("wrote {}", path.display());
+    }
+
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    let result = match args.first().map(|command| command.as_str()) {
+        Some("train") => train(&args[1..]),
+        Some("predict") => predict(&args[1..]),
+        Some("viz") => viz(&args[1..]),
+        _ => {
+            eprintln!("{}", USAGE);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use crate::{predict, train};
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn trains_and_predicts_from_a_config() {
+        let dir = std::env::temp_dir().join("backprop_cli");
+        fs::create_dir_all(&dir).unwrap();
+
+        // y = 2x, with the model and metrics written next to the config
+        let rows: String = (0..8).map(|i| format!("{},{}\n", i as f64 / 8.0, i as f64 / 4.0)).collect();
+        fs::write(dir.join("data.csv"), format!("x,y\n{}", rows)).unwrap();
+        let config = dir.join("model.toml");
+        fs::write(
+            &config,
+            "[[layers]]\ntype = \"dense\"\ninputs = 1\noutputs = 1\nactivation = \"linear\"\n\n\
+             [optimizer]\ntype = \"sgd\"\nlearning_rate = 0.1\n\n\
+             [dataset]\npath = \"data.csv\"\n\n\
+             [training]\nepochs = 3\n",
+        )
+        .unwrap();
+
+        train(&args(&["--config", config.to_str().unwrap()])).unwrap();
+        let metrics = fs::read_to_string(dir.join("metrics.csv")).unwrap();
+        assert_eq!(metrics.lines().count(), 4);
+
+        let model = dir.join("model.bin");
+        let (input, output) = (dir.join("inputs.csv"), dir.join("predictions.csv"));
+        fs::write(&input, "x\n0.5\n1.0\n").unwrap();
+        predict(&args(&["--model", model.to_str().unwrap(), "--input", input.to_str().unwrap(), "--output", output.to_str().unwrap()])).unwrap();
+
+        let predictions = fs::read_to_string(&output).unwrap();
+        assert_eq!(predictions.lines().collect::<Vec<&str>>()[0], "y1");
+        assert_eq!(predictions.lines().count(), 3);
+    }
+
+    #[test]
+    fn missing_flags_are_reported() {
+        let err = train(&args(&[])).unwrap_err();
+        assert!(err.to_string().starts_with("missing --config"));
+
+        let err = predict(&args(&["--model", "model.bin"])).unwrap_err();
+        assert!(err.to_string().starts_with("missing --input"));
+    }
+}